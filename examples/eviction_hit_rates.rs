@@ -0,0 +1,99 @@
+//! Compares the cache hit rates of the eviction policies on a Zipfian workload.
+//!
+//! Every lookup that misses the in-memory map is counted and the key is
+//! inserted, as a read-through cache would do after fetching it.
+
+#![allow(clippy::cast_precision_loss)]
+
+#[cfg(feature = "in_memory")]
+use persistent_map::{in_memory::InMemoryBackend, EvictionPolicy, PersistentMap, Result};
+
+/// Number of distinct keys.
+const KEYS: usize = 10_000;
+
+/// Number of entries kept in memory.
+const CAPACITY: usize = 500;
+
+/// Number of lookups per policy.
+const LOOKUPS: usize = 200_000;
+
+/// Skew of the key popularity; higher values concentrate on fewer keys.
+const EXPONENT: f64 = 1.0;
+
+/// Samples key indices following a Zipf distribution.
+struct Zipf {
+    cumulative: Vec<f64>,
+    state: u64,
+}
+
+impl Zipf {
+    fn new(keys: usize, exponent: f64, seed: u64) -> Self {
+        let mut total = 0.0;
+        let mut cumulative = Vec::with_capacity(keys);
+        for rank in 1..=keys {
+            total += 1.0 / (rank as f64).powf(exponent);
+            cumulative.push(total);
+        }
+        for weight in &mut cumulative {
+            *weight /= total;
+        }
+        Self {
+            cumulative,
+            state: seed,
+        }
+    }
+
+    /// Returns a uniform number in `[0, 1)` from a xorshift generator.
+    fn uniform(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn sample(&mut self) -> usize {
+        let u = self.uniform();
+        self.cumulative.partition_point(|&weight| weight < u)
+    }
+}
+
+#[cfg(feature = "in_memory")]
+async fn hit_rate(policy: EvictionPolicy) -> Result<f64> {
+    let map: PersistentMap<usize, usize, _> = PersistentMap::builder(InMemoryBackend::new())
+        .max_capacity(CAPACITY)
+        .eviction(policy)
+        .build()
+        .await?;
+
+    let mut zipf = Zipf::new(KEYS, EXPONENT, 0x9E37_79B9_7F4A_7C15);
+    let mut hits = 0;
+    for _ in 0..LOOKUPS {
+        let key = zipf.sample();
+        if map.get(&key).is_some() {
+            hits += 1;
+        } else {
+            map.insert(key, key).await?;
+        }
+    }
+    Ok(f64::from(hits) / LOOKUPS as f64)
+}
+
+#[cfg(feature = "in_memory")]
+#[tokio::main]
+async fn main() -> Result<()> {
+    println!("{LOOKUPS} lookups over {KEYS} keys (Zipf s = {EXPONENT}), capacity {CAPACITY}");
+    for policy in [
+        EvictionPolicy::Lru,
+        EvictionPolicy::Lfu,
+        EvictionPolicy::Fifo,
+    ] {
+        let rate = hit_rate(policy).await?;
+        println!("{policy:?}: {:.1}% hits", rate * 100.0);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "in_memory"))]
+fn main() {
+    println!("This example requires the 'in_memory' feature to be enabled.");
+}
@@ -0,0 +1,99 @@
+//! Derive macros for `persistent-map` key and value types.
+//!
+//! Enable the `derive` feature of `persistent-map` to use them; it
+//! re-exports both macros next to the traits they check, so they are
+//! imported with `use persistent_map::{PersistentKey, PersistentValue};`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, DeriveInput, GenericParam};
+
+/// Checks at the type's definition that it can be stored as a map value.
+///
+/// This generates no impls. It fails to compile, with the error pointing at
+/// the derive, if the type is missing one of the bounds of
+/// `persistent_map::PersistentValue`: `Clone`, `Serialize`,
+/// `DeserializeOwned`, `Send`, `Sync` and `'static`. Generic types are
+/// checked for type arguments that are values themselves.
+#[proc_macro_derive(PersistentValue)]
+pub fn derive_persistent_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    check(&input, &quote!(::persistent_map::PersistentValue)).into()
+}
+
+/// Implements the string encoding that backends storing keys as text
+/// require, and checks that the type can be used as a map key.
+///
+/// `Display` writes the key's JSON encoding and `FromStr` parses it back,
+/// so any serializable type works with `SqliteBackend`, `CsvBackend` and the
+/// other backends that need `ToString + FromStr` keys. A struct
+/// `UserId { tenant: 1, id: 7 }` is stored as `{"tenant":1,"id":7}`, and a
+/// newtype around a `String` keeps the JSON quotes, so prefix scans must
+/// include them. Serialization of a key must not fail, or formatting it
+/// panics.
+///
+/// The type must not implement `Display` or `FromStr` itself, and must also
+/// have the other bounds of `persistent_map::PersistentKey`, which the
+/// derive checks like [`PersistentValue`](derive@PersistentValue) does.
+#[proc_macro_derive(PersistentKey)]
+pub fn derive_persistent_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut display_where = where_clause.cloned().unwrap_or_else(|| parse_quote!(where));
+    display_where
+        .predicates
+        .push(parse_quote!(Self: ::persistent_map::__private::serde::Serialize));
+    let mut from_str_where = where_clause.cloned().unwrap_or_else(|| parse_quote!(where));
+    from_str_where.predicates.push(parse_quote!(
+        Self: ::persistent_map::__private::serde::de::DeserializeOwned
+    ));
+    let check = check(&input, &quote!(::persistent_map::PersistentKey));
+
+    quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #display_where {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::persistent_map::__private::write_key(self, f)
+            }
+        }
+
+        impl #impl_generics ::std::str::FromStr for #name #ty_generics #from_str_where {
+            type Err = ::persistent_map::__private::serde_json::Error;
+
+            fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+                ::persistent_map::__private::serde_json::from_str(s)
+            }
+        }
+
+        #check
+    }
+    .into()
+}
+
+/// Asserts that the derived type implements `bound`, assuming its type
+/// parameters are values.
+fn check(input: &DeriveInput, bound: &TokenStream2) -> TokenStream2 {
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param
+                .bounds
+                .push(parse_quote!(::persistent_map::PersistentValue));
+        }
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    quote! {
+        const _: () = {
+            fn assert_bound<T: #bound>() {}
+
+            #[allow(dead_code)]
+            fn check #impl_generics () #where_clause {
+                assert_bound::<#name #ty_generics>();
+            }
+        };
+    }
+}
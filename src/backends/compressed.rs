@@ -0,0 +1,277 @@
+//! Compressing wrapper around another backend.
+//!
+//! This module provides `CompressedBackend`, which stores each value as its
+//! JSON encoding, compressed with DEFLATE once it reaches a size threshold,
+//! in a backend of byte strings.
+
+use crate::StorageBackend;
+use crate::{PersistentError, Result};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    io::{Read, Write},
+    time::SystemTime,
+};
+
+/// Header byte of a value stored as-is.
+const RAW: u8 = 0;
+
+/// Header byte of a value stored DEFLATE-compressed.
+const DEFLATED: u8 = 1;
+
+/// A backend that compresses large values before handing them to another
+/// backend.
+///
+/// Values are serialized to JSON. Encodings shorter than the threshold are
+/// stored as they are, and longer ones are compressed, so small values do
+/// not pay for compression they would not benefit from. Each stored value
+/// starts with a one-byte header recording which form follows, so changing
+/// the threshold never makes existing rows unreadable.
+///
+/// The wrapped backend stores the resulting byte strings, which makes it a
+/// `StorageBackend<K, Vec<u8>>`. Keys are passed through unchanged.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// use persistent_map::compressed::CompressedBackend;
+/// # #[cfg(feature = "sqlite")]
+/// use persistent_map::sqlite::SqliteBackend;
+///
+/// # #[cfg(feature = "sqlite")]
+/// # async fn example() -> Result<()> {
+/// // Compress values whose JSON is 1 KiB or more
+/// let backend = CompressedBackend::new(SqliteBackend::new_binary("docs.db").await?, 1024);
+/// let docs: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "sqlite"))]
+/// # fn example() {}
+/// ```
+pub struct CompressedBackend<B> {
+    /// The backend storing the encoded values
+    inner: B,
+
+    /// Encodings at least this long are compressed
+    threshold: usize,
+}
+
+impl<B> CompressedBackend<B> {
+    /// Wraps `inner`, compressing values whose JSON encoding is at least
+    /// `threshold` bytes long.
+    ///
+    /// A threshold of zero compresses every value, and `usize::MAX`
+    /// compresses none.
+    pub const fn new(inner: B, threshold: usize) -> Self {
+        Self { inner, threshold }
+    }
+
+    /// Returns the size threshold at which values are compressed.
+    #[must_use]
+    pub const fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns the wrapped backend.
+    #[must_use]
+    pub const fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Encodes JSON bytes into the stored form, with its header.
+    fn pack(&self, json: &[u8]) -> Result<Vec<u8>> {
+        if json.len() < self.threshold {
+            let mut packed = Vec::with_capacity(json.len() + 1);
+            packed.push(RAW);
+            packed.extend_from_slice(json);
+            return Ok(packed);
+        }
+        let mut encoder = DeflateEncoder::new(vec![DEFLATED], Compression::default());
+        encoder.write_all(json)?;
+        Ok(encoder.finish()?)
+    }
+
+    /// Encodes a value into the stored form.
+    fn encode<V: Serialize>(&self, value: &V) -> Result<Vec<u8>> {
+        self.pack(&serde_json::to_vec(value)?)
+    }
+}
+
+/// Returns the JSON bytes held by a stored value.
+fn unpack(stored: &[u8]) -> Result<Vec<u8>> {
+    match stored.split_first() {
+        Some((&RAW, json)) => Ok(json.to_vec()),
+        Some((&DEFLATED, compressed)) => {
+            let mut json = Vec::new();
+            DeflateDecoder::new(compressed).read_to_end(&mut json)?;
+            Ok(json)
+        }
+        Some((header, _)) => Err(invalid_data(format!(
+            "unknown compressed value header {header}"
+        ))),
+        None => Err(invalid_data("empty compressed value".to_string())),
+    }
+}
+
+/// Decodes a stored value.
+fn decode<V: DeserializeOwned>(stored: &[u8]) -> Result<V> {
+    Ok(serde_json::from_slice(&unpack(stored)?)?)
+}
+
+fn invalid_data(message: String) -> PersistentError {
+    PersistentError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+#[async_trait::async_trait]
+impl<K, V, B> StorageBackend<K, V> for CompressedBackend<B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, Vec<u8>> + Send + Sync + 'static,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        self.inner
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(key, stored)| Ok((key, decode(&stored)?)))
+            .collect()
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.inner.save(key, self.encode(&value)?).await
+    }
+
+    async fn save_if_newer(&self, key: K, value: V, version: u64) -> Result<bool, PersistentError> {
+        self.inner
+            .save_if_newer(key, self.encode(&value)?, version)
+            .await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete(&self.inner, key).await
+    }
+
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.encode(&value)?)))
+            .collect::<Result<_>>()?;
+        self.inner.save_many(entries).await
+    }
+
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete_many(&self.inner, keys).await
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete_all(&self.inner).await
+    }
+
+    /// Moves the stored form as it is, without decompressing it.
+    async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::rename_keys(&self.inner, renames).await
+    }
+
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        let saves = saves
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.encode(&value)?)))
+            .collect::<Result<_>>()?;
+        self.inner.write_batch(saves, deletes).await
+    }
+
+    /// Returns the stored value's JSON, decompressed if needed.
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        match StorageBackend::<K, Vec<u8>>::load_one_raw(&self.inner, key).await? {
+            Some(stored) => Ok(Some(unpack(&serde_json::from_slice::<Vec<u8>>(&stored)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        self.inner
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(key, stored)| Ok((key, unpack(&stored)?)))
+            .collect()
+    }
+
+    /// Compresses the JSON bytes as they are, after checking they are
+    /// well-formed, without decoding them into `V`.
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        serde_json::from_slice::<serde::de::IgnoredAny>(&value)?;
+        self.inner.save(key, self.pack(&value)?).await
+    }
+
+    async fn flush(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::flush(&self.inner).await
+    }
+
+    async fn sync(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::sync(&self.inner).await
+    }
+
+    async fn checkpoint(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::checkpoint(&self.inner).await
+    }
+
+    async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::check_integrity(&self.inner, thorough).await
+    }
+
+    async fn save_expiring(
+        &self,
+        key: K,
+        value: V,
+        expires_at: SystemTime,
+    ) -> Result<(), PersistentError> {
+        self.inner
+            .save_expiring(key, self.encode(&value)?, expires_at)
+            .await
+    }
+
+    async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::load_expiries(&self.inner).await
+    }
+
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::load_write_times(&self.inner).await
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete_expired(&self.inner, now).await
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::contains_key(&self.inner, key).await
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::len(&self.inner).await
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::is_empty(&self.inner).await
+    }
+
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::last_modified(&self.inner).await
+    }
+
+    fn is_read_only(&self) -> bool {
+        StorageBackend::<K, Vec<u8>>::is_read_only(&self.inner)
+    }
+}
@@ -1,10 +1,51 @@
-use crate::{PersistentError, Result, StorageBackend};
-use csv::{ReaderBuilder, WriterBuilder};
+use crate::{BackendStats, PersistentError, Result, StorageBackend};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
 use serde::{de::DeserializeOwned, Serialize};
-use std::{collections::HashMap, fs::OpenOptions, hash::Hash, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    hash::Hash,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+    time::SystemTime,
+};
 
+/// A CSV file-based storage backend for `PersistentMap`.
+///
+/// Every write is appended as a new row and the file is replayed on load, so
+/// later rows override earlier ones. Batched writes are appended in exactly
+/// the order they are given.
+///
+/// Each row holds the key followed by the value's fields, one column per
+/// field, so a struct value reads as a regular table. Nested structs and
+/// tuples are flattened in field order. Values the CSV format cannot
+/// flatten, such as maps, structs with `#[serde(flatten)]` fields and struct
+/// enum variants, are written as a single column holding their JSON
+/// encoding instead, and are decoded from it on load.
+///
+/// Since updates append rows, the file keeps growing with every write of a
+/// key. [`compact`](Self::compact) rewrites it with only the latest row of
+/// each key, and [`with_compaction_threshold`](Self::with_compaction_threshold)
+/// does so automatically as the file grows.
+///
+/// Every read and write of the file, compaction included, holds a lock, so
+/// a compaction or a rewrite never loses rows appended by a concurrent
+/// write.
 pub struct CsvBackend {
     path: PathBuf,
+
+    /// Held by every read and write of the file
+    file_lock: Mutex<()>,
+
+    /// File size in bytes past which writes compact the file
+    compaction_threshold: Option<u64>,
+
+    /// File size in bytes after the last compaction
+    compacted_size: AtomicU64,
 }
 
 impl CsvBackend {
@@ -28,7 +69,175 @@ impl CsvBackend {
     /// let backend = CsvBackend::new("my_data.csv");
     /// ```
     pub fn new(path: impl Into<PathBuf>) -> Self {
-        Self { path: path.into() }
+        Self {
+            path: path.into(),
+            file_lock: Mutex::new(()),
+            compaction_threshold: None,
+            compacted_size: AtomicU64::new(0),
+        }
+    }
+
+    /// Compacts the file after a write once it is larger than `bytes`.
+    ///
+    /// So that a file whose live rows alone exceed the threshold is not
+    /// rewritten on every write, it is also only compacted once it has
+    /// doubled in size since the last compaction. Compaction rewrites the
+    /// whole file, and that write's call returns only once it is done.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::csv::CsvBackend;
+    ///
+    /// // Compact once the file grows past 1 MiB
+    /// let backend = CsvBackend::new("my_data.csv").with_compaction_threshold(1 << 20);
+    /// ```
+    #[must_use]
+    pub const fn with_compaction_threshold(mut self, bytes: u64) -> Self {
+        self.compaction_threshold = Some(bytes);
+        self
+    }
+
+    /// Rewrites the file with only the latest row of each key.
+    ///
+    /// Rows are kept as they are, in the order of their key's last write, so
+    /// loading the compacted file gives the same entries. The new file is
+    /// written next to the old one and renamed over it, so a crash leaves
+    /// either the old or the new file. Writes made by other tasks while it
+    /// runs wait for it to finish.
+    ///
+    /// It reads and writes the file synchronously, like the backend's other
+    /// operations.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::csv::CsvBackend;
+    /// use persistent_map::Result;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let backend = CsvBackend::new("my_data.csv");
+    /// backend.compact().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, parsed or replaced.
+    // Async like the backend's other I/O entry points
+    #[allow(clippy::unused_async)]
+    pub async fn compact(&self) -> Result<()> {
+        let _file = self.lock_file();
+        self.compact_locked()
+    }
+
+    /// Takes the lock held by every read and write of the file.
+    fn lock_file(&self) -> MutexGuard<'_, ()> {
+        self.file_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Compacts the file; the caller holds the file lock.
+    fn compact_locked(&self) -> Result<()> {
+        self.ensure_file_exists()?;
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&self.path)
+            .map_err(|e| PersistentError::Csv(e.to_string()))?;
+        let records = rdr
+            .records()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| PersistentError::Csv(e.to_string()))?;
+        let latest: HashMap<&str, usize> = records
+            .iter()
+            .enumerate()
+            .map(|(i, record)| (record.get(0).unwrap_or_default(), i))
+            .collect();
+
+        let mut wtr = WriterBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_writer(Vec::new());
+        for (i, record) in records.iter().enumerate() {
+            if latest.get(record.get(0).unwrap_or_default()) == Some(&i) {
+                wtr.write_record(record)
+                    .map_err(|e| PersistentError::Csv(e.to_string()))?;
+            }
+        }
+        let rows = wtr
+            .into_inner()
+            .map_err(|e| PersistentError::Csv(e.to_string()))?;
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".compact");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&rows)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        self.compacted_size.store(
+            u64::try_from(rows.len()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        Ok(())
+    }
+
+    /// Compacts the file if it grew past the compaction threshold; the
+    /// caller holds the file lock.
+    fn compact_if_grown(&self) -> Result<()> {
+        let Some(threshold) = self.compaction_threshold else {
+            return Ok(());
+        };
+        let compacted = self.compacted_size.load(Ordering::Relaxed);
+        if self.path.metadata()?.len() > threshold.max(compacted.saturating_mul(2)) {
+            self.compact_locked()?;
+        }
+        Ok(())
+    }
+
+    /// Appends encoded rows to the file, compacting it afterwards if it grew
+    /// past the threshold.
+    fn append(&self, rows: &[u8]) -> Result<()> {
+        let _file = self.lock_file();
+        self.ensure_file_exists()?;
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(rows)?;
+        self.compact_if_grown()
+    }
+
+    /// Reads every entry of the file; the caller holds the file lock.
+    fn read_all<K, V>(&self) -> Result<HashMap<K, V>>
+    where
+        K: Eq + Hash + std::str::FromStr,
+        V: DeserializeOwned,
+    {
+        // Ensure the file exists
+        self.ensure_file_exists()?;
+
+        // If the file was just created, it's empty, so return an empty HashMap
+        if self.path.metadata()?.len() == 0 {
+            return Ok(HashMap::new());
+        }
+
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&self.path)
+            .map_err(|e| PersistentError::Csv(e.to_string()))?;
+        let mut map = HashMap::new();
+        for result in rdr.records() {
+            let record = result.map_err(|e| PersistentError::Csv(e.to_string()))?;
+            let (kstr, v) = decode_row::<V>(&record)?;
+            let key = kstr.parse::<K>().map_err(|_| {
+                PersistentError::Serde(serde_json::Error::io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Invalid key",
+                )))
+            })?;
+            map.insert(key, v);
+        }
+        Ok(map)
     }
 
     /// Ensures the CSV file exists by creating it if it doesn't.
@@ -69,64 +278,151 @@ where
     V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
 {
     async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
-        // Ensure the file exists
-        self.ensure_file_exists()?;
-
-        // If the file was just created, it's empty, so return an empty HashMap
-        if self.path.metadata()?.len() == 0 {
-            return Ok(HashMap::new());
-        }
-
-        let mut rdr = ReaderBuilder::new()
-            .has_headers(false)
-            .from_path(&self.path)
-            .map_err(|e| PersistentError::Csv(e.to_string()))?;
-        let mut map = HashMap::new();
-        for result in rdr.deserialize::<(String, V)>() {
-            let (kstr, v) = result.map_err(|e| PersistentError::Csv(e.to_string()))?;
-            let key = kstr.parse::<K>().map_err(|_| {
-                PersistentError::Serde(serde_json::Error::io(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Invalid key",
-                )))
-            })?;
-            map.insert(key, v);
-        }
-        Ok(map)
+        let _file = self.lock_file();
+        self.read_all()
     }
 
     async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
-        // Ensure the file exists
-        self.ensure_file_exists()?;
+        let mut rows = Vec::new();
+        encode_row(&mut rows, &key.to_string(), &value)?;
+        self.append(&rows)
+    }
 
-        let file = OpenOptions::new().append(true).open(&self.path)?;
+    /// Appends all entries in a single writer pass.
+    ///
+    /// The CSV file is an append-only log replayed on load, so the rows are
+    /// written in exactly the order given to keep the replay deterministic.
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        let mut rows = Vec::new();
+        for (key, value) in &entries {
+            encode_row(&mut rows, &key.to_string(), value)?;
+        }
+        self.append(&rows)
+    }
 
-        let mut wtr = WriterBuilder::new().has_headers(false).from_writer(file);
+    /// Returns the modification time of the CSV file, or `None` if it
+    /// doesn't exist yet.
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        Ok(super::latest_mtime([&self.path])?)
+    }
 
-        wtr.serialize((key.to_string(), value))
-            .map_err(|e| PersistentError::Csv(e.to_string()))?;
+    /// Reports the `file_size_bytes` of the CSV file, which is zero before
+    /// anything was written.
+    async fn stats(&self) -> Result<BackendStats, PersistentError> {
+        let size = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        let mut stats = BackendStats::new();
+        stats.insert("file_size_bytes".to_string(), size.to_string());
+        Ok(stats)
+    }
 
-        wtr.flush()?;
+    /// Calls `fsync` on the CSV file.
+    async fn sync(&self) -> Result<(), PersistentError> {
+        self.ensure_file_exists()?;
+        std::fs::File::open(&self.path)?.sync_all()?;
         Ok(())
     }
 
     async fn delete(&self, key: &K) -> Result<(), PersistentError> {
-        let mut all: HashMap<K, V> = self.load_all().await?;
-        all.remove(key);
+        StorageBackend::<K, V>::delete_many(self, vec![key.clone()]).await
+    }
+
+    /// Removes all keys and rewrites the file once.
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        let _file = self.lock_file();
+        let mut all: HashMap<K, V> = self.read_all()?;
+        for key in &keys {
+            all.remove(key);
+        }
 
-        let file = OpenOptions::new()
+        let mut rows = Vec::new();
+        for (k, v) in &all {
+            encode_row(&mut rows, &k.to_string(), v)?;
+        }
+
+        let mut file = OpenOptions::new()
             .write(true)
             .truncate(true)
             .open(&self.path)?;
+        file.write_all(&rows)?;
+        Ok(())
+    }
+}
 
-        let mut wtr = WriterBuilder::new().has_headers(false).from_writer(file);
+/// Appends the row for `key` and `value` to `out`.
+///
+/// The row is encoded in memory first, so a value that cannot be written
+/// never leaves a partial row in the file.
+pub(crate) fn encode_row<V: Serialize>(out: &mut Vec<u8>, key: &str, value: &V) -> Result<()> {
+    let row = match flatten_row(key, value)? {
+        Some(row) => row,
+        // Not flattenable; write the value as one JSON column
+        None => write_row(&[key, &serde_json::to_string(value)?])?,
+    };
+    out.extend_from_slice(&row);
+    Ok(())
+}
 
-        for (k, v) in all {
-            wtr.serialize((k.to_string(), v))
-                .map_err(|e| PersistentError::Csv(e.to_string()))?;
-        }
+/// Encodes `key` followed by the fields of `value` as one row.
+///
+/// Returns `None` if the value cannot be flattened, or if it has no fields
+/// at all (such as an empty `Vec`), since a row holding only the key could
+/// not be told apart from other shapes on load.
+fn flatten_row<V: Serialize>(key: &str, value: &V) -> Result<Option<Vec<u8>>> {
+    let mut wtr = WriterBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_writer(Vec::new());
+    match wtr.serialize((key, value)) {
+        Ok(()) => {}
+        Err(e) if matches!(e.kind(), csv::ErrorKind::Serialize(_)) => return Ok(None),
+        Err(e) => return Err(PersistentError::Csv(e.to_string())),
+    }
+    let row = wtr
+        .into_inner()
+        .map_err(|e| PersistentError::Csv(e.to_string()))?;
+    if row == write_row(&[key])? {
+        return Ok(None);
+    }
+    Ok(Some(row))
+}
 
-        wtr.flush()?;
-        Ok(())
+/// Encodes `fields` as one row.
+fn write_row(fields: &[&str]) -> Result<Vec<u8>> {
+    let mut wtr = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    wtr.write_record(fields)
+        .map_err(|e| PersistentError::Csv(e.to_string()))?;
+    wtr.into_inner()
+        .map_err(|e| PersistentError::Csv(e.to_string()))
+}
+
+/// Decodes a row written by [`encode_row`] into its key string and value.
+///
+/// A two-column row whose value does not decode from its columns is tried
+/// as a JSON-encoded value, as written for values that cannot be flattened.
+/// An empty JSON array or object is tried first, since that is how values
+/// without fields are written; a value whose only field is the literal text
+/// `[]` or `{}` therefore reads back as empty.
+pub(crate) fn decode_row<V: DeserializeOwned>(record: &StringRecord) -> Result<(String, V)> {
+    if let (2, Some(key), Some(json @ ("[]" | "{}"))) = (record.len(), record.get(0), record.get(1))
+    {
+        if let Ok(value) = serde_json::from_str(json) {
+            return Ok((key.to_string(), value));
+        }
+    }
+    let error = match record.deserialize::<(String, V)>(None) {
+        Ok(row) => return Ok(row),
+        Err(e) => e,
+    };
+    if let (2, Some(key), Some(json)) = (record.len(), record.get(0), record.get(1)) {
+        if let Ok(value) = serde_json::from_str(json) {
+            return Ok((key.to_string(), value));
+        }
     }
+    Err(PersistentError::Csv(error.to_string()))
 }
@@ -0,0 +1,379 @@
+//! Wrapper around another backend that keeps large values in files.
+//!
+//! This module provides `ExternalBlobBackend`, which writes the JSON encoding
+//! of each value above a size threshold to a directory of content-addressed
+//! files, and stores only a reference to the file in the wrapped backend.
+
+use crate::StorageBackend;
+use crate::{PersistentError, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    hash::Hash,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+/// What an [`ExternalBlobBackend`] stores in its wrapped backend for a value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoredValue {
+    /// A value small enough to be stored as it is, stored as
+    /// `{"inline": <value>}`
+    Inline(serde_json::Value),
+
+    /// A value stored in the blob file named by the hex SHA-256 hash of its
+    /// JSON encoding, stored as `{"blob": "<hash>"}`
+    Blob(String),
+}
+
+/// A backend that moves large values out of another backend into files.
+///
+/// Values are serialized to JSON. Encodings shorter than the threshold stay
+/// inline in the wrapped backend, and longer ones are written to a file in
+/// the blob directory named by the SHA-256 hash of the encoding, with only
+/// the hash stored in the wrapped backend. Loads read the blob files back
+/// transparently. This keeps rows small for backends that slow down with
+/// large values, such as a `SQLite` table scanned on every load.
+///
+/// Blob files are written to a temporary name, synced and renamed before the
+/// reference to them is saved, so a stored reference always names a complete
+/// file. Because files are named by their content, keys holding the same
+/// large value share one file, and deleting or overwriting a key does not
+/// remove its file: call
+/// [`remove_unreferenced_blobs`](Self::remove_unreferenced_blobs) to reclaim
+/// the space.
+///
+/// The wrapped backend stores [`StoredValue`]s, which makes it a
+/// `StorageBackend<K, StoredValue>`. Inline values are kept as JSON values,
+/// so the wrapped backend must use a self-describing encoding, like
+/// `SqliteBackend::new` does; `SqliteBackend::new_binary` cannot decode
+/// them. Keys are passed through unchanged.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// use persistent_map::external::ExternalBlobBackend;
+/// # #[cfg(feature = "sqlite")]
+/// use persistent_map::sqlite::SqliteBackend;
+///
+/// # #[cfg(feature = "sqlite")]
+/// # async fn example() -> Result<()> {
+/// // Keep values whose JSON is 64 KiB or more out of the database
+/// let backend = ExternalBlobBackend::new(SqliteBackend::new("docs.db").await?, "docs-blobs", 64 * 1024)?;
+/// let docs: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "sqlite"))]
+/// # fn example() {}
+/// ```
+pub struct ExternalBlobBackend<B> {
+    /// The backend storing inline values and blob references
+    inner: B,
+
+    /// The directory holding the blob files
+    dir: PathBuf,
+
+    /// Encodings at least this long are written to blob files
+    threshold: usize,
+
+    /// Distinguishes the temporary files of concurrent blob writes
+    next_tmp: AtomicU64,
+}
+
+impl<B> ExternalBlobBackend<B> {
+    /// Wraps `inner`, writing values whose JSON encoding is at least
+    /// `threshold` bytes long to files in `dir`.
+    ///
+    /// The directory is created if it does not exist. A threshold of zero
+    /// moves every value out, and `usize::MAX` none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created.
+    pub fn new(inner: B, dir: impl Into<PathBuf>, threshold: usize) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            inner,
+            dir,
+            threshold,
+            next_tmp: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the size threshold at which values are written to blob files.
+    #[must_use]
+    pub const fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns the directory holding the blob files.
+    #[must_use]
+    pub fn blob_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the wrapped backend.
+    #[must_use]
+    pub const fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Deletes the blob files that no stored value refers to, and returns
+    /// how many were deleted.
+    ///
+    /// This reads every stored value, so run it off the hot path. A blob
+    /// written by a save that is still in flight is not referenced yet and
+    /// would be deleted, so only run it while nothing writes to the backend,
+    /// for example after flushing the map at startup or shutdown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the wrapped backend or the blob directory
+    /// fails, or a file cannot be deleted.
+    pub async fn remove_unreferenced_blobs<K>(&self) -> Result<usize>
+    where
+        K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+        B: StorageBackend<K, StoredValue> + Sync,
+    {
+        let referenced: HashSet<String> = self
+            .inner
+            .load_all()
+            .await?
+            .into_values()
+            .filter_map(|stored| match stored {
+                StoredValue::Blob(hash) => Some(hash),
+                StoredValue::Inline(_) => None,
+            })
+            .collect();
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if is_hash(name) && !referenced.contains(name) {
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Encodes JSON bytes into the stored form, writing a blob file if they
+    /// reach the threshold.
+    fn pack(&self, json: &[u8]) -> Result<StoredValue> {
+        if json.len() < self.threshold {
+            return Ok(StoredValue::Inline(serde_json::from_slice(json)?));
+        }
+        let hash = format!("{:x}", Sha256::digest(json));
+        let path = self.dir.join(&hash);
+        // The file already holds these exact bytes
+        if path.exists() {
+            return Ok(StoredValue::Blob(hash));
+        }
+        let tmp = self.dir.join(format!(
+            ".{hash}.{}.{}.tmp",
+            std::process::id(),
+            self.next_tmp.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = File::create(&tmp)?;
+        file.write_all(json)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(StoredValue::Blob(hash))
+    }
+
+    /// Encodes a value into the stored form.
+    fn encode<V: Serialize>(&self, value: &V) -> Result<StoredValue> {
+        self.pack(&serde_json::to_vec(value)?)
+    }
+
+    /// Returns the JSON bytes of a stored value, reading its blob file if it
+    /// has one.
+    fn unpack(&self, stored: StoredValue) -> Result<Vec<u8>> {
+        match stored {
+            StoredValue::Inline(value) => Ok(serde_json::to_vec(&value)?),
+            StoredValue::Blob(hash) if is_hash(&hash) => match fs::read(self.dir.join(&hash)) {
+                Ok(json) => Ok(json),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    Err(invalid_data(format!("missing blob file {hash}")))
+                }
+                Err(e) => Err(e.into()),
+            },
+            StoredValue::Blob(hash) => Err(invalid_data(format!("invalid blob hash {hash:?}"))),
+        }
+    }
+
+    /// Decodes a stored value.
+    fn decode<V: DeserializeOwned>(&self, stored: StoredValue) -> Result<V> {
+        match stored {
+            StoredValue::Inline(value) => Ok(serde_json::from_value(value)?),
+            blob @ StoredValue::Blob(_) => Ok(serde_json::from_slice(&self.unpack(blob)?)?),
+        }
+    }
+}
+
+/// Returns whether `name` is a hex SHA-256 hash, so blob references can't
+/// name files outside the blob directory.
+fn is_hash(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn invalid_data(message: String) -> PersistentError {
+    PersistentError::Io(std::io::Error::new(ErrorKind::InvalidData, message))
+}
+
+#[async_trait::async_trait]
+impl<K, V, B> StorageBackend<K, V> for ExternalBlobBackend<B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, StoredValue> + Send + Sync + 'static,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        self.inner
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(key, stored)| Ok((key, self.decode(stored)?)))
+            .collect()
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.inner.save(key, self.encode(&value)?).await
+    }
+
+    async fn save_if_newer(&self, key: K, value: V, version: u64) -> Result<bool, PersistentError> {
+        self.inner
+            .save_if_newer(key, self.encode(&value)?, version)
+            .await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::delete(&self.inner, key).await
+    }
+
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.encode(&value)?)))
+            .collect::<Result<_>>()?;
+        self.inner.save_many(entries).await
+    }
+
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::delete_many(&self.inner, keys).await
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::delete_all(&self.inner).await
+    }
+
+    /// Moves the stored references, leaving the blob files where they are.
+    async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::rename_keys(&self.inner, renames).await
+    }
+
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        let saves = saves
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.encode(&value)?)))
+            .collect::<Result<_>>()?;
+        self.inner.write_batch(saves, deletes).await
+    }
+
+    /// Returns the stored value's JSON, read from its blob file if needed.
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        match StorageBackend::<K, StoredValue>::load_one_raw(&self.inner, key).await? {
+            Some(stored) => Ok(Some(self.unpack(serde_json::from_slice(&stored)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        self.inner
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(key, stored)| Ok((key, self.unpack(stored)?)))
+            .collect()
+    }
+
+    /// Stores the JSON bytes as they are, without decoding them into `V`.
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        serde_json::from_slice::<serde::de::IgnoredAny>(&value)?;
+        self.inner.save(key, self.pack(&value)?).await
+    }
+
+    async fn flush(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::flush(&self.inner).await
+    }
+
+    async fn sync(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::sync(&self.inner).await
+    }
+
+    async fn checkpoint(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::checkpoint(&self.inner).await
+    }
+
+    async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+        StorageBackend::<K, StoredValue>::check_integrity(&self.inner, thorough).await
+    }
+
+    async fn save_expiring(
+        &self,
+        key: K,
+        value: V,
+        expires_at: SystemTime,
+    ) -> Result<(), PersistentError> {
+        self.inner
+            .save_expiring(key, self.encode(&value)?, expires_at)
+            .await
+    }
+
+    async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        StorageBackend::<K, StoredValue>::load_expiries(&self.inner).await
+    }
+
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        StorageBackend::<K, StoredValue>::load_write_times(&self.inner).await
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+        StorageBackend::<K, StoredValue>::delete_expired(&self.inner, now).await
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        StorageBackend::<K, StoredValue>::contains_key(&self.inner, key).await
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        StorageBackend::<K, StoredValue>::len(&self.inner).await
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        StorageBackend::<K, StoredValue>::is_empty(&self.inner).await
+    }
+
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        StorageBackend::<K, StoredValue>::last_modified(&self.inner).await
+    }
+
+    fn is_read_only(&self) -> bool {
+        StorageBackend::<K, StoredValue>::is_read_only(&self.inner)
+    }
+}
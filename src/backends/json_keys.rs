@@ -0,0 +1,255 @@
+//! Key-encoding wrapper around another backend.
+//!
+//! This module provides `JsonKeys`, which lets a backend that stores keys as
+//! text hold keys of any serializable type, such as tuples.
+
+use crate::{BackendStats, Change, ChangeToken, PersistentError, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, fmt, hash::Hash, str::FromStr, time::SystemTime};
+
+/// A key stored as the text of its JSON encoding.
+///
+/// `Display` writes the JSON encoding and `FromStr` parses it back, so any
+/// serializable key can be handed to a backend that needs
+/// `ToString + FromStr` keys. [`JsonKeys`] wraps and unwraps the keys, so
+/// maps rarely name this type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JsonKey<K>(pub K);
+
+impl<K: Serialize> fmt::Display for JsonKey<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(&self.0).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl<K: DeserializeOwned> FromStr for JsonKey<K> {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map(Self)
+    }
+}
+
+/// A backend that stores each key as a [`JsonKey`] in another backend.
+///
+/// The wrapped backend is a `StorageBackend<JsonKey<K>, V>`, so this only
+/// needs `K: Serialize + DeserializeOwned` rather than the
+/// `ToString + FromStr` bounds of backends such as `SqliteBackend`. Values
+/// are passed through unchanged.
+///
+/// Keys are compared by their JSON text in storage, so two keys that are
+/// equal in Rust must serialize identically: `HashMap` keys, whose JSON
+/// order varies, are unsuitable. `scan_prefix` and the paging methods are not
+/// forwarded, as they order keys by `ToString`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// # #[cfg(feature = "sqlite")]
+/// use persistent_map::sqlite::SqliteBackend;
+///
+/// # #[cfg(feature = "sqlite")]
+/// # async fn example() -> Result<()> {
+/// let backend = SqliteBackend::new_serialized_keys("grid.db").await?;
+/// let grid: PersistentMap<(i32, i32), String, _> = PersistentMap::new(backend).await?;
+/// grid.insert((3, -1), "tree".to_string()).await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "sqlite"))]
+/// # fn example() {}
+/// ```
+pub struct JsonKeys<B> {
+    /// The backend storing the encoded keys
+    inner: B,
+}
+
+impl<B> JsonKeys<B> {
+    /// Wraps `inner`, storing keys as their JSON encoding.
+    pub const fn new(inner: B) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the wrapped backend.
+    #[must_use]
+    pub const fn inner(&self) -> &B {
+        &self.inner
+    }
+}
+
+/// Unwraps the keys of a map loaded from the wrapped backend.
+fn unwrap_keys<K: Eq + Hash, T>(map: HashMap<JsonKey<K>, T>) -> HashMap<K, T> {
+    map.into_iter().map(|(key, value)| (key.0, value)).collect()
+}
+
+/// Wraps each key of `entries`.
+fn wrap_entries<K, V>(entries: Vec<(K, V)>) -> Vec<(JsonKey<K>, V)> {
+    entries
+        .into_iter()
+        .map(|(key, value)| (JsonKey(key), value))
+        .collect()
+}
+
+/// Wraps each key of `keys`.
+fn wrap_keys<K>(keys: Vec<K>) -> Vec<JsonKey<K>> {
+    keys.into_iter().map(JsonKey).collect()
+}
+
+#[async_trait::async_trait]
+impl<K, V, B> StorageBackend<K, V> for JsonKeys<B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<JsonKey<K>, V> + Send + Sync + 'static,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        Ok(unwrap_keys(self.inner.load_all().await?))
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.inner.save(JsonKey(key), value).await
+    }
+
+    async fn save_if_newer(&self, key: K, value: V, version: u64) -> Result<bool, PersistentError> {
+        self.inner.save_if_newer(JsonKey(key), value, version).await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        self.inner.delete(&JsonKey(key.clone())).await
+    }
+
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        self.inner.save_many(wrap_entries(entries)).await
+    }
+
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        self.inner.delete_many(wrap_keys(keys)).await
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        self.inner.delete_all().await
+    }
+
+    async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+        let renames = renames
+            .into_iter()
+            .map(|(from, to)| (JsonKey(from), JsonKey(to)))
+            .collect();
+        self.inner.rename_keys(renames).await
+    }
+
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        self.inner
+            .write_batch(wrap_entries(saves), wrap_keys(deletes))
+            .await
+    }
+
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        self.inner.load_one_raw(&JsonKey(key.clone())).await
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        Ok(unwrap_keys(self.inner.load_all_raw().await?))
+    }
+
+    async fn load_many_raw(&self, keys: &[K]) -> Result<Vec<Option<Vec<u8>>>, PersistentError> {
+        self.inner.load_many_raw(&wrap_keys(keys.to_vec())).await
+    }
+
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        self.inner.save_raw(JsonKey(key), value).await
+    }
+
+    async fn flush(&self) -> Result<(), PersistentError> {
+        self.inner.flush().await
+    }
+
+    async fn sync(&self) -> Result<(), PersistentError> {
+        self.inner.sync().await
+    }
+
+    async fn checkpoint(&self) -> Result<(), PersistentError> {
+        self.inner.checkpoint().await
+    }
+
+    async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+        self.inner.check_integrity(thorough).await
+    }
+
+    async fn save_expiring(
+        &self,
+        key: K,
+        value: V,
+        expires_at: SystemTime,
+    ) -> Result<(), PersistentError> {
+        self.inner
+            .save_expiring(JsonKey(key), value, expires_at)
+            .await
+    }
+
+    async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        Ok(unwrap_keys(self.inner.load_expiries().await?))
+    }
+
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        Ok(unwrap_keys(self.inner.load_write_times().await?))
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+        self.inner.delete_expired(now).await
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        self.inner.contains_key(&JsonKey(key.clone())).await
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        self.inner.len().await
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        self.inner.is_empty().await
+    }
+
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        self.inner.last_modified().await
+    }
+
+    async fn changes_since(
+        &self,
+        since: Option<ChangeToken>,
+    ) -> Result<(Vec<Change<K>>, ChangeToken), PersistentError> {
+        let (changes, token) = self.inner.changes_since(since).await?;
+        let changes = changes
+            .into_iter()
+            .map(|change| match change {
+                Change::Saved {
+                    key,
+                    value,
+                    expires_at,
+                } => Change::Saved {
+                    key: key.0,
+                    value,
+                    expires_at,
+                },
+                Change::Deleted { key } => Change::Deleted { key: key.0 },
+            })
+            .collect();
+        Ok((changes, token))
+    }
+
+    async fn stats(&self) -> Result<BackendStats, PersistentError> {
+        self.inner.stats().await
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+}
@@ -0,0 +1,361 @@
+//! JSON Lines file backend implementation for `PersistentMap`.
+//!
+//! This module provides a backend that stores the map as an append-only log
+//! with one JSON object per line.
+
+use crate::{BackendStats, PersistentError, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    hash::Hash,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+
+/// A JSON Lines file-based storage backend for `PersistentMap`.
+///
+/// Every write appends one line to the file: `{"k":<key>,"v":<value>}` for
+/// a save and `{"k":<key>,"d":true}` for a delete. The file is replayed on
+/// load, so later lines override earlier ones, and batched writes are
+/// appended in exactly the order they are given. Keys are stored as JSON,
+/// so any serializable key type works.
+///
+/// A last line without its terminating newline, as left by a write cut
+/// short by a crash, is ignored on load and dropped before the next append.
+/// Any other line that cannot be parsed fails the load.
+///
+/// Deletes only append tombstones, so the file keeps growing until
+/// [`compact`](Self::compact) rewrites it with the live entries only.
+pub struct JsonLinesBackend {
+    path: PathBuf,
+
+    /// Serializes file access, so a compaction never drops an append
+    lock: Mutex<()>,
+}
+
+/// A line, as read back from the file.
+#[derive(Deserialize)]
+struct Line<K> {
+    k: K,
+
+    #[serde(default)]
+    v: serde_json::Value,
+
+    /// Set on tombstones
+    #[serde(default)]
+    d: bool,
+}
+
+/// A save, as written to the file.
+#[derive(Serialize)]
+struct SaveLine<'a, K, V> {
+    k: &'a K,
+    v: &'a V,
+}
+
+/// A delete, as written to the file.
+#[derive(Serialize)]
+struct DeleteLine<'a, K> {
+    k: &'a K,
+    d: bool,
+}
+
+impl JsonLinesBackend {
+    /// Creates a new JSON Lines backend with the given file path.
+    ///
+    /// If the file doesn't exist, it will be created when needed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::jsonl::JsonLinesBackend;
+    ///
+    /// let backend = JsonLinesBackend::new("my_data.jsonl");
+    /// ```
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Rewrites the file with one line per live entry, dropping overwritten
+    /// values and tombstones. Returns the number of lines removed.
+    ///
+    /// The live entries are written to a temporary file next to the log,
+    /// which then replaces it, so a crash during compaction leaves either
+    /// the old or the new file. Keys are compared by their JSON encoding,
+    /// so no key or value type is needed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::Result;
+    /// use persistent_map::jsonl::JsonLinesBackend;
+    ///
+    /// # fn example() -> Result<()> {
+    /// let backend = JsonLinesBackend::new("my_data.jsonl");
+    /// let removed = backend.compact()?;
+    /// println!("dropped {removed} stale lines");
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, holds a corrupt line, or
+    /// cannot be replaced.
+    pub fn compact(&self) -> Result<usize> {
+        let _guard = self.lock();
+        let Some(lines) = self.read_lines::<serde_json::Value>()? else {
+            return Ok(0);
+        };
+        let total = lines.len();
+        let mut live: HashMap<String, serde_json::Value> = HashMap::new();
+        let mut order = Vec::new();
+        for line in lines {
+            let key = serde_json::to_string(&line.k)?;
+            if line.d {
+                live.remove(&key);
+            } else {
+                if !live.contains_key(&key) {
+                    order.push(key.clone());
+                }
+                live.insert(key, line.v);
+            }
+        }
+
+        let mut out = Vec::new();
+        let mut kept = 0;
+        for key in order {
+            // Keys deleted and saved again appear twice in `order`
+            if let Some(value) = live.remove(&key) {
+                let k: serde_json::Value = serde_json::from_str(&key)?;
+                push_line(&mut out, &SaveLine { k: &k, v: &value })?;
+                kept += 1;
+            }
+        }
+        let tmp = self.path.with_extension("jsonl.compact");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&out)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(total - kept)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Parses every line of the file, or returns `None` if it doesn't exist.
+    fn read_lines<K: DeserializeOwned>(&self) -> Result<Option<Vec<Line<K>>>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut reader = BufReader::new(file);
+        let mut lines = Vec::new();
+        let mut buf = String::new();
+        let mut number = 0;
+        loop {
+            buf.clear();
+            if reader.read_line(&mut buf)? == 0 {
+                break;
+            }
+            number += 1;
+            let terminated = buf.ends_with('\n');
+            let text = buf.trim_end();
+            if text.is_empty() {
+                continue;
+            }
+            match serde_json::from_str(text) {
+                Ok(line) => lines.push(line),
+                // A partial last line from an interrupted write
+                Err(_) if !terminated => break,
+                Err(e) => return Err(invalid_line(number, &e)),
+            }
+        }
+        Ok(Some(lines))
+    }
+
+    /// Appends encoded lines to the file, creating it if needed.
+    fn append(&self, lines: &[u8]) -> Result<()> {
+        let _guard = self.lock();
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        repair_tail(&mut file)?;
+        file.write_all(lines)?;
+        Ok(())
+    }
+}
+
+/// Ends the file with a newline, so the next line starts on its own.
+///
+/// An unterminated last line that parses is kept and terminated, and one
+/// that doesn't, the remains of an interrupted write, is truncated away.
+fn repair_tail(file: &mut File) -> Result<()> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(());
+    }
+    let mut last = [0; 1];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    if last[0] == b'\n' {
+        return Ok(());
+    }
+
+    let mut contents = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut contents)?;
+    let start = contents
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    if serde_json::from_slice::<Line<serde_json::Value>>(&contents[start..]).is_ok() {
+        file.write_all(b"\n")?;
+    } else {
+        file.set_len(start as u64)?;
+    }
+    Ok(())
+}
+
+/// Appends `line` and a newline to `out`.
+///
+/// The line is encoded in memory first, so a value that cannot be written
+/// never leaves a partial line in the file.
+fn push_line<T: Serialize>(out: &mut Vec<u8>, line: &T) -> Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    out.push(b'\n');
+    Ok(())
+}
+
+fn invalid_line(number: usize, error: &serde_json::Error) -> PersistentError {
+    PersistentError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("invalid line {number}: {error}"),
+    ))
+}
+
+#[async_trait::async_trait]
+impl<K, V> StorageBackend<K, V> for JsonLinesBackend
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        let lines = {
+            let _guard = self.lock();
+            self.read_lines::<K>()?
+        };
+        let mut map = HashMap::new();
+        for line in lines.unwrap_or_default() {
+            if line.d {
+                map.remove(&line.k);
+            } else {
+                map.insert(line.k, serde_json::from_value(line.v)?);
+            }
+        }
+        Ok(map)
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        let mut out = Vec::new();
+        push_line(&mut out, &SaveLine { k: &key, v: &value })?;
+        self.append(&out)
+    }
+
+    /// Appends all entries in one write, in the order given.
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        let mut out = Vec::new();
+        for (k, v) in &entries {
+            push_line(&mut out, &SaveLine { k, v })?;
+        }
+        self.append(&out)
+    }
+
+    /// Appends a tombstone for the key.
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        let mut out = Vec::new();
+        push_line(&mut out, &DeleteLine { k: key, d: true })?;
+        self.append(&out)
+    }
+
+    /// Appends a tombstone for every key in one write.
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        let mut out = Vec::new();
+        for k in &keys {
+            push_line(&mut out, &DeleteLine { k, d: true })?;
+        }
+        self.append(&out)
+    }
+
+    /// Truncates the file.
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        let _guard = self.lock();
+        match OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&self.path)
+        {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Appends the saves and then the deletes in one write.
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        let mut out = Vec::new();
+        for (k, v) in &saves {
+            push_line(&mut out, &SaveLine { k, v })?;
+        }
+        for k in &deletes {
+            push_line(&mut out, &DeleteLine { k, d: true })?;
+        }
+        self.append(&out)
+    }
+
+    /// Returns the modification time of the file, or `None` if it doesn't
+    /// exist yet.
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        Ok(super::latest_mtime([&self.path])?)
+    }
+
+    /// Reports the `file_size_bytes` of the file, which is zero before
+    /// anything was written.
+    async fn stats(&self) -> Result<BackendStats, PersistentError> {
+        let size = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        let mut stats = BackendStats::new();
+        stats.insert("file_size_bytes".to_string(), size.to_string());
+        Ok(stats)
+    }
+
+    /// Calls `fsync` on the file.
+    async fn sync(&self) -> Result<(), PersistentError> {
+        match File::open(&self.path) {
+            Ok(file) => Ok(file.sync_all()?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -1,6 +1,41 @@
+#[cfg(feature = "compression")]
+pub mod compressed;
 #[cfg(feature = "csv_backend")]
 pub mod csv;
+#[cfg(feature = "external_blobs")]
+pub mod external;
 #[cfg(feature = "in_memory")]
 pub mod in_memory;
+pub mod json_keys;
+#[cfg(feature = "jsonl_backend")]
+pub mod jsonl;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redis_backend")]
+pub mod redis;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod serialized;
+#[cfg(feature = "sled_backend")]
+pub mod sled;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
+
+/// Returns the latest modification time of the given files, skipping files
+/// that don't exist.
+#[cfg(any(feature = "csv_backend", feature = "jsonl_backend", feature = "sqlite"))]
+fn latest_mtime<I, P>(paths: I) -> std::io::Result<Option<std::time::SystemTime>>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<std::path::Path>,
+{
+    let mut latest = None;
+    for path in paths {
+        match std::fs::metadata(path) {
+            Ok(metadata) => latest = latest.max(Some(metadata.modified()?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(latest)
+}
@@ -0,0 +1,308 @@
+//! Postgres backend implementation for `PersistentMap`.
+//!
+//! This module provides a storage backend for `PostgreSQL`, built on a
+//! `sqlx` connection pool.
+//!
+//! `sqlx` 0.8 needs a newer Rust toolchain than the rest of this crate,
+//! whose minimum supported version is 1.65, so the `postgres` feature has
+//! the MSRV of `sqlx`.
+
+use crate::{PersistentError, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{types::Json, PgPool};
+use std::{collections::HashMap, hash::Hash, str::FromStr};
+
+/// Name of the table used when none is given.
+const DEFAULT_TABLE: &str = "kv";
+
+/// Longest identifier `PostgreSQL` accepts without truncating it.
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// A `PostgreSQL`-based storage backend for `PersistentMap`.
+///
+/// Entries live in a `kv (key TEXT PRIMARY KEY, value JSONB)` table, which
+/// the backend creates if it is absent. Keys are stored with `to_string`
+/// and values as `jsonb`, so they can be queried with the `PostgreSQL` JSON
+/// operators. Batched writes run in one transaction.
+///
+/// # Sharing the database
+///
+/// Several processes can use the same table. Each write is applied
+/// immediately and concurrent writers to a key follow last-writer-wins, but
+/// the map does not watch the table: `load_all` returns a point-in-time
+/// snapshot, and writes made by other processes afterwards are only seen by
+/// the next `load`.
+///
+/// `sqlx` is built without TLS support; enable one of its `tls-*` features
+/// in the application to connect to servers that require it.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// use persistent_map::postgres::PostgresBackend;
+///
+/// # async fn example() -> Result<()> {
+/// let pool = sqlx::PgPool::connect("postgres://localhost/app").await?;
+/// let backend = PostgresBackend::new(pool).await?;
+/// let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PostgresBackend {
+    /// The connection pool
+    pool: PgPool,
+
+    /// The table holding the entries
+    table: String,
+}
+
+impl PostgresBackend {
+    /// Creates a backend storing its entries in the `kv` table.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be created.
+    pub async fn new(pool: PgPool) -> Result<Self> {
+        Self::with_table(pool, DEFAULT_TABLE).await
+    }
+
+    /// Creates a backend storing its entries in `table` instead of `kv`.
+    ///
+    /// The name must start with an ASCII letter or an underscore, contain
+    /// only ASCII letters, digits and underscores, and be at most 63 bytes
+    /// long. It is used unquoted, so `PostgreSQL` folds it to lower case.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::postgres::PostgresBackend;
+    /// use persistent_map::Result;
+    ///
+    /// # async fn example(pool: sqlx::PgPool) -> Result<()> {
+    /// let sessions = PostgresBackend::with_table(pool.clone(), "sessions").await?;
+    /// let users = PostgresBackend::with_table(pool, "users").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns a `PersistentError::Config` error if `table` is not a valid
+    /// table name, or an error if the table cannot be created.
+    pub async fn with_table(pool: PgPool, table: &str) -> Result<Self> {
+        check_table_name(table)?;
+        let backend = Self {
+            pool,
+            table: table.to_string(),
+        };
+        sqlx::query(
+            &backend
+                .sql("CREATE TABLE IF NOT EXISTS kv (key TEXT PRIMARY KEY, value JSONB NOT NULL)"),
+        )
+        .execute(&backend.pool)
+        .await?;
+        Ok(backend)
+    }
+
+    /// Returns the connection pool.
+    #[must_use]
+    pub const fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    /// Returns the name of the table holding the entries.
+    #[must_use]
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Returns `statement`, written against the `kv` table, for this
+    /// backend's table.
+    fn sql(&self, statement: &str) -> String {
+        statement.replace(DEFAULT_TABLE, &self.table)
+    }
+
+    /// Returns the upsert statement used by the saves.
+    fn upsert(&self) -> String {
+        self.sql(
+            "INSERT INTO kv (key, value) VALUES ($1, $2) \
+             ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value",
+        )
+    }
+}
+
+/// Checks that `table` can be used as an unquoted table name.
+fn check_table_name(table: &str) -> Result<()> {
+    let valid = table
+        .chars()
+        .next()
+        .map_or(false, |first| first.is_ascii_alphabetic() || first == '_')
+        && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && table.len() <= MAX_IDENTIFIER_LEN;
+    if valid {
+        Ok(())
+    } else {
+        Err(PersistentError::Config(format!(
+            "invalid table name {table:?}: use at most 63 ASCII letters, digits and underscores, not starting with a digit"
+        )))
+    }
+}
+
+/// Parses a key stored in the table.
+fn parse_key<K>(key: &str) -> Result<K>
+where
+    K: FromStr,
+    <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+{
+    key.parse()
+        .map_err(|e| PersistentError::Postgres(sqlx::Error::Decode(Box::new(e))))
+}
+
+#[async_trait::async_trait]
+impl<K, V> StorageBackend<K, V> for PostgresBackend
+where
+    K: Eq
+        + Hash
+        + Clone
+        + Serialize
+        + DeserializeOwned
+        + Send
+        + Sync
+        + 'static
+        + ToString
+        + FromStr,
+    <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Returns the entries as of the start of the query.
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        sqlx::query_as::<_, (String, String)>(&self.sql("SELECT key, value::text FROM kv"))
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|(key, value)| Ok((parse_key(&key)?, serde_json::from_str(&value)?)))
+            .collect()
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        sqlx::query(&self.upsert())
+            .bind(key.to_string())
+            .bind(Json(value))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        sqlx::query(&self.sql("DELETE FROM kv WHERE key = $1"))
+            .bind(key.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Saves all entries in one transaction, the last value of a
+    /// duplicated key winning.
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        StorageBackend::<K, V>::write_batch(self, entries, Vec::new()).await
+    }
+
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        let keys: Vec<String> = keys.iter().map(ToString::to_string).collect();
+        sqlx::query(&self.sql("DELETE FROM kv WHERE key = ANY($1)"))
+            .bind(keys)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        sqlx::query(&self.sql("DELETE FROM kv"))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Applies the saves and then the deletes in one transaction.
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        let upsert = self.upsert();
+        let mut tx = self.pool.begin().await?;
+        for (key, value) in saves {
+            sqlx::query(&upsert)
+                .bind(key.to_string())
+                .bind(Json(value))
+                .execute(&mut *tx)
+                .await?;
+        }
+        if !deletes.is_empty() {
+            let keys: Vec<String> = deletes.iter().map(ToString::to_string).collect();
+            sqlx::query(&self.sql("DELETE FROM kv WHERE key = ANY($1)"))
+                .bind(keys)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Returns the stored `jsonb` as JSON text, which `PostgreSQL` may have
+    /// reformatted.
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        let value =
+            sqlx::query_scalar::<_, String>(&self.sql("SELECT value::text FROM kv WHERE key = $1"))
+                .bind(key.to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(value.map(String::into_bytes))
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        sqlx::query_as::<_, (String, String)>(&self.sql("SELECT key, value::text FROM kv"))
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|(key, value)| Ok((parse_key(&key)?, value.into_bytes())))
+            .collect()
+    }
+
+    /// Stores the JSON bytes as `jsonb`, after checking they are
+    /// well-formed.
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        let value: serde_json::Value = serde_json::from_slice(&value)?;
+        sqlx::query(&self.upsert())
+            .bind(key.to_string())
+            .bind(Json(value))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        Ok(sqlx::query_scalar::<_, bool>(
+            &self.sql("SELECT EXISTS (SELECT 1 FROM kv WHERE key = $1)"),
+        )
+        .bind(key.to_string())
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        let count = sqlx::query_scalar::<_, i64>(&self.sql("SELECT COUNT(*) FROM kv"))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(usize::try_from(count).unwrap_or(usize::MAX))
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        Ok(
+            !sqlx::query_scalar::<_, bool>(&self.sql("SELECT EXISTS (SELECT 1 FROM kv)"))
+                .fetch_one(&self.pool)
+                .await?,
+        )
+    }
+}
@@ -0,0 +1,254 @@
+//! Redis backend implementation for `PersistentMap`.
+//!
+//! This module provides a storage backend that keeps a map in a single Redis
+//! hash, using the `redis` crate's multiplexed async connection.
+
+use crate::{PersistentError, Result, StorageBackend};
+use redis::aio::MultiplexedConnection;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash, str::FromStr};
+
+/// A Redis-based storage backend for `PersistentMap`.
+///
+/// Every entry is a field of one Redis hash: the field is the key's
+/// `to_string` and the value is its JSON encoding. `save` and `delete` map
+/// to `HSET` and `HDEL`, `load_all` is a single `HGETALL`, and batches are
+/// sent as one `MULTI`/`EXEC` transaction, so Redis applies them atomically.
+///
+/// # Sharing the hash
+///
+/// Several processes, such as the replicas of a service, can use the same
+/// hash. Each write is applied immediately and concurrent writers to a key
+/// follow last-writer-wins, but nothing notifies the other processes:
+/// `load_all` returns a snapshot of the hash, and a map keeps serving its
+/// in-memory copy until [`load`](crate::PersistentMap::load) is called again.
+///
+/// Redis keeps data in memory and persists it according to its own
+/// configuration (RDB snapshots or the AOF log), so `flush` and `sync` do
+/// nothing here.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// use persistent_map::redis::RedisBackend;
+///
+/// # async fn example() -> Result<()> {
+/// let backend = RedisBackend::connect("redis://127.0.0.1/", "sessions").await?;
+/// let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RedisBackend {
+    /// The connection, cloned for each command
+    conn: MultiplexedConnection,
+
+    /// The Redis key of the hash holding the entries
+    hash: String,
+}
+
+impl RedisBackend {
+    /// Connects to the Redis server at `url` and stores the entries in the
+    /// hash named `hash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `url` is not a valid Redis URL or the connection
+    /// cannot be established.
+    pub async fn connect(url: &str, hash: impl Into<String>) -> Result<Self> {
+        let client = ::redis::Client::open(url)?;
+        let conn = client.get_multiplexed_async_connection().await?;
+        Ok(Self::with_connection(conn, hash))
+    }
+
+    /// Uses an already open connection, for example one shared with the
+    /// rest of the application, and stores the entries in the hash named
+    /// `hash`.
+    pub fn with_connection(conn: MultiplexedConnection, hash: impl Into<String>) -> Self {
+        Self {
+            conn,
+            hash: hash.into(),
+        }
+    }
+
+    /// Returns the Redis key of the hash holding the entries.
+    #[must_use]
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Runs `cmd` on a clone of the connection.
+    async fn query<T: ::redis::FromRedisValue>(&self, cmd: &::redis::Cmd) -> Result<T> {
+        let mut conn = self.conn.clone();
+        Ok(cmd.query_async(&mut conn).await?)
+    }
+}
+
+/// Parses a key stored as a hash field.
+fn parse_key<K>(field: &str) -> Result<K>
+where
+    K: FromStr,
+{
+    field
+        .parse()
+        .map_err(|_| PersistentError::Key(format!("invalid key in hash field: {field}")))
+}
+
+/// Encodes `entries` as the field-value pairs of an `HSET`.
+fn encode_entries<K: ToString, V: Serialize>(entries: &[(K, V)]) -> Result<Vec<(String, String)>> {
+    entries
+        .iter()
+        .map(|(key, value)| Ok((key.to_string(), serde_json::to_string(value)?)))
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl<K, V> StorageBackend<K, V> for RedisBackend
+where
+    K: Eq
+        + Hash
+        + Clone
+        + Serialize
+        + DeserializeOwned
+        + Send
+        + Sync
+        + 'static
+        + ToString
+        + FromStr,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        let fields: HashMap<String, String> =
+            self.query(::redis::cmd("HGETALL").arg(&self.hash)).await?;
+        fields
+            .into_iter()
+            .map(|(field, value)| Ok((parse_key(&field)?, serde_json::from_str(&value)?)))
+            .collect()
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.query(
+            ::redis::cmd("HSET")
+                .arg(&self.hash)
+                .arg(key.to_string())
+                .arg(serde_json::to_string(&value)?),
+        )
+        .await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        self.query(::redis::cmd("HDEL").arg(&self.hash).arg(key.to_string()))
+            .await
+    }
+
+    /// Sets all fields with one `HSET`, the last value of a duplicated key
+    /// winning.
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.query(
+            ::redis::cmd("HSET")
+                .arg(&self.hash)
+                .arg(encode_entries(&entries)?),
+        )
+        .await
+    }
+
+    /// Removes all fields with one `HDEL`.
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let fields: Vec<String> = keys.iter().map(ToString::to_string).collect();
+        self.query(::redis::cmd("HDEL").arg(&self.hash).arg(fields))
+            .await
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        self.query(::redis::cmd("DEL").arg(&self.hash)).await
+    }
+
+    /// Applies the saves and then the deletes in one `MULTI`/`EXEC`
+    /// transaction.
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        let mut pipe = ::redis::pipe();
+        pipe.atomic();
+        if !saves.is_empty() {
+            pipe.cmd("HSET")
+                .arg(&self.hash)
+                .arg(encode_entries(&saves)?)
+                .ignore();
+        }
+        if !deletes.is_empty() {
+            let fields: Vec<String> = deletes.iter().map(ToString::to_string).collect();
+            pipe.cmd("HDEL").arg(&self.hash).arg(fields).ignore();
+        }
+        let mut conn = self.conn.clone();
+        pipe.query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+
+    /// Returns the stored JSON as it is.
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        let value: Option<String> = self
+            .query(::redis::cmd("HGET").arg(&self.hash).arg(key.to_string()))
+            .await?;
+        Ok(value.map(String::into_bytes))
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        let fields: HashMap<String, String> =
+            self.query(::redis::cmd("HGETALL").arg(&self.hash)).await?;
+        fields
+            .into_iter()
+            .map(|(field, value)| Ok((parse_key(&field)?, value.into_bytes())))
+            .collect()
+    }
+
+    /// Reads all keys with one `HMGET`.
+    async fn load_many_raw(&self, keys: &[K]) -> Result<Vec<Option<Vec<u8>>>, PersistentError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let fields: Vec<String> = keys.iter().map(ToString::to_string).collect();
+        let values: Vec<Option<String>> = self
+            .query(::redis::cmd("HMGET").arg(&self.hash).arg(fields))
+            .await?;
+        Ok(values
+            .into_iter()
+            .map(|value| value.map(String::into_bytes))
+            .collect())
+    }
+
+    /// Stores the JSON bytes as they are, after checking they are
+    /// well-formed.
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        serde_json::from_slice::<serde::de::IgnoredAny>(&value)?;
+        self.query(
+            ::redis::cmd("HSET")
+                .arg(&self.hash)
+                .arg(key.to_string())
+                .arg(value),
+        )
+        .await
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        self.query(::redis::cmd("HEXISTS").arg(&self.hash).arg(key.to_string()))
+            .await
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        self.query(::redis::cmd("HLEN").arg(&self.hash)).await
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        Ok(StorageBackend::<K, V>::len(self).await? == 0)
+    }
+}
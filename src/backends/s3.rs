@@ -0,0 +1,539 @@
+//! S3 backend implementation for `PersistentMap`.
+//!
+//! This module provides a storage backend for Amazon S3 and S3-compatible
+//! object stores. It uses the official `aws-sdk-s3` client for asynchronous
+//! requests.
+//!
+//! Recent `aws-sdk-s3` 1.x releases need a much newer Rust toolchain than
+//! the rest of this crate, whose minimum supported version is 1.65, so the
+//! `s3` feature effectively has the MSRV of the `aws-sdk-s3` release it
+//! resolves to (see that crate's `rust-version`).
+
+use crate::{PersistentError, Result, StorageBackend};
+use aws_sdk_s3::{
+    error::DisplayErrorContext,
+    operation::{get_object::GetObjectError, head_object::HeadObjectError},
+    primitives::ByteStream,
+    types::{Delete, Object, ObjectIdentifier},
+    Client,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    str::FromStr,
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+/// Maximum number of keys in a single `DeleteObjects` request.
+const MAX_DELETE_BATCH: usize = 1000;
+
+/// An S3-based storage backend for `PersistentMap`.
+///
+/// The backend has two layouts:
+///
+/// - **Object per key** ([`S3Backend::new`]): every entry is stored as its own
+///   object named `prefix + key`, holding the JSON-encoded value. `save` and
+///   `delete` map directly to a put and a delete, and `load_all` lists the
+///   prefix and fetches every object.
+/// - **Snapshot** ([`S3Backend::snapshot`]): the whole map is stored as a
+///   single JSON object. Writes are applied to an in-memory copy of the
+///   snapshot and uploaded in one request by `flush`, so unflushed writes are
+///   lost if the process exits. This suits small maps with many writes.
+///
+/// # Consistency
+///
+/// Amazon S3 offers strong read-after-write consistency for puts, deletes
+/// and listings, so a new backend sees every completed write. Other
+/// S3-compatible stores may be eventually consistent; check their
+/// documentation. There are no transactions across objects: a batch that
+/// fails halfway leaves the earlier objects written. Concurrent writers to
+/// the same key, or to the same snapshot object, follow last-writer-wins.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// use persistent_map::s3::S3Backend;
+///
+/// # async fn example(client: aws_sdk_s3::Client) -> Result<()> {
+/// // `client` is configured by the application, e.g. with `aws-config`
+/// let backend = S3Backend::new(client, "my-bucket", "sessions/");
+/// let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct S3Backend {
+    /// The S3 client
+    client: Client,
+
+    /// The bucket holding the objects
+    bucket: String,
+
+    /// How entries map to objects
+    layout: Layout,
+}
+
+/// How entries are mapped to objects.
+#[derive(Debug)]
+enum Layout {
+    /// One object per key, named `prefix + key`
+    ObjectPerKey { prefix: String },
+
+    /// One object holding the whole map
+    Snapshot {
+        object: String,
+        state: Mutex<Option<Snapshot>>,
+    },
+}
+
+/// In-memory copy of a snapshot object.
+#[derive(Debug, Default)]
+struct Snapshot {
+    entries: BTreeMap<String, serde_json::Value>,
+    dirty: bool,
+}
+
+impl S3Backend {
+    /// Creates a backend that stores each key as its own object.
+    ///
+    /// Objects are named `prefix + key.to_string()`; use a prefix ending in
+    /// `/` to keep the map in its own "directory" of the bucket.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::s3::S3Backend;
+    ///
+    /// # fn example(client: aws_sdk_s3::Client) {
+    /// let backend = S3Backend::new(client, "my-bucket", "sessions/");
+    /// # }
+    /// ```
+    pub fn new(client: Client, bucket: impl Into<String>, prefix: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+            layout: Layout::ObjectPerKey {
+                prefix: prefix.into(),
+            },
+        }
+    }
+
+    /// Creates a backend that stores the whole map as a single JSON object.
+    ///
+    /// Writes only reach S3 when the backend is flushed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::s3::S3Backend;
+    ///
+    /// # fn example(client: aws_sdk_s3::Client) {
+    /// let backend = S3Backend::snapshot(client, "my-bucket", "settings.json");
+    /// # }
+    /// ```
+    pub fn snapshot(client: Client, bucket: impl Into<String>, object: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+            layout: Layout::Snapshot {
+                object: object.into(),
+                state: Mutex::new(None),
+            },
+        }
+    }
+
+    /// Returns the bucket the backend writes to.
+    #[must_use]
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Fetches an object, returning `None` if it doesn't exist.
+    async fn get_object(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let output = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(name)
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e)
+                if e.as_service_error()
+                    .map_or(false, GetObjectError::is_no_such_key) =>
+            {
+                return Ok(None)
+            }
+            Err(e) => return Err(s3_error(e)),
+        };
+        let body = output.body.collect().await.map_err(s3_error)?;
+        Ok(Some(body.into_bytes().to_vec()))
+    }
+
+    async fn put_object(&self, name: &str, body: Vec<u8>) -> Result<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(name)
+            .content_type("application/json")
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(s3_error)?;
+        Ok(())
+    }
+
+    /// Lists all objects under `prefix`.
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<Object>> {
+        let mut objects = Vec::new();
+        let mut token = None;
+        loop {
+            let output = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(token)
+                .send()
+                .await
+                .map_err(s3_error)?;
+            objects.extend(output.contents().iter().cloned());
+            match output.next_continuation_token() {
+                Some(next) if output.is_truncated() == Some(true) => token = Some(next.to_owned()),
+                _ => return Ok(objects),
+            }
+        }
+    }
+
+    /// Runs `f` on the snapshot, fetching it from S3 first if needed.
+    async fn with_snapshot<R>(
+        &self,
+        object: &str,
+        state: &Mutex<Option<Snapshot>>,
+        f: impl FnOnce(&mut Snapshot) -> R,
+    ) -> Result<R> {
+        if lock(state).is_none() {
+            let entries = match self.get_object(object).await? {
+                Some(bytes) => decode_snapshot(&bytes)?,
+                None => BTreeMap::new(),
+            };
+            // Another task may have fetched the snapshot in the meantime
+            lock(state).get_or_insert(Snapshot {
+                entries,
+                dirty: false,
+            });
+        }
+        Ok(f(lock(state).get_or_insert_with(Snapshot::default)))
+    }
+}
+
+fn lock(state: &Mutex<Option<Snapshot>>) -> std::sync::MutexGuard<'_, Option<Snapshot>> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn s3_error<E>(e: E) -> PersistentError
+where
+    E: std::error::Error,
+{
+    PersistentError::S3(DisplayErrorContext(e).to_string())
+}
+
+fn parse_key<K>(name: &str) -> Result<K>
+where
+    K: FromStr,
+{
+    name.parse()
+        .map_err(|_| PersistentError::S3(format!("invalid key in object name: {name}")))
+}
+
+/// Returns the name of the object holding `key` in the object-per-key layout.
+fn object_name<K: ToString>(prefix: &str, key: &K) -> String {
+    format!("{prefix}{}", key.to_string())
+}
+
+/// Parses the key back out of an object name listed under `prefix`.
+fn key_of_object<K: FromStr>(prefix: &str, name: &str) -> Result<K> {
+    let key = name
+        .strip_prefix(prefix)
+        .ok_or_else(|| PersistentError::S3(format!("object {name} is not under {prefix}")))?;
+    parse_key(key)
+}
+
+/// Encodes snapshot entries as the body of the snapshot object.
+fn encode_snapshot(entries: &BTreeMap<String, serde_json::Value>) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(entries)?)
+}
+
+/// Decodes the body of a snapshot object.
+fn decode_snapshot(bytes: &[u8]) -> Result<BTreeMap<String, serde_json::Value>> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+/// Converts snapshot entries into typed keys and values.
+fn snapshot_entries<K, V>(entries: BTreeMap<String, serde_json::Value>) -> Result<HashMap<K, V>>
+where
+    K: Eq + Hash + FromStr,
+    V: DeserializeOwned,
+{
+    entries
+        .into_iter()
+        .map(|(k, v)| Ok((parse_key(&k)?, serde_json::from_value(v)?)))
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl<K, V> StorageBackend<K, V> for S3Backend
+where
+    K: Eq
+        + Hash
+        + Clone
+        + Serialize
+        + DeserializeOwned
+        + Send
+        + Sync
+        + 'static
+        + ToString
+        + FromStr,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Loads every entry under the prefix, or the whole snapshot object.
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        match &self.layout {
+            Layout::ObjectPerKey { prefix } => {
+                let mut map = HashMap::new();
+                for name in self
+                    .list_objects(prefix)
+                    .await?
+                    .iter()
+                    .filter_map(Object::key)
+                {
+                    // Objects deleted between the listing and the fetch are skipped
+                    if let Some(bytes) = self.get_object(name).await? {
+                        let key = key_of_object(prefix, name)?;
+                        map.insert(key, serde_json::from_slice(&bytes)?);
+                    }
+                }
+                Ok(map)
+            }
+            Layout::Snapshot { object, state } => {
+                let entries = self
+                    .with_snapshot(object, state, |snapshot| snapshot.entries.clone())
+                    .await?;
+                snapshot_entries(entries)
+            }
+        }
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.save_many(vec![(key, value)]).await
+    }
+
+    /// Puts each entry as an object in order, or applies the whole batch to
+    /// the snapshot at once.
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        match &self.layout {
+            Layout::ObjectPerKey { prefix } => {
+                for (key, value) in entries {
+                    let name = object_name(prefix, &key);
+                    self.put_object(&name, serde_json::to_vec(&value)?).await?;
+                }
+                Ok(())
+            }
+            Layout::Snapshot { object, state } => {
+                let entries = entries
+                    .into_iter()
+                    .map(|(k, v)| Ok((k.to_string(), serde_json::to_value(v)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                self.with_snapshot(object, state, |snapshot| {
+                    snapshot.entries.extend(entries);
+                    snapshot.dirty = true;
+                })
+                .await
+            }
+        }
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        match &self.layout {
+            Layout::ObjectPerKey { prefix } => {
+                self.client
+                    .delete_object()
+                    .bucket(&self.bucket)
+                    .key(object_name(prefix, key))
+                    .send()
+                    .await
+                    .map_err(s3_error)?;
+                Ok(())
+            }
+            Layout::Snapshot { object, state } => {
+                let key = key.to_string();
+                self.with_snapshot(object, state, |snapshot| {
+                    if snapshot.entries.remove(&key).is_some() {
+                        snapshot.dirty = true;
+                    }
+                })
+                .await
+            }
+        }
+    }
+
+    /// Deletes the objects with `DeleteObjects` requests of up to 1000 keys
+    /// each, or removes all keys from the snapshot at once.
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        match &self.layout {
+            Layout::ObjectPerKey { prefix } => {
+                for chunk in keys.chunks(MAX_DELETE_BATCH) {
+                    let objects = chunk
+                        .iter()
+                        .map(|key| {
+                            ObjectIdentifier::builder()
+                                .key(object_name(prefix, key))
+                                .build()
+                                .map_err(s3_error)
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let delete = Delete::builder()
+                        .set_objects(Some(objects))
+                        .quiet(true)
+                        .build()
+                        .map_err(s3_error)?;
+                    let output = self
+                        .client
+                        .delete_objects()
+                        .bucket(&self.bucket)
+                        .delete(delete)
+                        .send()
+                        .await
+                        .map_err(s3_error)?;
+                    if let Some(error) = output.errors().first() {
+                        return Err(PersistentError::S3(format!(
+                            "failed to delete {}: {}",
+                            error.key().unwrap_or_default(),
+                            error.message().unwrap_or_default()
+                        )));
+                    }
+                }
+                Ok(())
+            }
+            Layout::Snapshot { object, state } => {
+                let keys: Vec<String> = keys.iter().map(ToString::to_string).collect();
+                self.with_snapshot(object, state, |snapshot| {
+                    for key in &keys {
+                        if snapshot.entries.remove(key).is_some() {
+                            snapshot.dirty = true;
+                        }
+                    }
+                })
+                .await
+            }
+        }
+    }
+
+    /// Returns the latest `LastModified` time of the map's objects.
+    ///
+    /// Unflushed snapshot writes are not reflected until they are uploaded.
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        let times = match &self.layout {
+            Layout::ObjectPerKey { prefix } => self
+                .list_objects(prefix)
+                .await?
+                .iter()
+                .filter_map(|object| object.last_modified().copied())
+                .collect(),
+            Layout::Snapshot { object, .. } => {
+                match self
+                    .client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(object)
+                    .send()
+                    .await
+                {
+                    Ok(output) => output.last_modified().copied().into_iter().collect(),
+                    Err(e)
+                        if e.as_service_error()
+                            .map_or(false, HeadObjectError::is_not_found) =>
+                    {
+                        Vec::new()
+                    }
+                    Err(e) => return Err(s3_error(e)),
+                }
+            }
+        };
+        times
+            .into_iter()
+            .map(|time| SystemTime::try_from(time).map_err(s3_error))
+            .try_fold(None, |latest, time| Ok(latest.max(Some(time?))))
+    }
+
+    /// Uploads the snapshot object if it has unflushed writes.
+    ///
+    /// In the object-per-key layout every write is already durable, so this
+    /// does nothing.
+    async fn flush(&self) -> Result<(), PersistentError> {
+        let Layout::Snapshot { object, state } = &self.layout else {
+            return Ok(());
+        };
+        let body = {
+            let mut guard = lock(state);
+            match guard.as_mut() {
+                Some(snapshot) if snapshot.dirty => {
+                    snapshot.dirty = false;
+                    encode_snapshot(&snapshot.entries)?
+                }
+                _ => return Ok(()),
+            }
+        };
+        if let Err(e) = self.put_object(object, body).await {
+            if let Some(snapshot) = lock(state).as_mut() {
+                snapshot.dirty = true;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_names_round_trip() -> Result<()> {
+        let name = object_name("sessions/", &42_u32);
+        assert_eq!(name, "sessions/42");
+        assert_eq!(key_of_object::<u32>("sessions/", &name)?, 42);
+
+        // Keys are appended verbatim, so separators in keys nest further
+        assert_eq!(object_name("", &"a/b".to_string()), "a/b");
+
+        assert!(key_of_object::<u32>("sessions/", "other/42").is_err());
+        assert!(key_of_object::<u32>("sessions/", "sessions/x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_encoding_round_trip() -> Result<()> {
+        let mut entries = BTreeMap::new();
+        entries.insert("1".to_string(), serde_json::json!(["a", "b"]));
+        entries.insert("2".to_string(), serde_json::json!([]));
+
+        let bytes = encode_snapshot(&entries)?;
+        assert_eq!(bytes, br#"{"1":["a","b"],"2":[]}"#);
+        assert_eq!(decode_snapshot(&bytes)?, entries);
+
+        let typed: HashMap<u32, Vec<String>> = snapshot_entries(decode_snapshot(&bytes)?)?;
+        assert_eq!(typed[&1], vec!["a".to_string(), "b".to_string()]);
+        assert!(typed[&2].is_empty());
+
+        // Snapshot keys must parse as the map's key type
+        let bad = decode_snapshot(br#"{"x":[]}"#)?;
+        assert!(snapshot_entries::<u32, Vec<String>>(bad).is_err());
+        assert!(decode_snapshot(b"[").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Serializing wrapper around another backend.
+//!
+//! This module provides `SerializedBackend`, which stores each value in the
+//! encoding of a [`Serializer`] in a backend of byte strings.
+
+use crate::{JsonSerializer, PersistentError, Result, Serializer, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash, time::SystemTime};
+
+/// A backend that encodes values with a [`Serializer`] before handing them
+/// to another backend.
+///
+/// The wrapped backend stores the encoded byte strings, which makes it a
+/// `StorageBackend<K, Vec<u8>>`. Keys are passed through unchanged. Pair it
+/// with a backend that stores bytes compactly, such as
+/// [`SqliteBackend::new_binary`](crate::sqlite::SqliteBackend::new_binary):
+/// a JSON backend stores them as arrays of numbers.
+///
+/// The raw methods such as `load_one_raw` still see JSON: stored values are
+/// decoded into the value type and re-encoded, so they must decode into the
+/// current value type.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{BincodeSerializer, PersistentMap, Result};
+/// use persistent_map::serialized::SerializedBackend;
+/// # #[cfg(feature = "sqlite")]
+/// use persistent_map::sqlite::SqliteBackend;
+///
+/// # #[cfg(feature = "sqlite")]
+/// # async fn example() -> Result<()> {
+/// let backend = SerializedBackend::new(
+///     SqliteBackend::new_binary("samples.db").await?,
+///     BincodeSerializer,
+/// );
+/// let samples: PersistentMap<String, Vec<f64>, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "sqlite"))]
+/// # fn example() {}
+/// ```
+pub struct SerializedBackend<B, S = JsonSerializer> {
+    /// The backend storing the encoded values
+    inner: B,
+
+    /// Encodes and decodes the values
+    serializer: S,
+}
+
+impl<B, S> SerializedBackend<B, S> {
+    /// Wraps `inner`, encoding values with `serializer`.
+    pub const fn new(inner: B, serializer: S) -> Self {
+        Self { inner, serializer }
+    }
+
+    /// Returns the serializer that encodes the values.
+    #[must_use]
+    pub const fn serializer(&self) -> &S {
+        &self.serializer
+    }
+
+    /// Returns the wrapped backend.
+    #[must_use]
+    pub const fn inner(&self) -> &B {
+        &self.inner
+    }
+}
+
+impl<B, S: Serializer> SerializedBackend<B, S> {
+    /// Converts a stored value to JSON bytes.
+    fn to_json<V>(&self, stored: &[u8]) -> Result<Vec<u8>>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        let value: V = self.serializer.deserialize(stored)?;
+        Ok(serde_json::to_vec(&value)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl<K, V, B, S> StorageBackend<K, V> for SerializedBackend<B, S>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, Vec<u8>> + Send + Sync + 'static,
+    S: Serializer,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        self.inner
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(key, stored)| Ok((key, self.serializer.deserialize(&stored)?)))
+            .collect()
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.inner
+            .save(key, self.serializer.serialize(&value)?)
+            .await
+    }
+
+    async fn save_if_newer(&self, key: K, value: V, version: u64) -> Result<bool, PersistentError> {
+        self.inner
+            .save_if_newer(key, self.serializer.serialize(&value)?, version)
+            .await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete(&self.inner, key).await
+    }
+
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.serializer.serialize(&value)?)))
+            .collect::<Result<_>>()?;
+        self.inner.save_many(entries).await
+    }
+
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete_many(&self.inner, keys).await
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete_all(&self.inner).await
+    }
+
+    /// Moves the encoded values as they are, without decoding them.
+    async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::rename_keys(&self.inner, renames).await
+    }
+
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        let saves = saves
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.serializer.serialize(&value)?)))
+            .collect::<Result<_>>()?;
+        self.inner.write_batch(saves, deletes).await
+    }
+
+    /// Returns the stored value re-encoded as JSON.
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        let Some(stored) = StorageBackend::<K, Vec<u8>>::load_one_raw(&self.inner, key).await?
+        else {
+            return Ok(None);
+        };
+        let stored: Vec<u8> = serde_json::from_slice(&stored)?;
+        Ok(Some(self.to_json::<V>(&stored)?))
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        self.inner
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(key, stored)| Ok((key, self.to_json::<V>(&stored)?)))
+            .collect()
+    }
+
+    /// Decodes the JSON bytes into `V` and stores them encoded.
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        let value: V = serde_json::from_slice(&value)?;
+        self.inner
+            .save(key, self.serializer.serialize(&value)?)
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::flush(&self.inner).await
+    }
+
+    async fn sync(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::sync(&self.inner).await
+    }
+
+    async fn checkpoint(&self) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::checkpoint(&self.inner).await
+    }
+
+    async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+        StorageBackend::<K, Vec<u8>>::check_integrity(&self.inner, thorough).await
+    }
+
+    async fn save_expiring(
+        &self,
+        key: K,
+        value: V,
+        expires_at: SystemTime,
+    ) -> Result<(), PersistentError> {
+        self.inner
+            .save_expiring(key, self.serializer.serialize(&value)?, expires_at)
+            .await
+    }
+
+    async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::load_expiries(&self.inner).await
+    }
+
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::load_write_times(&self.inner).await
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::delete_expired(&self.inner, now).await
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::contains_key(&self.inner, key).await
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::len(&self.inner).await
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::is_empty(&self.inner).await
+    }
+
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        StorageBackend::<K, Vec<u8>>::last_modified(&self.inner).await
+    }
+
+    fn is_read_only(&self) -> bool {
+        StorageBackend::<K, Vec<u8>>::is_read_only(&self.inner)
+    }
+}
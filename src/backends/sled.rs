@@ -0,0 +1,184 @@
+//! Sled backend implementation for `PersistentMap`.
+//!
+//! This module provides a storage backend built on `sled`, an embedded
+//! key-value store written in Rust.
+
+use crate::{BackendStats, PersistentError, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash, path::Path};
+
+/// A `sled`-based storage backend for `PersistentMap`.
+///
+/// Keys and values are both stored as their JSON encoding, so any
+/// serializable key type works. Each key is a single record of the database's
+/// default tree, and batched writes are applied atomically with one
+/// `sled::Batch`.
+///
+/// `sled` writes are buffered until the database flushes them, which it does
+/// in the background every 500 ms by default. `flush` and `sync` flush
+/// explicitly, so writes reach disk before they return.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// use persistent_map::sled::SledBackend;
+///
+/// # async fn example() -> Result<()> {
+/// let backend = SledBackend::open("my_data.sled")?;
+/// let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SledBackend {
+    db: ::sled::Db,
+}
+
+impl SledBackend {
+    /// Opens or creates the `sled` database at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened, for example
+    /// because another process holds it.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::with_db(::sled::open(path)?))
+    }
+
+    /// Uses an already open database, for example one opened with a
+    /// custom `sled::Config`.
+    #[must_use]
+    pub const fn with_db(db: ::sled::Db) -> Self {
+        Self { db }
+    }
+
+    /// Returns the underlying database.
+    #[must_use]
+    pub const fn db(&self) -> &::sled::Db {
+        &self.db
+    }
+}
+
+/// Builds a batch that applies `saves` and then `deletes`.
+fn batch<K: Serialize, V: Serialize>(saves: &[(K, V)], deletes: &[K]) -> Result<::sled::Batch> {
+    let mut batch = ::sled::Batch::default();
+    for (key, value) in saves {
+        batch.insert(serde_json::to_vec(key)?, serde_json::to_vec(value)?);
+    }
+    for key in deletes {
+        batch.remove(serde_json::to_vec(key)?);
+    }
+    Ok(batch)
+}
+
+#[async_trait::async_trait]
+impl<K, V> StorageBackend<K, V> for SledBackend
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        self.db
+            .iter()
+            .map(|record| {
+                let (key, value) = record?;
+                Ok((
+                    serde_json::from_slice(&key)?,
+                    serde_json::from_slice(&value)?,
+                ))
+            })
+            .collect()
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.db
+            .insert(serde_json::to_vec(&key)?, serde_json::to_vec(&value)?)?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        self.db.remove(serde_json::to_vec(key)?)?;
+        Ok(())
+    }
+
+    /// Applies all entries as one atomic batch, the last value of a
+    /// duplicated key winning.
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        self.db.apply_batch(batch::<K, V>(&entries, &[])?)?;
+        Ok(())
+    }
+
+    /// Removes all keys as one atomic batch.
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        self.db.apply_batch(batch::<K, V>(&[], &keys)?)?;
+        Ok(())
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        self.db.clear()?;
+        Ok(())
+    }
+
+    /// Applies the saves and the deletes as one atomic batch.
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        self.db.apply_batch(batch(&saves, &deletes)?)?;
+        Ok(())
+    }
+
+    /// Returns the stored JSON as it is.
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        Ok(self
+            .db
+            .get(serde_json::to_vec(key)?)?
+            .map(|value| value.to_vec()))
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        self.db
+            .iter()
+            .map(|record| {
+                let (key, value) = record?;
+                Ok((serde_json::from_slice(&key)?, value.to_vec()))
+            })
+            .collect()
+    }
+
+    /// Stores the JSON bytes as they are, after checking they are
+    /// well-formed.
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        serde_json::from_slice::<serde::de::IgnoredAny>(&value)?;
+        self.db.insert(serde_json::to_vec(&key)?, value)?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), PersistentError> {
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        Ok(self.db.contains_key(serde_json::to_vec(key)?)?)
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        Ok(self.db.len())
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        Ok(self.db.is_empty())
+    }
+
+    /// Reports the `size_on_disk_bytes` of the database.
+    async fn stats(&self) -> Result<BackendStats, PersistentError> {
+        let mut stats = BackendStats::new();
+        stats.insert(
+            "size_on_disk_bytes".to_string(),
+            self.db.size_on_disk()?.to_string(),
+        );
+        Ok(stats)
+    }
+}
@@ -3,17 +3,87 @@
 //! This module provides a `SQLite`-based storage backend for `PersistentMap`.
 //! It uses `tokio-rusqlite` for asynchronous `SQLite` operations.
 
-use crate::StorageBackend;
+use crate::json_keys::JsonKeys;
+use crate::{BackendStats, Change, ChangeToken, SaveOutcome, StorageBackend};
 use crate::{PersistentError, Result};
 use serde::{de::DeserializeOwned, Serialize};
-use std::{collections::HashMap, hash::Hash, str::FromStr};
-use tokio_rusqlite::{params, Connection};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    hash::Hash,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio_rusqlite::{
+    params,
+    types::{FromSql, FromSqlResult, Value as SqlValue, ValueRef},
+    Connection, OpenFlags, OptionalExtension,
+};
+
+/// The table that holds the rows unless another is named.
+const DEFAULT_TABLE: &str = "kv";
+
+/// Inserts or replaces a row, counting up its `version` and setting its
+/// `updated_at`. Takes the key, the value, the expiry and the current time.
+///
+/// `INSERT OR REPLACE` is kept over an upsert so the `kv_changes` triggers
+/// see the same statements as before the metadata columns existed.
+const UPSERT: &str = "INSERT OR REPLACE INTO kv (key, value, expires_at, version, updated_at)
+     VALUES (?1, ?2, ?3, COALESCE((SELECT version FROM kv WHERE key = ?1), 0) + 1, ?4)";
+
+/// Like [`UPSERT`], but also sets the caller's `source_version`. Takes the
+/// key, the value, the current time and the source version.
+const UPSERT_VERSIONED: &str =
+    "INSERT OR REPLACE INTO kv (key, value, expires_at, version, updated_at, source_version)
+     VALUES (?1, ?2, NULL, COALESCE((SELECT version FROM kv WHERE key = ?1), 0) + 1, ?3, ?4)";
 
 /// A `SQLite`-based storage backend for `PersistentMap`.
 ///
 /// This backend stores key-value pairs in a `SQLite` database, providing
 /// durable persistence with good performance characteristics.
 ///
+/// Each key is stored as a single row, so batched writes carry no ordering
+/// guarantee beyond the last value for a duplicated key winning. Entries
+/// saved with an expiry keep it in the `expires_at` column, in milliseconds
+/// since the Unix epoch, which is `NULL` for entries that never expire.
+///
+/// Every row also carries metadata in plain columns that other SQL queries
+/// can use: `version` counts the writes of the key, starting at 1, and
+/// `updated_at` holds the time of the last write in milliseconds since the
+/// Unix epoch, indexed for range queries such as
+/// [`query_updated_since`](Self::query_updated_since). A delete resets both.
+/// `source_version` holds the caller's version of rows written by
+/// `save_if_newer`, and is `NULL` for rows written any other way.
+/// Rows of databases created before the columns existed keep them `NULL`
+/// until they are next written.
+///
+/// By default every operation goes through one connection, so reads wait
+/// for each other and for writes. [`with_read_pool`](Self::with_read_pool)
+/// adds read-only connections that serve reads in parallel. Writes always go
+/// through the single writer connection, since `SQLite` allows only one
+/// writer at a time.
+///
+/// Values are stored as JSON text by default. [`new_binary`](Self::new_binary)
+/// stores them as `bincode` blobs instead, which are smaller for binary and
+/// numeric data and keep floats exact. The encoding is recorded in the
+/// `kv_meta` table when the database is created, and opening a database with
+/// the other encoding fails.
+///
+/// Rows live in the `kv` table unless [`with_table`](Self::with_table) names
+/// another, so several backends can share one database file. The
+/// companion tables, indexes and triggers take the table name as their
+/// prefix, such as `sessions_meta` and `sessions_changes` for a `sessions`
+/// table.
+///
+/// Dropping the future of a full-table read, such as `load_all`,
+/// `load_all_raw`, `load_expiries` or `scan_prefix`, stops the query at the
+/// next row, so a cancelled request frees its connection early. Other
+/// operations run to completion once they have started.
+///
 /// # Examples
 ///
 /// ```rust,no_run
@@ -31,8 +101,23 @@ use tokio_rusqlite::{params, Connection};
 /// ```
 #[derive(Debug)]
 pub struct SqliteBackend {
-    /// The `SQLite` connection
+    /// The `SQLite` connection used for writes, and for reads without a pool
     conn: Connection,
+
+    /// Read-only connections that serve reads in turn
+    readers: Vec<Connection>,
+
+    /// Index of the reader that serves the next read
+    next_reader: AtomicUsize,
+
+    /// How values are encoded in the `value` column
+    encoding: ValueEncoding,
+
+    /// The table that holds the rows
+    table: String,
+
+    /// Whether the database was opened read-only
+    read_only: bool,
 }
 
 impl SqliteBackend {
@@ -65,24 +150,387 @@ impl SqliteBackend {
     /// Returns an error if the database connection cannot be opened or if
     /// the initial table/index creation fails.
     pub async fn new(db_path: &str) -> Result<Self> {
+        Self::with_table(db_path, DEFAULT_TABLE).await
+    }
+
+    /// Creates a `SQLite` backend that stores its rows in `table` instead
+    /// of `kv`.
+    ///
+    /// Backends with different tables can share a database file without
+    /// seeing each other's entries. The name must start with an ASCII letter
+    /// or an underscore and contain only ASCII letters, digits and
+    /// underscores, and must not start with `sqlite_`, which `SQLite`
+    /// reserves. The backend also creates `<table>_meta` and
+    /// `<table>_changes`, so a table name must not collide with those of
+    /// another backend.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use persistent_map::Result;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let sessions = SqliteBackend::with_table("app.db", "sessions").await?;
+    /// let users = SqliteBackend::with_table("app.db", "users").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns a `PersistentError::Config` error if `table` is not a valid
+    /// table name, or an error if the database connection cannot be opened
+    /// or the initial table/index creation fails.
+    pub async fn with_table(db_path: &str, table: &str) -> Result<Self> {
+        Self::open(db_path, 0, ValueEncoding::Json, table).await
+    }
+
+    /// Creates a `SQLite` backend for keys that don't implement
+    /// `ToString + FromStr`, such as tuples.
+    ///
+    /// Keys are stored as the text of their JSON encoding in the same `kv`
+    /// table, through a [`JsonKeys`] wrapper, so the key only needs
+    /// `Serialize + DeserializeOwned`. A database written this way should
+    /// keep being opened with this constructor: its keys are JSON text, so
+    /// a string key `a` is stored as `"a"`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use persistent_map::{PersistentMap, Result};
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let backend = SqliteBackend::new_serialized_keys("grid.db").await?;
+    /// let grid: PersistentMap<(i32, i32), String, _> = PersistentMap::new(backend).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the database connection cannot be opened or the
+    /// initial table/index creation fails.
+    pub async fn new_serialized_keys(db_path: &str) -> Result<JsonKeys<Self>> {
+        Ok(JsonKeys::new(Self::new(db_path).await?))
+    }
+
+    /// Creates a `SQLite` backend that stores values as `bincode` blobs.
+    ///
+    /// `bincode` is a compact binary encoding: numbers and byte strings take
+    /// their natural size instead of their decimal or array spelling, and
+    /// floats round-trip exactly. It is not self-describing, so value types
+    /// that need it, such as `serde_json::Value`, untagged enums or structs
+    /// with flattened fields, cannot be stored. The raw methods such as
+    /// `load_one_raw` and value migration hooks still see JSON: stored values
+    /// are decoded into the value type and re-encoded, so they must decode
+    /// into the current value type.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use persistent_map::Result;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let backend = SqliteBackend::new_binary("samples.db").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns a `PersistentError::Config` error if the database already
+    /// stores JSON values, or an error if the database connection cannot be
+    /// opened or the initial table/index creation fails.
+    pub async fn new_binary(db_path: &str) -> Result<Self> {
+        Self::open(db_path, 0, ValueEncoding::Bincode, DEFAULT_TABLE).await
+    }
+
+    /// Creates a `SQLite` backend with `readers` additional read-only
+    /// connections to the database.
+    ///
+    /// Reads such as `load_all`, `load_one_raw`, `contains_key`, prefix
+    /// scans and pages are spread over the read connections in turn, so
+    /// concurrent reads run in parallel instead of queueing on one
+    /// connection. Writes and statistics still go through the single writer
+    /// connection and are serialized. With a pool the database is switched to
+    /// WAL mode, in which readers do not block the writer or each other, and
+    /// stays in WAL mode for later connections. Every read sees all writes
+    /// that completed before it started.
+    ///
+    /// With `readers` set to zero this is the same as [`new`](Self::new).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use persistent_map::Result;
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let backend = SqliteBackend::with_read_pool("my_database.db", 4).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns a `PersistentError::Config` error if a pool is requested for
+    /// an in-memory database, which read connections cannot share, or an
+    /// error if a connection cannot be opened or the initial table/index
+    /// creation fails.
+    pub async fn with_read_pool(db_path: &str, readers: usize) -> Result<Self> {
+        Self::open(db_path, readers, ValueEncoding::Json, DEFAULT_TABLE).await
+    }
+
+    /// Opens an existing database without write access.
+    ///
+    /// The connection uses `SQLite`'s read-only open flag, so the file is
+    /// never modified: reads work as usual, while writes, including
+    /// `flush`, `sync` and `checkpoint`, fail with
+    /// [`PersistentError::ReadOnly`]. This suits a database shipped
+    /// prebuilt with an application. The database must have been created
+    /// by a writable `SqliteBackend`; the value encoding it records, JSON or
+    /// `bincode`, is picked up from it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use persistent_map::{PersistentMap, Result};
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let backend = SqliteBackend::open_read_only("catalog.db").await?;
+    /// let catalog: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns a `PersistentError::Config` error if the database has no
+    /// `kv` table or records an unknown value encoding, or an error if the
+    /// database cannot be opened, for example because the file does not
+    /// exist.
+    pub async fn open_read_only(db_path: &str) -> Result<Self> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+            | OpenFlags::SQLITE_OPEN_URI;
+        let conn = Connection::open_with_flags(db_path, flags).await?;
+        let (has_table, stored) = conn
+            .call(|c| {
+                let exists =
+                    "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)";
+                let has_table: bool =
+                    c.query_row(exists, params![DEFAULT_TABLE], |row| row.get(0))?;
+                let has_meta: bool = c.query_row(exists, params!["kv_meta"], |row| row.get(0))?;
+                let stored = if has_meta {
+                    c.query_row(
+                        "SELECT value FROM kv_meta WHERE name = 'encoding'",
+                        [],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .optional()?
+                } else {
+                    None
+                };
+                Ok((has_table, stored))
+            })
+            .await?;
+        if !has_table {
+            return Err(PersistentError::Config(format!(
+                "database {db_path} has no {DEFAULT_TABLE} table to open read-only"
+            )));
+        }
+        // Tables from before the encoding was recorded hold JSON
+        let encoding = match stored.as_deref() {
+            None => ValueEncoding::Json,
+            Some(name) => ValueEncoding::from_name(name).ok_or_else(|| {
+                PersistentError::Config(format!("database stores unknown {name} values"))
+            })?,
+        };
+
+        Ok(Self {
+            conn,
+            readers: Vec::new(),
+            next_reader: AtomicUsize::new(0),
+            encoding,
+            table: DEFAULT_TABLE.to_string(),
+            read_only: true,
+        })
+    }
+
+    /// Opens the database, creating its tables, with `readers` read-only
+    /// connections and values stored with `encoding` in `table`.
+    async fn open(
+        db_path: &str,
+        readers: usize,
+        encoding: ValueEncoding,
+        table: &str,
+    ) -> Result<Self> {
+        check_table_name(table)?;
+        if readers > 0 && (db_path.is_empty() || db_path.contains(":memory:")) {
+            return Err(PersistentError::Config(
+                "a read pool needs a database file, not an in-memory database".to_string(),
+            ));
+        }
+        let sql = |statement: &'static str| table_sql(table, statement);
         let conn = Connection::open(db_path).await?;
-        conn.call(|c| {
-            c.execute(
-                "CREATE TABLE IF NOT EXISTS kv (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
-                [],
-            )
-            .map_err(tokio_rusqlite::Error::Rusqlite)
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS {table} (key TEXT PRIMARY KEY, value {} NOT NULL, expires_at INTEGER, version INTEGER, updated_at INTEGER, source_version INTEGER)",
+            encoding.column_type()
+        );
+        conn.call(move |c| {
+            c.execute(&create, [])
+                .map_err(tokio_rusqlite::Error::Rusqlite)
+        })
+        .await?;
+
+        // The encoding is recorded once so a database is never read with the
+        // wrong one. Tables from before it was recorded hold JSON.
+        let create =
+            sql("CREATE TABLE IF NOT EXISTS kv_meta (name TEXT PRIMARY KEY, value TEXT NOT NULL)");
+        let has_rows = sql("SELECT EXISTS(SELECT 1 FROM kv)");
+        let insert = sql("INSERT OR IGNORE INTO kv_meta (name, value) VALUES ('encoding', ?1)");
+        let select = sql("SELECT value FROM kv_meta WHERE name = 'encoding'");
+        let stored = conn
+            .call(move |c| {
+                c.execute(&create, [])?;
+                let has_rows: bool = c.query_row(&has_rows, [], |row| row.get(0))?;
+                let initial = if has_rows {
+                    ValueEncoding::Json
+                } else {
+                    encoding
+                };
+                c.execute(&insert, params![initial.name()])?;
+                let stored = c
+                    .query_row(&select, [], |row| row.get::<_, String>(0))
+                    .optional()?;
+                Ok(stored)
+            })
+            .await?;
+        if stored.as_deref() != Some(encoding.name()) {
+            return Err(PersistentError::Config(format!(
+                "database stores {} values, but the backend was opened for {} values",
+                stored.as_deref().unwrap_or("unknown"),
+                encoding.name()
+            )));
+        }
+
+        // Databases created before expiry and metadata were stored lack the
+        // columns, which stay `NULL` for existing rows until they are written
+        let table_info = sql("PRAGMA table_info(kv)");
+        let alter = sql("ALTER TABLE kv ADD COLUMN");
+        let indexes = sql(
+            "CREATE INDEX IF NOT EXISTS kv_expires_at_idx ON kv (expires_at);
+             CREATE INDEX IF NOT EXISTS kv_updated_at_idx ON kv (updated_at);",
+        );
+        conn.call(move |c| {
+            let columns = c
+                .prepare(&table_info)?
+                .query_map([], |row| row.get::<_, String>(1))?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            for column in ["expires_at", "version", "updated_at", "source_version"] {
+                if !columns.iter().any(|c| c == column) {
+                    c.execute(&format!("{alter} {column} INTEGER"), [])?;
+                }
+            }
+            c.execute_batch(&indexes)?;
+            Ok(())
+        })
+        .await?;
+
+        // Triggers keep one row per changed key, numbered in commit order,
+        // for `changes_since`
+        let changes = sql("CREATE TABLE IF NOT EXISTS kv_changes (
+                     seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                     key TEXT NOT NULL UNIQUE
+                 );
+                 CREATE TRIGGER IF NOT EXISTS kv_changes_insert AFTER INSERT ON kv BEGIN
+                     DELETE FROM kv_changes WHERE key = NEW.key;
+                     INSERT INTO kv_changes (key) VALUES (NEW.key);
+                 END;
+                 CREATE TRIGGER IF NOT EXISTS kv_changes_update AFTER UPDATE ON kv BEGIN
+                     DELETE FROM kv_changes WHERE key IN (OLD.key, NEW.key);
+                     INSERT INTO kv_changes (key) VALUES (OLD.key);
+                     INSERT OR IGNORE INTO kv_changes (key) VALUES (NEW.key);
+                 END;
+                 CREATE TRIGGER IF NOT EXISTS kv_changes_delete AFTER DELETE ON kv BEGIN
+                     DELETE FROM kv_changes WHERE key = OLD.key;
+                     INSERT INTO kv_changes (key) VALUES (OLD.key);
+                 END;");
+        conn.call(move |c| {
+            c.execute_batch(&changes)
+                .map_err(tokio_rusqlite::Error::Rusqlite)
         })
         .await?;
 
         // Create an index for faster lookups if it doesn't exist
-        conn.call(|c| {
-            c.execute("CREATE INDEX IF NOT EXISTS kv_key_idx ON kv (key)", [])
+        let index = sql("CREATE INDEX IF NOT EXISTS kv_key_idx ON kv (key)");
+        conn.call(move |c| {
+            c.execute(&index, [])
                 .map_err(tokio_rusqlite::Error::Rusqlite)
         })
         .await?;
 
-        Ok(Self { conn })
+        let mut pool = Vec::with_capacity(readers);
+        if readers > 0 {
+            conn.call(|c| {
+                c.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+                    row.get::<_, String>(0)
+                })
+                .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+            for _ in 0..readers {
+                let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                    | OpenFlags::SQLITE_OPEN_URI;
+                pool.push(Connection::open_with_flags(db_path, flags).await?);
+            }
+        }
+
+        Ok(Self {
+            conn,
+            readers: pool,
+            next_reader: AtomicUsize::new(0),
+            encoding,
+            table: table.to_string(),
+            read_only: false,
+        })
+    }
+
+    /// Returns the name of the table that holds the rows.
+    #[must_use]
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Returns `true` if the backend was opened with
+    /// [`open_read_only`](Self::open_read_only).
+    #[must_use]
+    pub const fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fails with [`PersistentError::ReadOnly`] if the backend is read-only.
+    fn check_writable(&self, operation: &str) -> Result<()> {
+        if self.read_only {
+            return Err(PersistentError::ReadOnly(operation.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Returns `statement`, written against the `kv` table, for the
+    /// backend's table.
+    fn sql(&self, statement: &'static str) -> Cow<'static, str> {
+        table_sql(&self.table, statement)
+    }
+
+    /// Returns the connection that serves the next read: the next read-only
+    /// connection of the pool, or the writer connection without a pool.
+    fn reader(&self) -> &Connection {
+        if self.readers.is_empty() {
+            return &self.conn;
+        }
+        let next = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        &self.readers[next % self.readers.len()]
     }
 
     /// Returns the path to the `SQLite` database file.
@@ -109,12 +557,137 @@ impl SqliteBackend {
 
         Ok(result)
     }
+
+    /// Returns the keys of the live entries written at or after `since`,
+    /// oldest write first.
+    ///
+    /// This reads the indexed `updated_at` column only, so no value is
+    /// decoded. Rows from before the column existed that were not written
+    /// since are never returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use persistent_map::Result;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// # async fn example() -> Result<()> {
+    /// let backend = SqliteBackend::new("my_database.db").await?;
+    /// let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+    /// let recent: Vec<String> = backend.query_updated_since(hour_ago).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the query fails or a stored key cannot be parsed.
+    pub async fn query_updated_since<K>(&self, since: SystemTime) -> Result<Vec<K>>
+    where
+        K: FromStr,
+        <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let since = unix_millis(since);
+        let now = unix_millis(SystemTime::now());
+        let select = self.sql(
+            "SELECT key FROM kv WHERE updated_at >= ?1 AND (expires_at IS NULL OR expires_at > ?2)
+             ORDER BY updated_at, key",
+        );
+
+        let keys = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let keys = stmt
+                    .query_map(params![since, now], |r| r.get::<_, String>(0))?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(keys)
+            })
+            .await?;
+
+        keys.iter().map(|k_str| parse_key(k_str)).collect()
+    }
+
+    /// Reads the `(key, value)` rows whose key starts with `prefix`, ordered by key.
+    ///
+    /// The prefix is matched with an escaped `GLOB` pattern so the lookup can
+    /// use the primary key index.
+    async fn prefix_rows(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let pattern = glob_prefix_pattern(prefix);
+
+        let cancel = CancelOnDrop::new();
+        let cancelled = cancel.flag();
+        let select = self.sql("SELECT key, value FROM kv WHERE key GLOB ?1 ORDER BY key");
+        let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows = stmt.query_map(params![pattern], |r| {
+                    Ok((r.get::<_, String>(0)?, r.get::<_, StoredValue>(1)?.0))
+                })?;
+                collect_until_cancelled(rows, &cancelled)
+            })
+            .await?;
+
+        Ok(rows)
+    }
+
+    /// Reads one page of `(key, value)` rows with `LIMIT` and `OFFSET`, ordered by key.
+    async fn page_rows(&self, offset: usize, limit: usize) -> Result<Vec<(String, Vec<u8>)>> {
+        let offset = i64::try_from(offset).unwrap_or(i64::MAX);
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let select = self.sql("SELECT key, value FROM kv ORDER BY key LIMIT ?1 OFFSET ?2");
+
+        let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows = stmt
+                    .query_map(params![limit, offset], |r| {
+                        Ok((r.get::<_, String>(0)?, r.get::<_, StoredValue>(1)?.0))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+
+        Ok(rows)
+    }
+
+    /// Reads up to `limit` `(key, value)` rows whose key comes after
+    /// `last_key`, ordered by key, or the first page if `last_key` is `None`.
+    async fn rows_after(
+        &self,
+        last_key: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let Some(last_key) = last_key else {
+            return self.page_rows(0, limit).await;
+        };
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let select = self.sql("SELECT key, value FROM kv WHERE key > ?1 ORDER BY key LIMIT ?2");
+
+        let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows = stmt
+                    .query_map(params![last_key, limit], |r| {
+                        Ok((r.get::<_, String>(0)?, r.get::<_, StoredValue>(1)?.0))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+
+        Ok(rows)
+    }
 }
 
 /// Implementation of the `StorageBackend` trait for `SqliteBackend`.
 ///
 /// This implementation provides methods for loading, saving, and deleting
-/// key-value pairs from a SQLite database.
+/// key-value pairs from a `SQLite` database.
 #[async_trait::async_trait]
 impl<K, V> StorageBackend<K, V> for SqliteBackend
 where
@@ -131,62 +704,418 @@ where
     <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
     V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
 {
-    /// Loads all key-value pairs from the SQLite database.
+    /// Loads all key-value pairs from the `SQLite` database.
     ///
     /// This method queries the database for all key-value pairs and deserializes
     /// them into the appropriate types.
     async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        let cancel = CancelOnDrop::new();
+        let cancelled = cancel.flag();
+        let select = self.sql("SELECT key, value FROM kv");
         let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows = stmt.query_map([], |r| {
+                    Ok((r.get::<_, String>(0)?, r.get::<_, StoredValue>(1)?.0))
+                })?;
+                collect_until_cancelled(rows, &cancelled)
+            })
+            .await?;
+
+        Ok(self.encoding.decode_rows(rows)?.into_iter().collect())
+    }
+
+    /// Saves a key-value pair to the `SQLite` database.
+    ///
+    /// This method serializes the key to a string and the value with the
+    /// backend's encoding, and inserts or replaces them in the database.
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.check_writable("save")?;
+        let key_str = key.to_string();
+        let val_json = self.encoding.encode(&value)?;
+        let now = unix_millis(SystemTime::now());
+        let upsert = self.sql(UPSERT);
+
+        self.conn
+            .call(move |c| {
+                c.execute(&upsert, params![key_str, val_json, None::<i64>, now])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Checks for a live row and replaces it in a single transaction, so the
+    /// report cannot race with other writers. An expired row counts as absent.
+    async fn save_reporting(&self, key: K, value: V) -> Result<SaveOutcome, PersistentError> {
+        self.check_writable("save_reporting")?;
+        let key_str = key.to_string();
+        let val_json = self.encoding.encode(&value)?;
+        let now = unix_millis(SystemTime::now());
+        let select = self.sql(
+            "SELECT EXISTS(SELECT 1 FROM kv WHERE key = ?1 AND (expires_at IS NULL OR expires_at > ?2))",
+        );
+        let upsert = self.sql(UPSERT);
+
+        let existed = self
             .conn
-            .call(|c| {
-                let mut stmt = c.prepare_cached("SELECT key, value FROM kv")?;
-                let mut map = HashMap::with_capacity(100); // Pre-allocate for better performance
-                let mut rows_iter = stmt.query_map([], |r| {
-                    let key_str: String = r.get(0)?;
-                    let val_str: String = r.get(1)?;
-                    Ok((key_str, val_str))
+            .call(move |c| {
+                let tx = c.transaction()?;
+                let existed: bool =
+                    tx.query_row(&select, params![key_str, now], |row| row.get(0))?;
+                tx.execute(&upsert, params![key_str, val_json, None::<i64>, now])?;
+                tx.commit()?;
+                Ok(existed)
+            })
+            .await?;
+
+        Ok(if existed {
+            SaveOutcome::Updated
+        } else {
+            SaveOutcome::Inserted
+        })
+    }
+
+    /// Compares with the row's `source_version` and replaces the row in a
+    /// single transaction. An expired row counts as absent.
+    async fn save_if_newer(&self, key: K, value: V, version: u64) -> Result<bool, PersistentError> {
+        self.check_writable("save_if_newer")?;
+        let version = i64::try_from(version).map_err(|_| {
+            PersistentError::Config(format!(
+                "version {version} exceeds the SQLite integer range"
+            ))
+        })?;
+        let key_str = key.to_string();
+        let val_json = self.encoding.encode(&value)?;
+        let now = unix_millis(SystemTime::now());
+        let select = self.sql(
+            "SELECT source_version FROM kv WHERE key = ?1 AND (expires_at IS NULL OR expires_at > ?2)",
+        );
+        let upsert = self.sql(UPSERT_VERSIONED);
+
+        let saved = self
+            .conn
+            .call(move |c| {
+                let tx = c.transaction()?;
+                let stored: Option<Option<i64>> = tx
+                    .query_row(&select, params![key_str, now], |row| row.get(0))
+                    .optional()?;
+                if stored.flatten().map_or(false, |stored| stored >= version) {
+                    return Ok(false);
+                }
+                tx.execute(&upsert, params![key_str, val_json, now, version])?;
+                tx.commit()?;
+                Ok(true)
+            })
+            .await?;
+
+        Ok(saved)
+    }
+
+    /// Saves a batch of key-value pairs in a single `SQLite` transaction.
+    ///
+    /// Each key maps to a single row, so the write order only matters for
+    /// duplicated keys; statements are executed in the given order, which
+    /// makes the last occurrence of a key win.
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        self.check_writable("save_many")?;
+        let rows = entries
+            .into_iter()
+            .map(|(k, v)| Ok((k.to_string(), self.encoding.encode(&v)?)))
+            .collect::<Result<Vec<_>, PersistentError>>()?;
+        let now = unix_millis(SystemTime::now());
+        let upsert = self.sql(UPSERT);
+
+        self.conn
+            .call(move |c| {
+                let tx = c.transaction()?;
+                {
+                    let mut stmt = tx.prepare_cached(&upsert)?;
+                    for (key_str, val_json) in rows {
+                        stmt.execute(params![key_str, val_json, None::<i64>, now])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Saves the pair with its expiry in the `expires_at` column.
+    async fn save_expiring(
+        &self,
+        key: K,
+        value: V,
+        expires_at: SystemTime,
+    ) -> Result<(), PersistentError> {
+        self.check_writable("save_expiring")?;
+        let key_str = key.to_string();
+        let val_json = self.encoding.encode(&value)?;
+        let expires_at = unix_millis(expires_at);
+        let now = unix_millis(SystemTime::now());
+        let upsert = self.sql(UPSERT);
+
+        self.conn
+            .call(move |c| {
+                c.execute(&upsert, params![key_str, val_json, expires_at, now])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Reads the rows whose `expires_at` is set.
+    async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        let cancel = CancelOnDrop::new();
+        let cancelled = cancel.flag();
+        let select = self.sql("SELECT key, expires_at FROM kv WHERE expires_at IS NOT NULL");
+        let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows =
+                    stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))?;
+                collect_until_cancelled(rows, &cancelled)
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(k_str, millis)| {
+                let key = k_str.parse().map_err(|e| {
+                    PersistentError::Sqlite(tokio_rusqlite::Error::Other(Box::new(e)))
+                })?;
+                Ok((key, from_unix_millis(millis)))
+            })
+            .collect()
+    }
+
+    /// Reads the `updated_at` column of every row written since the column
+    /// was added.
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        let cancel = CancelOnDrop::new();
+        let cancelled = cancel.flag();
+        let select = self.sql("SELECT key, updated_at FROM kv WHERE updated_at IS NOT NULL");
+        let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows =
+                    stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))?;
+                collect_until_cancelled(rows, &cancelled)
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(k_str, millis)| {
+                let key = k_str.parse().map_err(|e| {
+                    PersistentError::Sqlite(tokio_rusqlite::Error::Other(Box::new(e)))
                 })?;
+                Ok((key, from_unix_millis(millis)))
+            })
+            .collect()
+    }
+
+    /// Deletes the expired rows with a single `DELETE`, using the index on
+    /// `expires_at`.
+    async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+        self.check_writable("delete_expired")?;
+        let now = unix_millis(now);
+        let delete = self.sql("DELETE FROM kv WHERE expires_at <= ?1");
+
+        let deleted = self
+            .conn
+            .call(move |c| {
+                c.execute(&delete, params![now])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+
+    /// Loads all entries whose key starts with `prefix`, ordered by key.
+    ///
+    /// The prefix is matched with an escaped `GLOB` pattern so the lookup can
+    /// use the primary key index.
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.encoding.decode_rows(self.prefix_rows(prefix).await?)
+    }
+
+    /// Reads the page with `LIMIT` and `OFFSET`, ordered by key.
+    ///
+    /// `SQLite` still walks the skipped rows, so reading a page at a large
+    /// offset takes time proportional to the offset. To page through a large
+    /// table efficiently, use `load_after` instead.
+    async fn load_page(&self, offset: usize, limit: usize) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.encoding
+            .decode_rows(self.page_rows(offset, limit).await?)
+    }
 
-                while let Some(Ok((k_str, v_str))) = rows_iter.next() {
-                    // Deserialize the value from JSON
-                    let value: V = serde_json::from_str(&v_str)
-                        .map_err(|e| tokio_rusqlite::Error::Other(Box::new(e)))?;
+    /// Reads the next page with `WHERE key > ?`, which uses the primary key
+    /// index and costs the same at any depth.
+    async fn load_after(
+        &self,
+        last_key: Option<K>,
+        limit: usize,
+    ) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let rows = self
+            .rows_after(last_key.map(|k| k.to_string()), limit)
+            .await?;
+        self.encoding.decode_rows(rows)
+    }
+
+    /// Returns the JSON encoding of the entries matched by `scan_prefix`.
+    async fn scan_prefix_raw(&self, prefix: &str) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.encoding
+            .raw_rows::<K, V>(self.prefix_rows(prefix).await?)
+    }
+
+    /// Returns the JSON encoding of the page read by `load_page`.
+    async fn load_page_raw(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.encoding
+            .raw_rows::<K, V>(self.page_rows(offset, limit).await?)
+    }
+
+    /// Returns the JSON encoding of the page read by `load_after`.
+    async fn load_after_raw(
+        &self,
+        last_key: Option<K>,
+        limit: usize,
+    ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let rows = self
+            .rows_after(last_key.map(|k| k.to_string()), limit)
+            .await?;
+        self.encoding.raw_rows::<K, V>(rows)
+    }
 
-                    // Parse the key from string
-                    let key = k_str
-                        .parse()
-                        .map_err(|e| tokio_rusqlite::Error::Other(Box::new(e)))?;
+    /// Returns the stored JSON text for a key without deserializing it, or
+    /// the value re-encoded as JSON in binary mode.
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        let key_str = key.to_string();
+        let select = self.sql("SELECT value FROM kv WHERE key = ?1");
 
-                    map.insert(key, value);
+        let value = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let mut rows = stmt.query(params![key_str])?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(row.get::<_, StoredValue>(0)?.0)),
+                    None => Ok(None),
                 }
-                Ok(map)
             })
             .await?;
-        Ok(rows)
+
+        value
+            .map(|bytes| self.encoding.to_json::<V>(bytes))
+            .transpose()
+    }
+
+    /// Returns the stored JSON text for every key without deserializing it,
+    /// or the values re-encoded as JSON in binary mode.
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        let cancel = CancelOnDrop::new();
+        let cancelled = cancel.flag();
+        let select = self.sql("SELECT key, value FROM kv");
+        let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows = stmt.query_map([], |r| {
+                    Ok((r.get::<_, String>(0)?, r.get::<_, StoredValue>(1)?.0))
+                })?;
+                collect_until_cancelled(rows, &cancelled)
+            })
+            .await?;
+
+        Ok(self.encoding.raw_rows::<K, V>(rows)?.into_iter().collect())
+    }
+
+    /// Reads every key with one prepared statement in a single call to the
+    /// database thread, inside one read transaction.
+    async fn load_many_raw(&self, keys: &[K]) -> Result<Vec<Option<Vec<u8>>>, PersistentError> {
+        let key_strs: Vec<String> = keys.iter().map(ToString::to_string).collect();
+        let select = self.sql("SELECT value FROM kv WHERE key = ?1");
+
+        let values = self
+            .reader()
+            .call(move |c| {
+                let tx = c.transaction()?;
+                let mut values = Vec::with_capacity(key_strs.len());
+                {
+                    let mut stmt = tx.prepare_cached(&select)?;
+                    for key_str in key_strs {
+                        let value = stmt
+                            .query_row(params![key_str], |r| r.get::<_, StoredValue>(0))
+                            .optional()?;
+                        values.push(value.map(|v| v.0));
+                    }
+                }
+                tx.commit()?;
+                Ok(values)
+            })
+            .await?;
+
+        values
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|bytes| self.encoding.to_json::<V>(bytes))
+                    .transpose()
+            })
+            .collect()
     }
 
-    /// Saves a key-value pair to the SQLite database.
+    /// Stores already serialized JSON bytes for a key.
     ///
-    /// This method serializes the key and value to strings and inserts or
-    /// replaces them in the database.
-    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+    /// The bytes are checked to be well-formed JSON text so that later loads
+    /// can parse the row, but they are not decoded into `V`. In binary mode
+    /// they are decoded into `V` and stored as `bincode`.
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        self.check_writable("save_raw")?;
+        let val_json = self.encoding.encode_json::<V>(value)?;
         let key_str = key.to_string();
-        let val_json = serde_json::to_string(&value)?;
+        let now = unix_millis(SystemTime::now());
+        let upsert = self.sql(UPSERT);
 
         self.conn
             .call(move |c| {
-                c.execute(
-                    "INSERT OR REPLACE INTO kv (key, value) VALUES (?1, ?2)",
-                    params![key_str, val_json],
-                )
-                .map_err(tokio_rusqlite::Error::Rusqlite)
+                c.execute(&upsert, params![key_str, val_json, None::<i64>, now])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
             })
             .await?;
 
         Ok(())
     }
 
-    /// Deletes a key-value pair from the SQLite database.
+    /// Deletes a key-value pair from the `SQLite` database.
     ///
     /// This method removes the key-value pair with the specified key from the database.
     ///
@@ -195,11 +1124,104 @@ where
     /// Returns an error if deleting from the backend fails.
     #[inline]
     async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        self.check_writable("delete")?;
+        let key_str = key.to_string();
+        let delete = self.sql("DELETE FROM kv WHERE key = ?1");
+
+        self.conn
+            .call(move |c| {
+                c.execute(&delete, params![key_str])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Applies all saves and deletes in a single `SQLite` transaction.
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        self.check_writable("write_batch")?;
+        let rows = saves
+            .into_iter()
+            .map(|(k, v)| Ok((k.to_string(), self.encoding.encode(&v)?)))
+            .collect::<Result<Vec<_>, PersistentError>>()?;
+        let key_strs: Vec<String> = deletes.iter().map(ToString::to_string).collect();
+        let now = unix_millis(SystemTime::now());
+        let upsert = self.sql(UPSERT);
+        let delete = self.sql("DELETE FROM kv WHERE key = ?1");
+
+        self.conn
+            .call(move |c| {
+                let tx = c.transaction()?;
+                {
+                    let mut save = tx.prepare_cached(&upsert)?;
+                    for (key_str, val_json) in rows {
+                        save.execute(params![key_str, val_json, None::<i64>, now])?;
+                    }
+                    let mut delete = tx.prepare_cached(&delete)?;
+                    for key_str in key_strs {
+                        delete.execute(params![key_str])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Checks for the key with a primary key lookup instead of loading all rows.
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
         let key_str = key.to_string();
+        let select = self.sql("SELECT EXISTS(SELECT 1 FROM kv WHERE key = ?1)");
+
+        let exists = self
+            .reader()
+            .call(move |c| {
+                c.query_row(&select, params![key_str], |row| row.get(0))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+
+        Ok(exists)
+    }
+
+    /// Deletes a batch of keys in a single `SQLite` transaction.
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        self.check_writable("delete_many")?;
+        let key_strs: Vec<String> = keys.iter().map(ToString::to_string).collect();
+        let delete = self.sql("DELETE FROM kv WHERE key = ?1");
+
+        self.conn
+            .call(move |c| {
+                let tx = c.transaction()?;
+                {
+                    let mut stmt = tx.prepare_cached(&delete)?;
+                    for key_str in key_strs {
+                        stmt.execute(params![key_str])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes every row with a single `DELETE`.
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        self.check_writable("delete_all")?;
+        let delete = self.sql("DELETE FROM kv");
 
         self.conn
             .call(move |c| {
-                c.execute("DELETE FROM kv WHERE key = ?1", params![key_str])
+                c.execute(&delete, [])
                     .map_err(tokio_rusqlite::Error::Rusqlite)
             })
             .await?;
@@ -207,11 +1229,170 @@ where
         Ok(())
     }
 
-    /// Flushes any buffered writes to the SQLite database.
+    /// Moves the rows in one transaction, keeping their stored value and
+    /// expiry. Every old row is deleted before the new ones are inserted, so
+    /// a key can be both renamed and the target of another rename.
+    async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+        self.check_writable("rename_keys")?;
+        let renames: Vec<(String, String)> = renames
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect();
+        let now = unix_millis(SystemTime::now());
+        let select = self.sql("SELECT value, expires_at FROM kv WHERE key = ?1");
+        let delete = self.sql("DELETE FROM kv WHERE key = ?1");
+        let upsert = self.sql(UPSERT);
+
+        self.conn
+            .call(move |c| {
+                let tx = c.transaction()?;
+                {
+                    let mut select = tx.prepare_cached(&select)?;
+                    let mut delete = tx.prepare_cached(&delete)?;
+                    let mut rows = Vec::with_capacity(renames.len());
+                    for (from, to) in renames {
+                        let row = select
+                            .query_row(params![from], |r| {
+                                Ok((r.get::<_, SqlValue>(0)?, r.get::<_, Option<i64>>(1)?))
+                            })
+                            .optional()?;
+                        if let Some((value, expires_at)) = row {
+                            delete.execute(params![from])?;
+                            rows.push((to, value, expires_at));
+                        }
+                    }
+                    let mut insert = tx.prepare_cached(&upsert)?;
+                    for (to, value, expires_at) in rows {
+                        insert.execute(params![to, value, expires_at, now])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the modification time of the database file.
+    ///
+    /// The write-ahead log is included when the database runs in WAL mode,
+    /// since recent writes may only have reached the log. In-memory databases
+    /// return `None`.
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        let path = self.db_path().await?;
+        if path.is_empty() {
+            return Ok(None);
+        }
+        Ok(super::latest_mtime([format!("{path}-wal"), path])?)
+    }
+
+    /// Reads the `kv_changes` table, which triggers on `kv` keep up to date
+    /// with one row per changed key, including keys changed by other
+    /// connections.
+    ///
+    /// Rows for deleted keys are kept so other readers can see the delete,
+    /// so the table grows with the number of distinct keys ever stored.
+    async fn changes_since(
+        &self,
+        since: Option<ChangeToken>,
+    ) -> Result<(Vec<Change<K>>, ChangeToken), PersistentError> {
+        let Some(since) = since else {
+            let select = self.sql("SELECT COALESCE(MAX(seq), 0) FROM kv_changes");
+            let latest = self
+                .reader()
+                .call(move |c| {
+                    c.query_row(&select, [], |row| row.get::<_, i64>(0))
+                        .map_err(tokio_rusqlite::Error::Rusqlite)
+                })
+                .await?;
+            return Ok((Vec::new(), sequence_token(latest)));
+        };
+        let after = i64::try_from(since.sequence()).unwrap_or(i64::MAX);
+        let select = self.sql(
+            "SELECT c.seq, c.key, kv.value, kv.expires_at FROM kv_changes c
+             LEFT JOIN kv ON kv.key = c.key WHERE c.seq > ?1 ORDER BY c.seq",
+        );
+
+        let rows = self
+            .reader()
+            .call(move |c| {
+                let mut stmt = c.prepare_cached(&select)?;
+                let rows = stmt
+                    .query_map(params![after], |r| {
+                        Ok((
+                            r.get::<_, i64>(0)?,
+                            r.get::<_, String>(1)?,
+                            r.get::<_, Option<StoredValue>>(2)?.map(|v| v.0),
+                            r.get::<_, Option<i64>>(3)?,
+                        ))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+
+        let token = rows.last().map_or(since, |(seq, ..)| sequence_token(*seq));
+        let changes = rows
+            .into_iter()
+            .map(|(_, k_str, value, expires_at)| {
+                let key = k_str.parse().map_err(|e| {
+                    PersistentError::Sqlite(tokio_rusqlite::Error::Other(Box::new(e)))
+                })?;
+                Ok(match value {
+                    Some(value) => Change::Saved {
+                        key,
+                        value: self.encoding.to_json::<V>(value)?,
+                        expires_at: expires_at.map(from_unix_millis),
+                    },
+                    None => Change::Deleted { key },
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((changes, token))
+    }
+
+    /// Reports `page_count`, `page_size_bytes`, `freelist_count` (pages that
+    /// are allocated but unused, reclaimable with `VACUUM`), `journal_mode`
+    /// and the number of stored `entries`.
+    async fn stats(&self) -> Result<BackendStats, PersistentError> {
+        let count = self.sql("SELECT COUNT(*) FROM kv");
+        let stats = self
+            .conn
+            .call(move |c| {
+                let mut stats = BackendStats::new();
+                for (name, pragma) in [
+                    ("page_count", "page_count"),
+                    ("page_size_bytes", "page_size"),
+                    ("freelist_count", "freelist_count"),
+                ] {
+                    let value: i64 = c.pragma_query_value(None, pragma, |row| row.get(0))?;
+                    stats.insert(name.to_string(), value.to_string());
+                }
+                let journal_mode: String =
+                    c.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+                stats.insert("journal_mode".to_string(), journal_mode);
+                let entries: i64 = c.query_row(&count, [], |row| row.get(0))?;
+                stats.insert("entries".to_string(), entries.to_string());
+                Ok(stats)
+            })
+            .await?;
+
+        Ok(stats)
+    }
+
+    /// Returns `true` for a backend opened with
+    /// [`open_read_only`](SqliteBackend::open_read_only).
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Flushes any buffered writes to the `SQLite` database.
     ///
     /// This method ensures that all data is written to disk by executing
     /// a PRAGMA synchronous command.
     async fn flush(&self) -> Result<(), PersistentError> {
+        self.check_writable("flush")?;
         self.conn
             .call(|c| {
                 c.execute("PRAGMA synchronous = FULL", [])
@@ -221,4 +1402,314 @@ where
 
         Ok(())
     }
+
+    /// Syncs committed writes to disk.
+    ///
+    /// Switches the connection to `synchronous = FULL`, so later commits
+    /// wait for the disk, and checkpoints the write-ahead log into the
+    /// database file when the database runs in WAL mode.
+    async fn sync(&self) -> Result<(), PersistentError> {
+        self.check_writable("sync")?;
+        self.conn
+            .call(|c| {
+                c.pragma_update(None, "synchronous", "FULL")?;
+                c.query_row("PRAGMA wal_checkpoint(FULL)", [], |_| Ok(()))?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Copies the write-ahead log into the database file and truncates the
+    /// log to zero bytes, when the database runs in WAL mode.
+    ///
+    /// The checkpoint waits for readers of older snapshots to finish. It
+    /// does nothing for databases in rollback journal mode.
+    async fn checkpoint(&self) -> Result<(), PersistentError> {
+        self.check_writable("checkpoint")?;
+        self.conn
+            .call(|c| {
+                c.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Runs `PRAGMA quick_check`, or `PRAGMA integrity_check` if
+    /// `thorough`, which also checks that indexes match their tables.
+    async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+        let pragma = if thorough {
+            "PRAGMA integrity_check"
+        } else {
+            "PRAGMA quick_check"
+        };
+        let problems: Vec<String> = self
+            .conn
+            .call(move |c| {
+                Ok(c.prepare(pragma)?
+                    .query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<std::result::Result<Vec<_>, _>>()?)
+            })
+            .await?;
+
+        if problems.iter().all(|problem| problem == "ok") {
+            return Ok(());
+        }
+        Err(PersistentError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("sqlite integrity check failed: {}", problems.join("; ")),
+        )))
+    }
+}
+
+/// How values are stored in the `value` column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ValueEncoding {
+    /// JSON text
+    Json,
+
+    /// `bincode` blobs
+    Bincode,
+}
+
+impl ValueEncoding {
+    /// Returns the name recorded in the `kv_meta` table.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Bincode => "bincode",
+        }
+    }
+
+    /// Returns the encoding recorded in the `kv_meta` table as `name`.
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Json, Self::Bincode]
+            .into_iter()
+            .find(|encoding| encoding.name() == name)
+    }
+
+    /// Returns the declared type of the `value` column.
+    const fn column_type(self) -> &'static str {
+        match self {
+            Self::Json => "TEXT",
+            Self::Bincode => "BLOB",
+        }
+    }
+
+    /// Encodes `value` for the `value` column.
+    fn encode<V: Serialize>(self, value: &V) -> Result<SqlValue> {
+        match self {
+            Self::Json => Ok(SqlValue::Text(serde_json::to_string(value)?)),
+            Self::Bincode => Ok(SqlValue::Blob(
+                bincode::serialize(value).map_err(bincode_error)?,
+            )),
+        }
+    }
+
+    /// Decodes a value read from the `value` column.
+    fn decode<V: DeserializeOwned>(self, bytes: &[u8]) -> Result<V> {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(bytes)?),
+            Self::Bincode => bincode::deserialize(bytes).map_err(bincode_error),
+        }
+    }
+
+    /// Converts a value read from the `value` column to JSON bytes.
+    fn to_json<V>(self, bytes: Vec<u8>) -> Result<Vec<u8>>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        match self {
+            Self::Json => Ok(bytes),
+            Self::Bincode => Ok(serde_json::to_vec(&self.decode::<V>(&bytes)?)?),
+        }
+    }
+
+    /// Converts JSON bytes to a value for the `value` column.
+    ///
+    /// JSON is only checked to be well-formed, not decoded into `V`.
+    fn encode_json<V>(self, json: Vec<u8>) -> Result<SqlValue>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        match self {
+            Self::Json => {
+                serde_json::from_slice::<serde::de::IgnoredAny>(&json)?;
+                let text = String::from_utf8(json).map_err(|e| {
+                    PersistentError::Serde(serde_json::Error::io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        e,
+                    )))
+                })?;
+                Ok(SqlValue::Text(text))
+            }
+            Self::Bincode => self.encode(&serde_json::from_slice::<V>(&json)?),
+        }
+    }
+
+    /// Decodes `(key, value)` rows read from the `kv` table.
+    fn decode_rows<K, V>(self, rows: Vec<(String, Vec<u8>)>) -> Result<Vec<(K, V)>>
+    where
+        K: FromStr,
+        <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+        V: DeserializeOwned,
+    {
+        rows.into_iter()
+            .map(|(k_str, bytes)| Ok((parse_key(&k_str)?, self.decode(&bytes)?)))
+            .collect()
+    }
+
+    /// Parses the keys of `(key, value)` rows, converting the values to JSON bytes.
+    fn raw_rows<K, V>(self, rows: Vec<(String, Vec<u8>)>) -> Result<Vec<(K, Vec<u8>)>>
+    where
+        K: FromStr,
+        <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+        V: Serialize + DeserializeOwned,
+    {
+        rows.into_iter()
+            .map(|(k_str, bytes)| Ok((parse_key(&k_str)?, self.to_json::<V>(bytes)?)))
+            .collect()
+    }
+}
+
+/// The bytes of a `value` column, which holds text or a blob.
+struct StoredValue(Vec<u8>);
+
+impl FromSql for StoredValue {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value.as_bytes().map(|bytes| Self(bytes.to_vec()))
+    }
+}
+
+/// Stops a read running on a connection thread once the future waiting for
+/// it is dropped.
+///
+/// `tokio_rusqlite` keeps running a closure after its caller goes away, so
+/// without this a cancelled `load_all` would read the whole table for nobody
+/// and keep the connection busy.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl CancelOnDrop {
+    fn new() -> Self {
+        Self(Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Returns the flag to check from the closure.
+    fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.0)
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Collects `rows`, checking `cancelled` before each one.
+fn collect_until_cancelled<T, E>(
+    rows: impl Iterator<Item = std::result::Result<T, E>>,
+    cancelled: &AtomicBool,
+) -> tokio_rusqlite::Result<Vec<T>>
+where
+    tokio_rusqlite::Error: From<E>,
+{
+    let mut collected = Vec::new();
+    for row in rows {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(tokio_rusqlite::Error::Other(Box::new(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "read cancelled",
+            ))));
+        }
+        collected.push(row?);
+    }
+    Ok(collected)
+}
+
+/// Rewrites `statement`, written against the `kv` table, for `table`.
+///
+/// Every `kv` in the statements names the table or one of its companion
+/// tables, indexes and triggers, so all of them take the table's name.
+fn table_sql(table: &str, statement: &'static str) -> Cow<'static, str> {
+    if table == DEFAULT_TABLE {
+        Cow::Borrowed(statement)
+    } else {
+        Cow::Owned(statement.replace(DEFAULT_TABLE, table))
+    }
+}
+
+/// Checks that `table` can be used as an unquoted table name.
+fn check_table_name(table: &str) -> Result<()> {
+    let valid = table
+        .chars()
+        .next()
+        .map_or(false, |first| first.is_ascii_alphabetic() || first == '_')
+        && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !table.to_ascii_lowercase().starts_with("sqlite_");
+    if valid {
+        Ok(())
+    } else {
+        Err(PersistentError::Config(format!(
+            "invalid table name {table:?}: use ASCII letters, digits and underscores, not starting with a digit or `sqlite_`"
+        )))
+    }
+}
+
+/// Parses a key stored in the `kv` table.
+fn parse_key<K>(k_str: &str) -> Result<K>
+where
+    K: FromStr,
+    <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+{
+    k_str
+        .parse()
+        .map_err(|e| PersistentError::Sqlite(tokio_rusqlite::Error::Other(Box::new(e))))
+}
+
+/// Wraps a `bincode` error like other errors of the `SQLite` backend.
+fn bincode_error(e: bincode::Error) -> PersistentError {
+    PersistentError::Sqlite(tokio_rusqlite::Error::Other(e))
+}
+
+/// Converts `time` to milliseconds since the Unix epoch, as stored in the
+/// `expires_at` and `updated_at` columns. Times before the epoch map to zero.
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| {
+        i64::try_from(since.as_millis()).unwrap_or(i64::MAX)
+    })
+}
+
+/// Converts milliseconds since the Unix epoch, as stored in the
+/// `expires_at` column, back to a time. Negative values map to the epoch.
+fn from_unix_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(u64::try_from(millis).unwrap_or(0))
+}
+
+/// Converts a `kv_changes` sequence number to a change token.
+fn sequence_token(seq: i64) -> ChangeToken {
+    ChangeToken::new(u64::try_from(seq).unwrap_or(0))
+}
+
+/// Builds a `GLOB` pattern matching every string that starts with `prefix`.
+///
+/// The `GLOB` metacharacters `*`, `?` and `[` are wrapped in brackets so they
+/// match literally.
+fn glob_prefix_pattern(prefix: &str) -> String {
+    let mut pattern = String::with_capacity(prefix.len() + 1);
+    for ch in prefix.chars() {
+        match ch {
+            '*' | '?' | '[' => {
+                pattern.push('[');
+                pattern.push(ch);
+                pattern.push(']');
+            }
+            _ => pattern.push(ch),
+        }
+    }
+    pattern.push('*');
+    pattern
 }
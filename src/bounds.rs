@@ -0,0 +1,44 @@
+//! Names for the bounds the map puts on its keys and values.
+//!
+//! Both traits are implemented for every type with the bounds, so they can
+//! be used as shorthand in generic code. With the `derive` feature, the
+//! derive macros of the same names check them where a type is defined,
+//! instead of where it is first used with a map.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{hash::Hash, str::FromStr};
+
+/// A type that can be stored as a map value.
+///
+/// This is every `Clone + Serialize + DeserializeOwned + Send + Sync +
+/// 'static` type.
+pub trait PersistentValue: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+impl<T> PersistentValue for T where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+/// A type that can be used as a map key with every built-in backend.
+///
+/// On top of the bounds of [`PersistentValue`], keys need `Eq + Hash`, and
+/// the backends that store keys as text, such as `SqliteBackend`, also need
+/// `ToString + FromStr`. Those backends further require the `FromStr` error
+/// to be a `Send + Sync` error, which generic code has to state itself.
+/// `#[derive(PersistentKey)]` implements the string round trip from the
+/// key's JSON encoding.
+pub trait PersistentKey: PersistentValue + Eq + Hash + ToString + FromStr {}
+
+impl<T> PersistentKey for T where T: PersistentValue + Eq + Hash + ToString + FromStr {}
+
+/// Items used by the code the derive macros generate.
+#[cfg(feature = "derive")]
+pub mod derive_support {
+    pub use serde;
+    pub use serde_json;
+
+    /// Writes the JSON encoding of `key`, for derived `Display` impls.
+    pub fn write_key<K: serde::Serialize>(
+        key: &K,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(key).map_err(|_| std::fmt::Error)?)
+    }
+}
@@ -0,0 +1,899 @@
+//! Builder for configuring a `PersistentMap`.
+
+use crate::consistency::LocalWrites;
+use crate::eviction::{Eviction, EvictionCallback, EvictionPolicy};
+use crate::migrate::{self, ValueRepair};
+use crate::normalize::{KeyNormalizer, Normalize};
+#[cfg(feature = "runtime")]
+use crate::startup::{self, StartupLoadPolicy};
+#[cfg(feature = "runtime")]
+use crate::write_behind::WriteBuffer;
+use crate::{PersistentMap, Result, StorageBackend, ValueMigration};
+use dashmap::DashMap;
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "runtime")]
+use std::path::PathBuf;
+use std::{
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Drain interval and pending-key threshold used by `wal` without `write_behind`.
+#[cfg(feature = "runtime")]
+const DEFAULT_WAL_DRAIN: (Duration, usize) = (Duration::from_millis(100), 1024);
+
+/// A builder for `PersistentMap` with optional settings.
+///
+/// Created with [`PersistentMap::builder`]. Options that are not set keep
+/// the behavior of [`PersistentMap::new`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result};
+/// # #[cfg(feature = "in_memory")]
+/// use persistent_map::in_memory::InMemoryBackend;
+///
+/// # #[cfg(feature = "in_memory")]
+/// # async fn example() -> Result<()> {
+/// let map: PersistentMap<String, String, _> = PersistentMap::builder(InMemoryBackend::new())
+///     .max_capacity(1_000)
+///     .on_evict(|key, _value| println!("evicted {key}"))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "in_memory"))]
+/// # fn example() {}
+/// ```
+pub struct PersistentMapBuilder<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// The storage backend for persistence
+    backend: B,
+
+    /// Maximum number of entries kept in memory
+    max_capacity: Option<usize>,
+
+    /// Which entries are evicted when over capacity
+    eviction: EvictionPolicy,
+
+    /// Number of entries at which inserts of new keys fail
+    max_entries: Option<usize>,
+
+    /// Callback invoked with evicted entries
+    on_evict: Option<EvictionCallback<K, V>>,
+
+    /// Kind of per-key lock
+    #[cfg(feature = "runtime")]
+    key_lock: crate::KeyLock,
+
+    /// Base interval and maximum jitter of the periodic flush
+    #[cfg(feature = "runtime")]
+    auto_flush: Option<(Duration, Duration)>,
+
+    /// Drain interval and pending-key threshold of write-behind mode
+    #[cfg(feature = "runtime")]
+    write_behind: Option<(Duration, usize)>,
+
+    /// Path of the write-ahead log in front of the backend
+    #[cfg(feature = "runtime")]
+    wal: Option<PathBuf>,
+
+    /// Time allowed for the initial load
+    #[cfg(feature = "runtime")]
+    startup_load_timeout: Option<Duration>,
+
+    /// What happens when the initial load fails or times out
+    #[cfg(feature = "runtime")]
+    startup_load_policy: StartupLoadPolicy,
+
+    /// How long read-through misses wait to be batched together
+    #[cfg(feature = "runtime")]
+    load_batch_window: Option<Duration>,
+
+    /// Hook that upgrades stored values on load
+    migrate_value: Option<ValueMigration>,
+
+    /// Hook that fixes up stored values that fail to decode
+    repair_value: Option<ValueRepair>,
+
+    /// Function mapping keys to their canonical form
+    normalize_keys: Option<KeyNormalizer<K>>,
+
+    /// Whether every write is synced to stable storage
+    durable: bool,
+
+    /// How long local writes shadow the backend on reload
+    read_your_writes: Option<Duration>,
+
+    /// Whether the write time of each cached entry is kept
+    track_entry_age: bool,
+}
+
+impl<K, V, B> PersistentMapBuilder<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    pub(crate) const fn new(backend: B) -> Self {
+        Self {
+            backend,
+            max_capacity: None,
+            eviction: EvictionPolicy::Lru,
+            max_entries: None,
+            on_evict: None,
+            #[cfg(feature = "runtime")]
+            key_lock: crate::KeyLock::Async,
+            #[cfg(feature = "runtime")]
+            auto_flush: None,
+            #[cfg(feature = "runtime")]
+            write_behind: None,
+            #[cfg(feature = "runtime")]
+            wal: None,
+            #[cfg(feature = "runtime")]
+            startup_load_timeout: None,
+            #[cfg(feature = "runtime")]
+            startup_load_policy: StartupLoadPolicy::FailFast,
+            #[cfg(feature = "runtime")]
+            load_batch_window: None,
+            migrate_value: None,
+            repair_value: None,
+            normalize_keys: None,
+            durable: false,
+            read_your_writes: None,
+            track_entry_age: false,
+        }
+    }
+
+    /// Bounds the number of entries kept in memory.
+    ///
+    /// When an insert or load pushes the map over `capacity`, entries are
+    /// evicted from memory as chosen by the [`eviction`](Self::eviction)
+    /// policy, least recently used first by default. Evicted entries stay in
+    /// the storage backend. A capacity of zero is treated as one.
+    #[must_use]
+    pub const fn max_capacity(mut self, capacity: usize) -> Self {
+        self.max_capacity = Some(capacity);
+        self
+    }
+
+    /// Chooses which entries are evicted when the map exceeds its
+    /// [`max_capacity`](Self::max_capacity).
+    ///
+    /// Defaults to [`EvictionPolicy::Lru`]. Has no effect on unbounded maps.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{EvictionPolicy, PersistentMap, Result};
+    /// # #[cfg(feature = "in_memory")]
+    /// use persistent_map::in_memory::InMemoryBackend;
+    ///
+    /// # #[cfg(feature = "in_memory")]
+    /// # async fn example() -> Result<()> {
+    /// let map: PersistentMap<String, String, _> = PersistentMap::builder(InMemoryBackend::new())
+    ///     .max_capacity(1_000)
+    ///     .eviction(EvictionPolicy::Lfu)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "in_memory"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub const fn eviction(mut self, policy: EvictionPolicy) -> Self {
+        self.eviction = policy;
+        self
+    }
+
+    /// Makes inserts of new keys fail once the map holds `max` entries.
+    ///
+    /// Unlike [`max_capacity`](Self::max_capacity), which evicts entries from
+    /// memory to make room, this is a hard limit for bounded buffers and
+    /// queues where silently dropping data is wrong: `insert` and the other
+    /// `insert` methods return [`PersistentError::CapacityExceeded`] instead
+    /// of adding a key, and leave the map and the backend unchanged.
+    /// Updates of keys already in the map are always allowed, so a full map
+    /// stays writable. Removing entries makes room again.
+    ///
+    /// The limit applies to the number of entries in memory, as reported by
+    /// [`PersistentMap::len`]. Entries only in the backend are not counted,
+    /// so a map that holds part of its backend, because entries were evicted
+    /// by `max_capacity` or loaded page by page with
+    /// [`load_range`](PersistentMap::load_range), can hold more in total.
+    /// Expired entries count until they are purged. Loads and other writes
+    /// that add keys, such as
+    /// [`compare_and_swap_bytes`](PersistentMap::compare_and_swap_bytes) and
+    /// [`sync_changes`](PersistentMap::sync_changes), are not limited, but
+    /// their keys count against later inserts.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentError, PersistentMap, Result};
+    /// # #[cfg(feature = "in_memory")]
+    /// use persistent_map::in_memory::InMemoryBackend;
+    ///
+    /// # #[cfg(feature = "in_memory")]
+    /// # async fn example() -> Result<()> {
+    /// let queue: PersistentMap<u64, String, _> = PersistentMap::builder(InMemoryBackend::new())
+    ///     .max_entries(10_000)
+    ///     .build()
+    ///     .await?;
+    ///
+    /// match queue.insert(1, "job".to_string()).await {
+    ///     Err(PersistentError::CapacityExceeded(max)) => eprintln!("queue full at {max} jobs"),
+    ///     other => {
+    ///         other?;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "in_memory"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub const fn max_entries(mut self, max: usize) -> Self {
+        self.max_entries = Some(max);
+        self
+    }
+
+    /// Sets a callback invoked with every entry evicted from memory.
+    ///
+    /// The callback runs when an entry expires or is dropped because the map
+    /// exceeded its maximum capacity. Explicit `remove` calls do not invoke it.
+    /// It is called after all internal locks have been released, so it may
+    /// safely access the map again.
+    #[must_use]
+    pub fn on_evict<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(K, V) + Send + Sync + 'static,
+    {
+        self.on_evict = Some(Arc::new(callback));
+        self
+    }
+
+    /// Transforms each stored value before it is decoded during a load.
+    ///
+    /// The hook receives the stored value as JSON and returns the JSON to
+    /// decode into `V`, which allows old value shapes to be upgraded lazily
+    /// instead of in a separate batch job. Values already in the current shape
+    /// should be returned unchanged. An error returned by the hook fails the
+    /// load.
+    ///
+    /// Migrated values are only changed in memory; they are written back to
+    /// the backend the next time the entry is inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "in_memory")]
+    /// use persistent_map::in_memory::InMemoryBackend;
+    ///
+    /// # #[cfg(feature = "in_memory")]
+    /// # async fn example() -> Result<()> {
+    /// // Version 1 stored bare names; version 2 stores `{ "name": .. }` objects
+    /// let map: PersistentMap<String, serde_json::Value, _> =
+    ///     PersistentMap::builder(InMemoryBackend::new())
+    ///         .migrate_value(|value| {
+    ///             Ok(match value {
+    ///                 serde_json::Value::String(name) => serde_json::json!({ "name": name }),
+    ///                 current => current,
+    ///             })
+    ///         })
+    ///         .build()
+    ///         .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "in_memory"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub fn migrate_value<F>(mut self, migration: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Result<serde_json::Value> + Send + Sync + 'static,
+    {
+        self.migrate_value = Some(Arc::new(migration));
+        self
+    }
+
+    /// Fixes up stored values that fail to decode into `V` during a load.
+    ///
+    /// Each stored value that does not decode, for example because a field
+    /// was added to `V` after it was saved, is passed to `repair` as JSON and
+    /// the returned JSON is decoded instead. Values that decode are not
+    /// touched. A value that still fails after the repair fails the load. If
+    /// a [`migrate_value`](Self::migrate_value) hook is also set, it runs
+    /// first and the repair sees its output.
+    ///
+    /// Setting a repair hook makes loads read the stored values as JSON and
+    /// decode each one twice when it needs repairing. Repaired values are
+    /// only changed in memory; they are written back to the backend the next
+    /// time the entry is inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "in_memory")]
+    /// use persistent_map::in_memory::InMemoryBackend;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Clone, Serialize, Deserialize)]
+    /// struct Settings {
+    ///     theme: String,
+    ///     // Added in version 2
+    ///     font_size: u32,
+    /// }
+    ///
+    /// # #[cfg(feature = "in_memory")]
+    /// # async fn example() -> Result<()> {
+    /// let map: PersistentMap<String, Settings, _> =
+    ///     PersistentMap::builder(InMemoryBackend::new())
+    ///         .repair_value(|mut value| {
+    ///             if let Some(fields) = value.as_object_mut() {
+    ///                 fields.entry("font_size").or_insert(14.into());
+    ///             }
+    ///             value
+    ///         })
+    ///         .build()
+    ///         .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "in_memory"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub fn repair_value<F>(mut self, repair: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> serde_json::Value + Send + Sync + 'static,
+    {
+        self.repair_value = Some(Arc::new(repair));
+        self
+    }
+
+    /// Maps every key to a canonical form before it is used.
+    ///
+    /// The function is applied to the key passed to `insert`, `get`,
+    /// `remove`, `contains_key` and the other keyed operations, and to every
+    /// key loaded from the backend, so keys that normalize to the same value
+    /// refer to the same entry. The normalized key is what is stored in
+    /// memory and persisted.
+    ///
+    /// This changes key identity: with a lowercasing normalizer, inserting
+    /// `Alice` and then `alice` overwrites a single entry stored as `alice`.
+    /// If the backend already holds keys that collide after normalization,
+    /// one of them wins on load in an unspecified order. Prefixes passed to
+    /// `scan_prefix` are matched against stored keys as given.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "in_memory")]
+    /// use persistent_map::in_memory::InMemoryBackend;
+    ///
+    /// # #[cfg(feature = "in_memory")]
+    /// # async fn example() -> Result<()> {
+    /// let users: PersistentMap<String, u32, _> = PersistentMap::builder(InMemoryBackend::new())
+    ///     .normalize_keys(|name: &String| name.to_lowercase())
+    ///     .build()
+    ///     .await?;
+    ///
+    /// users.insert("Alice".to_string(), 1).await?;
+    /// assert_eq!(users.get(&"alice".to_string()), Some(1));
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "in_memory"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub fn normalize_keys<F>(mut self, normalizer: F) -> Self
+    where
+        F: Fn(&K) -> K + Send + Sync + 'static,
+    {
+        self.normalize_keys = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Chooses the kind of per-key lock used by read-modify-write operations.
+    ///
+    /// The default, [`KeyLock::Async`](crate::KeyLock::Async), holds an async
+    /// mutex across the backend write so that memory and storage always see
+    /// writes to a key in the same order. [`KeyLock::Sync`](crate::KeyLock::Sync)
+    /// uses cheaper synchronous locks for hot keys, at the cost of releasing
+    /// the lock before the backend write; see its documentation for the exact
+    /// trade-off. A synchronous lock is never held across an `.await`, since
+    /// that could block the runtime thread or deadlock.
+    #[cfg(feature = "runtime")]
+    #[must_use]
+    pub const fn key_lock(mut self, kind: crate::KeyLock) -> Self {
+        self.key_lock = kind;
+        self
+    }
+
+    /// Flushes the backend periodically in a background task.
+    ///
+    /// Each flush happens `base` plus a random delay of up to `jitter` after
+    /// the previous one, so many map instances started at the same time don't
+    /// all hit the disk in the same instant. Flush errors are ignored by the
+    /// task and retried on the next tick.
+    ///
+    /// The task is spawned on the current tokio runtime by [`build`](Self::build)
+    /// and runs until [`PersistentMap::shutdown`] is called or the map is dropped.
+    #[cfg(feature = "runtime")]
+    #[must_use]
+    pub const fn auto_flush_jittered(mut self, base: Duration, jitter: Duration) -> Self {
+        self.auto_flush = Some((base, jitter));
+        self
+    }
+
+    /// Persists writes in the background instead of on every call.
+    ///
+    /// In write-behind mode, `insert`, `remove` and the other writes update
+    /// the in-memory map immediately and queue the backend operation. Only
+    /// the last operation per key is kept. A background task drains the
+    /// queue every `interval`, or as soon as `max_pending` keys are waiting,
+    /// writing the whole batch with one `write_batch` call (a single
+    /// transaction for `SqliteBackend`). [`PersistentMap::flush`] drains the
+    /// queue and waits for the write to finish, and so does
+    /// [`PersistentMap::shutdown`].
+    ///
+    /// Queued writes live only in memory: if the process crashes or the map
+    /// is dropped without a flush, they are lost, unless a write-ahead log is
+    /// configured with [`wal`](Self::wal). Backend errors surface from
+    /// `flush`; failed operations stay queued and are retried.
+    ///
+    /// The task is spawned on the current tokio runtime by [`build`](Self::build).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let map: PersistentMap<String, u64, _> =
+    ///     PersistentMap::builder(SqliteBackend::new("counters.db").await?)
+    ///         .write_behind(Duration::from_millis(100), 1_000)
+    ///         .build()
+    ///         .await?;
+    ///
+    /// map.insert("hits".to_string(), 1).await?; // returns without touching the database
+    /// map.flush().await?; // the write is persisted here at the latest
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[cfg(feature = "runtime")]
+    #[must_use]
+    pub const fn write_behind(mut self, interval: Duration, max_pending: usize) -> Self {
+        self.write_behind = Some((interval, max_pending));
+        self
+    }
+
+    /// Logs every write to a local write-ahead log before it returns, and
+    /// applies the log to the backend in the background.
+    ///
+    /// This is [`write_behind`](Self::write_behind) mode made durable: each
+    /// write is appended to the file at `path` and synced to disk, then
+    /// queued. The background task writes the queue to the backend in one
+    /// batch and, once that succeeded, removes the applied records from the
+    /// log. Writes thus return at local disk speed while surviving a crash,
+    /// which suits slow or remote backends. The drain interval and threshold
+    /// come from `write_behind` if it is set, and default to every 100 ms or
+    /// 1024 pending keys otherwise.
+    ///
+    /// On [`build`](Self::build), records left in the log by a previous run
+    /// are written to the backend before the map loads. The log holds one
+    /// JSON record per line, `{"save":[key,value]}` or `{"delete":key}`. A
+    /// last line without a trailing newline comes from a write that crashed
+    /// before it was acknowledged and is discarded; any other line that
+    /// cannot be decoded fails the build rather than silently dropping
+    /// acknowledged writes.
+    ///
+    /// Only one map may use a given log file at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let map: PersistentMap<String, String, _> =
+    ///     PersistentMap::builder(SqliteBackend::new("remote.db").await?)
+    ///         .wal("data/map.wal")
+    ///         .build()
+    ///         .await?;
+    ///
+    /// map.insert("key".to_string(), "value".to_string()).await?; // logged, not yet in the database
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[cfg(feature = "runtime")]
+    #[must_use]
+    pub fn wal(mut self, path: impl Into<PathBuf>) -> Self {
+        self.wal = Some(path.into());
+        self
+    }
+
+    /// Bounds the load that [`build`](Self::build) runs to fill the map.
+    ///
+    /// A load that takes longer than `timeout` is abandoned and handled like
+    /// a failed load, as chosen by
+    /// [`startup_load_policy`](Self::startup_load_policy): by default `build`
+    /// returns an error of kind [`std::io::ErrorKind::TimedOut`]. With
+    /// [`StartupLoadPolicy::EmptyThenRetry`], each background retry is
+    /// bounded by the same timeout. Without this option the load may take
+    /// as long as the backend does.
+    #[cfg(feature = "runtime")]
+    #[must_use]
+    pub const fn startup_load_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_load_timeout = Some(timeout);
+        self
+    }
+
+    /// Chooses what [`build`](Self::build) does when its initial load fails
+    /// or exceeds the [`startup_load_timeout`](Self::startup_load_timeout).
+    ///
+    /// [`StartupLoadPolicy::FailFast`], the default, returns the error.
+    /// [`StartupLoadPolicy::EmptyThenRetry`] keeps a service bootable while
+    /// its store is degraded: `build` returns an empty map and a background
+    /// task retries the load, waiting half a second before the first retry
+    /// and doubling the wait up to 30 seconds, until it succeeds. The map is
+    /// usable in the meantime; reads miss until the load lands, and writes
+    /// go to the backend as usual, so they fail while it is unavailable.
+    ///
+    /// The retried load only fills in keys that are not in memory, so values
+    /// written since the map was built are kept. A key removed while the
+    /// retry is reading the backend may reappear. Calling
+    /// [`PersistentMap::load`] loads on demand in the meantime.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result, StartupLoadPolicy};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let map: PersistentMap<String, String, _> =
+    ///     PersistentMap::builder(SqliteBackend::new("/mnt/shared/my_database.db").await?)
+    ///         .startup_load_timeout(Duration::from_secs(5))
+    ///         .startup_load_policy(StartupLoadPolicy::EmptyThenRetry)
+    ///         .build()
+    ///         .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[cfg(feature = "runtime")]
+    #[must_use]
+    pub const fn startup_load_policy(mut self, policy: StartupLoadPolicy) -> Self {
+        self.startup_load_policy = policy;
+        self
+    }
+
+    /// Batches the backend reads of read-through misses that happen within
+    /// `window` of each other.
+    ///
+    /// A miss of [`get_durable`](PersistentMap::get_durable), or of another
+    /// method that reads a missing key from the backend, normally costs one
+    /// `load_one_raw` call. With a window, the first miss waits for it and
+    /// then reads every key missed in the meantime with a single
+    /// [`load_many_raw`](StorageBackend::load_many_raw) call, which
+    /// `SqliteBackend` answers with one query. This cuts round trips when
+    /// many tasks miss different keys at once, such as on a cold cache, at
+    /// the cost of delaying every miss by up to `window`. A window of a
+    /// millisecond or two is usually enough.
+    ///
+    /// If the batched read fails, each waiting miss retries its own key, so
+    /// errors are reported to the callers they concern.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let map: PersistentMap<String, String, _> =
+    ///     PersistentMap::builder(SqliteBackend::new("my_database.db").await?)
+    ///         .max_capacity(10_000)
+    ///         .load_batch_window(Duration::from_millis(1))
+    ///         .build()
+    ///         .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[cfg(feature = "runtime")]
+    #[must_use]
+    pub const fn load_batch_window(mut self, window: Duration) -> Self {
+        self.load_batch_window = Some(window);
+        self
+    }
+
+    /// Syncs every write to stable storage before it returns.
+    ///
+    /// With `durable(true)`, `insert`, `remove` and the other writes call the
+    /// backend's [`sync`](StorageBackend::sync) after the write, so a write
+    /// that returned `Ok` survives a power failure. `SqliteBackend` switches
+    /// to `synchronous = FULL` and checkpoints its write-ahead log, and
+    /// `CsvBackend` calls `fsync` on its file. Expect a large drop in write
+    /// throughput, since every write waits for the disk.
+    ///
+    /// Writes queued by [`write_behind`](Self::write_behind) mode are not
+    /// synced individually; call [`PersistentMap::flush`] to make them
+    /// durable.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let ledger: PersistentMap<String, i64, _> =
+    ///     PersistentMap::builder(SqliteBackend::new("ledger.db").await?)
+    ///         .durable(true)
+    ///         .build()
+    ///         .await?;
+    ///
+    /// ledger.insert("balance".to_string(), 100).await?; // on disk once this returns
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub const fn durable(mut self, durable: bool) -> Self {
+        self.durable = durable;
+        self
+    }
+
+    /// Keeps keys written through this map from being overwritten by reloads
+    /// until the backend has caught up with the write.
+    ///
+    /// [`load`](PersistentMap::load) and
+    /// [`sync_changes`](PersistentMap::sync_changes) normally apply whatever
+    /// the backend returns. When the backend lags behind this map, for
+    /// example a replica or an eventually consistent store, a reload right
+    /// after `insert` could bring back the old value. In this mode the map
+    /// remembers the content version of every value it writes and every key
+    /// it removes, and a reload leaves such a key as it is in memory until
+    /// one of these happens:
+    ///
+    /// - the backend returns exactly the written value, or lacks the key
+    ///   after a remove, which confirms the write, or
+    /// - `window` has passed since the write.
+    ///
+    /// After that the key is no longer tracked and reloads apply the
+    /// backend's value again, including changes made by other writers.
+    /// Content versions carry no order, so a different value from another
+    /// writer cannot be told apart from a stale one; it wins only once the
+    /// window has passed. Set `window` to the longest lag expected of the
+    /// backend.
+    ///
+    /// Only writes through this map are tracked, and the check costs one
+    /// hash of the value per write.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let map: PersistentMap<String, String, _> =
+    ///     PersistentMap::builder(SqliteBackend::new("replica.db").await?)
+    ///         .read_your_writes(Duration::from_secs(30))
+    ///         .build()
+    ///         .await?;
+    ///
+    /// map.insert("greeting".to_string(), "hello".to_string()).await?;
+    /// map.load().await?;
+    /// assert_eq!(map.get(&"greeting".to_string()), Some("hello".to_string()));
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub const fn read_your_writes(mut self, window: Duration) -> Self {
+        self.read_your_writes = Some(window);
+        self
+    }
+
+    /// Keeps the time each cached entry was last written, for
+    /// [`PersistentMap::entry_age`].
+    ///
+    /// Writes through the map record the current time. Loads take the time
+    /// from the backend's [`load_write_times`](StorageBackend::load_write_times),
+    /// which `SqliteBackend` answers from its `updated_at` column; entries
+    /// loaded from backends without write times have no known age until
+    /// they are written again. Entries applied by
+    /// [`sync_changes`](PersistentMap::sync_changes) count as written when
+    /// the change is applied.
+    ///
+    /// This costs one clone of the key and a timestamp per cached entry, and
+    /// one more backend read per load.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let prices: PersistentMap<String, f64, _> =
+    ///     PersistentMap::builder(SqliteBackend::new("prices.db").await?)
+    ///         .track_entry_age()
+    ///         .build()
+    ///         .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub const fn track_entry_age(mut self) -> Self {
+        self.track_entry_age = true;
+        self
+    }
+
+    /// Builds the map and loads all existing entries from the backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the write-ahead log cannot be opened or replayed,
+    /// or if loading from the backend fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if background tasks are configured and this is not called from
+    /// within a tokio runtime.
+    pub async fn build(self) -> Result<PersistentMap<K, V, B>> {
+        #[cfg(feature = "runtime")]
+        let write_behind = self
+            .write_behind
+            .or_else(|| self.wal.as_ref().map(|_| DEFAULT_WAL_DRAIN));
+        #[cfg(feature = "runtime")]
+        let buffer = match (write_behind, self.wal) {
+            (Some((_, max_pending)), Some(path)) => {
+                Some(Arc::new(WriteBuffer::with_wal(max_pending, path)?))
+            }
+            (Some((_, max_pending)), None) => Some(Arc::new(WriteBuffer::new(max_pending))),
+            (None, _) => None,
+        };
+
+        let pm = PersistentMap {
+            map: Arc::new(DashMap::new()),
+            backend: Arc::new(self.backend),
+            eviction: Arc::new(Eviction::new(
+                self.max_capacity,
+                self.eviction,
+                self.on_evict,
+                self.track_entry_age,
+            )),
+            max_entries: self.max_entries,
+            admission: std::sync::Mutex::new(0),
+            keys: Normalize::new(self.normalize_keys),
+            migrate_value: migrate::with_repair::<V>(self.migrate_value, self.repair_value),
+            durable: self.durable,
+            local_writes: self.read_your_writes.map(LocalWrites::new),
+            #[cfg(feature = "runtime")]
+            load_lock: tokio::sync::Mutex::new(()),
+            #[cfg(feature = "runtime")]
+            key_locks: crate::key_lock::KeyedLock::new(self.key_lock),
+            #[cfg(feature = "runtime")]
+            batch_loader: self.load_batch_window.map(crate::loader::BatchLoader::new),
+            #[cfg(feature = "runtime")]
+            write_behind: buffer,
+            load_generation: AtomicU64::new(0),
+            loaded_count: AtomicUsize::new(0),
+            change_token: std::sync::Mutex::new(None),
+            #[cfg(feature = "runtime")]
+            tasks: crate::tasks::BackgroundTasks::default(),
+            #[cfg(feature = "runtime")]
+            waiters: Arc::default(),
+            #[cfg(feature = "latency")]
+            latency: crate::latency::Latencies::default(),
+        };
+        #[cfg(feature = "runtime")]
+        {
+            let loaded = match self.startup_load_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, pm.load())
+                    .await
+                    .unwrap_or_else(|_| Err(startup::timed_out(timeout))),
+                None => pm.load().await,
+            };
+            match (loaded, self.startup_load_policy) {
+                (Ok(_), _) => {}
+                (Err(e), StartupLoadPolicy::FailFast) => return Err(e),
+                (Err(_), StartupLoadPolicy::EmptyThenRetry) => {
+                    pm.tasks.push(startup::spawn_retry(
+                        Arc::clone(&pm.backend),
+                        pm.migrate_value.clone(),
+                        self.startup_load_timeout,
+                        startup::Cache {
+                            map: Arc::clone(&pm.map),
+                            eviction: Arc::clone(&pm.eviction),
+                            waiters: Arc::clone(&pm.waiters),
+                            keys: pm.keys.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+        #[cfg(not(feature = "runtime"))]
+        pm.load().await?;
+
+        #[cfg(feature = "runtime")]
+        if let (Some(buffer), Some((interval, _))) = (&pm.write_behind, write_behind) {
+            pm.tasks.push(crate::tasks::spawn_write_behind(
+                Arc::clone(buffer),
+                Arc::clone(&pm.backend),
+                interval,
+            ));
+        }
+
+        #[cfg(feature = "runtime")]
+        if let Some((base, jitter)) = self.auto_flush {
+            pm.tasks.push(crate::tasks::spawn_auto_flush(
+                Arc::clone(&pm.backend),
+                base,
+                jitter,
+            ));
+        }
+
+        Ok(pm)
+    }
+}
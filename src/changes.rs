@@ -0,0 +1,134 @@
+//! Incremental syncing with changes made by other processes.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    hash::Hash,
+    sync::PoisonError,
+    time::{Instant, SystemTime},
+};
+
+/// A position in a backend's change sequence.
+///
+/// Returned by [`StorageBackend::changes_since`] and passed back to it to
+/// read only the changes made after that position. Tokens are only
+/// meaningful to the backend that issued them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChangeToken(u64);
+
+impl ChangeToken {
+    /// Creates a token from a backend's change sequence number.
+    #[must_use]
+    pub const fn new(sequence: u64) -> Self {
+        Self(sequence)
+    }
+
+    /// Returns the change sequence number of the token.
+    #[must_use]
+    pub const fn sequence(self) -> u64 {
+        self.0
+    }
+}
+
+/// A change to one key, reported by [`StorageBackend::changes_since`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change<K> {
+    /// The key was saved.
+    Saved {
+        /// The changed key
+        key: K,
+        /// The current value, as the JSON bytes returned by `load_one_raw`
+        value: Vec<u8>,
+        /// When the entry expires, if it was saved with an expiry
+        expires_at: Option<SystemTime>,
+    },
+
+    /// The key was deleted.
+    Deleted {
+        /// The deleted key
+        key: K,
+    },
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Applies the backend changes made since the last load or sync to the
+    /// in-memory map, returning how many keys changed.
+    ///
+    /// This picks up writes made by other processes sharing the backend
+    /// without reloading everything: saved keys are updated in memory and
+    /// deleted keys are removed. Values pass through the
+    /// [`migrate_value`](crate::PersistentMapBuilder::migrate_value) hook if
+    /// one is set. The map's own writes are reported too, and applying them
+    /// again leaves the map unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let changed = map.sync_changes().await?;
+    /// println!("{changed} keys changed since the last sync");
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns `PersistentError::Unsupported` if the backend does not track
+    /// changes, or another error if reading from the backend or decoding a
+    /// value fails.
+    pub async fn sync_changes(&self) -> Result<usize> {
+        // Pending writes must land first or older stored values would revert them
+        self.drain_pending().await?;
+
+        let since = *self
+            .change_token
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (changes, token) = self.backend.changes_since(since).await?;
+
+        let count = changes.len();
+        let (now, system_now) = (Instant::now(), SystemTime::now());
+        for change in changes {
+            match change {
+                Change::Saved {
+                    key,
+                    value,
+                    expires_at,
+                } => {
+                    let key = self.keys.owned(key);
+                    let value = self.decode_stored(&value)?;
+                    if self.keeps_local(&key, Some(&value)) {
+                        continue;
+                    }
+                    let expires_at = expires_at.and_then(|at| {
+                        now.checked_add(at.duration_since(system_now).unwrap_or_default())
+                    });
+                    self.insert_cached_locked(key, value, expires_at);
+                }
+                Change::Deleted { key } => {
+                    let key = self.keys.owned(key);
+                    if !self.keeps_local(&key, None) {
+                        self.remove_cached(&key);
+                    }
+                }
+            }
+        }
+        self.evict_over_capacity();
+        self.set_change_token(Some(token));
+        Ok(count)
+    }
+
+    /// Records the backend's change token after a load or change sync.
+    pub(crate) fn set_change_token(&self, token: Option<ChangeToken>) {
+        *self
+            .change_token
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = token;
+    }
+}
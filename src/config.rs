@@ -0,0 +1,146 @@
+//! Configuration-driven backend construction.
+
+use crate::{DynBackend, PersistentError, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{hash::Hash, str::FromStr};
+
+/// Environment variable selecting the backend in [`BackendConfig::from_env`].
+pub const BACKEND_ENV: &str = "PERSISTENT_MAP_BACKEND";
+
+/// Environment variable holding the storage path in [`BackendConfig::from_env`].
+pub const PATH_ENV: &str = "PERSISTENT_MAP_PATH";
+
+/// Describes which storage backend to use and how to open it.
+///
+/// Only the variants of enabled backend features exist. The enum can be
+/// deserialized from configuration files, using a `type` tag:
+///
+/// ```toml
+/// [storage]
+/// type = "sqlite"
+/// path = "data/app.db"
+/// ```
+///
+/// The S3 backend needs a configured client and is not available here;
+/// construct it directly and box it as a [`DynBackend`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum BackendConfig {
+    /// A `SQLite` database file.
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        /// Path of the database file
+        path: String,
+    },
+
+    /// A CSV file.
+    #[cfg(feature = "csv_backend")]
+    Csv {
+        /// Path of the CSV file
+        path: std::path::PathBuf,
+    },
+
+    /// The non-persistent in-memory backend.
+    #[cfg(feature = "in_memory")]
+    InMemory,
+}
+
+impl BackendConfig {
+    /// Reads the backend configuration from the environment.
+    ///
+    /// `PERSISTENT_MAP_BACKEND` names the backend (`sqlite`, `csv` or
+    /// `in_memory`) and `PERSISTENT_MAP_PATH` gives the storage path for
+    /// the file-based backends.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{build_backend, BackendConfig, PersistentMap, Result};
+    ///
+    /// # async fn example() -> Result<()> {
+    /// // PERSISTENT_MAP_BACKEND=sqlite PERSISTENT_MAP_PATH=app.db
+    /// let backend = build_backend(&BackendConfig::from_env()?).await?;
+    /// let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `PersistentError::Config` if a variable is missing or names a
+    /// backend that is unknown or not enabled.
+    pub fn from_env() -> Result<Self> {
+        let backend = env_var(BACKEND_ENV)?;
+        match backend.as_str() {
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(Self::Sqlite {
+                path: env_var(PATH_ENV)?,
+            }),
+            #[cfg(feature = "csv_backend")]
+            "csv" => Ok(Self::Csv {
+                path: env_var(PATH_ENV)?.into(),
+            }),
+            #[cfg(feature = "in_memory")]
+            "in_memory" => Ok(Self::InMemory),
+            other => Err(PersistentError::Config(format!(
+                "unknown or disabled backend `{other}` in {BACKEND_ENV}"
+            ))),
+        }
+    }
+}
+
+fn env_var(name: &str) -> Result<String> {
+    std::env::var(name).map_err(|e| PersistentError::Config(format!("{name}: {e}")))
+}
+
+/// Constructs the backend described by `config`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{build_backend, BackendConfig, PersistentMap, Result};
+///
+/// # #[cfg(feature = "sqlite")]
+/// # async fn example() -> Result<()> {
+/// let config = BackendConfig::Sqlite {
+///     path: "my_database.db".to_string(),
+/// };
+/// let map: PersistentMap<String, String, _> =
+///     PersistentMap::new(build_backend(&config).await?).await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "sqlite"))]
+/// # fn example() {}
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the backend cannot be opened.
+pub async fn build_backend<K, V>(config: &BackendConfig) -> Result<DynBackend<K, V>>
+where
+    K: Eq
+        + Hash
+        + Clone
+        + Serialize
+        + DeserializeOwned
+        + Send
+        + Sync
+        + 'static
+        + ToString
+        + FromStr,
+    <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    match *config {
+        #[cfg(feature = "sqlite")]
+        BackendConfig::Sqlite { ref path } => {
+            Ok(Box::new(crate::sqlite::SqliteBackend::new(path).await?))
+        }
+        #[cfg(feature = "csv_backend")]
+        BackendConfig::Csv { ref path } => Ok(Box::new(crate::csv::CsvBackend::new(path.clone()))),
+        #[cfg(feature = "in_memory")]
+        BackendConfig::InMemory => Ok(Box::new(crate::in_memory::InMemoryBackend::new())),
+    }
+}
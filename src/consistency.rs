@@ -0,0 +1,124 @@
+//! Read-your-writes consistency across reloads.
+//!
+//! In read-your-writes mode the map remembers, for each key it wrote, the
+//! content version of the value it wrote, or that it deleted the key. Reloads
+//! skip a tracked key until the backend returns the written version, which
+//! confirms that the backend has seen the write, or until the tracking
+//! window elapses.
+
+use crate::version::version_of;
+use crate::{PersistentMap, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// The local writes not yet confirmed by the backend.
+pub struct LocalWrites<K> {
+    /// How long a write shadows the backend's value of its key
+    window: Duration,
+
+    /// The version written to each key, `None` for deletes, and when
+    writes: Mutex<HashMap<K, (Option<u64>, Instant)>>,
+}
+
+impl<K> LocalWrites<K>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            writes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub const fn window(&self) -> Duration {
+        self.window
+    }
+
+    fn writes(&self) -> MutexGuard<'_, HashMap<K, (Option<u64>, Instant)>> {
+        self.writes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Records a local write of `version` to `key`, `None` for a delete.
+    fn record(&self, key: &K, version: Option<u64>) {
+        self.writes().insert(key.clone(), (version, Instant::now()));
+    }
+
+    /// Returns whether the local write of `key` must be kept over the
+    /// backend's `stored` version, `None` if the backend lacks the key.
+    ///
+    /// The write stops being tracked once the backend returns its version or
+    /// the window elapses.
+    fn shadows(&self, key: &K, stored: Option<u64>) -> bool {
+        let mut writes = self.writes();
+        let Some(&(written, at)) = writes.get(key) else {
+            return false;
+        };
+        let confirmed = written == stored || at.elapsed() >= self.window;
+        if confirmed {
+            writes.remove(key);
+        }
+        drop(writes);
+        !confirmed
+    }
+
+    /// Stops tracking writes whose window has elapsed, and deletes of keys
+    /// the backend no longer holds, as told by `stored`.
+    fn settle(&self, stored: impl Fn(&K) -> bool) {
+        self.writes().retain(|key, (written, at)| {
+            at.elapsed() < self.window && (written.is_some() || stored(key))
+        });
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Records a local write of `value` to `key`, or a delete if `None`, in
+    /// read-your-writes mode.
+    pub(crate) fn note_write(&self, key: &K, value: Option<&V>) {
+        let Some(local) = &self.local_writes else {
+            return;
+        };
+        // Unserializable values are rejected before they are written
+        let Ok(version) = value.map(version_of).transpose() else {
+            return;
+        };
+        local.record(key, version);
+    }
+
+    /// Returns whether a reload must leave `key` as it is in memory instead
+    /// of applying the backend's `stored` value, `None` if the backend
+    /// lacks the key.
+    pub(crate) fn keeps_local(&self, key: &K, stored: Option<&V>) -> bool {
+        let Some(local) = &self.local_writes else {
+            return false;
+        };
+        stored
+            .map(version_of)
+            .transpose()
+            .map_or(false, |version| local.shadows(key, version))
+    }
+
+    /// Stops tracking the local writes a full load of `loaded` shows are no
+    /// longer in doubt, so writes to keys that are never reloaded don't pile
+    /// up.
+    pub(crate) fn settle_local_writes(&self, loaded: &[(K, V)]) {
+        let Some(local) = &self.local_writes else {
+            return;
+        };
+        let stored: HashSet<K> = loaded
+            .iter()
+            .map(|(k, _)| self.keys.owned(k.clone()))
+            .collect();
+        local.settle(|k| stored.contains(k));
+    }
+}
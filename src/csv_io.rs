@@ -0,0 +1,111 @@
+//! CSV export and import for maps over any backend.
+//!
+//! Rows use the same layout as the CSV backend: the key followed by the
+//! value's fields, or the value's JSON encoding in one column when it cannot
+//! be flattened. A file exported from any map can therefore be opened as a
+//! `CsvBackend`, and a `CsvBackend` file can be imported into any map.
+
+use crate::backends::csv::{decode_row, encode_row};
+use crate::{PersistentError, PersistentMap, Result, StorageBackend};
+use csv::ReaderBuilder;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{hash::Hash, path::Path, str::FromStr};
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Writes the cached, unexpired entries to a CSV file at `path`,
+    /// replacing the file if it exists.
+    ///
+    /// Keys are written with `ToString`, like the CSV backend does, and rows
+    /// are sorted by key so exporting the same data twice produces the same
+    /// file. Only the in-memory map is exported; entries that were evicted or
+    /// never loaded are not included. Returns the number of rows written.
+    ///
+    /// The file is written synchronously, like the CSV backend's own writes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let rows = map.export_csv("export.csv")?;
+    /// println!("exported {rows} rows");
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if a value cannot be encoded or the file cannot be
+    /// written.
+    pub fn export_csv(&self, path: impl AsRef<Path>) -> Result<usize>
+    where
+        K: ToString,
+    {
+        let mut entries: Vec<(String, V)> = self
+            .map
+            .iter()
+            .filter(|entry| !self.eviction.is_expired(entry.key()))
+            .map(|entry| (entry.key().to_string(), entry.value().clone()))
+            .collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let mut rows = Vec::new();
+        for (key, value) in &entries {
+            encode_row(&mut rows, key, value)?;
+        }
+        std::fs::write(path, rows)?;
+        Ok(entries.len())
+    }
+
+    /// Inserts every row of the CSV file at `path` into the map.
+    ///
+    /// Keys are parsed with `FromStr`, like the CSV backend does. Rows are
+    /// inserted in file order, so when a key appears more than once the last
+    /// row wins, and are saved to the backend as one batch. Returns the
+    /// number of rows read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.import_csv("seed.csv").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, if a row's key or value
+    /// cannot be decoded, in which case nothing is inserted, or if saving to
+    /// the backend fails.
+    pub async fn import_csv(&self, path: impl AsRef<Path> + Send) -> Result<usize>
+    where
+        K: FromStr,
+        <K as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| PersistentError::Csv(e.to_string()))?;
+        let mut entries = Vec::new();
+        for record in rdr.records() {
+            let record = record.map_err(|e| PersistentError::Csv(e.to_string()))?;
+            let (key, value) = decode_row::<V>(&record)?;
+            let key = key
+                .parse::<K>()
+                .map_err(|e| PersistentError::Csv(format!("invalid key {key:?}: {e}")))?;
+            entries.push((key, value));
+        }
+
+        let imported = entries.len();
+        self.insert_batch_ordered(entries).await?;
+        Ok(imported)
+    }
+}
@@ -0,0 +1,87 @@
+//! Human-readable dumps of a map's state for diagnostics.
+
+use crate::{PersistentMap, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt::{Debug, Write},
+    hash::Hash,
+};
+
+/// Maximum number of characters of each value shown by `debug_dump`.
+const DEFAULT_MAX_VALUE_LEN: usize = 80;
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + Debug + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + Debug + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Formats the cached entries and a short summary for diagnostics.
+    ///
+    /// The dump starts with the backend type, the number of cached entries,
+    /// the capacity and, in write-behind mode, the number of keys with
+    /// unflushed writes, followed by one `key => value` line per entry using
+    /// `Debug`, sorted by key. Values longer than 80 characters are
+    /// truncated; use [`debug_dump_truncated`](Self::debug_dump_truncated) to
+    /// choose the limit. Only the in-memory map is dumped; the backend is not
+    /// read.
+    ///
+    /// The format is meant for people and may change between releases.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// eprintln!("{}", map.debug_dump());
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn debug_dump(&self) -> String {
+        self.debug_dump_truncated(DEFAULT_MAX_VALUE_LEN)
+    }
+
+    /// Like [`debug_dump`](Self::debug_dump), but truncates each formatted
+    /// value to `max_value_len` characters.
+    #[must_use]
+    pub fn debug_dump_truncated(&self, max_value_len: usize) -> String {
+        let mut lines: Vec<(String, String)> = self
+            .map
+            .iter()
+            .map(|entry| {
+                let mut value = truncate(format!("{:?}", entry.value()), max_value_len);
+                if self.eviction.is_expired(entry.key()) {
+                    value.push_str(" (expired)");
+                }
+                (format!("{:?}", entry.key()), value)
+            })
+            .collect();
+        lines.sort();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "backend: {}", std::any::type_name::<B>());
+        let _ = writeln!(out, "entries: {}", lines.len());
+        let _ = match self.eviction.capacity() {
+            Some(capacity) => writeln!(out, "capacity: {capacity}"),
+            None => writeln!(out, "capacity: unbounded"),
+        };
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            let _ = writeln!(out, "pending writes: {}", buffer.len());
+        }
+        for (key, value) in lines {
+            let _ = writeln!(out, "{key} => {value}");
+        }
+        out
+    }
+}
+
+/// Shortens `text` to at most `max_len` characters, marking the cut.
+fn truncate(mut text: String, max_len: usize) -> String {
+    if let Some((cut, _)) = text.char_indices().nth(max_len) {
+        text.truncate(cut);
+        text.push_str("...");
+    }
+    text
+}
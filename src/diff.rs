@@ -0,0 +1,105 @@
+//! Comparing the in-memory map against its backend.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash, time::SystemTime};
+
+/// The differences between a map's cache and its backend, returned by
+/// [`PersistentMap::diff_backend`].
+///
+/// Each list is in unspecified order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendDiff<K, V> {
+    /// Keys cached in memory but not stored in the backend
+    pub only_in_memory: Vec<K>,
+
+    /// Keys stored in the backend but not cached in memory
+    pub only_in_backend: Vec<K>,
+
+    /// Keys whose cached value differs from the stored one, with the cached
+    /// value first
+    pub differs: Vec<(K, V, V)>,
+}
+
+impl<K, V> BackendDiff<K, V> {
+    /// Returns whether the cache and the backend hold the same entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.only_in_memory.is_empty() && self.only_in_backend.is_empty() && self.differs.is_empty()
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Compares the cached entries against the entries stored in the
+    /// backend.
+    ///
+    /// This reads and decodes the whole backend, like [`load`](Self::load),
+    /// and holds it in memory while comparing, so it is an auditing tool to
+    /// run off the hot path, for example from a periodic consistency check
+    /// or a test. Expired entries are ignored on both sides.
+    ///
+    /// Some differences are expected: entries that were evicted or never
+    /// loaded are only in the backend, and in write-behind mode writes that
+    /// were not flushed yet show up until the next flush. Queued writes are
+    /// deliberately not flushed first, so the diff shows what the backend
+    /// actually holds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.flush().await?;
+    /// let diff = map.diff_backend().await?;
+    /// for (key, cached, stored) in &diff.differs {
+    ///     eprintln!("{key}: cached {cached:?}, stored {stored:?}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if reading from the backend fails or a stored value
+    /// cannot be decoded.
+    pub async fn diff_backend(&self) -> Result<BackendDiff<K, V>>
+    where
+        V: PartialEq,
+    {
+        let expiries = self.backend.load_expiries().await?;
+        let now = SystemTime::now();
+        let mut stored: HashMap<K, V> = self
+            .load_backend_entries()
+            .await?
+            .into_iter()
+            .filter(|(k, _)| expiries.get(k).map_or(true, |at| *at > now))
+            .map(|(k, v)| (self.keys.owned(k), v))
+            .collect();
+
+        let mut diff = BackendDiff {
+            only_in_memory: Vec::new(),
+            only_in_backend: Vec::new(),
+            differs: Vec::new(),
+        };
+        for entry in self.map.iter() {
+            let key = entry.key();
+            if self.eviction.is_expired(key) {
+                continue;
+            }
+            match stored.remove(key) {
+                Some(value) if value == *entry.value() => {}
+                Some(value) => diff
+                    .differs
+                    .push((key.clone(), entry.value().clone(), value)),
+                None => diff.only_in_memory.push(key.clone()),
+            }
+        }
+        diff.only_in_backend = stored.into_keys().collect();
+        Ok(diff)
+    }
+}
@@ -0,0 +1,181 @@
+//! Streaming backup and restore over async byte streams.
+
+use crate::{migrate, PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    hash::Hash,
+    io::{Error, ErrorKind},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Marks the start of a dump.
+const MAGIC: &[u8; 4] = b"PMAP";
+
+/// Version of the frame layout written by `dump`.
+const VERSION: u8 = 1;
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Writes every entry of the backend to `w`.
+    ///
+    /// Pending writes are flushed to the backend first, and the entries are
+    /// read from the backend so ones that are not cached are included. They
+    /// are written sorted by their encoded key, so dumping the same data
+    /// twice produces the same bytes.
+    ///
+    /// # Format
+    ///
+    /// A dump starts with the 4-byte magic `PMAP` and a one-byte format
+    /// version (currently `1`), followed by one frame per entry:
+    ///
+    /// | field        | size           | content                            |
+    /// |--------------|----------------|------------------------------------|
+    /// | key length   | 4 bytes        | unsigned, big-endian, non-zero     |
+    /// | key          | `key length`   | the key encoded as JSON            |
+    /// | value length | 4 bytes        | unsigned, big-endian               |
+    /// | value        | `value length` | the value as stored by the backend |
+    ///
+    /// The dump ends with a key length of zero, so a truncated stream is
+    /// detected on restore. Values are the backend's stored bytes (JSON for
+    /// all built-in backends) and are not re-encoded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let file = tokio::fs::File::create("backup.pmap").await?;
+    /// map.dump(tokio::io::BufWriter::new(file)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if reading from the backend, encoding a key or
+    /// writing to `w` fails.
+    pub async fn dump<W>(&self, mut w: W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.drain_pending().await?;
+        let mut entries = self
+            .backend
+            .load_all_raw()
+            .await?
+            .into_iter()
+            .map(|(key, value)| Ok((serde_json::to_vec(&key)?, value)))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        w.write_all(MAGIC).await?;
+        w.write_u8(VERSION).await?;
+        for (key, value) in entries {
+            write_frame(&mut w, &key).await?;
+            write_frame(&mut w, &value).await?;
+        }
+        w.write_u32(0).await?;
+        w.flush().await?;
+        Ok(())
+    }
+
+    /// Reads a dump written by [`dump`](Self::dump) from `r` and inserts its
+    /// entries, returning how many were read.
+    ///
+    /// Entries are merged into the map: restored keys overwrite existing
+    /// ones and other keys are kept. Values go through the
+    /// [`migrate_value`](crate::PersistentMapBuilder::migrate_value) hook if
+    /// one is set. The whole dump is read and validated before anything is
+    /// inserted, so a corrupt or truncated stream leaves the map unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let file = tokio::fs::File::open("backup.pmap").await?;
+    /// let restored = map.restore(tokio::io::BufReader::new(file)).await?;
+    /// println!("Restored {restored} entries");
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if reading from `r` fails, the stream is not a valid
+    /// dump, an entry cannot be decoded, or saving to the backend fails.
+    pub async fn restore<R>(&self, mut r: R) -> Result<usize>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic).await?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a persistent-map dump".to_string()));
+        }
+        let version = r.read_u8().await?;
+        if version != VERSION {
+            return Err(invalid_data(format!("unsupported dump version {version}")));
+        }
+
+        let mut entries = Vec::new();
+        loop {
+            let key = read_frame(&mut r).await?;
+            if key.is_empty() {
+                break;
+            }
+            let value = read_frame(&mut r).await?;
+            let key: K = serde_json::from_slice(&key)?;
+            let value: V = match &self.migrate_value {
+                Some(migration) => migrate::decode(&value, migration)?,
+                None => serde_json::from_slice(&value)?,
+            };
+            entries.push((key, value));
+        }
+
+        let count = entries.len();
+        if count > 0 {
+            self.insert_batch_ordered(entries).await?;
+        }
+        Ok(count)
+    }
+}
+
+/// Writes `bytes` preceded by their length.
+async fn write_frame<W>(w: &mut W, bytes: &[u8]) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let len = u32::try_from(bytes.len())
+        .map_err(|_| invalid_data(format!("frame of {} bytes is too large", bytes.len())))?;
+    w.write_u32(len).await?;
+    w.write_all(bytes).await?;
+    Ok(())
+}
+
+/// Reads one length-prefixed frame.
+async fn read_frame<R>(r: &mut R) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let len = r.read_u32().await?;
+    // The length is untrusted, so let the buffer grow with the data read
+    // instead of allocating it up front
+    let mut bytes = Vec::new();
+    (&mut *r)
+        .take(u64::from(len))
+        .read_to_end(&mut bytes)
+        .await?;
+    if bytes.len() != len as usize {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "truncated dump frame").into());
+    }
+    Ok(bytes)
+}
+
+fn invalid_data(message: String) -> crate::PersistentError {
+    Error::new(ErrorKind::InvalidData, message).into()
+}
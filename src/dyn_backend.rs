@@ -0,0 +1,260 @@
+//! Boxed, type-erased and shared storage backends.
+//!
+//! `StorageBackend` is dyn-compatible: its async methods return boxed futures
+//! through `async_trait`, so a backend can be chosen at runtime and stored as
+//! a `Box<dyn StorageBackend<K, V> + Send + Sync>`. The blanket
+//! implementations below forward every method to the pointed-to backend,
+//! which lets a boxed backend be used anywhere a concrete one is expected,
+//! and lets several maps share one backend through an `Arc`.
+
+use crate::{
+    BackendStats, Change, ChangeToken, PersistentError, Result, SaveOutcome, StorageBackend,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash, sync::Arc, time::SystemTime};
+
+/// A type-erased storage backend that can be selected at runtime.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{DynBackend, PersistentMap, Result};
+/// # #[cfg(all(feature = "sqlite", feature = "in_memory"))]
+/// use persistent_map::{in_memory::InMemoryBackend, sqlite::SqliteBackend};
+///
+/// # #[cfg(all(feature = "sqlite", feature = "in_memory"))]
+/// # async fn example(persistent: bool) -> Result<()> {
+/// let backend: DynBackend<String, String> = if persistent {
+///     Box::new(SqliteBackend::new("my_database.db").await?)
+/// } else {
+///     Box::new(InMemoryBackend::new())
+/// };
+/// let map = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(all(feature = "sqlite", feature = "in_memory")))]
+/// # fn example() {}
+/// ```
+pub type DynBackend<K, V> = Box<dyn StorageBackend<K, V> + Send + Sync>;
+
+/// Implements `StorageBackend` for a pointer type by forwarding every method
+/// to the backend it points to.
+macro_rules! forward_to_pointee {
+    ($pointer:ident) => {
+        #[async_trait::async_trait]
+        impl<K, V, B> StorageBackend<K, V> for $pointer<B>
+        where
+            K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+            V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+            B: StorageBackend<K, V> + Send + Sync + ?Sized,
+        {
+            async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+                (**self).load_all().await
+            }
+
+            async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+                (**self).save(key, value).await
+            }
+
+            async fn save_reporting(
+                &self,
+                key: K,
+                value: V,
+            ) -> Result<SaveOutcome, PersistentError> {
+                (**self).save_reporting(key, value).await
+            }
+
+            async fn save_if_newer(
+                &self,
+                key: K,
+                value: V,
+                version: u64,
+            ) -> Result<bool, PersistentError> {
+                (**self).save_if_newer(key, value, version).await
+            }
+
+            async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+                (**self).delete(key).await
+            }
+
+            async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+                (**self).save_many(entries).await
+            }
+
+            async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+                (**self).delete_many(keys).await
+            }
+
+            async fn delete_all(&self) -> Result<(), PersistentError> {
+                (**self).delete_all().await
+            }
+
+            async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+                (**self).rename_keys(renames).await
+            }
+
+            async fn write_batch(
+                &self,
+                saves: Vec<(K, V)>,
+                deletes: Vec<K>,
+            ) -> Result<(), PersistentError> {
+                (**self).write_batch(saves, deletes).await
+            }
+
+            async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+                (**self).load_one_raw(key).await
+            }
+
+            async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+                (**self).load_all_raw().await
+            }
+
+            async fn load_many_raw(
+                &self,
+                keys: &[K],
+            ) -> Result<Vec<Option<Vec<u8>>>, PersistentError> {
+                (**self).load_many_raw(keys).await
+            }
+
+            async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+                (**self).save_raw(key, value).await
+            }
+
+            async fn flush(&self) -> Result<(), PersistentError> {
+                (**self).flush().await
+            }
+
+            async fn sync(&self) -> Result<(), PersistentError> {
+                (**self).sync().await
+            }
+
+            async fn checkpoint(&self) -> Result<(), PersistentError> {
+                (**self).checkpoint().await
+            }
+
+            async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+                (**self).check_integrity(thorough).await
+            }
+
+            async fn save_expiring(
+                &self,
+                key: K,
+                value: V,
+                expires_at: SystemTime,
+            ) -> Result<(), PersistentError> {
+                (**self).save_expiring(key, value, expires_at).await
+            }
+
+            async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+                (**self).load_expiries().await
+            }
+
+            async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+                (**self).load_write_times().await
+            }
+
+            async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+                (**self).delete_expired(now).await
+            }
+
+            async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(K, V)>, PersistentError>
+            where
+                K: ToString,
+            {
+                (**self).scan_prefix(prefix).await
+            }
+
+            async fn load_page(
+                &self,
+                offset: usize,
+                limit: usize,
+            ) -> Result<Vec<(K, V)>, PersistentError>
+            where
+                K: ToString,
+            {
+                (**self).load_page(offset, limit).await
+            }
+
+            async fn load_after(
+                &self,
+                last_key: Option<K>,
+                limit: usize,
+            ) -> Result<Vec<(K, V)>, PersistentError>
+            where
+                K: ToString,
+            {
+                (**self).load_after(last_key, limit).await
+            }
+
+            async fn scan_prefix_raw(
+                &self,
+                prefix: &str,
+            ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+            where
+                K: ToString,
+            {
+                (**self).scan_prefix_raw(prefix).await
+            }
+
+            async fn load_page_raw(
+                &self,
+                offset: usize,
+                limit: usize,
+            ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+            where
+                K: ToString,
+            {
+                (**self).load_page_raw(offset, limit).await
+            }
+
+            async fn load_after_raw(
+                &self,
+                last_key: Option<K>,
+                limit: usize,
+            ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+            where
+                K: ToString,
+            {
+                (**self).load_after_raw(last_key, limit).await
+            }
+
+            async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+                (**self).contains_key(key).await
+            }
+
+            async fn len(&self) -> Result<usize, PersistentError> {
+                (**self).len().await
+            }
+
+            async fn is_empty(&self) -> Result<bool, PersistentError> {
+                (**self).is_empty().await
+            }
+
+            async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+                (**self).last_modified().await
+            }
+
+            async fn changes_since(
+                &self,
+                since: Option<ChangeToken>,
+            ) -> Result<(Vec<Change<K>>, ChangeToken), PersistentError> {
+                (**self).changes_since(since).await
+            }
+
+            async fn stats(&self) -> Result<BackendStats, PersistentError> {
+                (**self).stats().await
+            }
+
+            fn is_read_only(&self) -> bool {
+                (**self).is_read_only()
+            }
+        }
+    };
+}
+
+forward_to_pointee!(Box);
+
+// An `Arc` shares one backend, and so one connection or file handle, between
+// several maps, for example maps of different value types over one database.
+forward_to_pointee!(Arc);
@@ -0,0 +1,231 @@
+//! Entry-style access to a single key.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    future::{Future, IntoFuture},
+    hash::Hash,
+    pin::Pin,
+};
+
+/// A modification recorded by [`Entry::and_modify`].
+type Modify<'a, V> = Box<dyn FnOnce(&mut V) + Send + 'a>;
+
+/// A pending change to one key of a map, created with
+/// [`PersistentMap::entry`].
+///
+/// Like the `Entry` of `std`'s `HashMap`, it modifies the value if the key is
+/// present and inserts one if it is absent, but nothing happens until it is
+/// awaited: [`and_modify`](Self::and_modify) only records the change, and
+/// [`or_insert`](Self::or_insert) and [`or_insert_with`](Self::or_insert_with)
+/// return a future that checks the key and applies whichever change is due.
+/// An entry with only `and_modify` is awaited directly. A present key is
+/// saved to the backend whenever a modification was recorded, even if it
+/// leaves the value unchanged, and is not written otherwise.
+///
+/// With the `runtime` feature the key stays locked from the check until the
+/// backend write completes, like [`with_mut`](PersistentMap::with_mut).
+/// Maps built with [`KeyLock::Sync`](crate::KeyLock::Sync) do not hold off
+/// other writers of the key during the write.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use persistent_map::{PersistentMap, StorageBackend, Result};
+/// #
+/// # async fn example(map: PersistentMap<String, u64, impl StorageBackend<String, u64> + Send + Sync>) -> Result<()> {
+/// // Count a visit, starting at one
+/// let visits = map
+///     .entry("home".to_string())
+///     .and_modify(|n| *n += 1)
+///     .or_insert(1)
+///     .await?;
+///
+/// // Only touch keys that exist
+/// map.entry("about".to_string()).and_modify(|n| *n += 1).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "an entry does nothing unless it is awaited"]
+pub struct Entry<'a, K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// The map holding the key
+    map: &'a PersistentMap<K, V, B>,
+
+    /// The key of the entry
+    key: K,
+
+    /// Applied to the value if the key is present
+    modify: Option<Modify<'a, V>>,
+}
+
+impl<'a, K, V, B> Entry<'a, K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns the key of the entry.
+    pub const fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Records `f` to be applied to the value if the key is present and
+    /// unexpired.
+    ///
+    /// The modified value is saved, keeping the entry's time-to-live. Like
+    /// `std`, chained calls are all applied, in the order they were made.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V) + Send + 'a,
+    {
+        self.modify = Some(match self.modify.take() {
+            Some(previous) => Box::new(move |value: &mut V| {
+                previous(value);
+                f(value);
+            }),
+            None => Box::new(f),
+        });
+        self
+    }
+
+    /// Inserts `default` if the key is absent or expired, and returns the
+    /// resulting value.
+    ///
+    /// A present key is modified by [`and_modify`](Self::and_modify), if it
+    /// was called, and its value returned. Like
+    /// [`insert`](PersistentMap::insert), an inserted value has no
+    /// time-to-live.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the new or modified value cannot be serialized,
+    /// if saving it to the backend fails, in which case the map is left
+    /// unchanged, or [`PersistentError::CapacityExceeded`] if the key is
+    /// absent and the map already holds its
+    /// [`max_entries`](crate::PersistentMapBuilder::max_entries).
+    ///
+    /// [`PersistentError::CapacityExceeded`]: crate::PersistentError::CapacityExceeded
+    pub async fn or_insert(self, default: V) -> Result<V> {
+        self.or_insert_with(|| default).await
+    }
+
+    /// Inserts the value computed by `default` if the key is absent or
+    /// expired, and returns the resulting value.
+    ///
+    /// `default` is only called when the key is absent. Otherwise this is
+    /// [`or_insert`](Self::or_insert).
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `or_insert`.
+    pub async fn or_insert_with<F>(self, default: F) -> Result<V>
+    where
+        F: FnOnce() -> V + Send,
+    {
+        let map = self.map;
+        let key = map.keys.owned(self.key);
+        #[cfg(feature = "runtime")]
+        let _guard = map.key_locks.lock(&key).await;
+        if let Some(value) = Self::modify_present(map, &key, self.modify).await? {
+            return Ok(value);
+        }
+
+        let value = default();
+        PersistentMap::<K, V, B>::check_serializable(&value)?;
+        let mut reservation = map.reserve([&key])?;
+        map.persist(key.clone(), value.clone()).await?;
+        map.insert_reserved(&mut reservation, key, value.clone(), None);
+        map.evict_over_capacity();
+        Ok(value)
+    }
+
+    /// Applies `modify`, if set, to the value of `key` and saves it, and
+    /// returns the resulting value, or `None` if the key is absent or
+    /// expired. The caller holds the key's lock.
+    async fn modify_present(
+        map: &PersistentMap<K, V, B>,
+        key: &K,
+        modify: Option<Modify<'a, V>>,
+    ) -> Result<Option<V>> {
+        if map.eviction.is_expired(key) {
+            return Ok(None);
+        }
+        let Some(mut value) = map.map.get(key).map(|r| r.value().clone()) else {
+            return Ok(None);
+        };
+        let Some(modify) = modify else {
+            return Ok(Some(value));
+        };
+        modify(&mut value);
+        PersistentMap::<K, V, B>::check_serializable(&value)?;
+        let expires_at = map.eviction.expires_at(key);
+        map.persist_expiring(key.clone(), value.clone(), expires_at)
+            .await?;
+        map.insert_cached_locked(key.clone(), value.clone(), expires_at);
+        map.evict_over_capacity();
+        Ok(Some(value))
+    }
+}
+
+impl<'a, K, V, B> IntoFuture for Entry<'a, K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// The modified value, or `None` if the key is absent or expired
+    type Output = Result<Option<V>>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send + 'a>>;
+
+    /// Applies the modification recorded by
+    /// [`and_modify`](Entry::and_modify) if the key is present, without
+    /// inserting anything.
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let map = self.map;
+            let key = map.keys.owned(self.key);
+            #[cfg(feature = "runtime")]
+            let _guard = map.key_locks.lock(&key).await;
+            Entry::modify_present(map, &key, self.modify).await
+        })
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns the [`Entry`] of `key`, to modify or insert its value.
+    ///
+    /// The entry does nothing until it is awaited. See [`Entry`] for the
+    /// details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, Vec<String>, impl StorageBackend<String, Vec<String>> + Send + Sync>) -> Result<()> {
+    /// let tags = map
+    ///     .entry("post-1".to_string())
+    ///     .and_modify(|tags| tags.push("rust".to_string()))
+    ///     .or_insert_with(|| vec!["rust".to_string()])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, B> {
+        Entry {
+            map: self,
+            key,
+            modify: None,
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Capacity and expiry tracking for the in-memory map.
+//!
+//! `PersistentMap` keeps every loaded entry in memory unless it is configured
+//! with a maximum capacity or entries are inserted with a time-to-live. This
+//! module keeps the bookkeeping needed to decide which entries to evict and
+//! hands evicted entries to the user's eviction callback. It also keeps the
+//! write time of each cached entry when the map tracks entry ages, so that
+//! bookkeeping is dropped together with the entry.
+
+use dashmap::DashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Instant, SystemTime},
+};
+
+/// A callback invoked with each entry evicted from the in-memory map.
+///
+/// Entries are evicted when they expire or when the map exceeds its maximum
+/// capacity. Explicit removals never trigger the callback.
+pub type EvictionCallback<K, V> = Arc<dyn Fn(K, V) + Send + Sync>;
+
+/// Decides which entry is dropped when a bounded map exceeds its capacity.
+///
+/// Set with [`PersistentMapBuilder::eviction`](crate::PersistentMapBuilder::eviction).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evicts the least recently used entry. Every read and write counts as
+    /// a use. This is the default.
+    #[default]
+    Lru,
+
+    /// Evicts the least frequently used entry, breaking ties by evicting the
+    /// least recently used one.
+    ///
+    /// Suits workloads with a stable set of hot keys, which LRU can flush out
+    /// during a scan of cold keys. Use counts are kept while an entry is
+    /// cached and start over if it is evicted and loaded again. The entry
+    /// cached last is never the one evicted.
+    Lfu,
+
+    /// Evicts the entry that was cached first. Reads and overwrites do not
+    /// change an entry's position.
+    Fifo,
+}
+
+/// Eviction state shared by all operations of a `PersistentMap`.
+pub struct Eviction<K, V> {
+    /// Maximum number of entries kept in memory, if bounded
+    capacity: Option<usize>,
+
+    /// Eviction order of the cached keys, only maintained when bounded
+    order: Mutex<Ranking<K>>,
+
+    /// Expiry deadlines of entries inserted with a time-to-live, with the
+    /// tick that makes each one unique in `deadlines`
+    expiry: DashMap<K, (Instant, u64)>,
+
+    /// The keys of `expiry` ordered by deadline, so expired keys are found
+    /// without scanning the others
+    deadlines: Mutex<Deadlines<K>>,
+
+    /// Callback invoked with evicted entries
+    callback: Option<EvictionCallback<K, V>>,
+
+    /// When each cached entry was last written, if entry ages are tracked
+    written: Option<DashMap<K, SystemTime>>,
+}
+
+/// Keys with a deadline, earliest first, ties broken by insertion order.
+struct Deadlines<K> {
+    tick: u64,
+    order: BTreeMap<(Instant, u64), K>,
+}
+
+/// Ordering of keys by eviction rank, lowest first.
+///
+/// A rank is a use count (only tracked for LFU) followed by a tick, so the
+/// same structure serves all policies.
+struct Ranking<K> {
+    policy: EvictionPolicy,
+    tick: u64,
+    order: BTreeMap<(u64, u64), K>,
+    ranks: HashMap<K, (u64, u64)>,
+}
+
+impl<K> Ranking<K>
+where
+    K: Eq + Hash + Clone,
+{
+    fn new(policy: EvictionPolicy) -> Self {
+        Self {
+            policy,
+            tick: 0,
+            order: BTreeMap::new(),
+            ranks: HashMap::new(),
+        }
+    }
+
+    fn touch(&mut self, key: &K) {
+        let old = self.ranks.get(key).copied();
+        let uses = match (self.policy, old) {
+            (EvictionPolicy::Fifo, Some(_)) => return,
+            (EvictionPolicy::Lfu, Some((uses, _))) => uses + 1,
+            _ => 0,
+        };
+        self.tick += 1;
+        let rank = (uses, self.tick);
+        if let Some(old) = old {
+            self.order.remove(&old);
+        }
+        self.ranks.insert(key.clone(), rank);
+        self.order.insert(rank, key.clone());
+    }
+
+    fn forget(&mut self, key: &K) {
+        if let Some(old) = self.ranks.remove(key) {
+            self.order.remove(&old);
+        }
+    }
+
+    /// Removes and returns the lowest ranked key, other than the key touched
+    /// last: that is the entry just cached, and evicting it straight away
+    /// would keep LFU from ever admitting a new key.
+    fn pop_lowest(&mut self) -> Option<K> {
+        let rank = *self.order.keys().find(|rank| rank.1 != self.tick)?;
+        let key = self.order.remove(&rank)?;
+        self.ranks.remove(&key);
+        Some(key)
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.ranks.clear();
+    }
+}
+
+impl<K, V> Eviction<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new(
+        capacity: Option<usize>,
+        policy: EvictionPolicy,
+        callback: Option<EvictionCallback<K, V>>,
+        track_writes: bool,
+    ) -> Self {
+        Self {
+            capacity: capacity.map(|c| c.max(1)),
+            order: Mutex::new(Ranking::new(policy)),
+            expiry: DashMap::new(),
+            deadlines: Mutex::new(Deadlines {
+                tick: 0,
+                order: BTreeMap::new(),
+            }),
+            callback,
+            written: track_writes.then(DashMap::new),
+        }
+    }
+
+    /// Returns empty bookkeeping with the same capacity, policy and callback.
+    pub fn empty_like(&self) -> Self {
+        Self::new(
+            self.capacity,
+            self.order().policy,
+            self.callback.clone(),
+            self.written.is_some(),
+        )
+    }
+
+    fn order(&self) -> MutexGuard<'_, Ranking<K>> {
+        self.order.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn deadlines(&self) -> MutexGuard<'_, Deadlines<K>> {
+        self.deadlines
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sets or clears the deadline of `key`.
+    ///
+    /// `expiry` is only changed under the `deadlines` lock, so the two
+    /// always agree.
+    fn set_deadline(&self, key: &K, deadline: Option<Instant>) {
+        // Keeps writes without a time-to-live off the lock
+        if deadline.is_none() && !self.expiry.contains_key(key) {
+            return;
+        }
+        let mut deadlines = self.deadlines();
+        let old = deadline.map_or_else(
+            || self.expiry.remove(key).map(|(_, rank)| rank),
+            |deadline| {
+                deadlines.tick += 1;
+                let rank = (deadline, deadlines.tick);
+                deadlines.order.insert(rank, key.clone());
+                self.expiry.insert(key.clone(), rank)
+            },
+        );
+        if let Some(old) = old {
+            deadlines.order.remove(&old);
+        }
+    }
+
+    pub const fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Records a read or write of `key`.
+    pub fn touch(&self, key: &K) {
+        if self.capacity.is_some() {
+            self.order().touch(key);
+        }
+    }
+
+    /// Records a write of `key`, replacing any previous deadline.
+    pub fn record_insert(&self, key: &K, expires_at: Option<Instant>) {
+        self.set_deadline(key, expires_at);
+        self.touch(key);
+    }
+
+    /// Records that `key` was written at `at`, if entry ages are tracked.
+    pub fn record_written(&self, key: &K, at: SystemTime) {
+        if let Some(written) = &self.written {
+            written.insert(key.clone(), at);
+        }
+    }
+
+    /// Returns when `key` was last written, if entry ages are tracked and
+    /// the time is known.
+    pub fn written_at(&self, key: &K) -> Option<SystemTime> {
+        self.written.as_ref()?.get(key).map(|at| *at)
+    }
+
+    pub const fn tracks_writes(&self) -> bool {
+        self.written.is_some()
+    }
+
+    /// Forgets all bookkeeping for `key`.
+    pub fn forget(&self, key: &K) {
+        self.set_deadline(key, None);
+        if let Some(written) = &self.written {
+            written.remove(key);
+        }
+        if self.capacity.is_some() {
+            self.order().forget(key);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut deadlines = self.deadlines();
+        self.expiry.clear();
+        deadlines.order.clear();
+        drop(deadlines);
+        self.order().clear();
+        if let Some(written) = &self.written {
+            written.clear();
+        }
+    }
+
+    /// Returns the key the policy evicts next, removing it from the ordering.
+    pub fn pop_victim(&self) -> Option<K> {
+        self.order().pop_lowest()
+    }
+
+    /// Returns `true` if `key` was inserted with a time-to-live that has elapsed.
+    pub fn is_expired(&self, key: &K) -> bool {
+        self.expiry
+            .get(key)
+            .map_or(false, |deadline| deadline.0 <= Instant::now())
+    }
+
+    /// Returns the expiry deadline of `key`, if it has one.
+    pub fn expires_at(&self, key: &K) -> Option<Instant> {
+        self.expiry.get(key).map(|deadline| deadline.0)
+    }
+
+    /// Returns all keys whose time-to-live has elapsed, earliest deadline
+    /// first.
+    ///
+    /// Only the expired keys are visited, in O(k log n) for k expired keys
+    /// out of n with a deadline.
+    pub fn expired_keys(&self) -> Vec<K> {
+        let now = Instant::now();
+        self.deadlines()
+            .order
+            .range(..=(now, u64::MAX))
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+
+    /// Evicts entries from `map` until it is within capacity, then hands
+    /// them to the callback.
+    pub fn evict_over_capacity(&self, map: &DashMap<K, V>) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        let mut evicted = Vec::new();
+        while map.len() > capacity {
+            let Some(key) = self.pop_victim() else {
+                break;
+            };
+            self.forget(&key);
+            if let Some(entry) = map.remove(&key) {
+                evicted.push(entry);
+            }
+        }
+        self.notify(evicted);
+    }
+
+    /// Hands evicted entries to the callback.
+    ///
+    /// Callers must not hold any map or bookkeeping lock while calling this.
+    pub fn notify(&self, evicted: Vec<(K, V)>) {
+        if let Some(callback) = &self.callback {
+            for (key, value) in evicted {
+                callback(key, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Helpers for maps holding schemaless JSON documents.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::hash::Hash;
+
+/// Accessors for maps whose values are `serde_json::Value` documents.
+///
+/// Paths are [JSON Pointers](https://datatracker.ietf.org/doc/html/rfc6901)
+/// such as `/address/city` or `/tags/0`; the empty path refers to the whole
+/// document. Only the addressed part of the document is cloned.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{JsonMapExt, PersistentMap, StorageBackend, Result};
+/// use serde_json::{json, Value};
+///
+/// # async fn example(map: PersistentMap<String, Value, impl StorageBackend<String, Value> + Send + Sync>) -> Result<()> {
+/// map.insert("user:1".to_string(), json!({ "address": { "city": "Oslo" } }))
+///     .await?;
+/// assert_eq!(
+///     map.get_path(&"user:1".to_string(), "/address/city"),
+///     Some(json!("Oslo"))
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub trait JsonMapExt<K> {
+    /// Returns the value at `path` inside the document stored under `key`.
+    ///
+    /// Returns `None` if the key is absent or the path doesn't exist.
+    fn get_path(&self, key: &K, path: &str) -> Option<Value>;
+
+    /// Returns the value at `path` deserialized into `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value at `path` cannot be deserialized into `T`.
+    fn get_path_as<T>(&self, key: &K, path: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.get_path(key, path)
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(Into::into)
+    }
+}
+
+impl<K, B> JsonMapExt<K> for PersistentMap<K, Value, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, Value> + Send + Sync + 'static,
+{
+    fn get_path(&self, key: &K, path: &str) -> Option<Value> {
+        self.read(key, |document| document.pointer(path).cloned())
+            .flatten()
+    }
+}
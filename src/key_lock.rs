@@ -0,0 +1,173 @@
+//! Per-key locks.
+//!
+//! Read-modify-write operations lock the key they touch, so concurrent
+//! writers to the same key are serialized while writers to different keys
+//! proceed in parallel. Two kinds of lock are available, see [`KeyLock`].
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash, Hasher},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::sync::OwnedMutexGuard;
+
+/// Number of stripes used by the synchronous lock.
+const STRIPES: usize = 64;
+
+/// The kind of per-key lock used by a `PersistentMap`.
+///
+/// Set with [`PersistentMapBuilder::key_lock`](crate::PersistentMapBuilder::key_lock).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyLock {
+    /// An async mutex per key that is held across the backend write.
+    ///
+    /// Writes to the same key reach the backend in the same order as they
+    /// reach memory, and `with_mut` only updates memory once the backend
+    /// write succeeded. This is the default.
+    #[default]
+    Async,
+
+    /// A striped synchronous mutex that only guards the in-memory update.
+    ///
+    /// Taking the lock is cheaper, which helps with hot keys, but because a
+    /// synchronous lock must never be held across an `.await` it is released
+    /// before the backend write. As a result, concurrent writes to the same
+    /// key may reach the backend in a different order than they reached
+    /// memory, and `with_mut` updates memory before persisting, so a failed
+    /// backend write leaves the mutated value in memory. Keys that hash to
+    /// the same stripe briefly contend with each other.
+    Sync,
+}
+
+/// The per-key locks of a map.
+pub enum KeyedLock<K> {
+    Async(AsyncLocks<K>),
+    Sync(StripedLocks),
+}
+
+impl<K> KeyedLock<K>
+where
+    K: Eq + Hash + Clone + Send + Sync,
+{
+    pub fn new(kind: KeyLock) -> Self {
+        match kind {
+            KeyLock::Async => Self::Async(AsyncLocks {
+                locks: Mutex::new(HashMap::new()),
+            }),
+            KeyLock::Sync => Self::Sync(StripedLocks {
+                hasher: RandomState::new(),
+                stripes: (0..STRIPES).map(|_| Mutex::new(())).collect(),
+            }),
+        }
+    }
+
+    pub const fn kind(&self) -> KeyLock {
+        match self {
+            Self::Async(_) => KeyLock::Async,
+            Self::Sync(_) => KeyLock::Sync,
+        }
+    }
+
+    /// Takes the async lock of `key`, to be held across the backend write.
+    ///
+    /// Returns `None` without waiting when the map uses synchronous locks.
+    pub async fn lock(&self, key: &K) -> Option<KeyGuard<'_, K>> {
+        match self {
+            Self::Async(locks) => Some(locks.lock(key).await),
+            Self::Sync(_) => None,
+        }
+    }
+
+    /// Takes the synchronous lock of `key`, to be held around an in-memory
+    /// update only.
+    ///
+    /// Returns `None` when the map uses async locks.
+    pub fn lock_sync(&self, key: &K) -> Option<MutexGuard<'_, ()>> {
+        match self {
+            Self::Async(_) => None,
+            Self::Sync(locks) => Some(locks.lock(key)),
+        }
+    }
+
+    pub const fn is_sync(&self) -> bool {
+        matches!(self, Self::Sync(_))
+    }
+}
+
+/// A table of async mutexes, one per locked key.
+///
+/// A key's mutex is dropped from the table as soon as nobody holds or waits
+/// for it.
+pub struct AsyncLocks<K> {
+    locks: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl<K> AsyncLocks<K>
+where
+    K: Eq + Hash + Clone + Send + Sync,
+{
+    fn locks(&self) -> MutexGuard<'_, HashMap<K, Arc<tokio::sync::Mutex<()>>>> {
+        self.locks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits until the lock of `key` is available and takes it.
+    async fn lock(&self, key: &K) -> KeyGuard<'_, K> {
+        let mutex = {
+            let mut locks = self.locks();
+            Arc::clone(locks.entry(key.clone()).or_default())
+        };
+        let guard = Arc::clone(&mutex).lock_owned().await;
+        KeyGuard {
+            table: self,
+            key: key.clone(),
+            mutex,
+            guard: Some(guard),
+        }
+    }
+}
+
+/// Holds the async lock of one key until dropped.
+pub struct KeyGuard<'a, K>
+where
+    K: Eq + Hash + Clone + Send + Sync,
+{
+    table: &'a AsyncLocks<K>,
+    key: K,
+    mutex: Arc<tokio::sync::Mutex<()>>,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl<K> Drop for KeyGuard<'_, K>
+where
+    K: Eq + Hash + Clone + Send + Sync,
+{
+    fn drop(&mut self) {
+        self.guard.take();
+        let mut locks = self.table.locks();
+        // The table and this guard are the only owners: nobody is waiting
+        if Arc::strong_count(&self.mutex) == 2 {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+/// A fixed set of synchronous mutexes shared by all keys.
+pub struct StripedLocks {
+    hasher: RandomState,
+    stripes: Vec<Mutex<()>>,
+}
+
+impl StripedLocks {
+    fn lock<K>(&self, key: &K) -> MutexGuard<'_, ()>
+    where
+        K: Hash,
+    {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        #[allow(clippy::cast_possible_truncation)]
+        let stripe = hasher.finish() as usize % self.stripes.len();
+        self.stripes[stripe]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,185 @@
+//! Latency histograms of backend operations.
+//!
+//! Durations are counted in log-linear buckets: each power of two of
+//! nanoseconds is split into eight equal buckets, so a reported percentile is
+//! within 12.5% of the true value. Recording is one atomic increment, and
+//! the buckets have a fixed size no matter how many operations are recorded.
+
+use crate::{PersistentMap, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    future::Future,
+    hash::Hash,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Number of buckets per power of two, as a power of two.
+const SUB_BUCKET_BITS: u32 = 3;
+
+/// Number of buckets per power of two.
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Number of buckets needed to cover every `u64` nanosecond count.
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// Latency percentiles of one kind of backend operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Percentiles {
+    /// Number of recorded operations
+    pub count: u64,
+
+    /// Median latency
+    pub p50: Duration,
+
+    /// 95th percentile latency
+    pub p95: Duration,
+
+    /// 99th percentile latency
+    pub p99: Duration,
+}
+
+/// Latency percentiles of backend operations, returned by
+/// [`PersistentMap::latency_percentiles`].
+///
+/// Percentiles of an operation that was never recorded are zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Loads of the whole backend
+    pub load: Percentiles,
+
+    /// Saves, single or batched
+    pub save: Percentiles,
+
+    /// Deletes, single or batched
+    pub delete: Percentiles,
+}
+
+/// A histogram of operation durations.
+pub struct Histogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl Histogram {
+    /// Runs `operation`, recording how long it took.
+    pub async fn time<F: Future>(&self, operation: F) -> F::Output {
+        let started = Instant::now();
+        let output = operation.await;
+        self.record(started.elapsed());
+        output
+    }
+
+    fn record(&self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.buckets[bucket_of(nanos)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn percentiles(&self) -> Percentiles {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let count: u64 = counts.iter().sum();
+        let at = |per_mille: u64| {
+            if count == 0 {
+                return Duration::ZERO;
+            }
+            // The rank of the percentile, rounded up, counted from 1
+            let rank = (count.saturating_mul(per_mille) + 999) / 1000;
+            let mut seen = 0;
+            for (bucket, &n) in counts.iter().enumerate() {
+                seen += n;
+                if seen >= rank {
+                    return Duration::from_nanos(highest_in(bucket));
+                }
+            }
+            Duration::from_nanos(u64::MAX)
+        };
+        Percentiles {
+            count,
+            p50: at(500),
+            p95: at(950),
+            p99: at(990),
+        }
+    }
+}
+
+/// Returns the bucket counting `nanos`.
+// The truncating casts only see values below `SUB_BUCKETS`
+#[allow(clippy::cast_possible_truncation)]
+const fn bucket_of(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+    let exponent = 63 - nanos.leading_zeros();
+    let sub_bucket = (nanos >> (exponent - SUB_BUCKET_BITS)) as usize & (SUB_BUCKETS - 1);
+    (exponent - SUB_BUCKET_BITS + 1) as usize * SUB_BUCKETS + sub_bucket
+}
+
+/// Returns the largest nanosecond count that falls into `bucket`.
+// `bucket` is below `BUCKETS`, so the shift fits in a `u32`
+#[allow(clippy::cast_possible_truncation)]
+const fn highest_in(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let shift = (bucket / SUB_BUCKETS - 1) as u32;
+    let lowest = ((SUB_BUCKETS + bucket % SUB_BUCKETS) as u64) << shift;
+    lowest + ((1 << shift) - 1)
+}
+
+/// The histograms of a map's backend operations.
+#[derive(Default)]
+pub struct Latencies {
+    pub load: Histogram,
+    pub save: Histogram,
+    pub delete: Histogram,
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns latency percentiles of the backend operations this map ran.
+    ///
+    /// Every full load, save and delete that the map sends to its backend is
+    /// timed, including failed ones, from the call to its completion. Writes
+    /// queued in write-behind mode are not recorded, since they reach the
+    /// backend in batches drained in the background. Percentiles cover every
+    /// operation since the map was created and are accurate to within 12.5%.
+    ///
+    /// Only available with the `latency` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// let stats = map.latency_percentiles();
+    /// println!(
+    ///     "{} saves, p99 {:?}",
+    ///     stats.save.count, stats.save.p99
+    /// );
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn latency_percentiles(&self) -> LatencyStats {
+        LatencyStats {
+            load: self.latency.load.percentiles(),
+            save: self.latency.save.percentiles(),
+            delete: self.latency.delete.percentiles(),
+        }
+    }
+}
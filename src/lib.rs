@@ -41,325 +41,186 @@
 
 use dashmap::DashMap;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{collections::HashMap, hash::Hash};
-use thiserror::Error;
-/// A trait for implementing storage backends for `PersistentMap`.
-///
-/// This trait defines the interface that all storage backends must implement.
-/// It provides methods for loading, saving, and deleting key-value pairs.
-///
-/// # Type Parameters
-///
-/// * `K`: The key type, which must be hashable, serializable, and cloneable
-/// * `V`: The value type, which must be serializable and cloneable
-///
-/// # Examples
-///
-/// Implementing a custom backend:
-///
-/// The `StorageBackend` trait defines the interface for persistent storage backends.
-/// By implementing this trait, you can create custom storage solutions for the `PersistentMap`.
-///
-/// # Implementing a Custom Backend
-///
-/// To implement a custom backend, you need to:
-///
-/// 1. Create a struct to hold your backend-specific data
-/// 2. Implement the required methods: `load_all`, `save`, and `delete`
-/// 3. Optionally override the `flush` method if your backend buffers writes
-///
-/// # Example Implementation
-///
-/// Here's an example of a custom backend that stores data in a JSON file:
-///
-/// ```rust
-/// use persistent_map::{StorageBackend, PersistentError, Result};
-/// use std::collections::HashMap;
-/// use std::path::PathBuf;
-/// use std::fs;
-/// use serde::{Serialize, de::DeserializeOwned};
-/// use std::hash::Hash;
-///
-/// struct JsonFileBackend {
-///     path: PathBuf,
-/// }
-///
-/// impl JsonFileBackend {
-///     pub fn new(path: impl Into<PathBuf>) -> Self {
-///         Self { path: path.into() }
-///     }
-///
-///     // Helper method to ensure the file exists
-///     fn ensure_file_exists(&self) -> std::io::Result<()> {
-///         if !self.path.exists() {
-///             // Create parent directories if they don't exist
-///             if let Some(parent) = self.path.parent() {
-///                 if !parent.exists() {
-///                     fs::create_dir_all(parent)?;
-///                 }
-///             }
-///
-///             // Create the file with an empty JSON object
-///             fs::write(&self.path, "{}")?;
-///         }
-///         Ok(())
-///     }
-/// }
-///
-/// #[async_trait::async_trait]
-/// impl<K, V> StorageBackend<K, V> for JsonFileBackend
-/// where
-///     K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + ToString + 'static,
-///     V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
-/// {
-///     async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
-///         // Ensure the file exists
-///         self.ensure_file_exists()?;
-///
-///         // If the file is empty or contains just "{}", return an empty HashMap
-///         let content = fs::read_to_string(&self.path)?;
-///         if content.trim() == "{}" {
-///             return Ok(HashMap::new());
-///         }
-///
-///         // Parse the JSON file
-///         let map = serde_json::from_str(&content)
-///             .map_err(|e| PersistentError::Serde(e))?;
-///
-///         Ok(map)
-///     }
-///
-///     async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
-///         // Ensure the file exists
-///         self.ensure_file_exists()?;
-///
-///         // Load existing data
-///         let mut map: HashMap<K, V> = self.load_all().await?;
-///
-///         // Update the map
-///         map.insert(key, value);
-///
-///         // Write back to the file
-///         let content = serde_json::to_string_pretty(&map)
-///             .map_err(|e| PersistentError::Serde(e))?;
-///
-///         fs::write(&self.path, content)?;
-///
-///         Ok(())
-///     }
-///
-///     async fn delete(&self, key: &K) -> Result<(), PersistentError> {
-///         // Ensure the file exists
-///         self.ensure_file_exists()?;
-///
-///         // Load existing data
-///         let mut map: HashMap<K, V> = self.load_all().await?;
-///
-///         // Remove the key
-///         map.remove(key);
-///
-///         // Write back to the file
-///         let content = serde_json::to_string_pretty(&map)
-///             .map_err(|e| PersistentError::Serde(e))?;
-///
-///         fs::write(&self.path, content)?;
-///
-///         Ok(())
-///     }
-///
-///     async fn flush(&self) -> Result<(), PersistentError> {
-///         // No buffering in this implementation, so nothing to flush
-///         Ok(())
-///     }
-/// }
-/// ```
-///
-/// # Best Practices for Custom Backends
-///
-/// 1. **Error Handling**: Convert backend-specific errors to `PersistentError`
-/// 2. **Concurrency**: Ensure your backend is safe for concurrent access
-/// 3. **Performance**: Consider caching or batching operations for better performance
-/// 4. **Resilience**: Handle edge cases like missing files or corrupted data gracefully
-/// 5. **Testing**: Create tests that verify persistence across application restarts
-#[async_trait::async_trait]
-pub trait StorageBackend<K, V>
-where
-    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
-    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
-{
-    /// Load all key-value pairs from the storage backend.
-    ///
-    /// This method is called when initializing a `PersistentMap` to populate
-    /// the in-memory map with existing data.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `PersistentError` if loading fails for any reason, such as:
-    /// - The storage location doesn't exist
-    /// - The data is corrupted or in an invalid format
-    /// - There are permission issues
-    ///
-    /// # Implementation Notes
-    ///
-    /// - This method should be idempotent and safe to call multiple times
-    /// - If the storage is empty or doesn't exist yet, return an empty `HashMap`
-    /// - Consider adding error recovery mechanisms for corrupted data
-    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError>;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-    /// Save a key-value pair to the storage backend.
-    ///
-    /// This method is called whenever a key-value pair is inserted into the map.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `PersistentError` if saving fails for any reason, such as:
-    /// - The storage location is not writable
-    /// - There are permission issues
-    /// - The backend has reached capacity
-    ///
-    /// # Implementation Notes
-    ///
-    /// - This method should be atomic if possible
-    /// - Consider batching or caching writes for better performance
-    /// - If your backend requires serialization, handle serialization errors appropriately
-    async fn save(&self, key: K, value: V) -> Result<(), PersistentError>;
+// Re-export backends
+#[cfg(feature = "compression")]
+pub use crate::backends::compressed;
 
-    /// Delete a key-value pair from the storage backend.
-    ///
-    /// This method is called whenever a key-value pair is removed from the map.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `PersistentError` if deletion fails for any reason, such as:
-    /// - The storage location is not writable
-    /// - There are permission issues
-    ///
-    /// # Implementation Notes
-    ///
-    /// - This method should be idempotent - deleting a non-existent key should not be an error
-    /// - Consider optimizing for the case where the key doesn't exist
-    async fn delete(&self, key: &K) -> Result<(), PersistentError>;
+#[cfg(feature = "csv_backend")]
+pub use crate::backends::csv;
 
-    /// Flush any buffered writes to the storage backend.
-    ///
-    /// This method is called when the user explicitly requests to ensure all data is persisted.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `PersistentError` if flushing fails for any reason, such as:
-    /// - The storage location is not writable
-    /// - There are permission issues
-    ///
-    /// # Implementation Notes
-    ///
-    /// - This method is optional and has a default implementation that does nothing
-    /// - Backends that buffer writes should override this method to ensure data is persisted
-    /// - This method should be idempotent and safe to call multiple times
-    async fn flush(&self) -> Result<(), PersistentError> {
-        Ok(())
-    }
+#[cfg(feature = "external_blobs")]
+pub use crate::backends::external;
 
-    /// Check if a key exists in the storage backend.
-    ///
-    /// This is an optional method with a default implementation that loads all data
-    /// and checks if the key exists. Backend implementations can override this
-    /// for better performance if they can check for existence without loading all data.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `PersistentError` if the check fails for any reason.
-    ///
-    /// # Implementation Notes
-    ///
-    /// - The default implementation is inefficient for large datasets
-    /// - Override this method if your backend can check for existence more efficiently
-    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
-        let all = self.load_all().await?;
-        Ok(all.contains_key(key))
-    }
+#[cfg(feature = "in_memory")]
+pub use crate::backends::in_memory;
 
-    /// Get the number of key-value pairs in the storage backend.
-    ///
-    /// This is an optional method with a default implementation that loads all data
-    /// and counts the entries. Backend implementations can override this
-    /// for better performance if they can count entries without loading all data.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `PersistentError` if the count operation fails for any reason.
-    ///
-    /// # Implementation Notes
-    ///
-    /// - The default implementation is inefficient for large datasets
-    /// - Override this method if your backend can count entries more efficiently
-    async fn len(&self) -> Result<usize, PersistentError> {
-        let all = self.load_all().await?;
-        Ok(all.len())
-    }
+pub use crate::backends::json_keys;
 
-    /// Check if the storage backend is empty.
-    ///
-    /// This is an optional method with a default implementation that uses `len()`.
-    /// Backend implementations can override this for better performance.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `PersistentError` if the check fails for any reason.
-    ///
-    /// # Implementation Notes
-    ///
-    /// - The default implementation uses `len()`, which may be inefficient
-    /// - Override this method if your backend can check emptiness more efficiently
-    async fn is_empty(&self) -> Result<bool, PersistentError> {
-        Ok(self.len().await? == 0)
-    }
-}
+#[cfg(feature = "jsonl_backend")]
+pub use crate::backends::jsonl;
 
-/// Errors that can occur when using `PersistentMap`.
-///
-/// This enum represents all the possible errors that can occur when using
-/// the various storage backends.
-#[derive(Error, Debug)]
-pub enum PersistentError {
-    /// An error occurred in the `SQLite` backend.
-    #[cfg(feature = "sqlite")]
-    #[error("sqlite error: {0}")]
-    Sqlite(#[from] tokio_rusqlite::Error),
-
-    /// An error occurred in the CSV backend.
-    #[cfg(feature = "csv_backend")]
-    #[error("csv error: {0}")]
-    Csv(String),
-
-    /// An I/O error occurred.
-    #[error("io error: {0}")]
-    Io(#[from] std::io::Error),
-
-    /// A serialization or deserialization error occurred.
-    #[error("serde error: {0}")]
-    Serde(#[from] serde_json::Error),
-
-    /// An error occurred in the Sled backend.
-    #[cfg(feature = "sled_backend")]
-    #[error("sled error: {0}")]
-    Sled(#[from] sled::Error),
-}
+#[cfg(feature = "postgres")]
+pub use crate::backends::postgres;
 
-/// Shorthand Result with error defaulting to `PersistentError`.
-pub type Result<T, E = PersistentError> = std::result::Result<T, E>;
+#[cfg(feature = "redis_backend")]
+pub use crate::backends::redis;
 
-// Re-export backends
-#[cfg(feature = "csv_backend")]
-pub use crate::backends::csv;
+#[cfg(feature = "s3")]
+pub use crate::backends::s3;
 
-#[cfg(feature = "in_memory")]
-pub use crate::backends::in_memory;
+pub use crate::backends::serialized;
+
+#[cfg(feature = "sled_backend")]
+pub use crate::backends::sled;
 
 #[cfg(feature = "sqlite")]
 pub use crate::backends::sqlite;
 
 mod backends;
 
+mod bounds;
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub use crate::bounds::derive_support as __private;
+pub use crate::bounds::{PersistentKey, PersistentValue};
+#[cfg(feature = "derive")]
+pub use persistent_map_derive::{PersistentKey, PersistentValue};
+
+mod builder;
+pub use crate::builder::PersistentMapBuilder;
+
+mod changes;
+pub use crate::changes::{Change, ChangeToken};
+
+#[cfg(any(feature = "sqlite", feature = "csv_backend", feature = "in_memory"))]
+mod config;
+#[cfg(any(feature = "sqlite", feature = "csv_backend", feature = "in_memory"))]
+pub use crate::config::{build_backend, BackendConfig, BACKEND_ENV, PATH_ENV};
+
+#[cfg(feature = "csv_backend")]
+mod csv_io;
+
+mod consistency;
+
+mod debug;
+
+mod diff;
+pub use crate::diff::BackendDiff;
+
+mod dyn_backend;
+pub use crate::dyn_backend::DynBackend;
+
+mod entry;
+pub use crate::entry::Entry;
+
+mod eviction;
+pub use crate::eviction::{EvictionCallback, EvictionPolicy};
+
+mod json;
+pub use crate::json::JsonMapExt;
+
+#[cfg(feature = "latency")]
+mod latency;
+#[cfg(feature = "latency")]
+pub use crate::latency::{LatencyStats, Percentiles};
+
+mod lookups;
+pub use crate::lookups::KeyLookups;
+
+mod migrate;
+pub use crate::migrate::ValueMigration;
+
+mod normalize;
+pub use crate::normalize::KeyNormalizer;
+
+mod pages;
+pub use crate::pages::BackendPages;
+
+pub mod prelude;
+
+mod rate;
+pub use crate::rate::RateCounter;
+
+mod replace;
+
+mod sample;
+
+mod serializer;
+#[cfg(feature = "bincode")]
+pub use crate::serializer::BincodeSerializer;
+pub use crate::serializer::{CompressingSerializer, Compression, JsonSerializer, Serializer};
+
+mod set;
+pub use crate::set::PersistentSet;
+
+mod snapshot;
+pub use crate::snapshot::Snapshot;
+
+mod storage;
+pub use crate::storage::{BackendStats, PersistentError, Result, SaveOutcome, StorageBackend};
+
+mod transaction;
+pub use crate::transaction::TransactionBuilder;
+
+mod verify;
+pub use crate::verify::VerifyDepth;
+
+mod version;
+pub use crate::version::IfChanged;
+
+mod view;
+pub use crate::view::PrefixView;
+
+#[cfg(feature = "in_memory")]
+mod volatile;
+#[cfg(feature = "in_memory")]
+pub use crate::volatile::VolatileMap;
+
+#[cfg(feature = "runtime")]
+mod dump;
+
+#[cfg(feature = "runtime")]
+mod key_lock;
+#[cfg(feature = "runtime")]
+pub use crate::key_lock::KeyLock;
+
+#[cfg(feature = "runtime")]
+mod loader;
+
+#[cfg(feature = "runtime")]
+mod shared;
+#[cfg(feature = "runtime")]
+pub use crate::shared::SharedBackend;
+
+#[cfg(feature = "runtime")]
+mod startup;
+#[cfg(feature = "runtime")]
+pub use crate::startup::StartupLoadPolicy;
+
+#[cfg(feature = "runtime")]
+mod tasks;
+
+#[cfg(feature = "runtime")]
+mod wait;
+
+#[cfg(feature = "runtime")]
+mod wal;
+
+#[cfg(feature = "runtime")]
+mod write_behind;
+#[cfg(feature = "runtime")]
+pub use crate::write_behind::WriteAck;
+
 /// A persistent key-value map with in-memory caching.
 ///
 /// `PersistentMap` combines a fast in-memory `DashMap` with a persistent
@@ -408,11 +269,93 @@ where
     V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
     B: StorageBackend<K, V> + Send + Sync + 'static,
 {
-    /// The in-memory map for fast access
-    map: DashMap<K, V>,
+    /// The in-memory map for fast access, shared with background tasks
+    map: Arc<DashMap<K, V>>,
+
+    /// The storage backend for persistence, shared with background tasks
+    backend: Arc<B>,
+
+    /// Capacity and expiry bookkeeping for the in-memory map, shared with
+    /// background tasks
+    eviction: Arc<eviction::Eviction<K, V>>,
+
+    /// Number of entries at which inserts of new keys fail
+    max_entries: Option<usize>,
+
+    /// Serializes inserts of new keys under `max_entries`, so concurrent
+    /// inserts cannot overshoot it, and counts the room reserved by inserts
+    /// waiting on the backend
+    admission: Mutex<usize>,
+
+    /// Maps keys to their canonical form
+    keys: normalize::Normalize<K>,
+
+    /// Hook that upgrades stored values before they are decoded
+    migrate_value: Option<ValueMigration>,
+
+    /// Whether every backend write is synced to stable storage
+    durable: bool,
+
+    /// Local writes that reloads must not overwrite, in read-your-writes mode
+    local_writes: Option<consistency::LocalWrites<K>>,
+
+    /// Serializes concurrent loads so they share one backend fetch
+    #[cfg(feature = "runtime")]
+    load_lock: tokio::sync::Mutex<()>,
+
+    /// Serializes writes to the same key
+    #[cfg(feature = "runtime")]
+    key_locks: key_lock::KeyedLock<K>,
+
+    /// Coalesces the backend reads of read-through misses, if enabled
+    #[cfg(feature = "runtime")]
+    batch_loader: Option<loader::BatchLoader<K>>,
+
+    /// Queued backend writes, when write-behind is enabled
+    #[cfg(feature = "runtime")]
+    write_behind: Option<Arc<write_behind::WriteBuffer<K, V>>>,
+
+    /// Number of loads that completed successfully
+    load_generation: AtomicU64,
 
-    /// The storage backend for persistence
-    backend: B,
+    /// Number of entries cached by the last successful load
+    loaded_count: AtomicUsize,
+
+    /// The backend's change token as of the last load or change sync
+    change_token: Mutex<Option<ChangeToken>>,
+
+    /// Background tasks such as the periodic flush
+    #[cfg(feature = "runtime")]
+    tasks: tasks::BackgroundTasks,
+
+    /// Tasks waiting in `wait_for_key`, shared with background tasks
+    #[cfg(feature = "runtime")]
+    waiters: Arc<wait::KeyWaiters<K>>,
+
+    /// Latency histograms of backend operations
+    #[cfg(feature = "latency")]
+    latency: latency::Latencies,
+}
+
+/// Room held under `max_entries` for new keys whose insert waits on the
+/// backend, given back when dropped.
+struct Reservation<'a> {
+    /// The map's admission counter
+    admission: &'a Mutex<usize>,
+
+    /// Number of keys still holding room
+    count: usize,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            *self
+                .admission
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) -= self.count;
+        }
+    }
 }
 
 impl<K, V, B> PersistentMap<K, V, B>
@@ -448,10 +391,34 @@ where
     /// Returns an error if loading from the backend fails.
     #[inline]
     pub async fn new(backend: B) -> Result<Self> {
-        let map = DashMap::new();
-        let pm = Self { map, backend };
-        pm.load().await?;
-        Ok(pm)
+        Self::builder(backend).build().await
+    }
+
+    /// Returns a builder for a `PersistentMap` with optional settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// # let backend = SqliteBackend::new("my_database.db").await?;
+    /// let map: PersistentMap<String, String, _> = PersistentMap::builder(backend)
+    ///     .max_capacity(10_000)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[inline]
+    pub const fn builder(backend: B) -> PersistentMapBuilder<K, V, B> {
+        PersistentMapBuilder::new(backend)
     }
 
     /// Loads all key-value pairs from the storage backend into memory.
@@ -459,6 +426,22 @@ where
     /// This method is called automatically when creating a new `PersistentMap`,
     /// but can also be called manually to refresh the in-memory cache.
     ///
+    /// If the map was built with a [`migrate_value`](PersistentMapBuilder::migrate_value)
+    /// hook, every stored value is passed through it before being decoded.
+    ///
+    /// Returns the number of entries this load put into the in-memory map,
+    /// which is not the map's size: entries cached before the load and not
+    /// stored in the backend still count towards [`len`](Self::len), and
+    /// entries kept for [`read_your_writes`](PersistentMapBuilder::read_your_writes)
+    /// are skipped. The count is also available afterwards from
+    /// [`loaded_count`](Self::loaded_count).
+    ///
+    /// With the `runtime` feature, concurrent calls are coalesced: while one
+    /// load is in flight, other callers wait for it and return as soon as it
+    /// succeeds instead of fetching everything from the backend again, with
+    /// its count. If the in-flight load fails, the next waiter performs its
+    /// own load.
+    ///
     /// # Examples
     ///
     /// ```rust,no_run
@@ -466,7 +449,8 @@ where
     /// #
     /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
     /// // Reload all data from the storage backend
-    /// map.load().await?;
+    /// let loaded = map.load().await?;
+    /// println!("Reloaded {loaded} entries");
     /// # Ok(())
     /// # }
     /// ```
@@ -474,12 +458,90 @@ where
     ///
     /// Returns an error if loading from the backend fails.
     #[inline]
-    pub async fn load(&self) -> Result<(), PersistentError> {
-        let all = self.backend.load_all().await?;
+    pub async fn load(&self) -> Result<usize, PersistentError> {
+        let observed = self.load_generation.load(Ordering::Acquire);
+        #[cfg(feature = "runtime")]
+        let _guard = self.load_lock.lock().await;
+        if self.load_generation.load(Ordering::Acquire) != observed {
+            // Another caller completed a load while we were waiting
+            return Ok(self.loaded_count());
+        }
+        // Pending writes must land first or the load would revert them
+        self.drain_pending().await?;
+        // Taken before reading so `sync_changes` picks up concurrent writes
+        let change_token = match self.backend.changes_since(None).await {
+            Ok((_, token)) => Some(token),
+            Err(PersistentError::Unsupported(_)) => None,
+            Err(e) => return Err(e),
+        };
+
+        let loaded = self.load_backend_entries();
+        #[cfg(feature = "latency")]
+        let loaded = self.latency.load.time(loaded);
+        let all = loaded.await?;
+        let expiries = self.backend.load_expiries().await?;
+        let write_times = self.load_write_times().await?;
+        self.settle_local_writes(&all);
+        let (now, system_now) = (Instant::now(), SystemTime::now());
+        let mut count = 0;
         for (k, v) in all {
+            let expires_at = expiries
+                .get(&k)
+                .and_then(|at| now.checked_add(at.duration_since(system_now).unwrap_or_default()));
+            let written = write_times.get(&k).copied();
+            let k = self.keys.owned(k);
+            if self.keeps_local(&k, Some(&v)) {
+                continue;
+            }
+            match expires_at {
+                Some(expires_at) => self.eviction.record_insert(&k, Some(expires_at)),
+                None => self.eviction.touch(&k),
+            }
+            if let Some(written) = written {
+                self.eviction.record_written(&k, written);
+            }
             self.map.insert(k, v);
+            count += 1;
         }
-        Ok(())
+        #[cfg(feature = "runtime")]
+        self.waiters.wake_all();
+        self.evict_over_capacity();
+        self.set_change_token(change_token);
+        self.loaded_count.store(count, Ordering::Release);
+        self.load_generation.fetch_add(1, Ordering::Release);
+        Ok(count)
+    }
+
+    /// Returns the number of entries the last successful
+    /// [`load`](Self::load) put into the in-memory map, including the load
+    /// that [`new`](Self::new) and [`build`](PersistentMapBuilder::build)
+    /// perform.
+    ///
+    /// It is zero before the first successful load, for example while a map
+    /// built with the `EmptyThenRetry` startup load policy retries its
+    /// initial load in the background.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use persistent_map::{PersistentMap, Result};
+    /// # #[cfg(feature = "sqlite")]
+    /// use persistent_map::sqlite::SqliteBackend;
+    ///
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<()> {
+    /// let backend = SqliteBackend::new("my_database.db").await?;
+    /// let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+    /// println!("Loaded {} entries", map.loaded_count());
+    /// # Ok(())
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "sqlite"))]
+    /// # fn example() {}
+    /// ```
+    #[must_use]
+    pub fn loaded_count(&self) -> usize {
+        self.loaded_count.load(Ordering::Acquire)
     }
 
     /// Inserts a key-value pair into the map and persists it to the storage backend.
@@ -505,72 +567,1783 @@ where
     /// ```
     /// # Errors
     ///
-    /// Returns an error if saving to the backend fails.
+    /// Returns an error if the value cannot be serialized, in which case the
+    /// map is left unchanged, or if saving to the backend fails. Returns
+    /// [`PersistentError::CapacityExceeded`] if the key is new and the map
+    /// already holds its [`max_entries`](PersistentMapBuilder::max_entries).
     #[inline]
     pub async fn insert(&self, key: K, value: V) -> Result<Option<V>> {
-        let old = self.map.insert(key.clone(), value.clone());
-        self.backend.save(key, value).await?;
+        let key = self.keys.owned(key);
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        Self::check_serializable(&value)?;
+        let old = self.insert_admitted(key.clone(), value.clone(), None)?;
+        self.persist(key, value).await?;
+        self.evict_over_capacity();
         Ok(old)
     }
 
-    /// Retrieves a value from the map by its key.
-    ///
-    /// This method only accesses the in-memory map and does not interact with
-    /// the storage backend, making it very fast.
+    /// Inserts a key-value pair that expires after `ttl`.
+    ///
+    /// Once the time-to-live has elapsed the entry is treated as absent by
+    /// `get` and `contains_key`, and the next [`purge_expired`](Self::purge_expired)
+    /// removes it from memory and from the storage backend. The deadline is
+    /// saved with [`StorageBackend::save_expiring`], so on backends that store
+    /// it, such as `SQLite`, an entry reloaded after a restart still expires.
+    /// In write-behind mode, and on backends that do not store expiry, the
+    /// deadline is only tracked in memory. Inserting the key again without a
+    /// TTL clears the deadline.
+    ///
+    /// Deadlines are also kept in an index ordered by time, which is what
+    /// lets `purge_expired` skip the live entries. It costs one more clone
+    /// of the key, plus a deadline and a B-tree slot, for each entry with a
+    /// TTL; entries without one cost nothing extra.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// # use std::time::Duration;
     /// #
-    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
-    /// // Get a value
-    /// if let Some(value) = map.get(&"key".to_string()) {
-    ///     println!("Value: {}", value);
-    /// }
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.insert_with_ttl("session".to_string(), "token".to_string(), Duration::from_secs(60))
+    ///     .await?;
+    /// # Ok(())
     /// # }
     /// ```
-    #[inline]
-    pub fn get(&self, key: &K) -> Option<V> {
-        self.map.get(key).map(|r| r.value().clone())
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized or if saving to the
+    /// backend fails.
+    pub async fn insert_with_ttl(&self, key: K, value: V, ttl: Duration) -> Result<Option<V>> {
+        let key = self.keys.owned(key);
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        Self::check_serializable(&value)?;
+        let expires_at = Instant::now() + ttl;
+        let old = self.insert_admitted(key.clone(), value.clone(), Some(expires_at))?;
+        self.persist_expiring(key, value, Some(expires_at)).await?;
+        self.evict_over_capacity();
+        Ok(old)
     }
 
-    /// Removes a key-value pair from the map and the storage backend.
+    /// Returns the cached value of `key`, or computes it with `f`, inserts
+    /// it and returns it if the key is missing.
     ///
-    /// If the map contains the key, the key-value pair is removed and the old value
-    /// is returned. Otherwise, `None` is returned.
+    /// Concurrent callers for the same key wait for the key's lock, so only
+    /// the first one runs `f` and the others get the value it inserted. The
+    /// new value is persisted like with [`insert`](Self::insert); a value
+    /// that was already cached is returned without a backend write. With
+    /// [`KeyLock::Sync`] callers are not held off, so several may run `f`,
+    /// but only the first value is inserted and every caller gets it.
+    ///
+    /// Only memory is checked: a key evicted for capacity counts as missing
+    /// and its stored value is overwritten. Call
+    /// [`get_durable`](Self::get_durable) first to find such keys. Expired
+    /// keys count as missing too.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// # use persistent_map::{PersistentMap, StorageBackend, Result};
     /// #
+    /// # async fn render(page: &str) -> String { page.to_uppercase() }
     /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
-    /// // Remove a key-value pair
-    /// let old = map.remove(&"key".to_string()).await?;
-    /// if let Some(value) = old {
-    ///     println!("Removed value: {}", value);
-    /// }
+    /// let html = map
+    ///     .get_or_insert_with("home".to_string(), || render("home"))
+    ///     .await?;
     /// # Ok(())
     /// # }
     /// ```
     /// # Errors
     ///
-    /// Returns an error if deleting from the backend fails.
-    #[inline]
-    pub async fn remove(&self, key: &K) -> Result<Option<V>> {
-        let old = self.map.remove(key).map(|(_, v)| v);
-        if old.is_some() {
-            match self.backend.delete(key).await {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            }
+    /// Returns an error if the computed value cannot be serialized, in which
+    /// case the map is left unchanged, or if saving it to the backend fails.
+    /// Returns [`PersistentError::CapacityExceeded`] if the map already
+    /// holds its [`max_entries`](PersistentMapBuilder::max_entries).
+    pub async fn get_or_insert_with<F, Fut>(&self, key: K, f: F) -> Result<V>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: std::future::Future<Output = V> + Send,
+    {
+        let key = self.keys.owned(key);
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
         }
-        Ok(old)
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        // Another caller may have inserted the value while this one waited
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = f().await;
+        Self::check_serializable(&value)?;
+        if let Some(resident) = self.get_or_insert_cached(&key, &value)? {
+            return Ok(resident);
+        }
+        self.persist(key, value.clone()).await?;
+        self.evict_over_capacity();
+        Ok(value)
     }
 
-    /// Returns the number of key-value pairs in the map.
-    ///
+    /// Returns the cached value of `key`, or caches `value` and returns
+    /// `None`, under the synchronous key lock when the map uses
+    /// [`KeyLock::Sync`].
+    fn get_or_insert_cached(&self, key: &K, value: &V) -> Result<Option<V>> {
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock_sync(key);
+        if let Some(resident) = self.get(key) {
+            return Ok(Some(resident));
+        }
+        let admitted = self.admit([key])?;
+        self.insert_cached(key.clone(), value.clone(), None);
+        drop(admitted);
+        Ok(None)
+    }
+
+    /// Inserts a key-value pair like [`insert`](Self::insert), but reports
+    /// the previous value even when it is not cached.
+    ///
+    /// On an in-memory miss, for example after the key was evicted for
+    /// capacity, the backend's current value is read before it is
+    /// overwritten, so callers that use the old value for conflict detection
+    /// see what was actually stored. Keys that expired in memory report
+    /// `None`. A stored row that expired after its key was evicted is still
+    /// reported, since reading a single value does not check its expiry. The
+    /// miss costs a backend read, so prefer `insert` when the map holds every
+    /// key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// if let Some(old) = map.insert_durable("key".to_string(), "value".to_string()).await? {
+    ///     println!("overwrote {old}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized, in which case the
+    /// map is left unchanged, or if reading from or saving to the backend
+    /// fails.
+    pub async fn insert_durable(&self, key: K, value: V) -> Result<Option<V>> {
+        let key = self.keys.owned(key);
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        Self::check_serializable(&value)?;
+        let previous = self.current_value(&key).await?;
+        self.insert_admitted(key.clone(), value.clone(), None)?;
+        self.persist(key, value).await?;
+        self.evict_over_capacity();
+        Ok(previous)
+    }
+
+    /// Inserts a key-value pair and reports whether the backend already
+    /// stored the key.
+    ///
+    /// Unlike the previous value returned by [`insert`](Self::insert), which
+    /// only reflects the cache, the outcome comes from
+    /// [`StorageBackend::save_reporting`], so it is accurate for keys that
+    /// were evicted or never loaded. That makes it suitable for counting
+    /// distinct new keys. The write always goes straight to the backend: in
+    /// write-behind mode, queued writes are flushed first so the backend's
+    /// view is current.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, SaveOutcome, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, u64, impl StorageBackend<String, u64> + Send + Sync>) -> Result<()> {
+    /// if map.insert_reporting("visitor-42".to_string(), 1).await? == SaveOutcome::Inserted {
+    ///     println!("new visitor");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized, in which case the
+    /// map is left unchanged, or if flushing or saving to the backend fails.
+    pub async fn insert_reporting(&self, key: K, value: V) -> Result<SaveOutcome> {
+        let key = self.keys.owned(key);
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        Self::check_serializable(&value)?;
+        self.drain_pending().await?;
+        self.insert_admitted(key.clone(), value.clone(), None)?;
+        self.note_write(&key, Some(&value));
+        let outcome = self.backend.save_reporting(key, value).await?;
+        self.sync_if_durable().await?;
+        self.evict_over_capacity();
+        Ok(outcome)
+    }
+
+    /// Mutates the value of `key` in place and persists the result.
+    ///
+    /// If the key is present, `f` is applied to a copy of the value, the
+    /// mutated value is saved to the backend, and only then replaces the
+    /// in-memory value, so a failed save leaves the map unchanged. Returns
+    /// `f`'s result, or `None` without calling `f` if the key is absent or
+    /// expired. An entry's time-to-live, if any, is kept.
+    ///
+    /// With the `runtime` feature the key stays locked for the whole
+    /// read-modify-write, so concurrent `with_mut`, `insert`, `insert_raw`
+    /// and `remove` calls on the same key cannot interleave with it. Batch
+    /// writes such as [`insert_batch_ordered`](Self::insert_batch_ordered)
+    /// and `restore` do not take the key locks, so a value they write while
+    /// `f` runs can be overwritten by the mutated value. Maps built with
+    /// [`KeyLock::Sync`] only lock the in-memory update and persist the
+    /// mutated value afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, Vec<String>, impl StorageBackend<String, Vec<String>> + Send + Sync>) -> Result<()> {
+    /// let len = map
+    ///     .with_mut(&"todo".to_string(), |items| {
+    ///         items.push("write docs".to_string());
+    ///         items.len()
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the mutated value cannot be serialized, in which
+    /// case the map is left unchanged, or if saving to the backend fails.
+    pub async fn with_mut<F, R>(&self, key: &K, f: F) -> Result<Option<R>>
+    where
+        F: FnOnce(&mut V) -> R,
+    {
+        self.check_writable("with_mut")?;
+        let key = &*self.keys.borrowed(key);
+        #[cfg(feature = "runtime")]
+        if self.key_locks.is_sync() {
+            let Some((result, value, expires_at)) = self.with_mut_cached(key, f)? else {
+                return Ok(None);
+            };
+            self.persist_expiring(key.clone(), value, expires_at)
+                .await?;
+            return Ok(Some(result));
+        }
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(key).await;
+        if self.eviction.is_expired(key) {
+            return Ok(None);
+        }
+        let Some(mut value) = self.map.get(key).map(|r| r.value().clone()) else {
+            return Ok(None);
+        };
+        let result = f(&mut value);
+        Self::check_serializable(&value)?;
+        let expires_at = self.eviction.expires_at(key);
+        self.persist_expiring(key.clone(), value.clone(), expires_at)
+            .await?;
+        self.insert_cached(key.clone(), value, expires_at);
+        self.evict_over_capacity();
+        Ok(Some(result))
+    }
+
+    /// Mutates the value of `key` in place, persists it and returns the new
+    /// value.
+    ///
+    /// This is [`with_mut`](Self::with_mut) returning a clone of the mutated
+    /// value instead of the closure's result, with the same locking and
+    /// failure behavior. Returns `None` without calling `f` if the key is
+    /// absent or expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, u64, impl StorageBackend<String, u64> + Send + Sync>) -> Result<()> {
+    /// if let Some(visits) = map.update(&"visits".to_string(), |n| *n += 1).await? {
+    ///     println!("{visits} visits");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the mutated value cannot be serialized, in which
+    /// case the map is left unchanged, or if saving to the backend fails.
+    pub async fn update<F>(&self, key: &K, f: F) -> Result<Option<V>>
+    where
+        F: FnOnce(&mut V),
+    {
+        self.with_mut(key, |value| {
+            f(value);
+            value.clone()
+        })
+        .await
+    }
+
+    /// Replaces the value of `key` with `new` if its serialized form equals
+    /// that of `expected`, and persists it.
+    ///
+    /// This is a compare-and-swap for value types that do not implement
+    /// `PartialEq`: the expected and current values are both serialized to
+    /// JSON and compared byte for byte. With `expected` set to `None`, the
+    /// swap only happens if the key is absent or expired. Returns `true` if
+    /// the value was swapped. Like [`insert`](Self::insert), the new value
+    /// has no time-to-live.
+    ///
+    /// The comparison is only as reliable as the serialization is canonical.
+    /// Equal values that serialize differently, such as a `HashMap` whose
+    /// iteration order differs between the two copies, compare as different
+    /// and the swap fails. Serialize map fields through a `BTreeMap`, or with
+    /// a `serialize_with` function that sorts them, to get stable bytes.
+    ///
+    /// With the `runtime` feature the comparison and the write happen under
+    /// the key's lock, like [`with_mut`](Self::with_mut), so no write from
+    /// `insert`, `remove` or `with_mut` to the same key can slip in between.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let key = "leader".to_string();
+    /// let current = map.get(&key);
+    /// if map
+    ///     .compare_and_swap_bytes(key, current.as_ref(), "node-2".to_string())
+    ///     .await?
+    /// {
+    ///     println!("took over leadership");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if `expected`, the current value or `new` cannot be
+    /// serialized, in which case the map is left unchanged, or if saving to
+    /// the backend fails. Returns [`PersistentError::CapacityExceeded`] if the
+    /// swap would add a key to a map that already holds its
+    /// [`max_entries`](PersistentMapBuilder::max_entries).
+    pub async fn compare_and_swap_bytes(
+        &self,
+        key: K,
+        expected: Option<&V>,
+        new: V,
+    ) -> Result<bool> {
+        let expected = expected.map(serde_json::to_vec).transpose()?;
+        self.swap_if(key, new, |current| {
+            Ok(current.map(serde_json::to_vec).transpose()? == expected)
+        })
+        .await
+    }
+
+    /// Replaces the value of `key` with `new` if the current value equals
+    /// `expected`, and persists it.
+    ///
+    /// With `expected` set to `None`, the swap only happens if the key is
+    /// absent or expired. Returns `true` if the value was swapped, and
+    /// `false` without touching the backend otherwise. Like
+    /// [`insert`](Self::insert), the new value has no time-to-live.
+    ///
+    /// The comparison and the write are atomic in the same way as
+    /// [`compare_and_swap_bytes`](Self::compare_and_swap_bytes), which
+    /// works for values without `PartialEq`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, u64, impl StorageBackend<String, u64> + Send + Sync>) -> Result<()> {
+    /// let key = "counter".to_string();
+    /// loop {
+    ///     let current = map.get(&key);
+    ///     let next = current.unwrap_or(0) + 1;
+    ///     if map.compare_and_swap(&key, current.as_ref(), next).await? {
+    ///         break;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if `new` cannot be serialized, in which case the map
+    /// is left unchanged, or if saving to the backend fails. Returns
+    /// [`PersistentError::CapacityExceeded`] if the swap would add a key to a
+    /// map that already holds its
+    /// [`max_entries`](PersistentMapBuilder::max_entries).
+    pub async fn compare_and_swap(&self, key: &K, expected: Option<&V>, new: V) -> Result<bool>
+    where
+        V: PartialEq,
+    {
+        self.swap_if(key.clone(), new, |current| Ok(current == expected))
+            .await
+    }
+
+    /// Replaces the value of `key` with `new` and persists it if `matches`
+    /// accepts the current value, `None` if the key is absent or expired.
+    ///
+    /// The check and the write happen under the key's lock.
+    async fn swap_if<F>(&self, key: K, new: V, matches: F) -> Result<bool>
+    where
+        F: Fn(Option<&V>) -> Result<bool>,
+    {
+        let key = self.keys.owned(key);
+        Self::check_serializable(&new)?;
+        #[cfg(feature = "runtime")]
+        if self.key_locks.is_sync() {
+            {
+                let _guard = self.key_locks.lock_sync(&key);
+                if !self.cached_matches(&key, &matches)? {
+                    return Ok(false);
+                }
+                let admitted = self.admit([&key])?;
+                self.insert_cached(key.clone(), new.clone(), None);
+                drop(admitted);
+            }
+            self.persist(key, new).await?;
+            self.evict_over_capacity();
+            return Ok(true);
+        }
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        if !self.cached_matches(&key, &matches)? {
+            return Ok(false);
+        }
+        let mut reservation = self.reserve([&key])?;
+        self.persist(key.clone(), new.clone()).await?;
+        self.insert_reserved(&mut reservation, key, new, None);
+        self.evict_over_capacity();
+        Ok(true)
+    }
+
+    /// Inserts `value` only if `version` is newer than the version stored
+    /// for `key`, and returns whether it was written.
+    ///
+    /// The comparison and the write happen atomically in the backend through
+    /// [`StorageBackend::save_if_newer`], which stores the caller's version
+    /// next to the value, so processes syncing from the same source of truth
+    /// through a shared backend agree on last-write-wins by version. The
+    /// backend is always consulted, since the map does not cache versions,
+    /// and the cached value is only replaced once the backend accepts the
+    /// write. A key without a stored version, because it is absent or was
+    /// last written by another method such as [`insert`](Self::insert),
+    /// accepts any version. Like `insert`, the written value has no
+    /// time-to-live.
+    ///
+    /// In write-behind mode the pending writes are drained first and the
+    /// conditional write goes straight to the backend. With
+    /// [`KeyLock::Sync`] concurrent upserts of one key are not held off, so
+    /// the cache may briefly keep the older of two accepted values.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let wrote = map
+    ///     .upsert_if_newer("plan".to_string(), "pro".to_string(), 42)
+    ///     .await?;
+    /// if !wrote {
+    ///     println!("already have version 42 or later");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized, or if draining
+    /// pending writes or the backend write fails. Returns
+    /// `PersistentError::Unsupported` on backends that do not store versions;
+    /// the built-in `SQLite` backend does. Returns
+    /// [`PersistentError::CapacityExceeded`] if the key is new and the map
+    /// already holds its [`max_entries`](PersistentMapBuilder::max_entries).
+    pub async fn upsert_if_newer(&self, key: K, value: V, version: u64) -> Result<bool> {
+        let key = self.keys.owned(key);
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        Self::check_serializable(&value)?;
+        let mut reservation = self.reserve([&key])?;
+        self.drain_pending().await?;
+        if !self
+            .backend
+            .save_if_newer(key.clone(), value.clone(), version)
+            .await?
+        {
+            return Ok(false);
+        }
+        self.note_write(&key, Some(&value));
+        self.sync_if_durable().await?;
+        self.insert_reserved(&mut reservation, key, value, None);
+        self.evict_over_capacity();
+        Ok(true)
+    }
+
+    /// Exchanges the values of `a` and `b`.
+    ///
+    /// Both keys are locked, in key order so concurrent swaps cannot
+    /// deadlock, and both writes reach the backend in one
+    /// [`write_batch`](StorageBackend::write_batch) call, which the built-in
+    /// transactional backends apply atomically. Keys missing from memory,
+    /// for example after eviction, are read from the backend first.
+    ///
+    /// An absent or expired key counts as a missing value: if only one key
+    /// has a value, it moves to the other key and the first is removed, and
+    /// if neither has one nothing happens. Swapping a key with itself does
+    /// nothing. Like [`insert`](Self::insert), the swapped values have no
+    /// time-to-live.
+    ///
+    /// The in-memory entries are updated one after the other once the
+    /// backend write succeeds, so a concurrent [`get`](Self::get) may see
+    /// one key swapped before the other; writes to either key wait for the
+    /// swap. With [`KeyLock::Sync`] writes are not held off, and may
+    /// interleave with the swap.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.swap(&"primary".to_string(), &"standby".to_string()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if reading a missing key from the backend fails, or
+    /// if saving to the backend fails, in which case the map is left
+    /// unchanged.
+    pub async fn swap(&self, a: &K, b: &K) -> Result<()>
+    where
+        K: Ord,
+    {
+        let a = self.keys.owned(a.clone());
+        let b = self.keys.owned(b.clone());
+        if a == b {
+            return Ok(());
+        }
+        // Lock in a consistent order so concurrent swaps can't deadlock
+        #[cfg(feature = "runtime")]
+        let _guards = {
+            let (first, second) = if a < b { (&a, &b) } else { (&b, &a) };
+            (
+                self.key_locks.lock(first).await,
+                self.key_locks.lock(second).await,
+            )
+        };
+        let value_a = self.current_value(&a).await?;
+        let value_b = self.current_value(&b).await?;
+        if value_a.is_none() && value_b.is_none() {
+            return Ok(());
+        }
+
+        // Removals first, so the map never holds more entries than before
+        let mut updates = [(a, value_b), (b, value_a)];
+        updates.sort_by_key(|(_, value)| value.is_some());
+        let mut saves = Vec::new();
+        let mut deletes = Vec::new();
+        for (key, value) in &updates {
+            match value {
+                Some(value) => saves.push((key.clone(), value.clone())),
+                None => deletes.push(key.clone()),
+            }
+        }
+        self.persist_batch(saves, deletes).await?;
+        for (key, value) in updates {
+            match value {
+                Some(value) => {
+                    self.insert_cached_locked(key, value, None);
+                }
+                None => {
+                    self.remove_cached(&key);
+                }
+            }
+        }
+        self.evict_over_capacity();
+        Ok(())
+    }
+
+    /// Returns the value of `key`, reading it from the backend if it is not
+    /// cached, or `None` if it is absent or expired.
+    async fn current_value(&self, key: &K) -> Result<Option<V>> {
+        if let Some(cached) = self.map.get(key).map(|r| r.value().clone()) {
+            return Ok((!self.eviction.is_expired(key)).then_some(cached));
+        }
+        self.drain_pending().await?;
+        self.load_one_raw(key)
+            .await?
+            .map(|bytes| self.decode_stored(&bytes))
+            .transpose()
+    }
+
+    /// Reads the stored bytes of `key`, batched with other misses when the
+    /// map has a [`load_batch_window`](PersistentMapBuilder::load_batch_window).
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>> {
+        #[cfg(feature = "runtime")]
+        if let Some(loader) = &self.batch_loader {
+            return loader
+                .load(key, |keys| async move {
+                    self.backend.load_many_raw(&keys).await
+                })
+                .await;
+        }
+        self.backend.load_one_raw(key).await
+    }
+
+    /// Applies `matches` to the cached value of `key`, or to `None` if it
+    /// is absent or expired.
+    fn cached_matches<F>(&self, key: &K, matches: &F) -> Result<bool>
+    where
+        F: Fn(Option<&V>) -> Result<bool>,
+    {
+        if self.eviction.is_expired(key) {
+            return matches(None);
+        }
+        let cached = self.map.get(key);
+        matches(cached.as_deref())
+    }
+
+    /// Decodes a value read from the backend as JSON bytes, passing it
+    /// through the migration hook if one is set.
+    fn decode_stored(&self, bytes: &[u8]) -> Result<V> {
+        match &self.migrate_value {
+            Some(migration) => migrate::decode(bytes, migration),
+            None => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+
+    /// Reads every entry of the backend, decoding values through the
+    /// [`migrate_value`](PersistentMapBuilder::migrate_value) hook if one is set.
+    async fn load_backend_entries(&self) -> Result<Vec<(K, V)>> {
+        migrate::load_entries(&*self.backend, self.migrate_value.as_ref()).await
+    }
+
+    /// Fails if `value` cannot be serialized.
+    ///
+    /// Writes call this before touching the in-memory map, so a value that
+    /// could never be persisted is rejected with the map unchanged.
+    fn check_serializable(value: &V) -> Result<()> {
+        serde_json::to_writer(std::io::sink(), value)?;
+        Ok(())
+    }
+
+    /// Fails with [`PersistentError::ReadOnly`] if the backend refuses
+    /// writes.
+    ///
+    /// Writes that touch the in-memory map before the backend call this
+    /// first, so a write the backend would refuse leaves the map unchanged.
+    fn check_writable(&self, operation: &str) -> Result<()> {
+        if self.backend.is_read_only() {
+            return Err(PersistentError::ReadOnly(operation.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Applies `f` to the cached value under the synchronous key lock.
+    ///
+    /// Returns `f`'s result and the new value to persist with its deadline.
+    #[cfg(feature = "runtime")]
+    #[allow(clippy::type_complexity)]
+    fn with_mut_cached<F, R>(&self, key: &K, f: F) -> Result<Option<(R, V, Option<Instant>)>>
+    where
+        F: FnOnce(&mut V) -> R,
+    {
+        let guard = self.key_locks.lock_sync(key);
+        if self.eviction.is_expired(key) {
+            return Ok(None);
+        }
+        let Some(mut value) = self.map.get(key).map(|r| r.value().clone()) else {
+            return Ok(None);
+        };
+        let result = f(&mut value);
+        Self::check_serializable(&value)?;
+        let expires_at = self.eviction.expires_at(key);
+        self.insert_cached(key.clone(), value.clone(), expires_at);
+        drop(guard);
+        self.evict_over_capacity();
+        Ok(Some((result, value, expires_at)))
+    }
+
+    /// Removes all expired entries from memory and from the storage backend.
+    ///
+    /// Every removed entry is passed to the eviction callback, if one is set.
+    /// Returns the number of entries removed from memory. The backend is then
+    /// asked to [delete its expired rows](StorageBackend::delete_expired), so
+    /// expired entries that are not cached, such as ones evicted for
+    /// capacity, do not accumulate on disk either.
+    ///
+    /// Expired entries are found through an index of deadlines, so a sweep
+    /// costs O(k log n) for k expired entries out of n with a TTL, however
+    /// many entries are live, and frequent sweeps stay cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let purged = map.purge_expired().await?;
+    /// println!("Purged {} expired entries", purged);
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if deleting from the backend fails. Entries purged
+    /// before the failure are still passed to the eviction callback.
+    pub async fn purge_expired(&self) -> Result<usize> {
+        self.check_writable("purge_expired")?;
+        let mut evicted = Vec::new();
+        let mut result = Ok(());
+        for key in self.eviction.expired_keys() {
+            // Skip keys that were refreshed since the scan
+            if !self.eviction.is_expired(&key) {
+                continue;
+            }
+            self.eviction.forget(&key);
+            if let Some(entry) = self.map.remove(&key) {
+                evicted.push(entry);
+            }
+            if let Err(e) = self.persist_delete(&key).await {
+                result = Err(e);
+                break;
+            }
+        }
+        let count = evicted.len();
+        self.eviction.notify(evicted);
+        result?;
+        self.backend.delete_expired(SystemTime::now()).await?;
+        Ok(count)
+    }
+
+    /// Retrieves a value from memory, removing the entry from memory and
+    /// from the storage backend if it has expired.
+    ///
+    /// This is [`get`](Self::get) with the cleanup of
+    /// [`purge_expired`](Self::purge_expired) done lazily for the one key,
+    /// so an expired entry that is read again does not wait for the next
+    /// sweep to leave the backend. The removed entry is passed to the
+    /// eviction callback, if one is set. Live and absent keys are looked up
+    /// without touching the backend.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let Some(token) = map.get_or_purge(&"session".to_string()).await? else {
+    ///     println!("Session expired, please log in again");
+    ///     return Ok(());
+    /// };
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if deleting the expired entry from the backend
+    /// fails. The entry is still removed from memory and passed to the
+    /// eviction callback.
+    pub async fn get_or_purge(&self, key: &K) -> Result<Option<V>> {
+        let key = self.keys.owned(key.clone());
+        if !self.eviction.is_expired(&key) {
+            return Ok(self.get(&key));
+        }
+        self.check_writable("get_or_purge")?;
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        let evicted = {
+            #[cfg(feature = "runtime")]
+            let _guard = self.key_locks.lock_sync(&key);
+            // The key may have been written again while waiting for its lock
+            if !self.eviction.is_expired(&key) {
+                return Ok(self.get(&key));
+            }
+            self.eviction.forget(&key);
+            self.map.remove(&key)
+        };
+        let result = self.persist_delete(&key).await;
+        self.eviction.notify(evicted.into_iter().collect());
+        result.map(|()| None)
+    }
+
+    /// Updates the in-memory map and the eviction bookkeeping for a write.
+    ///
+    /// Returns the previous value, unless it had already expired.
+    fn insert_cached(&self, key: K, value: V, expires_at: Option<Instant>) -> Option<V> {
+        let expired = self.eviction.is_expired(&key);
+        self.eviction.record_insert(&key, expires_at);
+        self.eviction.record_written(&key, SystemTime::now());
+        // Waiters are woken while the entry's shard is locked, so their next
+        // look at the map waits for the new value
+        #[cfg(feature = "runtime")]
+        let old = match self.map.entry(key) {
+            dashmap::mapref::entry::Entry::Occupied(mut entry) => {
+                self.waiters.wake(entry.key());
+                Some(entry.insert(value))
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                self.waiters.wake(entry.key());
+                entry.insert(value);
+                None
+            }
+        };
+        #[cfg(not(feature = "runtime"))]
+        let old = self.map.insert(key, value);
+        old.filter(|_| !expired)
+    }
+
+    /// Checks that inserting `keys` keeps the map within `max_entries`,
+    /// counting the room held by [`Reservation`]s.
+    ///
+    /// Returns a guard to hold until the keys are inserted, so that other
+    /// inserts cannot take the room in between, or `None` without a limit.
+    fn admit<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k K>,
+    ) -> Result<Option<MutexGuard<'_, usize>>>
+    where
+        K: 'k,
+    {
+        Ok(self.admit_new(keys)?.map(|(guard, _)| guard))
+    }
+
+    /// Like [`admit`](Self::admit), also returning how many of `keys` are
+    /// new.
+    ///
+    /// Every insert passes through here before caching its keys, so this
+    /// also rejects inserts into a map over a read-only backend.
+    fn admit_new<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k K>,
+    ) -> Result<Option<(MutexGuard<'_, usize>, usize)>>
+    where
+        K: 'k,
+    {
+        self.check_writable("insert")?;
+        let Some(max) = self.max_entries else {
+            return Ok(None);
+        };
+        let guard = self
+            .admission
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let new_keys: HashSet<&K> = keys
+            .into_iter()
+            .filter(|key| !self.map.contains_key(key))
+            .collect();
+        if self.map.len() + *guard + new_keys.len() > max {
+            return Err(PersistentError::CapacityExceeded(max));
+        }
+        Ok(Some((guard, new_keys.len())))
+    }
+
+    /// Admits `keys` like [`admit`](Self::admit), and holds the room of the
+    /// new ones until they are inserted with
+    /// [`insert_reserved`](Self::insert_reserved) or the reservation is
+    /// dropped.
+    ///
+    /// Unlike the guard returned by `admit`, the reservation can be held
+    /// across the backend write, for operations that only cache a value
+    /// once the backend accepts it.
+    fn reserve<'k>(&self, keys: impl IntoIterator<Item = &'k K>) -> Result<Reservation<'_>>
+    where
+        K: 'k,
+    {
+        let count = match self.admit_new(keys)? {
+            Some((mut reserved, count)) => {
+                *reserved += count;
+                count
+            }
+            None => 0,
+        };
+        Ok(Reservation {
+            admission: &self.admission,
+            count,
+        })
+    }
+
+    /// Like [`insert_cached_locked`](Self::insert_cached_locked), for a key
+    /// admitted with [`reserve`](Self::reserve): a new key takes over its
+    /// reserved room.
+    fn insert_reserved(
+        &self,
+        reservation: &mut Reservation<'_>,
+        key: K,
+        value: V,
+        expires_at: Option<Instant>,
+    ) -> Option<V> {
+        if reservation.count == 0 {
+            return self.insert_cached_locked(key, value, expires_at);
+        }
+        let mut reserved = self
+            .admission
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let new = !self.map.contains_key(&key);
+        let old = self.insert_cached_locked(key, value, expires_at);
+        if new {
+            reservation.count -= 1;
+            *reserved -= 1;
+        }
+        old
+    }
+
+    /// Like [`insert_cached_locked`](Self::insert_cached_locked), but fails
+    /// with [`PersistentError::CapacityExceeded`] instead of adding a key to
+    /// a full map.
+    fn insert_admitted(&self, key: K, value: V, expires_at: Option<Instant>) -> Result<Option<V>> {
+        let admitted = self.admit([&key])?;
+        let old = self.insert_cached_locked(key, value, expires_at);
+        drop(admitted);
+        Ok(old)
+    }
+
+    /// Like [`insert_cached`](Self::insert_cached), under the synchronous key
+    /// lock when the map uses [`KeyLock::Sync`].
+    fn insert_cached_locked(&self, key: K, value: V, expires_at: Option<Instant>) -> Option<V> {
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock_sync(&key);
+        self.insert_cached(key, value, expires_at)
+    }
+
+    /// Removes `key` from the in-memory map and the eviction bookkeeping,
+    /// under the synchronous key lock when the map uses [`KeyLock::Sync`].
+    ///
+    /// Returns the previous value, unless it had already expired.
+    fn remove_cached(&self, key: &K) -> Option<V> {
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock_sync(key);
+        let expired = self.eviction.is_expired(key);
+        self.eviction.forget(key);
+        let old = self.map.remove(key).map(|(_, v)| v);
+        old.filter(|_| !expired)
+    }
+
+    /// Persists a write, or queues it in write-behind mode.
+    async fn persist(&self, key: K, value: V) -> Result<()> {
+        self.note_write(&key, Some(&value));
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            buffer.save(key, value)?;
+            return Ok(());
+        }
+        let saved = self.backend.save(key, value);
+        #[cfg(feature = "latency")]
+        let saved = self.latency.save.time(saved);
+        saved.await?;
+        self.sync_if_durable().await
+    }
+
+    /// Persists a write that expires at `expires_at`, if set, or queues it
+    /// without its expiry in write-behind mode.
+    async fn persist_expiring(&self, key: K, value: V, expires_at: Option<Instant>) -> Result<()> {
+        let Some(expires_at) = expires_at else {
+            return self.persist(key, value).await;
+        };
+        self.note_write(&key, Some(&value));
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            buffer.save(key, value)?;
+            return Ok(());
+        }
+        let remaining = expires_at.saturating_duration_since(Instant::now());
+        let saved = self
+            .backend
+            .save_expiring(key, value, SystemTime::now() + remaining);
+        #[cfg(feature = "latency")]
+        let saved = self.latency.save.time(saved);
+        saved.await?;
+        self.sync_if_durable().await
+    }
+
+    /// Persists a batch of writes, or queues them in write-behind mode.
+    async fn persist_many(&self, entries: Vec<(K, V)>) -> Result<()> {
+        for (k, v) in &entries {
+            self.note_write(k, Some(v));
+        }
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            return buffer.save_many(entries);
+        }
+        let saved = self.backend.save_many(entries);
+        #[cfg(feature = "latency")]
+        let saved = self.latency.save.time(saved);
+        saved.await?;
+        self.sync_if_durable().await
+    }
+
+    /// Persists a delete, or queues it in write-behind mode.
+    async fn persist_delete(&self, key: &K) -> Result<()> {
+        self.note_write(key, None);
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            return buffer.delete(key.clone());
+        }
+        let deleted = self.backend.delete(key);
+        #[cfg(feature = "latency")]
+        let deleted = self.latency.delete.time(deleted);
+        deleted.await?;
+        self.sync_if_durable().await
+    }
+
+    /// Persists key renames, or queues them as deletes and saves in
+    /// write-behind mode.
+    async fn persist_renames(&self, renames: &[(K, K, V)]) -> Result<()> {
+        for (from, to, value) in renames {
+            self.note_write(from, None);
+            self.note_write(to, Some(value));
+        }
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            buffer.delete_many(renames.iter().map(|(from, _, _)| from.clone()).collect())?;
+            return buffer.save_many(
+                renames
+                    .iter()
+                    .map(|(_, to, value)| (to.clone(), value.clone()))
+                    .collect(),
+            );
+        }
+        let pairs = renames
+            .iter()
+            .map(|(from, to, _)| (from.clone(), to.clone()))
+            .collect();
+        self.backend.rename_keys(pairs).await?;
+        self.sync_if_durable().await
+    }
+
+    /// Persists a batch of deletes, or queues them in write-behind mode.
+    async fn persist_delete_many(&self, keys: Vec<K>) -> Result<()> {
+        for k in &keys {
+            self.note_write(k, None);
+        }
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            return buffer.delete_many(keys);
+        }
+        let deleted = self.backend.delete_many(keys);
+        #[cfg(feature = "latency")]
+        let deleted = self.latency.delete.time(deleted);
+        deleted.await?;
+        self.sync_if_durable().await
+    }
+
+    /// Persists saves and deletes of distinct keys as one batch, or queues
+    /// them in write-behind mode.
+    async fn persist_batch(&self, saves: Vec<(K, V)>, deletes: Vec<K>) -> Result<()> {
+        for (k, v) in &saves {
+            self.note_write(k, Some(v));
+        }
+        for k in &deletes {
+            self.note_write(k, None);
+        }
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            buffer.delete_many(deletes)?;
+            return buffer.save_many(saves);
+        }
+        let written = self.backend.write_batch(saves, deletes);
+        #[cfg(feature = "latency")]
+        let written = self.latency.save.time(written);
+        written.await?;
+        self.sync_if_durable().await
+    }
+
+    /// Syncs the backend to stable storage if the map is durable.
+    async fn sync_if_durable(&self) -> Result<()> {
+        if self.durable {
+            self.backend.sync().await?;
+        }
+        Ok(())
+    }
+
+    /// Writes all queued write-behind operations to the backend.
+    ///
+    /// Operations that read the backend directly call this first so they
+    /// observe the map's own writes.
+    #[cfg_attr(not(feature = "runtime"), allow(clippy::unused_async))]
+    async fn drain_pending(&self) -> Result<()> {
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            return buffer.drain(&*self.backend).await;
+        }
+        Ok(())
+    }
+
+    /// Reads the backend's write times if entry ages are tracked.
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>> {
+        if self.eviction.tracks_writes() {
+            self.backend.load_write_times().await
+        } else {
+            Ok(HashMap::new())
+        }
+    }
+
+    /// Evicts entries picked by the eviction policy until the map fits its capacity.
+    fn evict_over_capacity(&self) {
+        self.eviction.evict_over_capacity(&self.map);
+    }
+
+    /// Inserts a batch of key-value pairs, preserving the order they were given in.
+    ///
+    /// The in-memory map is updated entry by entry and the whole batch is then
+    /// handed to the backend's `save_many` in the same order. If a key appears
+    /// more than once, the last occurrence wins both in memory and in storage,
+    /// which keeps replayed logs (such as the append-only CSV file) deterministic.
+    ///
+    /// In a map with a [`max_capacity`](PersistentMapBuilder::max_capacity),
+    /// the backend still receives every entry, and once the batch is saved
+    /// the cache keeps whichever entries the eviction policy picks: with LRU,
+    /// the last ones in the batch. The others are evicted like any other
+    /// entry, passed to the eviction callback, and stay in the backend only,
+    /// where [`get_durable`](Self::get_durable) finds them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.insert_batch_ordered(vec![
+    ///     ("a".to_string(), "1".to_string()),
+    ///     ("b".to_string(), "2".to_string()),
+    ///     ("a".to_string(), "3".to_string()),
+    /// ])
+    /// .await?;
+    /// assert_eq!(map.get(&"a".to_string()), Some("3".to_string()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if any value cannot be serialized or the new keys
+    /// would exceed [`max_entries`](PersistentMapBuilder::max_entries), in
+    /// which case nothing is inserted, or if saving the batch to the backend
+    /// fails.
+    pub async fn insert_batch_ordered(&self, entries: Vec<(K, V)>) -> Result<()> {
+        let entries: Vec<(K, V)> = entries
+            .into_iter()
+            .map(|(key, value)| (self.keys.owned(key), value))
+            .collect();
+        for (_, value) in &entries {
+            Self::check_serializable(value)?;
+        }
+        let admitted = self.admit(entries.iter().map(|(key, _)| key))?;
+        for (key, value) in &entries {
+            self.insert_cached(key.clone(), value.clone(), None);
+        }
+        drop(admitted);
+        self.persist_many(entries).await?;
+        self.evict_over_capacity();
+        Ok(())
+    }
+
+    /// Inserts many key-value pairs, saving them to the backend in one batch.
+    ///
+    /// This is [`insert_batch_ordered`](Self::insert_batch_ordered) for any
+    /// iterator of pairs: every entry is cached and the whole batch is then
+    /// handed to the backend's [`save_many`](StorageBackend::save_many),
+    /// which `SQLite` runs in one transaction and the CSV backend appends in
+    /// one write, instead of one save per key. Later pairs win over earlier
+    /// ones with the same key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, u32, impl StorageBackend<String, u32> + Send + Sync>) -> Result<()> {
+    /// map.insert_many((0..1000).map(|i| (format!("key{i}"), i))).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns the errors of `insert_batch_ordered`. A
+    /// [`PersistentError::PartialBatch`] means the backend saved the first
+    /// `saved` pairs before failing; the map still caches every pair.
+    pub async fn insert_many(&self, pairs: impl IntoIterator<Item = (K, V)> + Send) -> Result<()> {
+        self.insert_batch_ordered(pairs.into_iter().collect()).await
+    }
+
+    /// Retrieves a value from the map by its key.
+    ///
+    /// This method only accesses the in-memory map and does not interact with
+    /// the storage backend, making it very fast.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// // Get a value
+    /// if let Some(value) = map.get(&"key".to_string()) {
+    ///     println!("Value: {}", value);
+    /// }
+    /// # }
+    /// ```
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.read(key, Clone::clone)
+    }
+
+    /// Retrieves the values of many keys, in the order of `keys`.
+    ///
+    /// Like [`get`](Self::get), this only reads the in-memory map: each
+    /// element is a clone of the cached value, or `None` if the key is
+    /// absent or expired. A key given more than once is returned each time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// let keys = ["title".to_string(), "author".to_string()];
+    /// for (key, value) in keys.iter().zip(map.get_many(&keys)) {
+    ///     println!("{key}: {}", value.unwrap_or_default());
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn get_many(&self, keys: &[K]) -> Vec<Option<V>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
+    /// Applies `f` to the cached value of `key` without cloning it.
+    ///
+    /// Counts as an access for eviction, like `get`. Returns `None` if the key
+    /// is absent or expired.
+    pub(crate) fn read<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        let key = &*self.keys.borrowed(key);
+        if self.eviction.is_expired(key) {
+            return None;
+        }
+        let result = self.map.get(key).map(|r| f(r.value()));
+        if result.is_some() {
+            self.eviction.touch(key);
+        }
+        result
+    }
+
+    /// Returns the serialized bytes the backend holds for a key.
+    ///
+    /// The value is read from the storage backend and returned without being
+    /// deserialized into `V`, which avoids a decode/encode round-trip when the
+    /// value is only forwarded elsewhere. The bytes are JSON for all built-in
+    /// backends.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// if let Some(bytes) = map.get_raw(&"key".to_string()).await? {
+    ///     println!("Stored {} bytes", bytes.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if reading from the backend fails.
+    pub async fn get_raw(&self, key: &K) -> Result<Option<Vec<u8>>> {
+        self.drain_pending().await?;
+        self.backend.load_one_raw(&self.keys.borrowed(key)).await
+    }
+
+    /// Inserts an already serialized value, storing the bytes as given.
+    ///
+    /// The bytes must be a JSON encoding of `V` (the format returned by
+    /// [`get_raw`](Self::get_raw)). They are decoded once to update the
+    /// in-memory map, then handed to the backend unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.insert_raw("key".to_string(), br#""value""#.to_vec()).await?;
+    /// assert_eq!(map.get(&"key".to_string()), Some("value".to_string()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not a valid encoding of `V` or if
+    /// saving to the backend fails.
+    pub async fn insert_raw(&self, key: K, bytes: Vec<u8>) -> Result<Option<V>> {
+        let key = self.keys.owned(key);
+        let value: V = serde_json::from_slice(&bytes)?;
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        let old = self.insert_admitted(key.clone(), value.clone(), None)?;
+        self.note_write(&key, Some(&value));
+        #[cfg(feature = "runtime")]
+        if let Some(buffer) = &self.write_behind {
+            // Buffered writes are re-encoded when the buffer is drained
+            buffer.save(key, value)?;
+            self.evict_over_capacity();
+            return Ok(old);
+        }
+        self.backend.save_raw(key, bytes).await?;
+        self.sync_if_durable().await?;
+        self.evict_over_capacity();
+        Ok(old)
+    }
+
+    /// Loads one page of backend entries into the in-memory map.
+    ///
+    /// Entries are ordered by the string form of their key; the page skips
+    /// the first `offset` entries and holds at most `limit`. Returns the
+    /// number of entries loaded, so a result below `limit` means the last
+    /// page was reached. Loading pages one at a time gives a controlled,
+    /// resumable warm-up of a store too large to [`load`](Self::load) at
+    /// once. Values go through the
+    /// [`migrate_value`](PersistentMapBuilder::migrate_value) hook if one is
+    /// set, as in `load`.
+    ///
+    /// Offsets are positions in the current ordering, so keys inserted or
+    /// removed between calls can shift entries across page boundaries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let mut offset = 0;
+    /// loop {
+    ///     let loaded = map.load_range(offset, 1_000).await?;
+    ///     offset += loaded;
+    ///     if loaded < 1_000 {
+    ///         break;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if reading from the backend fails.
+    pub async fn load_range(&self, offset: usize, limit: usize) -> Result<usize>
+    where
+        K: ToString,
+    {
+        // Pending writes must land first or the page would revert them
+        self.drain_pending().await?;
+        let page = match &self.migrate_value {
+            Some(migration) => {
+                let raw = self.backend.load_page_raw(offset, limit).await?;
+                migrate::decode_entries(raw, migration)?
+            }
+            None => self.backend.load_page(offset, limit).await?,
+        };
+        let loaded = page.len();
+        for (k, v) in page {
+            let k = self.keys.owned(k);
+            if self.keeps_local(&k, Some(&v)) {
+                continue;
+            }
+            self.eviction.touch(&k);
+            self.map.insert(k, v);
+        }
+        #[cfg(feature = "runtime")]
+        self.waiters.wake_all();
+        self.evict_over_capacity();
+        Ok(loaded)
+    }
+
+    /// Returns all entries whose key starts with `prefix`.
+    ///
+    /// Keys are matched on their `to_string()` representation. The backend is
+    /// scanned so entries that are not held in memory (for example after
+    /// eviction) are included; entries that are cached take their in-memory
+    /// value. Expired entries are skipped.
+    ///
+    /// The order of the returned entries is unspecified unless the backend
+    /// returns them sorted, as `SqliteBackend` does.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// for (key, value) in map.scan_prefix("user:").await? {
+    ///     println!("{key} => {value}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if scanning the backend fails.
+    pub async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(K, V)>>
+    where
+        K: ToString,
+    {
+        self.scan_prefix_with(prefix, |k, v| (k, v)).await
+    }
+
+    /// Removes all entries whose key starts with `prefix` and returns them.
+    ///
+    /// The matching entries are found as in [`scan_prefix`](Self::scan_prefix),
+    /// then deleted from the backend with a single `delete_many` call and
+    /// finally removed from memory. If the backend delete fails, the map is
+    /// left unchanged.
+    ///
+    /// With the `runtime` feature every matched key is locked from the scan
+    /// until it is removed, so a concurrent `insert` of a matched key either
+    /// completes before the drain (and its value is the one returned) or
+    /// waits and then recreates the key afterwards. Keys inserted under the
+    /// prefix after the scan are not drained.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// for (key, job) in map.drain_prefix("pending:alice:").await? {
+    ///     println!("processing {key}: {job}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if scanning or deleting from the backend fails.
+    pub async fn drain_prefix(&self, prefix: &str) -> Result<Vec<(K, V)>>
+    where
+        K: ToString,
+    {
+        let mut entries = self.scan_prefix(prefix).await?;
+        // Lock in a consistent order so concurrent drains can't deadlock
+        entries.sort_by_cached_key(|(key, _)| key.to_string());
+
+        #[cfg(feature = "runtime")]
+        let mut guards = Vec::with_capacity(entries.len());
+        for (key, value) in &mut entries {
+            #[cfg(feature = "runtime")]
+            guards.push(self.key_locks.lock(key).await);
+            // Pick up writes that landed between the scan and the lock
+            if let Some(current) = self.map.get(key) {
+                *value = current.value().clone();
+            }
+        }
+
+        let keys: Vec<K> = entries.iter().map(|(key, _)| key.clone()).collect();
+        self.persist_delete_many(keys).await?;
+        for (key, _) in &entries {
+            self.remove_cached(key);
+        }
+        // Release the keys only once they are gone from memory as well
+        #[cfg(feature = "runtime")]
+        drop(guards);
+        Ok(entries)
+    }
+
+    /// Moves every entry whose key starts with `from` to the same key with
+    /// `to` in place of `from`, returning how many entries were moved.
+    ///
+    /// Keys are matched and rewritten on their string form, as in
+    /// [`scan_prefix`](Self::scan_prefix), and parsed back with `FromStr`.
+    /// The backend moves the entries with one
+    /// [`rename_keys`](StorageBackend::rename_keys) call, which `SQLite`
+    /// applies in a single transaction, and cached entries are moved in
+    /// memory along with their expiry. Entries only stored in the backend
+    /// stay uncached. Renaming a prefix to itself moves nothing.
+    ///
+    /// If a rewritten key already exists and is not moved itself, nothing
+    /// is moved and an error is returned. With the `runtime` feature the old
+    /// and new keys are locked while they are checked and moved.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// // "users/alice" becomes "accounts/alice"
+    /// let moved = map.rename_prefix("users/", "accounts/").await?;
+    /// println!("moved {moved} entries");
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns [`PersistentError::Key`] if a rewritten key already exists or
+    /// cannot be parsed, in which case nothing is moved, or an error if
+    /// reading from or writing to the backend fails.
+    pub async fn rename_prefix(&self, from: &str, to: &str) -> Result<usize>
+    where
+        K: ToString + std::str::FromStr,
+        <K as std::str::FromStr>::Err: std::fmt::Display,
+    {
+        if from == to {
+            return Ok(0);
+        }
+        let mut renames = Vec::new();
+        for (key, value) in self.scan_prefix(from).await? {
+            let rewritten = format!("{to}{}", &key.to_string()[from.len()..]);
+            let new_key = rewritten
+                .parse::<K>()
+                .map_err(|e| PersistentError::Key(format!("invalid key {rewritten:?}: {e}")))?;
+            renames.push((key, self.keys.owned(new_key), value));
+        }
+
+        // Lock in a consistent order so concurrent renames can't deadlock
+        #[cfg(feature = "runtime")]
+        let _guards = {
+            let mut keys: Vec<&K> = renames
+                .iter()
+                .flat_map(|(from, to, _)| [from, to])
+                .collect();
+            keys.sort_by_cached_key(|key| key.to_string());
+            keys.dedup();
+            let mut guards = Vec::with_capacity(keys.len());
+            for key in keys {
+                guards.push(self.key_locks.lock(key).await);
+            }
+            guards
+        };
+        for (key, _, value) in &mut renames {
+            // Pick up writes that landed between the scan and the lock
+            if let Some(current) = self.map.get(key) {
+                *value = current.value().clone();
+            }
+        }
+
+        let moved: std::collections::HashSet<&K> =
+            renames.iter().map(|(from, _, _)| from).collect();
+        let targets: Vec<K> = renames
+            .iter()
+            .map(|(_, to, _)| to)
+            .filter(|to| !moved.contains(to))
+            .cloned()
+            .collect();
+        let conflict = if let Some(to) = targets.iter().find(|to| self.get(to).is_some()) {
+            Some(to.clone())
+        } else {
+            let stored = self.backend.load_many_raw(&targets).await?;
+            targets
+                .into_iter()
+                .zip(stored)
+                .find_map(|(to, value)| value.map(|_| to))
+        };
+        if let Some(to) = conflict {
+            return Err(PersistentError::Key(format!(
+                "key {:?} already exists",
+                to.to_string()
+            )));
+        }
+
+        self.persist_renames(&renames).await?;
+
+        let mut cached = Vec::new();
+        for (from, to, _) in &renames {
+            let expires_at = self.eviction.expires_at(from);
+            if let Some(value) = self.remove_cached(from) {
+                cached.push((to.clone(), value, expires_at));
+            }
+        }
+        for (to, value, expires_at) in cached {
+            self.insert_cached_locked(to, value, expires_at);
+        }
+        Ok(renames.len())
+    }
+
+    /// Returns the values of all entries whose key starts with `prefix`.
+    ///
+    /// This is [`scan_prefix`](Self::scan_prefix) without the keys, with the
+    /// same matching and ordering rules.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let pending = map.values_with_prefix("queue:alice:").await?;
+    /// println!("{} pending items", pending.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if scanning the backend fails.
+    pub async fn values_with_prefix(&self, prefix: &str) -> Result<Vec<V>>
+    where
+        K: ToString,
+    {
+        self.scan_prefix_with(prefix, |_, v| v).await
+    }
+
+    /// Scans the backend for keys under `prefix`, decoding values through the
+    /// [`migrate_value`](PersistentMapBuilder::migrate_value) hook if one is set.
+    async fn backend_scan_prefix(&self, prefix: &str) -> Result<Vec<(K, V)>>
+    where
+        K: ToString,
+    {
+        match &self.migrate_value {
+            Some(migration) => {
+                let raw = self.backend.scan_prefix_raw(prefix).await?;
+                migrate::decode_entries(raw, migration)
+            }
+            None => self.backend.scan_prefix(prefix).await,
+        }
+    }
+
+    /// Scans the backend and the cache for keys under `prefix`, mapping each match with `f`.
+    async fn scan_prefix_with<T, F>(&self, prefix: &str, mut f: F) -> Result<Vec<T>>
+    where
+        K: ToString,
+        F: FnMut(K, V) -> T,
+    {
+        self.drain_pending().await?;
+        let stored = self.backend_scan_prefix(prefix).await?;
+        let mut seen = std::collections::HashSet::with_capacity(stored.len());
+        let mut out = Vec::with_capacity(stored.len());
+        for (key, value) in stored {
+            if self.eviction.is_expired(&key) {
+                continue;
+            }
+            let value = self.map.get(&key).map_or(value, |r| r.value().clone());
+            seen.insert(key.clone());
+            out.push(f(key, value));
+        }
+
+        // Include cached entries the backend doesn't report
+        let cached: Vec<(K, V)> = self
+            .map
+            .iter()
+            .filter(|r| !seen.contains(r.key()) && r.key().to_string().starts_with(prefix))
+            .map(|r| (r.key().clone(), r.value().clone()))
+            .collect();
+        for (key, value) in cached {
+            if !self.eviction.is_expired(&key) {
+                out.push(f(key, value));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Removes a key-value pair from the map and the storage backend.
+    ///
+    /// If the map contains the key, the key-value pair is removed and the old value
+    /// is returned. Otherwise, `None` is returned. The key is deleted from the
+    /// backend either way, so entries that were evicted from memory are removed
+    /// too.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// // Remove a key-value pair
+    /// let old = map.remove(&"key".to_string()).await?;
+    /// if let Some(value) = old {
+    ///     println!("Removed value: {}", value);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if deleting from the backend fails.
+    #[inline]
+    pub async fn remove(&self, key: &K) -> Result<Option<V>> {
+        self.check_writable("remove")?;
+        let key = &*self.keys.borrowed(key);
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(key).await;
+        let old = self.remove_cached(key);
+        self.persist_delete(key).await?;
+        Ok(old)
+    }
+
+    /// Removes many keys from the map, deleting them from the backend in one
+    /// batch.
+    ///
+    /// Returns the removed values in the order of `keys`, `None` for keys
+    /// that were absent or expired; a key given twice is `None` the second
+    /// time. All keys are deleted from the backend with a single
+    /// [`delete_many`](StorageBackend::delete_many) call, which `SQLite` runs
+    /// in one transaction and the CSV backend applies with one rewrite of its
+    /// file, instead of one delete per key, and are then removed from
+    /// memory. If the backend delete fails, the map is left unchanged.
+    ///
+    /// With the `runtime` feature every key is locked, in key order so
+    /// concurrent batches cannot deadlock, until it is removed from memory,
+    /// so a concurrent `insert` of one of the keys either completes before
+    /// the batch or recreates the key afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let stale = vec!["a".to_string(), "b".to_string()];
+    /// let removed = map.remove_many(&stale).await?;
+    /// assert_eq!(removed.len(), stale.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if deleting from the backend fails.
+    pub async fn remove_many(&self, keys: &[K]) -> Result<Vec<Option<V>>>
+    where
+        K: Ord,
+    {
+        let keys: Vec<K> = keys.iter().map(|k| self.keys.owned(k.clone())).collect();
+        // Lock in a consistent order so concurrent batches can't deadlock
+        let mut unique: Vec<&K> = keys.iter().collect();
+        unique.sort_unstable();
+        unique.dedup();
+
+        #[cfg(feature = "runtime")]
+        let mut guards = Vec::with_capacity(unique.len());
+        #[cfg(feature = "runtime")]
+        for key in &unique {
+            guards.push(self.key_locks.lock(key).await);
+        }
+
+        self.persist_delete_many(unique.iter().map(|&key| key.clone()).collect())
+            .await?;
+        let mut removed: HashMap<&K, Option<V>> = unique
+            .into_iter()
+            .map(|key| (key, self.remove_cached(key)))
+            .collect();
+        // Release the keys only once they are gone from memory as well
+        #[cfg(feature = "runtime")]
+        drop(guards);
+        Ok(keys
+            .iter()
+            .map(|key| removed.get_mut(key).and_then(Option::take))
+            .collect())
+    }
+
+    /// Returns the number of key-value pairs in the map.
+    ///
+    /// Expired entries are counted until they are removed by `purge_expired`.
+    ///
     /// # Examples
     ///
     /// ```rust,no_run
@@ -604,6 +2377,55 @@ where
         self.map.is_empty()
     }
 
+    /// Returns the number of entries the in-memory map can hold without
+    /// reallocating.
+    ///
+    /// Removing entries does not release memory, so after a large batch of
+    /// removals the capacity stays far above [`len`](Self::len). Compare
+    /// the two to decide whether [`shrink_to_fit`](Self::shrink_to_fit) is
+    /// worthwhile.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// if map.capacity() > 4 * map.len() + 1024 {
+    ///     map.shrink_to_fit();
+    /// }
+    /// # }
+    /// ```
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    /// Releases the memory the in-memory map no longer needs for its
+    /// entries.
+    ///
+    /// Each shard of the map is write-locked in turn while it is rebuilt at
+    /// its current size, so reads and writes of keys in that shard wait
+    /// briefly. The cost grows with the number of entries, so call this
+    /// after a large shrink rather than routinely. The backend is not
+    /// touched.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.purge_expired().await?;
+    /// map.shrink_to_fit();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn shrink_to_fit(&self) {
+        self.map.shrink_to_fit();
+    }
+
     /// Returns `true` if the map contains the specified key.
     ///
     /// # Examples
@@ -619,7 +2441,304 @@ where
     /// ```
     #[inline]
     pub fn contains_key(&self, key: &K) -> bool {
-        self.map.contains_key(key)
+        let key = &*self.keys.borrowed(key);
+        self.map.contains_key(key) && !self.eviction.is_expired(key)
+    }
+
+    /// Returns how long ago the cached value of `key` was written.
+    ///
+    /// Callers can use it for their own freshness rules, such as fetching a
+    /// value again once it is older than five minutes, without expiring it.
+    /// Ages are only kept when the map was built with
+    /// [`track_entry_age`](PersistentMapBuilder::track_entry_age), which
+    /// describes where the write times come from. Returns `None` for keys
+    /// that are absent or expired, for keys of unknown age, and when ages
+    /// are not tracked. A write time in the future, as read from a backend
+    /// shared with a machine whose clock runs ahead, counts as age zero.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// # use std::time::Duration;
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// let key = "quote".to_string();
+    /// if map.entry_age(&key).map_or(true, |age| age > Duration::from_secs(300)) {
+    ///     // Fetch a fresh quote
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn entry_age(&self, key: &K) -> Option<Duration> {
+        let key = &*self.keys.borrowed(key);
+        if !self.contains_key(key) {
+            return None;
+        }
+        let written = self.eviction.written_at(key)?;
+        Some(
+            SystemTime::now()
+                .duration_since(written)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Returns `true` if any cached entry holds `value`.
+    ///
+    /// This scans the whole in-memory map, comparing every value, so it
+    /// costs O(n) and briefly read-locks each shard in turn. Entries only in
+    /// the backend, for example after an eviction, are not found, and
+    /// expired entries are skipped. For frequent reverse lookups keep a
+    /// secondary index instead, such as a second map from value to keys that
+    /// is updated alongside this one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// if map.contains_value(&"admin".to_string()) {
+    ///     println!("Someone is an admin");
+    /// }
+    /// # }
+    /// ```
+    pub fn contains_value(&self, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        self.map
+            .iter()
+            .any(|entry| entry.value() == value && !self.eviction.is_expired(entry.key()))
+    }
+
+    /// Returns every cached key whose value equals `value`, in unspecified
+    /// order.
+    ///
+    /// Like [`contains_value`](Self::contains_value), this scans the whole
+    /// in-memory map in O(n), skips expired entries and does not consult the
+    /// backend.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// let admins = map.find_keys_for_value(&"admin".to_string());
+    /// println!("{} admins", admins.len());
+    /// # }
+    /// ```
+    pub fn find_keys_for_value(&self, value: &V) -> Vec<K>
+    where
+        V: PartialEq,
+    {
+        self.map
+            .iter()
+            .filter(|entry| entry.value() == value && !self.eviction.is_expired(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Retrieves a value from memory, or from the storage backend if it is
+    /// not cached.
+    ///
+    /// On a miss, for example after the key was evicted for capacity, the
+    /// backend's value is read, cached and returned, so the entry may evict
+    /// another one. Keys that expired in memory are `None` without a backend
+    /// read. A stored row that expired after its key was evicted is still
+    /// returned, since reading a single value does not check its expiry.
+    /// Use [`get`](Self::get) for the fast in-memory lookup.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// if let Some(value) = map.get_durable(&"key".to_string()).await? {
+    ///     println!("Value: {value}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the backend lookup fails or the stored value
+    /// cannot be decoded.
+    pub async fn get_durable(&self, key: &K) -> Result<Option<V>> {
+        let key = self.keys.owned(key.clone());
+        if let Some(value) = self.get(&key) {
+            return Ok(Some(value));
+        }
+        // Keeps a concurrent write from being replaced by the stored value
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        let Some(value) = self.current_value(&key).await? else {
+            return Ok(None);
+        };
+        if let dashmap::mapref::entry::Entry::Vacant(entry) = self.map.entry(key) {
+            self.eviction.touch(entry.key());
+            #[cfg(feature = "runtime")]
+            self.waiters.wake(entry.key());
+            entry.insert(value.clone());
+        }
+        self.evict_over_capacity();
+        Ok(Some(value))
+    }
+
+    /// Retrieves a value from memory if it is younger than `max_age`, or
+    /// reloads it from the storage backend.
+    ///
+    /// This bounds how stale a read can be, for caches of values that other
+    /// processes update in the backend, such as configuration or feature
+    /// flags. The age is the one reported by [`entry_age`](Self::entry_age),
+    /// and a reload resets it, since the reloaded value is current as of the
+    /// read. Entries of unknown age are always reloaded, so without
+    /// [`track_entry_age`](PersistentMapBuilder::track_entry_age) every call
+    /// reads the backend.
+    ///
+    /// A reload replaces the cached value, keeping its expiry, or caches a
+    /// missing entry like [`get_durable`](Self::get_durable) does. If the
+    /// backend no longer holds the key, it is removed from memory and `None`
+    /// is returned. In write-behind mode the pending writes are drained
+    /// first, so a reload never reverts a local write. Keys that expired in
+    /// memory are `None` without a backend read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// # use std::time::Duration;
+    /// #
+    /// # async fn example(flags: PersistentMap<String, bool, impl StorageBackend<String, bool> + Send + Sync>) -> Result<()> {
+    /// // Never act on a flag that is more than 30 seconds old
+    /// let enabled = flags
+    ///     .get_fresh(&"new_checkout".to_string(), Duration::from_secs(30))
+    ///     .await?
+    ///     .unwrap_or(false);
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if draining pending writes or the backend lookup
+    /// fails, or the stored value cannot be decoded.
+    pub async fn get_fresh(&self, key: &K, max_age: Duration) -> Result<Option<V>> {
+        let key = self.keys.owned(key.clone());
+        if self.eviction.is_expired(&key) {
+            return Ok(None);
+        }
+        if let Some(value) = self.get(&key) {
+            if self.entry_age(&key).map_or(false, |age| age < max_age) {
+                return Ok(Some(value));
+            }
+        }
+        // Keeps a concurrent write from being replaced by the stored value
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        self.drain_pending().await?;
+        let Some(bytes) = self.load_one_raw(&key).await? else {
+            self.remove_cached(&key);
+            return Ok(None);
+        };
+        let value = self.decode_stored(&bytes)?;
+        let cached = self.map.entry(key).insert(value.clone());
+        self.eviction.touch(cached.key());
+        self.eviction
+            .record_written(cached.key(), SystemTime::now());
+        #[cfg(feature = "runtime")]
+        self.waiters.wake(cached.key());
+        drop(cached);
+        self.evict_over_capacity();
+        Ok(Some(value))
+    }
+
+    /// Returns `true` if the key exists in memory or in the storage backend.
+    ///
+    /// Memory is checked first; on a miss the backend's `contains_key` is
+    /// consulted, which tells "not cached" (for example after an eviction)
+    /// apart from "doesn't exist". Expired entries count as absent. Use
+    /// [`contains_key`](Self::contains_key) for the fast in-memory check.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// if map.contains_key_durable(&"key".to_string()).await? {
+    ///     println!("Key exists");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the backend lookup fails.
+    pub async fn contains_key_durable(&self, key: &K) -> Result<bool> {
+        let key = &*self.keys.borrowed(key);
+        if self.eviction.is_expired(key) {
+            return Ok(false);
+        }
+        if self.map.contains_key(key) {
+            return Ok(true);
+        }
+        self.drain_pending().await?;
+        self.backend.contains_key(key).await
+    }
+
+    /// Returns the time of the most recent write to the storage backend.
+    ///
+    /// Pollers can compare this with the time of their last [`load`](Self::load)
+    /// to skip reloading when nothing changed. File-based backends report the
+    /// modification time of their files, which also covers writes made by
+    /// other processes. Returns `None` if the backend cannot tell, as with the
+    /// in-memory backend.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// # use std::time::SystemTime;
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>, last_load: SystemTime) -> Result<()> {
+    /// if map.last_modified().await?.map_or(true, |modified| modified > last_load) {
+    ///     map.load().await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the backend fails to read the modification time.
+    pub async fn last_modified(&self) -> Result<Option<SystemTime>> {
+        self.backend.last_modified().await
+    }
+
+    /// Returns storage-level metrics reported by the backend.
+    ///
+    /// The metrics depend on the backend: `SqliteBackend` reports page and
+    /// free-page counts, `CsvBackend` the size of its file. Backends that
+    /// report nothing return an empty map.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// for (name, value) in map.backend_stats().await? {
+    ///     println!("{name}: {value}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the backend fails to read its metrics.
+    pub async fn backend_stats(&self) -> Result<BackendStats> {
+        self.backend.stats().await
     }
 
     /// Clears the in-memory map without affecting the storage backend.
@@ -639,15 +2758,60 @@ where
     /// assert_eq!(map.len(), 0);
     /// # }
     /// ```
+    ///
+    /// In write-behind mode, writes that are still queued are kept and reach
+    /// the backend on the next flush. Use [`clear_all`](Self::clear_all) to
+    /// empty the backend as well.
     #[inline]
     pub fn clear(&self) {
         self.map.clear();
+        self.eviction.clear();
+    }
+
+    /// Removes every entry from the in-memory map and the storage backend.
+    ///
+    /// In write-behind mode, queued writes are discarded rather than flushed,
+    /// along with their write-ahead log records, so a write made before the
+    /// call cannot reach the backend after it. A drain that is already
+    /// writing to the backend is waited for, and no drain starts until the
+    /// backend is empty.
+    ///
+    /// Writes made by other tasks while `clear_all` runs are not ordered with
+    /// it: such a write may survive in memory, in the backend or in neither.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.clear_all().await?;
+    /// assert!(map.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if truncating the write-ahead log or deleting from
+    /// the backend fails.
+    pub async fn clear_all(&self) -> Result<()> {
+        self.check_writable("clear_all")?;
+        #[cfg(feature = "runtime")]
+        let _drains = match &self.write_behind {
+            Some(buffer) => Some(buffer.discard().await?),
+            None => None,
+        };
+        self.clear();
+        self.backend.delete_all().await?;
+        self.sync_if_durable().await
     }
 
     /// Flushes any buffered writes to the storage backend.
     ///
     /// This method is useful for backends that buffer writes for performance.
-    /// It ensures that all data is persisted to the storage medium.
+    /// It ensures that all data is persisted to the storage medium. In
+    /// write-behind mode the pending writes are drained first, and this
+    /// method returns once they have been written.
     ///
     /// # Examples
     ///
@@ -665,9 +2829,93 @@ where
     /// Returns an error if flushing the backend fails.
     #[inline]
     pub async fn flush(&self) -> Result<(), PersistentError> {
+        self.drain_pending().await?;
         self.backend.flush().await
     }
 
+    /// Checkpoints the backend's write-ahead log, if it has one.
+    ///
+    /// Unlike [`flush`](Self::flush), which makes sure writes reach the
+    /// backend, this only reorganizes what the backend already stored: on
+    /// `SQLite` in WAL mode it copies the log into the database file and
+    /// truncates the log. Call it periodically on long-running writers to
+    /// bound the size of the log file. Backends without a log ignore it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.checkpoint().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the backend fails to checkpoint.
+    pub async fn checkpoint(&self) -> Result<()> {
+        self.backend.checkpoint().await
+    }
+
+    /// Checks that the backend can store, read back and delete an entry.
+    ///
+    /// A sentinel entry with a `V::default()` value is saved straight to the
+    /// backend under a key starting with `__persistent_map_verify__:`, which
+    /// includes the process id and the current time so concurrent checks do
+    /// not collide. The entry is read back with `load_one_raw`, bypassing the
+    /// in-memory map, compared with what was written and deleted again.
+    ///
+    /// Call it at startup to fail fast on a read-only filesystem or a wrong
+    /// path instead of on the first real write. Backends that do not persist
+    /// anything, like the in-memory backend, fail the check.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.verify_roundtrip().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns a `PersistentError::Config` error if the key type cannot hold
+    /// the sentinel key or if the value read back differs from the one
+    /// written, or the backend's error if saving, reading or deleting fails.
+    pub async fn verify_roundtrip(&self) -> Result<()>
+    where
+        K: std::str::FromStr,
+        V: Default,
+    {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let sentinel = format!("__persistent_map_verify__:{}:{nanos}", std::process::id());
+        let key: K = sentinel.parse().map_err(|_| {
+            PersistentError::Config(format!("key type cannot hold the sentinel key {sentinel}"))
+        })?;
+        let value = V::default();
+        let expected = serde_json::to_value(&value)?;
+
+        self.backend.save(key.clone(), value).await?;
+        let stored = self.backend.load_one_raw(&key).await;
+        self.backend.delete(&key).await?;
+        let stored = match stored? {
+            Some(bytes) => Some(serde_json::from_slice::<serde_json::Value>(&bytes)?),
+            None => None,
+        };
+        if stored.as_ref() != Some(&expected) {
+            return Err(PersistentError::Config(format!(
+                "backend did not return the sentinel entry {sentinel} it saved"
+            )));
+        }
+        Ok(())
+    }
+
     /// Returns a reference to the storage backend.
     ///
     /// This method is useful for accessing backend-specific functionality.
@@ -685,7 +2933,32 @@ where
     /// # }
     /// ```
     #[inline]
-    pub const fn backend(&self) -> &B {
+    pub fn backend(&self) -> &B {
         &self.backend
     }
+
+    /// Stops all background tasks and flushes the backend one last time.
+    ///
+    /// Background tasks (such as the periodic flush configured with
+    /// [`auto_flush_jittered`](PersistentMapBuilder::auto_flush_jittered))
+    /// are also stopped when the map is dropped, but without the final flush.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.shutdown().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the final flush fails.
+    pub async fn shutdown(&self) -> Result<(), PersistentError> {
+        #[cfg(feature = "runtime")]
+        self.tasks.abort_all();
+        self.flush().await
+    }
 }
@@ -0,0 +1,127 @@
+//! Coalescing of concurrent read-through misses into batched backend reads.
+//!
+//! The first miss opens a batch and becomes its leader: it waits for the
+//! batching window, closes the batch and reads every key that joined it with
+//! one `load_many_raw` call. Misses during the window join the open batch
+//! and wait for the leader's result. If the leader's read fails, or its
+//! future is dropped, the others read their own key instead, so each caller
+//! sees its own error.
+
+use crate::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+use tokio::sync::watch;
+
+/// The stored bytes of the keys of a batch, absent keys left out.
+type Loaded<K> = Option<Arc<HashMap<K, Vec<u8>>>>;
+
+/// A batch of misses waiting for its leader to read them.
+struct Batch<K> {
+    keys: Mutex<HashSet<K>>,
+    loaded: watch::Receiver<Loaded<K>>,
+}
+
+/// Coalesces the backend reads of misses within a window.
+pub struct BatchLoader<K> {
+    window: Duration,
+    open: Mutex<Option<Arc<Batch<K>>>>,
+}
+
+impl<K> BatchLoader<K>
+where
+    K: Eq + Hash + Clone,
+{
+    pub const fn new(window: Duration) -> Self {
+        Self {
+            window,
+            open: Mutex::new(None),
+        }
+    }
+
+    pub const fn window(&self) -> Duration {
+        self.window
+    }
+
+    fn open(&self) -> MutexGuard<'_, Option<Arc<Batch<K>>>> {
+        self.open.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Adds `key` to the open batch, or opens a new one and returns the
+    /// sender its leader publishes the result with.
+    fn join(&self, key: &K) -> (Arc<Batch<K>>, Option<watch::Sender<Loaded<K>>>) {
+        let mut open = self.open();
+        // A batch whose leader was dropped is replaced
+        if let Some(batch) = open
+            .as_ref()
+            .filter(|batch| batch.loaded.has_changed().is_ok())
+        {
+            batch
+                .keys
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(key.clone());
+            return (Arc::clone(batch), None);
+        }
+        let (sender, loaded) = watch::channel(None);
+        let batch = Arc::new(Batch {
+            keys: Mutex::new(HashSet::from([key.clone()])),
+            loaded,
+        });
+        *open = Some(Arc::clone(&batch));
+        drop(open);
+        (batch, Some(sender))
+    }
+
+    /// Reads the stored bytes of `key` with `load_many`, together with the
+    /// other keys missed within the window.
+    pub async fn load<F, Fut>(&self, key: &K, load_many: F) -> Result<Option<Vec<u8>>>
+    where
+        K: Send + Sync,
+        F: FnOnce(Vec<K>) -> Fut + Send,
+        Fut: Future<Output = Result<Vec<Option<Vec<u8>>>>> + Send,
+    {
+        let (batch, leader) = self.join(key);
+        let Some(sender) = leader else {
+            let mut loaded = batch.loaded.clone();
+            drop(batch);
+            let found = loaded
+                .wait_for(Option::is_some)
+                .await
+                .map(|loaded| loaded.as_ref().and_then(|loaded| loaded.get(key).cloned()));
+            return match found {
+                Ok(found) => Ok(found),
+                Err(_) => Ok(load_many(vec![key.clone()]).await?.pop().flatten()),
+            };
+        };
+
+        tokio::time::sleep(self.window).await;
+        {
+            let mut open = self.open();
+            if open
+                .as_ref()
+                .map_or(false, |open| Arc::ptr_eq(open, &batch))
+            {
+                *open = None;
+            }
+        }
+        let keys: Vec<K> =
+            std::mem::take(&mut *batch.keys.lock().unwrap_or_else(PoisonError::into_inner))
+                .into_iter()
+                .collect();
+        // On failure the sender is dropped and the others read their own keys
+        let values = load_many(keys.clone()).await?;
+        let loaded: HashMap<K, Vec<u8>> = keys
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect();
+        let value = loaded.get(key).cloned();
+        sender.send_replace(Some(Arc::new(loaded)));
+        Ok(value)
+    }
+}
@@ -0,0 +1,131 @@
+//! Incremental lookups of large key sets.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::VecDeque, hash::Hash};
+
+/// Number of keys resolved per backend round trip.
+const CHUNK_SIZE: usize = 512;
+
+/// Resolves a list of keys in chunks, yielding one result per key.
+///
+/// Created with [`PersistentMap::get_stream`]. Each chunk of keys is looked
+/// up in the in-memory map first, and the keys it misses are read from the
+/// backend with a single [`load_many_raw`](StorageBackend::load_many_raw)
+/// call, so only one chunk of values is held at a time.
+pub struct KeyLookups<'a, K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// The map whose keys are looked up
+    map: &'a PersistentMap<K, V, B>,
+
+    /// Keys not resolved yet, in request order
+    keys: VecDeque<K>,
+
+    /// Results of the current chunk not returned yet
+    resolved: VecDeque<(K, Option<V>)>,
+}
+
+impl<K, V, B> KeyLookups<'_, K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns the next key with its value, or `None` once every key was
+    /// returned.
+    ///
+    /// Keys are returned in the order they were given, each with `None` if
+    /// it is neither cached nor stored. Keys that expired in memory are
+    /// reported as `None` without reading the backend. Pending write-behind
+    /// writes are flushed before each chunk reads the backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from the backend fails or a stored value
+    /// cannot be decoded. The cursor does not advance, so the call can be
+    /// retried.
+    pub async fn next(&mut self) -> Result<Option<(K, Option<V>)>> {
+        if self.resolved.is_empty() {
+            self.resolve_chunk().await?;
+        }
+        Ok(self.resolved.pop_front())
+    }
+
+    /// Looks up the next chunk of keys, leaving the keys in place on error.
+    async fn resolve_chunk(&mut self) -> Result<()> {
+        let chunk = self.keys.len().min(CHUNK_SIZE);
+        let mut values = Vec::with_capacity(chunk);
+        let mut misses = Vec::new();
+        for (index, key) in self.keys.range(..chunk).enumerate() {
+            let cached = self.map.map.get(key).map(|r| r.value().clone());
+            match cached {
+                Some(_) if self.map.eviction.is_expired(key) => values.push(None),
+                Some(value) => {
+                    self.map.eviction.touch(key);
+                    values.push(Some(value));
+                }
+                None => {
+                    values.push(None);
+                    misses.push((index, key.clone()));
+                }
+            }
+        }
+
+        if !misses.is_empty() {
+            self.map.drain_pending().await?;
+            let (indexes, keys): (Vec<usize>, Vec<K>) = misses.into_iter().unzip();
+            let stored = self.map.backend.load_many_raw(&keys).await?;
+            for (index, bytes) in indexes.into_iter().zip(stored) {
+                if let Some(bytes) = bytes {
+                    values[index] = Some(self.map.decode_stored(&bytes)?);
+                }
+            }
+        }
+
+        self.resolved = self.keys.drain(..chunk).zip(values).collect();
+        Ok(())
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns a cursor that looks up each of `keys`, yielding results one
+    /// key at a time.
+    ///
+    /// Keys are resolved in chunks: cached keys are answered from memory and
+    /// the misses of a chunk are read from the backend in one batch, without
+    /// being added to the in-memory map. Only one chunk of results is held at
+    /// a time, so a very large key set can be processed as it is resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>, keys: Vec<String>) -> Result<()> {
+    /// let mut lookups = map.get_stream(keys);
+    /// while let Some((key, value)) = lookups.next().await? {
+    ///     match value {
+    ///         Some(value) => println!("{key} => {value}"),
+    ///         None => println!("{key} is missing"),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_stream(&self, keys: Vec<K>) -> KeyLookups<'_, K, V, B> {
+        KeyLookups {
+            map: self,
+            keys: keys.into_iter().map(|key| self.keys.owned(key)).collect(),
+            resolved: VecDeque::new(),
+        }
+    }
+}
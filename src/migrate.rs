@@ -0,0 +1,92 @@
+//! On-load migration of stored values.
+//!
+//! A migration hook sees each stored value as a `serde_json::Value` before it
+//! is decoded into the map's value type, which lets old value shapes be
+//! upgraded lazily as they are loaded instead of in a separate batch job. A
+//! repair hook only sees the values that fail to decode.
+
+use crate::{Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{hash::Hash, sync::Arc};
+
+/// A hook that transforms a stored value into the current value shape.
+///
+/// The hook receives the stored value as JSON and returns the JSON that is
+/// decoded into the map's value type. Values already in the current shape
+/// should be returned unchanged.
+pub type ValueMigration = Arc<dyn Fn(serde_json::Value) -> Result<serde_json::Value> + Send + Sync>;
+
+/// A hook that fixes up a stored value that does not decode into the map's
+/// value type.
+pub type ValueRepair = Arc<dyn Fn(serde_json::Value) -> serde_json::Value + Send + Sync>;
+
+/// Combines a migration and a repair hook into one migration.
+///
+/// The repair runs on the migrated value when it does not decode into `V`.
+pub fn with_repair<V>(
+    migration: Option<ValueMigration>,
+    repair: Option<ValueRepair>,
+) -> Option<ValueMigration>
+where
+    V: DeserializeOwned,
+{
+    let Some(repair) = repair else {
+        return migration;
+    };
+    Some(Arc::new(move |stored| {
+        let value = match &migration {
+            Some(migration) => migration(stored)?,
+            None => stored,
+        };
+        if V::deserialize(&value).is_ok() {
+            Ok(value)
+        } else {
+            Ok(repair(value))
+        }
+    }))
+}
+
+/// Decodes stored bytes into `V`, running them through `migration` first.
+pub fn decode<V>(bytes: &[u8], migration: &ValueMigration) -> Result<V>
+where
+    V: DeserializeOwned,
+{
+    let stored: serde_json::Value = serde_json::from_slice(bytes)?;
+    Ok(serde_json::from_value(migration(stored)?)?)
+}
+
+/// Decodes stored `(key, bytes)` entries, running each value through `migration`.
+pub fn decode_entries<K, V>(
+    entries: Vec<(K, Vec<u8>)>,
+    migration: &ValueMigration,
+) -> Result<Vec<(K, V)>>
+where
+    V: DeserializeOwned,
+{
+    entries
+        .into_iter()
+        .map(|(key, bytes)| Ok((key, decode(&bytes, migration)?)))
+        .collect()
+}
+
+/// Reads every entry of `backend`, running each value through `migration`
+/// if one is set.
+pub async fn load_entries<K, V, B>(
+    backend: &B,
+    migration: Option<&ValueMigration>,
+) -> Result<Vec<(K, V)>>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    match migration {
+        Some(migration) => backend
+            .load_all_raw()
+            .await?
+            .into_iter()
+            .map(|(k, bytes)| Ok((k, decode(&bytes, migration)?)))
+            .collect(),
+        None => Ok(backend.load_all().await?.into_iter().collect()),
+    }
+}
@@ -0,0 +1,44 @@
+//! Key normalization.
+//!
+//! A normalizer maps every key to a canonical form before it reaches the
+//! in-memory map or the backend, so keys that normalize to the same value
+//! (for example `Alice` and `alice` under lowercasing) share one entry.
+
+use std::{borrow::Cow, sync::Arc};
+
+/// A function mapping a key to its canonical form.
+///
+/// The function should be idempotent: normalizing an already normalized key
+/// must return it unchanged.
+pub type KeyNormalizer<K> = Arc<dyn Fn(&K) -> K + Send + Sync>;
+
+/// Applies the map's key normalizer, if one is configured.
+#[derive(Clone)]
+pub struct Normalize<K> {
+    normalizer: Option<KeyNormalizer<K>>,
+}
+
+impl<K> Normalize<K>
+where
+    K: Clone,
+{
+    pub const fn new(normalizer: Option<KeyNormalizer<K>>) -> Self {
+        Self { normalizer }
+    }
+
+    /// Returns the canonical form of an owned key.
+    pub fn owned(&self, key: K) -> K {
+        match &self.normalizer {
+            Some(normalize) => normalize(&key),
+            None => key,
+        }
+    }
+
+    /// Returns the canonical form of a borrowed key, borrowing it when no
+    /// normalizer is configured.
+    pub fn borrowed<'a>(&self, key: &'a K) -> Cow<'a, K> {
+        self.normalizer
+            .as_ref()
+            .map_or(Cow::Borrowed(key), |normalize| Cow::Owned(normalize(key)))
+    }
+}
@@ -0,0 +1,113 @@
+//! Cursor-based iteration over the entries of a backend.
+
+use crate::{migrate, PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+/// Pages through every entry stored in a map's backend, ordered by key.
+///
+/// Created with [`PersistentMap::iter_backend_paged`]. Each call to
+/// [`next_page`](Self::next_page) reads the entries after the last key of
+/// the previous page, so iteration stays correct and costs the same at any
+/// depth even while keys are inserted or removed.
+pub struct BackendPages<'a, K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// The map whose backend is read
+    map: &'a PersistentMap<K, V, B>,
+
+    /// Maximum number of entries per page
+    page_size: usize,
+
+    /// Last key of the previous page
+    last_key: Option<K>,
+
+    /// Set once a short page was returned
+    done: bool,
+}
+
+impl<K, V, B> BackendPages<'_, K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + ToString + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns the next page, or `None` once every entry was returned.
+    ///
+    /// Pages hold at most the configured page size and are never empty.
+    /// Pending write-behind writes are flushed before each page is read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from the backend fails. The cursor does
+    /// not advance, so the call can be retried.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<(K, V)>>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.map.drain_pending().await?;
+        let (backend, last_key) = (&self.map.backend, self.last_key.clone());
+        let page = match &self.map.migrate_value {
+            Some(migration) => {
+                let raw = backend.load_after_raw(last_key, self.page_size).await?;
+                migrate::decode_entries(raw, migration)?
+            }
+            None => backend.load_after(last_key, self.page_size).await?,
+        };
+        if page.len() < self.page_size {
+            self.done = true;
+        }
+        match page.last() {
+            Some((key, _)) => {
+                self.last_key = Some(key.clone());
+                Ok(Some(page))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns a cursor over every entry of the backend, in pages of at most
+    /// `page_size` entries ordered by the string form of their key.
+    ///
+    /// Entries are read straight from the backend with keyset pagination and
+    /// are not added to the in-memory map, which makes this suitable for
+    /// exporting or reindexing a store larger than memory. A page size of
+    /// zero is treated as one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let mut pages = map.iter_backend_paged(1_000);
+    /// while let Some(page) = pages.next_page().await? {
+    ///     for (key, value) in page {
+    ///         println!("{key} => {value}");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_backend_paged(&self, page_size: usize) -> BackendPages<'_, K, V, B>
+    where
+        K: ToString,
+    {
+        BackendPages {
+            map: self,
+            page_size: page_size.max(1),
+            last_key: None,
+            done: false,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Common imports.
+//!
+//! `use persistent_map::prelude::*;` brings in the map types, the backend
+//! trait, the error and result types, and the backend types of every
+//! enabled backend feature. Backends whose feature is off are simply left
+//! out, so the glob import works with any feature set.
+//!
+//! The prelude's `Result` defaults its error type to `PersistentError` but
+//! still accepts an explicit one, so it can stand in for `std::result::Result`.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use persistent_map::prelude::*;
+//!
+//! # #[cfg(feature = "sqlite")]
+//! # async fn example() -> Result<()> {
+//! let backend = SqliteBackend::new("my_database.db").await?;
+//! let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+//! # Ok(())
+//! # }
+//! #
+//! # #[cfg(not(feature = "sqlite"))]
+//! # fn example() {}
+//! ```
+
+pub use crate::{
+    DynBackend, JsonMapExt, PersistentError, PersistentMap, PersistentMapBuilder, PersistentSet,
+    Result, StorageBackend,
+};
+
+#[cfg(feature = "runtime")]
+pub use crate::SharedBackend;
+
+#[cfg(feature = "csv_backend")]
+pub use crate::csv::CsvBackend;
+
+#[cfg(feature = "in_memory")]
+pub use crate::in_memory::InMemoryBackend;
+
+#[cfg(feature = "postgres")]
+pub use crate::postgres::PostgresBackend;
+
+#[cfg(feature = "redis_backend")]
+pub use crate::redis::RedisBackend;
+
+#[cfg(feature = "s3")]
+pub use crate::s3::S3Backend;
+
+#[cfg(feature = "sled_backend")]
+pub use crate::sled::SledBackend;
+
+#[cfg(feature = "sqlite")]
+pub use crate::sqlite::SqliteBackend;
@@ -0,0 +1,150 @@
+//! Fixed-window rate limiting on top of the map.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    hash::Hash,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The state of one rate limit counter: how many calls were counted in the
+/// current window and when the window started.
+///
+/// Used as the value type of the map that
+/// [`check_and_increment`](PersistentMap::check_and_increment) is called on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateCounter {
+    /// Calls counted in the current window
+    count: u64,
+
+    /// When the current window started
+    window_start: SystemTime,
+}
+
+impl RateCounter {
+    /// Returns the number of calls counted in the current window.
+    #[must_use]
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns when the current window started.
+    #[must_use]
+    pub const fn window_start(&self) -> SystemTime {
+        self.window_start
+    }
+}
+
+impl<K, B> PersistentMap<K, RateCounter, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, RateCounter> + Send + Sync + 'static,
+{
+    /// Counts a call against the rate limit of `key`, returning `true` if
+    /// the limit was already reached.
+    ///
+    /// Each key allows `limit` calls per `window`. The window starts with
+    /// the first call and is fixed: once it has passed, the next call starts
+    /// a new one with a fresh count. Calls over the limit are rejected
+    /// without being counted, so they do not extend the window.
+    ///
+    /// The counter is persisted with a time-to-live that ends with its
+    /// window, so limits survive restarts and stale counters are removed by
+    /// [`purge_expired`](Self::purge_expired). With the `runtime` feature the
+    /// check and the increment happen under the key's lock, so concurrent
+    /// calls for the same key never both take the last slot. Counters that
+    /// were evicted from memory for capacity start over.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, RateCounter, StorageBackend, Result};
+    /// # use std::time::Duration;
+    /// #
+    /// # async fn example(limits: PersistentMap<String, RateCounter, impl StorageBackend<String, RateCounter> + Send + Sync>) -> Result<()> {
+    /// // At most 100 requests per client per minute
+    /// if limits
+    ///     .check_and_increment("client-42".to_string(), Duration::from_secs(60), 100)
+    ///     .await?
+    /// {
+    ///     println!("429 Too Many Requests");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if saving the counter to the backend fails.
+    pub async fn check_and_increment(&self, key: K, window: Duration, limit: u64) -> Result<bool> {
+        self.check_writable("check_and_increment")?;
+        let key = self.keys.owned(key);
+        #[cfg(feature = "runtime")]
+        if self.key_locks.is_sync() {
+            let next = {
+                let _guard = self.key_locks.lock_sync(&key);
+                let next = self.next_rate_counter(&key, window, limit);
+                if let Some((counter, expires_at)) = &next {
+                    self.insert_cached(key.clone(), counter.clone(), *expires_at);
+                }
+                next
+            };
+            let Some((counter, expires_at)) = next else {
+                return Ok(true);
+            };
+            self.persist_expiring(key, counter, expires_at).await?;
+            self.evict_over_capacity();
+            return Ok(false);
+        }
+        #[cfg(feature = "runtime")]
+        let _guard = self.key_locks.lock(&key).await;
+        let Some((counter, expires_at)) = self.next_rate_counter(&key, window, limit) else {
+            return Ok(true);
+        };
+        self.persist_expiring(key.clone(), counter.clone(), expires_at)
+            .await?;
+        self.insert_cached(key, counter, expires_at);
+        self.evict_over_capacity();
+        Ok(false)
+    }
+
+    /// Returns the counter of `key` after counting one more call, with the
+    /// deadline of its window, or `None` if the limit was reached.
+    fn next_rate_counter(
+        &self,
+        key: &K,
+        window: Duration,
+        limit: u64,
+    ) -> Option<(RateCounter, Option<Instant>)> {
+        let now = SystemTime::now();
+        let cached = if self.eviction.is_expired(key) {
+            None
+        } else {
+            self.map.get(key).map(|r| r.value().clone())
+        };
+        // A window too long to represent never ends
+        let counter = cached
+            .filter(|counter| {
+                counter
+                    .window_start
+                    .checked_add(window)
+                    .map_or(true, |end| now < end)
+            })
+            .unwrap_or(RateCounter {
+                count: 0,
+                window_start: now,
+            });
+        if counter.count >= limit {
+            return None;
+        }
+        let expires_at = counter.window_start.checked_add(window).and_then(|end| {
+            Instant::now().checked_add(end.duration_since(now).unwrap_or_default())
+        });
+        Some((
+            RateCounter {
+                count: counter.count + 1,
+                ..counter
+            },
+            expires_at,
+        ))
+    }
+}
@@ -0,0 +1,122 @@
+//! Moving a map's cache onto another backend.
+
+use crate::consistency::LocalWrites;
+use crate::{PersistentMap, Result, StorageBackend};
+use dashmap::DashMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize},
+        Arc, Mutex,
+    },
+    time::{Instant, SystemTime},
+};
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Copies the cached entries into `new` and returns a map over it that
+    /// takes over this map's cache.
+    ///
+    /// This moves a live map to another backend, for example from `SQLite`
+    /// to a server database, without reloading: queued writes are flushed
+    /// to the current backend, every cached, unexpired entry is saved to
+    /// `new` with its remaining time-to-live, and `new` is flushed. The
+    /// returned map then serves the same entries straight away, with the
+    /// same eviction order, capacity, key normalizer, value migration and
+    /// durability. Write-behind and the periodic flush are not carried
+    /// over, so the returned map writes through to `new`.
+    ///
+    /// Only cached entries are copied. In a map with a capacity, entries
+    /// that were evicted are left behind; copy them first by paging through
+    /// the backend with [`iter_backend_paged`](Self::iter_backend_paged).
+    /// Stored values of the same keys in `new` are overwritten, and other
+    /// keys it holds are kept but not loaded.
+    ///
+    /// Taking `&mut self` keeps other tasks from writing during the copy.
+    /// Once it succeeds this map is left with an empty cache over the old
+    /// backend, and should be dropped; if it fails, this map is unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example<B2: StorageBackend<String, String> + Send + Sync + 'static>(
+    /// #     mut map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>,
+    /// #     new_backend: B2,
+    /// # ) -> Result<()> {
+    /// let map = map.replace_backend(new_backend).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if flushing the current backend fails, or if saving
+    /// to or flushing `new` fails.
+    pub async fn replace_backend<B2>(&mut self, new: B2) -> Result<PersistentMap<K, V, B2>>
+    where
+        B2: StorageBackend<K, V> + Send + Sync + 'static,
+    {
+        self.flush().await?;
+
+        let (now, system_now) = (Instant::now(), SystemTime::now());
+        let mut plain = Vec::new();
+        let mut expiring = Vec::new();
+        for entry in self.map.iter() {
+            let (key, value) = (entry.key().clone(), entry.value().clone());
+            match self.eviction.expires_at(&key) {
+                None => plain.push((key, value)),
+                Some(at) if at > now => expiring.push((key, value, system_now + (at - now))),
+                Some(_) => {}
+            }
+        }
+        if !plain.is_empty() {
+            new.save_many(plain).await?;
+        }
+        for (key, value, expires_at) in expiring {
+            new.save_expiring(key, value, expires_at).await?;
+        }
+        new.flush().await?;
+
+        let eviction = Arc::new(self.eviction.empty_like());
+        Ok(PersistentMap {
+            map: std::mem::replace(&mut self.map, Arc::new(DashMap::new())),
+            backend: Arc::new(new),
+            eviction: std::mem::replace(&mut self.eviction, eviction),
+            max_entries: self.max_entries,
+            admission: Mutex::new(0),
+            keys: self.keys.clone(),
+            migrate_value: self.migrate_value.clone(),
+            durable: self.durable,
+            local_writes: self
+                .local_writes
+                .as_ref()
+                .map(|local| LocalWrites::new(local.window())),
+            #[cfg(feature = "runtime")]
+            load_lock: tokio::sync::Mutex::new(()),
+            #[cfg(feature = "runtime")]
+            key_locks: crate::key_lock::KeyedLock::new(self.key_locks.kind()),
+            #[cfg(feature = "runtime")]
+            batch_loader: self
+                .batch_loader
+                .as_ref()
+                .map(|loader| crate::loader::BatchLoader::new(loader.window())),
+            #[cfg(feature = "runtime")]
+            write_behind: None,
+            load_generation: AtomicU64::new(0),
+            loaded_count: AtomicUsize::new(0),
+            change_token: Mutex::new(None),
+            #[cfg(feature = "runtime")]
+            tasks: crate::tasks::BackgroundTasks::default(),
+            #[cfg(feature = "runtime")]
+            waiters: Arc::default(),
+            #[cfg(feature = "latency")]
+            latency: crate::latency::Latencies::default(),
+        })
+    }
+}
@@ -0,0 +1,80 @@
+//! Small samples of the cached entries for quick inspection.
+
+use crate::{PersistentMap, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash, Hasher},
+};
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns up to `n` cached entries, without cloning the rest of the map.
+    ///
+    /// Which entries are returned, and their order, is unspecified: it
+    /// follows the iteration order of the underlying `DashMap`, which depends
+    /// on key hashes and can change as the map is modified. Expired entries
+    /// are skipped and the backend is not read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// for (key, value) in map.first_n(5) {
+    ///     println!("example entry: {key} = {value}");
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn first_n(&self, n: usize) -> Vec<(K, V)> {
+        self.map
+            .iter()
+            .filter(|entry| !self.eviction.is_expired(entry.key()))
+            .take(n)
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Returns up to `n` cached entries chosen pseudo-randomly.
+    ///
+    /// Every live entry is equally likely to be picked. The map is walked
+    /// once but only the sampled entries are cloned. The order of the
+    /// returned entries is unspecified, expired entries are skipped and the
+    /// backend is not read.
+    ///
+    /// The randomness comes from the randomly keyed std hasher, which is good
+    /// enough for picking examples but not for anything security-sensitive.
+    #[must_use]
+    pub fn sample(&self, n: usize) -> Vec<(K, V)> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let random = RandomState::new();
+        let mut sample = Vec::with_capacity(n.min(self.map.len()));
+        let live = self
+            .map
+            .iter()
+            .filter(|entry| !self.eviction.is_expired(entry.key()));
+        // Reservoir sampling: the i-th entry replaces a random slot with
+        // probability n / (i + 1)
+        for (seen, entry) in live.enumerate() {
+            if seen < n {
+                sample.push((entry.key().clone(), entry.value().clone()));
+                continue;
+            }
+            let mut hasher = random.build_hasher();
+            hasher.write_usize(seen);
+            let slot = usize::try_from(hasher.finish() % (seen as u64 + 1)).unwrap_or(usize::MAX);
+            if slot < n {
+                sample[slot] = (entry.key().clone(), entry.value().clone());
+            }
+        }
+        sample
+    }
+}
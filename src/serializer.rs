@@ -0,0 +1,229 @@
+//! Value encodings for backends that store byte strings.
+
+use crate::{PersistentError, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+/// Encodes values to bytes and decodes them back.
+///
+/// [`SerializedBackend`](crate::serialized::SerializedBackend) uses a
+/// serializer to store values in a backend of byte strings, so the encoding
+/// can be chosen without writing a backend. Implement it for other formats,
+/// such as `MessagePack`.
+///
+/// The methods are generic over the value type, so a serializer is chosen
+/// with a type parameter rather than boxed.
+pub trait Serializer: Send + Sync + 'static {
+    /// Encodes `value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be encoded.
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>>;
+
+    /// Decodes a value encoded by [`serialize`](Self::serialize).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` are not a valid encoding of a `V`.
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V>;
+}
+
+/// Encodes values as JSON text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonSerializer;
+
+impl Serializer for JsonSerializer {
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Encodes values with `bincode`.
+///
+/// The encoding is compact and keeps floats exact, but is not
+/// self-describing: value types that need it, such as `serde_json::Value`,
+/// untagged enums or structs with flattened fields, cannot be decoded.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BincodeSerializer;
+
+#[cfg(feature = "bincode")]
+impl Serializer for BincodeSerializer {
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>> {
+        bincode::serialize(value).map_err(invalid_data)
+    }
+
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V> {
+        bincode::deserialize(bytes).map_err(invalid_data)
+    }
+}
+
+/// Header byte of a value stored uncompressed.
+const UNCOMPRESSED: u8 = 0;
+
+/// Header byte of a value compressed with gzip.
+const GZIP: u8 = 1;
+
+/// Header byte of a value compressed with Zstandard.
+const ZSTD: u8 = 2;
+
+/// A compression algorithm applied by [`CompressingSerializer`].
+///
+/// `None` is always available; the algorithms are enabled by the
+/// `compression` feature for gzip and the `zstd_compression` feature for
+/// Zstandard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Store the encoding as it is.
+    #[default]
+    None,
+
+    /// Compress with gzip at the default level.
+    #[cfg(feature = "compression")]
+    Gzip,
+
+    /// Compress with Zstandard at the default level.
+    #[cfg(feature = "zstd_compression")]
+    Zstd,
+}
+
+/// Compresses the encoding of another [`Serializer`].
+///
+/// Values are encoded by the wrapped serializer, JSON by default, and the
+/// bytes are then compressed. Each stored value starts with a one-byte
+/// header recording the algorithm, so values written with another
+/// [`Compression`], or before compression was turned on for a
+/// `CompressingSerializer`, still decode. Decoding a value needs the
+/// feature of the algorithm it was compressed with.
+///
+/// Use it with [`SerializedBackend`](crate::serialized::SerializedBackend)
+/// to compress the values of any backend of byte strings.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{CompressingSerializer, Compression, JsonSerializer, PersistentMap, Result};
+/// use persistent_map::serialized::SerializedBackend;
+/// # #[cfg(feature = "sqlite")]
+/// use persistent_map::sqlite::SqliteBackend;
+///
+/// # #[cfg(all(feature = "sqlite", feature = "compression"))]
+/// # async fn example() -> Result<()> {
+/// let backend = SerializedBackend::new(
+///     SqliteBackend::new_binary("docs.db").await?,
+///     CompressingSerializer::new(JsonSerializer, Compression::Gzip),
+/// );
+/// let docs: PersistentMap<String, serde_json::Value, _> = PersistentMap::new(backend).await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(all(feature = "sqlite", feature = "compression")))]
+/// # fn example() {}
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressingSerializer<S = JsonSerializer> {
+    /// Encodes the values before compression
+    inner: S,
+
+    /// The algorithm applied to new values
+    compression: Compression,
+}
+
+impl<S> CompressingSerializer<S> {
+    /// Wraps `inner`, compressing its encodings with `compression`.
+    pub const fn new(inner: S, compression: Compression) -> Self {
+        Self { inner, compression }
+    }
+
+    /// Returns the algorithm applied to new values.
+    #[must_use]
+    pub const fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Returns the wrapped serializer.
+    #[must_use]
+    pub const fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: Serializer> Serializer for CompressingSerializer<S> {
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>> {
+        let encoded = self.inner.serialize(value)?;
+        match self.compression {
+            Compression::None => {
+                let mut stored = Vec::with_capacity(encoded.len() + 1);
+                stored.push(UNCOMPRESSED);
+                stored.extend_from_slice(&encoded);
+                Ok(stored)
+            }
+            #[cfg(feature = "compression")]
+            Compression::Gzip => {
+                use std::io::Write;
+
+                let mut gzip =
+                    flate2::write::GzEncoder::new(vec![GZIP], flate2::Compression::default());
+                gzip.write_all(&encoded)?;
+                Ok(gzip.finish()?)
+            }
+            #[cfg(feature = "zstd_compression")]
+            Compression::Zstd => {
+                let mut stored = vec![ZSTD];
+                zstd::stream::copy_encode(encoded.as_slice(), &mut stored, 0)?;
+                Ok(stored)
+            }
+        }
+    }
+
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V> {
+        let Some((&header, body)) = bytes.split_first() else {
+            return Err(invalid_data("empty compressed value"));
+        };
+        let encoded = match header {
+            UNCOMPRESSED => Cow::Borrowed(body),
+            #[cfg(feature = "compression")]
+            GZIP => {
+                use std::io::Read;
+
+                let mut encoded = Vec::new();
+                flate2::read::GzDecoder::new(body).read_to_end(&mut encoded)?;
+                Cow::Owned(encoded)
+            }
+            #[cfg(not(feature = "compression"))]
+            GZIP => return Err(missing_feature("gzip", "compression")),
+            #[cfg(feature = "zstd_compression")]
+            ZSTD => Cow::Owned(zstd::stream::decode_all(body)?),
+            #[cfg(not(feature = "zstd_compression"))]
+            ZSTD => return Err(missing_feature("zstd", "zstd_compression")),
+            header => {
+                return Err(invalid_data(format!(
+                    "unknown compressed value header {header}"
+                )))
+            }
+        };
+        self.inner.deserialize(&encoded)
+    }
+}
+
+/// Returns the error for a value compressed with an algorithm whose feature
+/// is disabled.
+#[cfg(not(all(feature = "compression", feature = "zstd_compression")))]
+fn missing_feature(algorithm: &str, feature: &str) -> PersistentError {
+    PersistentError::Unsupported(format!(
+        "{algorithm} decompression needs the `{feature}` feature"
+    ))
+}
+
+fn invalid_data<E>(e: E) -> PersistentError
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    PersistentError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,168 @@
+//! A persistent set of keys built on the `PersistentMap` machinery.
+
+use crate::{PersistentError, PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashSet, hash::Hash};
+
+/// A persistent set of keys.
+///
+/// `PersistentSet` is a thin layer over a `PersistentMap<K, (), B>`: every
+/// member is stored in the backend with a unit value, so any backend that
+/// implements `StorageBackend<K, ()>` can hold a set. Membership checks are
+/// served from memory, while inserts and removals are persisted immediately.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentSet, Result};
+/// # #[cfg(feature = "sqlite")]
+/// use persistent_map::sqlite::SqliteBackend;
+///
+/// # #[cfg(feature = "sqlite")]
+/// # async fn example() -> Result<()> {
+/// let backend = SqliteBackend::new("tags.db").await?;
+/// let tags: PersistentSet<String, _> = PersistentSet::new(backend).await?;
+///
+/// tags.insert("rust".to_string()).await?;
+/// assert!(tags.contains(&"rust".to_string()));
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "sqlite"))]
+/// # fn example() {}
+/// ```
+pub struct PersistentSet<K, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, ()> + Send + Sync + 'static,
+{
+    /// The underlying map with unit values
+    inner: PersistentMap<K, (), B>,
+}
+
+impl<K, B> PersistentSet<K, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, ()> + Send + Sync + 'static,
+{
+    /// Creates a new `PersistentSet` and loads all existing members from the backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if loading from the backend fails.
+    pub async fn new(backend: B) -> Result<Self> {
+        Ok(Self {
+            inner: PersistentMap::new(backend).await?,
+        })
+    }
+
+    /// Adds a key to the set and persists it.
+    ///
+    /// Returns `true` if the key was not already a member.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if saving to the backend fails.
+    #[inline]
+    pub async fn insert(&self, key: K) -> Result<bool> {
+        Ok(self.inner.insert(key, ()).await?.is_none())
+    }
+
+    /// Removes a key from the set and the backend.
+    ///
+    /// Returns `true` if the key was a member in memory. The key is deleted
+    /// from the backend either way.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if deleting from the backend fails.
+    #[inline]
+    pub async fn remove(&self, key: &K) -> Result<bool> {
+        Ok(self.inner.remove(key).await?.is_some())
+    }
+
+    /// Returns `true` if the key is a member of the set.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, key: &K) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Returns the number of members.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the set has no members.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns an iterator over clones of the members, in arbitrary order.
+    ///
+    /// The iterator reads the in-memory set, so members inserted or removed
+    /// while iterating may or may not be observed.
+    pub fn iter(&self) -> impl Iterator<Item = K> + '_ {
+        self.inner
+            .map
+            .iter()
+            .filter(|entry| !self.inner.eviction.is_expired(entry.key()))
+            .map(|entry| entry.key().clone())
+    }
+
+    /// Returns the members that are in this set, `other`, or both.
+    #[must_use]
+    pub fn union<B2>(&self, other: &PersistentSet<K, B2>) -> HashSet<K>
+    where
+        B2: StorageBackend<K, ()> + Send + Sync + 'static,
+    {
+        self.iter().chain(other.iter()).collect()
+    }
+
+    /// Returns the members that are in both this set and `other`.
+    #[must_use]
+    pub fn intersection<B2>(&self, other: &PersistentSet<K, B2>) -> HashSet<K>
+    where
+        B2: StorageBackend<K, ()> + Send + Sync + 'static,
+    {
+        self.iter().filter(|key| other.contains(key)).collect()
+    }
+
+    /// Returns the members that are in this set but not in `other`.
+    #[must_use]
+    pub fn difference<B2>(&self, other: &PersistentSet<K, B2>) -> HashSet<K>
+    where
+        B2: StorageBackend<K, ()> + Send + Sync + 'static,
+    {
+        self.iter().filter(|key| !other.contains(key)).collect()
+    }
+
+    /// Removes every member from memory.
+    ///
+    /// Like [`PersistentMap::clear`], this does not delete anything from the backend.
+    #[inline]
+    pub fn clear(&self) {
+        self.inner.clear();
+    }
+
+    /// Flushes any buffered writes to the backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if flushing the backend fails.
+    #[inline]
+    pub async fn flush(&self) -> Result<(), PersistentError> {
+        self.inner.flush().await
+    }
+
+    /// Returns a reference to the storage backend.
+    #[inline]
+    #[must_use]
+    pub fn backend(&self) -> &B {
+        self.inner.backend()
+    }
+}
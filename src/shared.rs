@@ -0,0 +1,354 @@
+//! Backends shared by several maps.
+//!
+//! A [`SharedBackend`] is a cheaply cloneable handle to one backend. Every
+//! method is forwarded to it, except that `flush` and `sync` calls from
+//! different handles are coalesced, so maps that flush together only flush
+//! the backend once.
+
+use crate::{
+    BackendStats, Change, ChangeToken, PersistentError, Result, SaveOutcome, StorageBackend,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::Mutex, time::Instant};
+
+/// A reference-counted handle to a backend shared by several maps.
+///
+/// Each map gets its own clone of the handle. Flushes are coalesced: a call
+/// to `flush` returns without flushing again if a flush of the backend
+/// started after the call was made, which happens when several maps flush
+/// at once and one of them is already flushing. The same applies to `sync`.
+///
+/// A flush window widens this to calls made shortly after a flush started,
+/// for apps that flush every map in a row. Writes made inside the window
+/// after a flush started are then only flushed by the next flush outside
+/// it, so keep the window short. The default window is zero.
+///
+/// The maps share the backend's entries too, so each map loads the entries
+/// written by the others.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use persistent_map::{PersistentMap, Result, SharedBackend};
+/// # #[cfg(feature = "sqlite")]
+/// use persistent_map::sqlite::SqliteBackend;
+/// use std::time::Duration;
+///
+/// # #[cfg(feature = "sqlite")]
+/// # async fn example() -> Result<()> {
+/// let backend = SharedBackend::with_flush_window(
+///     SqliteBackend::new("app.db").await?,
+///     Duration::from_millis(50),
+/// );
+/// let sessions: PersistentMap<String, String, _> = PersistentMap::new(backend.clone()).await?;
+/// let settings: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+///
+/// // The backend is flushed once
+/// sessions.flush().await?;
+/// settings.flush().await?;
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "sqlite"))]
+/// # fn example() {}
+/// ```
+pub struct SharedBackend<B> {
+    inner: Arc<Shared<B>>,
+}
+
+/// The state shared by every handle.
+struct Shared<B> {
+    /// The wrapped backend
+    backend: B,
+
+    /// How long after a flush started later calls are covered by it
+    window: Duration,
+
+    /// Coalesces `flush` calls
+    flushed: Coalesced,
+
+    /// Coalesces `sync` calls
+    synced: Coalesced,
+}
+
+impl<B> SharedBackend<B> {
+    /// Wraps `backend` in a shared handle with a zero flush window.
+    #[must_use]
+    pub fn new(backend: B) -> Self {
+        Self::with_flush_window(backend, Duration::ZERO)
+    }
+
+    /// Wraps `backend` in a shared handle whose `flush` and `sync` calls
+    /// are also covered by a flush that started up to `window` earlier.
+    #[must_use]
+    pub fn with_flush_window(backend: B, window: Duration) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                backend,
+                window,
+                flushed: Coalesced::default(),
+                synced: Coalesced::default(),
+            }),
+        }
+    }
+
+    /// Returns a reference to the wrapped backend.
+    #[must_use]
+    pub fn backend(&self) -> &B {
+        &self.inner.backend
+    }
+
+    /// Returns the number of handles to the backend, including this one.
+    #[must_use]
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+}
+
+impl<B> Clone for SharedBackend<B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// Runs an operation at most once for callers that overlap with it.
+#[derive(Default)]
+struct Coalesced {
+    /// When the last successful run started
+    last_started: Mutex<Option<Instant>>,
+}
+
+impl Coalesced {
+    /// Runs `op`, unless a run that started after this call, or less than
+    /// `window` before it, has completed successfully.
+    async fn run<F, Fut>(&self, window: Duration, op: F) -> Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let called = Instant::now();
+        let mut last_started = self.last_started.lock().await;
+        if let Some(started) = *last_started {
+            if started > called || called - started < window {
+                return Ok(());
+            }
+        }
+        let started = Instant::now();
+        op().await?;
+        *last_started = Some(started);
+        drop(last_started);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<K, V, B> StorageBackend<K, V> for SharedBackend<B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync,
+{
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+        self.inner.backend.load_all().await
+    }
+
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+        self.inner.backend.save(key, value).await
+    }
+
+    async fn save_reporting(&self, key: K, value: V) -> Result<SaveOutcome, PersistentError> {
+        self.inner.backend.save_reporting(key, value).await
+    }
+
+    async fn save_if_newer(&self, key: K, value: V, version: u64) -> Result<bool, PersistentError> {
+        self.inner.backend.save_if_newer(key, value, version).await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+        self.inner.backend.delete(key).await
+    }
+
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        self.inner.backend.save_many(entries).await
+    }
+
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        self.inner.backend.delete_many(keys).await
+    }
+
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        self.inner.backend.delete_all().await
+    }
+
+    async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+        self.inner.backend.rename_keys(renames).await
+    }
+
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        self.inner.backend.write_batch(saves, deletes).await
+    }
+
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        self.inner.backend.load_one_raw(key).await
+    }
+
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        self.inner.backend.load_all_raw().await
+    }
+
+    async fn load_many_raw(&self, keys: &[K]) -> Result<Vec<Option<Vec<u8>>>, PersistentError> {
+        self.inner.backend.load_many_raw(keys).await
+    }
+
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        self.inner.backend.save_raw(key, value).await
+    }
+
+    /// Flushes the backend once for every caller that arrives within the
+    /// flush window.
+    async fn flush(&self) -> Result<(), PersistentError> {
+        self.inner
+            .flushed
+            .run(self.inner.window, || self.inner.backend.flush())
+            .await
+    }
+
+    /// Syncs the backend once for every caller that arrives within the
+    /// flush window.
+    async fn sync(&self) -> Result<(), PersistentError> {
+        self.inner
+            .synced
+            .run(self.inner.window, || self.inner.backend.sync())
+            .await
+    }
+
+    async fn checkpoint(&self) -> Result<(), PersistentError> {
+        self.inner.backend.checkpoint().await
+    }
+
+    async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+        self.inner.backend.check_integrity(thorough).await
+    }
+
+    async fn save_expiring(
+        &self,
+        key: K,
+        value: V,
+        expires_at: SystemTime,
+    ) -> Result<(), PersistentError> {
+        self.inner
+            .backend
+            .save_expiring(key, value, expires_at)
+            .await
+    }
+
+    async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        self.inner.backend.load_expiries().await
+    }
+
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        self.inner.backend.load_write_times().await
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+        self.inner.backend.delete_expired(now).await
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.inner.backend.scan_prefix(prefix).await
+    }
+
+    async fn load_page(&self, offset: usize, limit: usize) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.inner.backend.load_page(offset, limit).await
+    }
+
+    async fn load_after(
+        &self,
+        last_key: Option<K>,
+        limit: usize,
+    ) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.inner.backend.load_after(last_key, limit).await
+    }
+
+    async fn scan_prefix_raw(&self, prefix: &str) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.inner.backend.scan_prefix_raw(prefix).await
+    }
+
+    async fn load_page_raw(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.inner.backend.load_page_raw(offset, limit).await
+    }
+
+    async fn load_after_raw(
+        &self,
+        last_key: Option<K>,
+        limit: usize,
+    ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        self.inner.backend.load_after_raw(last_key, limit).await
+    }
+
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        self.inner.backend.contains_key(key).await
+    }
+
+    async fn len(&self) -> Result<usize, PersistentError> {
+        self.inner.backend.len().await
+    }
+
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        self.inner.backend.is_empty().await
+    }
+
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        self.inner.backend.last_modified().await
+    }
+
+    async fn changes_since(
+        &self,
+        since: Option<ChangeToken>,
+    ) -> Result<(Vec<Change<K>>, ChangeToken), PersistentError> {
+        self.inner.backend.changes_since(since).await
+    }
+
+    async fn stats(&self) -> Result<BackendStats, PersistentError> {
+        self.inner.backend.stats().await
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.backend.is_read_only()
+    }
+}
@@ -0,0 +1,264 @@
+//! Point-in-time copies of the in-memory map, as snapshots, plain
+//! `HashMap`s or projections into another map.
+
+use crate::{normalize::Normalize, PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash, ops::Index, sync::Arc};
+
+/// An immutable copy of a map's cached entries.
+///
+/// Created with [`PersistentMap::snapshot`]. A snapshot owns its entries, so
+/// it can hand out plain references and supports indexing with `snapshot[&key]`.
+/// `PersistentMap` itself cannot implement [`Index`]: a reference into it
+/// would have to keep a shard of the concurrent map locked, and another task
+/// could remove the entry while the reference is alive.
+///
+/// Lookups apply the map's key normalizer, like the map's own methods.
+pub struct Snapshot<K, V> {
+    /// The copied entries, by normalized key
+    entries: HashMap<K, V>,
+
+    /// Maps lookup keys to their canonical form
+    keys: Normalize<K>,
+}
+
+impl<K, V> Snapshot<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Returns a reference to the value of `key`, if it was cached.
+    #[must_use]
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(&*self.keys.borrowed(key))
+    }
+
+    /// Returns `true` if `key` was cached.
+    #[must_use]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of entries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the snapshot has no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter()
+    }
+
+    /// Consumes the snapshot, returning its entries.
+    #[must_use]
+    pub fn into_inner(self) -> HashMap<K, V> {
+        self.entries
+    }
+}
+
+impl<K, V> Index<&K> for Snapshot<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    type Output = V;
+
+    /// Returns a reference to the value of `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not present in the snapshot, like indexing a
+    /// `HashMap`. Use [`get`](Snapshot::get) when the key may be absent.
+    fn index(&self, key: &K) -> &V {
+        self.get(key).expect("key not present in snapshot")
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Copies the cached, unexpired entries into an immutable [`Snapshot`].
+    ///
+    /// The snapshot supports `snapshot[&key]` indexing for code that assumes
+    /// presence. It clones every cached entry, so take one per batch of reads
+    /// rather than per lookup. Later writes to the map are not reflected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend};
+    /// #
+    /// # fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) {
+    /// let config = map.snapshot();
+    /// let host = &config[&"host".to_string()]; // panics if "host" is missing
+    /// let port = config.get(&"port".to_string()).map_or("80", String::as_str);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot<K, V> {
+        let entries = self
+            .map
+            .iter()
+            .filter(|entry| !self.eviction.is_expired(entry.key()))
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        Snapshot {
+            entries,
+            keys: self.keys.clone(),
+        }
+    }
+
+    /// Clones the cached, unexpired entries into a plain `HashMap`.
+    ///
+    /// Like [`snapshot`](Self::snapshot), but returns a type that code
+    /// unaware of `PersistentMap` accepts. Keys are in their normalized form.
+    /// The backend is not read.
+    #[must_use]
+    pub fn to_hashmap(&self) -> HashMap<K, V> {
+        self.snapshot().into_inner()
+    }
+
+    /// Returns an iterator over clones of the cached, unexpired entries, in
+    /// arbitrary order.
+    ///
+    /// The entries are copied up front, so no lock of the map is held while
+    /// iterating and the map can be read and written from the loop body.
+    /// Later writes are not reflected. The backend is not read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, u32, impl StorageBackend<String, u32> + Send + Sync>) -> Result<()> {
+    /// for (key, count) in map.iter() {
+    ///     map.insert(key, count + 1).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> {
+        self.to_hashmap().into_iter()
+    }
+
+    /// Returns an iterator over clones of the cached, unexpired keys, in
+    /// arbitrary order.
+    ///
+    /// Like [`iter`](Self::iter), the keys are copied up front; values are
+    /// not cloned.
+    pub fn keys(&self) -> impl Iterator<Item = K> {
+        self.map
+            .iter()
+            .filter(|entry| !self.eviction.is_expired(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Returns an iterator over clones of the cached, unexpired values, in
+    /// arbitrary order.
+    ///
+    /// Like [`iter`](Self::iter), the values are copied up front; keys are
+    /// not cloned.
+    pub fn values(&self) -> impl Iterator<Item = V> {
+        self.map
+            .iter()
+            .filter(|entry| !self.eviction.is_expired(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Consumes the map, returning its cached, unexpired entries without
+    /// cloning them.
+    ///
+    /// The backend and any background tasks are dropped. In write-behind
+    /// mode, writes that are still queued are discarded, so call
+    /// [`flush`](Self::flush) first if the backend must keep them. Entries
+    /// that were evicted or never loaded are not included.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// # use std::collections::HashMap;
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.flush().await?;
+    /// let plain: HashMap<String, String> = map.into_hashmap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn into_hashmap(self) -> HashMap<K, V> {
+        let Self { map, eviction, .. } = self;
+        // An aborted background task may not have released its handle yet
+        let map = Arc::try_unwrap(map).unwrap_or_else(|shared| (*shared).clone());
+        map.into_iter()
+            .filter(|(key, _)| !eviction.is_expired(key))
+            .collect()
+    }
+
+    /// Builds a new map over `dst` whose values are `f` applied to this
+    /// map's cached, unexpired values.
+    ///
+    /// This materializes a projection of the map into another backend. The
+    /// entries are copied as with [`snapshot`](Self::snapshot), so `f` runs
+    /// without any lock held and may read the source map. The new map first
+    /// loads whatever `dst` already holds, then the projected entries are
+    /// written with one [`insert_batch_ordered`](Self::insert_batch_ordered)
+    /// call, replacing stored values of the same keys. Time-to-live is not
+    /// carried over.
+    ///
+    /// Only cached entries are projected. In a map with a capacity, entries
+    /// that were evicted are left out; to project every stored entry, page
+    /// through the backend with [`iter_backend_paged`](Self::iter_backend_paged)
+    /// and insert each page into the destination map instead.
+    ///
+    /// The cost is one clone of every cached entry, one call to `f` per
+    /// entry, a full load of `dst` and one batched backend write.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example<B2: StorageBackend<String, usize> + Send + Sync + 'static>(
+    /// #     map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>,
+    /// #     lengths_backend: B2,
+    /// # ) -> Result<()> {
+    /// let lengths = map.map_values(String::len, lengths_backend).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if loading from `dst` fails, if a projected value
+    /// cannot be serialized, or if saving to `dst` fails.
+    pub async fn map_values<W, F, B2>(&self, mut f: F, dst: B2) -> Result<PersistentMap<K, W, B2>>
+    where
+        W: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+        F: FnMut(&V) -> W,
+        B2: StorageBackend<K, W> + Send + Sync + 'static,
+    {
+        let entries: Vec<(K, W)> = self
+            .snapshot()
+            .into_inner()
+            .into_iter()
+            .map(|(key, value)| (key, f(&value)))
+            .collect();
+        let projection = PersistentMap::new(dst).await?;
+        if !entries.is_empty() {
+            projection.insert_batch_ordered(entries).await?;
+        }
+        Ok(projection)
+    }
+}
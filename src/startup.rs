@@ -0,0 +1,137 @@
+//! Bounding the load that builds a map, and retrying it in the background.
+
+use crate::eviction::Eviction;
+use crate::normalize::Normalize;
+use crate::wait::KeyWaiters;
+use crate::{migrate, PersistentError, Result, StorageBackend, ValueMigration};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::task::JoinHandle;
+
+/// Delay before the first background retry of a failed startup load.
+const FIRST_RETRY: Duration = Duration::from_millis(500);
+
+/// Longest delay between background retries of a failed startup load.
+const MAX_RETRY: Duration = Duration::from_secs(30);
+
+/// What building a map does when its initial load fails or times out.
+///
+/// Set with
+/// [`PersistentMapBuilder::startup_load_policy`](crate::PersistentMapBuilder::startup_load_policy).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StartupLoadPolicy {
+    /// `build` returns the error. This is the default.
+    #[default]
+    FailFast,
+
+    /// `build` returns an empty map and the load is retried in the
+    /// background until it succeeds.
+    EmptyThenRetry,
+}
+
+/// The in-memory state a background retry loads entries into.
+pub struct Cache<K, V> {
+    pub map: Arc<DashMap<K, V>>,
+    pub eviction: Arc<Eviction<K, V>>,
+    pub waiters: Arc<KeyWaiters<K>>,
+    pub keys: Normalize<K>,
+}
+
+/// Returns the error of a startup load that took longer than `timeout`.
+pub fn timed_out(timeout: Duration) -> PersistentError {
+    PersistentError::Io(std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        format!("startup load timed out after {timeout:?}"),
+    ))
+}
+
+/// Every entry of a backend, with the expiries and, if asked for, the write
+/// times it stores.
+type Fetched<K, V> = (Vec<(K, V)>, HashMap<K, SystemTime>, HashMap<K, SystemTime>);
+
+/// Reads every entry of `backend` with its expiry, and its write time if
+/// `write_times` is set, failing after `timeout` if set.
+async fn fetch<K, V, B>(
+    backend: &B,
+    migration: Option<&ValueMigration>,
+    write_times: bool,
+    timeout: Option<Duration>,
+) -> Result<Fetched<K, V>>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    let fetched = async {
+        let entries = migrate::load_entries(backend, migration).await?;
+        let expiries = backend.load_expiries().await?;
+        let written = if write_times {
+            backend.load_write_times().await?
+        } else {
+            HashMap::new()
+        };
+        Ok((entries, expiries, written))
+    };
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, fetched)
+            .await
+            .unwrap_or_else(|_| Err(timed_out(timeout))),
+        None => fetched.await,
+    }
+}
+
+/// Spawns a task that retries loading `backend` into `cache`, backing off
+/// from [`FIRST_RETRY`] to [`MAX_RETRY`] between attempts, until a load
+/// succeeds.
+///
+/// Keys already in memory were written since the map was built, so the
+/// loaded entries only fill in the keys that are missing.
+pub fn spawn_retry<K, V, B>(
+    backend: Arc<B>,
+    migration: Option<ValueMigration>,
+    timeout: Option<Duration>,
+    cache: Cache<K, V>,
+) -> JoinHandle<()>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        let mut delay = FIRST_RETRY;
+        let write_times = cache.eviction.tracks_writes();
+        let (entries, expiries, written) = loop {
+            tokio::time::sleep(delay).await;
+            match fetch(&*backend, migration.as_ref(), write_times, timeout).await {
+                Ok(loaded) => break loaded,
+                Err(_) => delay = (delay * 2).min(MAX_RETRY),
+            }
+        };
+
+        let (now, system_now) = (Instant::now(), SystemTime::now());
+        for (k, v) in entries {
+            let expires_at = expiries
+                .get(&k)
+                .and_then(|at| now.checked_add(at.duration_since(system_now).unwrap_or_default()));
+            let written_at = written.get(&k).copied();
+            if let Entry::Vacant(entry) = cache.map.entry(cache.keys.owned(k)) {
+                match expires_at {
+                    Some(expires_at) => cache.eviction.record_insert(entry.key(), Some(expires_at)),
+                    None => cache.eviction.touch(entry.key()),
+                }
+                if let Some(written_at) = written_at {
+                    cache.eviction.record_written(entry.key(), written_at);
+                }
+                entry.insert(v);
+            }
+        }
+        cache.waiters.wake_all();
+        cache.eviction.evict_over_capacity(&cache.map);
+    })
+}
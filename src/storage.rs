@@ -0,0 +1,1078 @@
+//! The storage backend trait and the error types it returns.
+//!
+//! This is the surface a backend implements, and it only depends on
+//! `async-trait`, `serde`, `serde_json` and `thiserror`: nothing here uses
+//! tokio or another runtime, so a backend for an embedded target or another
+//! executor can be written against it with the default features turned off.
+//! The optional error variants exist only with the backend features that
+//! produce them.
+
+use crate::changes::{Change, ChangeToken};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    time::SystemTime,
+};
+use thiserror::Error;
+
+/// A trait for implementing storage backends for `PersistentMap`.
+///
+/// This trait defines the interface that all storage backends must implement.
+/// It provides methods for loading, saving, and deleting key-value pairs.
+///
+/// # Type Parameters
+///
+/// * `K`: The key type, which must be hashable, serializable, and cloneable
+/// * `V`: The value type, which must be serializable and cloneable
+///
+/// # Examples
+///
+/// Implementing a custom backend:
+///
+/// The `StorageBackend` trait defines the interface for persistent storage backends.
+/// By implementing this trait, you can create custom storage solutions for the `PersistentMap`.
+///
+/// # Implementing a Custom Backend
+///
+/// To implement a custom backend, you need to:
+///
+/// 1. Create a struct to hold your backend-specific data
+/// 2. Implement the required methods: `load_all`, `save`, and `delete`
+/// 3. Optionally override the `flush` method if your backend buffers writes
+///
+/// # Cancellation
+///
+/// Dropping a backend future, for example when a request handler is
+/// cancelled, stops the operation only at its next `.await`. Backends that
+/// hand work to another thread should notice the drop themselves, so that a
+/// long read does not keep running for nobody. The built-in backends behave
+/// as follows:
+///
+/// - `SqliteBackend` stops full-table reads between rows; other statements
+///   run to completion
+/// - `S3Backend` stops between requests, and dropping a request aborts it
+/// - `CsvBackend` and `InMemoryBackend` work without yielding, so their
+///   operations cannot be cancelled midway
+///
+/// # Example Implementation
+///
+/// Here's an example of a custom backend that stores data in a JSON file:
+///
+/// ```rust
+/// use persistent_map::{StorageBackend, PersistentError, Result};
+/// use std::collections::HashMap;
+/// use std::path::PathBuf;
+/// use std::fs;
+/// use serde::{Serialize, de::DeserializeOwned};
+/// use std::hash::Hash;
+///
+/// struct JsonFileBackend {
+///     path: PathBuf,
+/// }
+///
+/// impl JsonFileBackend {
+///     pub fn new(path: impl Into<PathBuf>) -> Self {
+///         Self { path: path.into() }
+///     }
+///
+///     // Helper method to ensure the file exists
+///     fn ensure_file_exists(&self) -> std::io::Result<()> {
+///         if !self.path.exists() {
+///             // Create parent directories if they don't exist
+///             if let Some(parent) = self.path.parent() {
+///                 if !parent.exists() {
+///                     fs::create_dir_all(parent)?;
+///                 }
+///             }
+///
+///             // Create the file with an empty JSON object
+///             fs::write(&self.path, "{}")?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl<K, V> StorageBackend<K, V> for JsonFileBackend
+/// where
+///     K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + ToString + 'static,
+///     V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+/// {
+///     async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError> {
+///         // Ensure the file exists
+///         self.ensure_file_exists()?;
+///
+///         // If the file is empty or contains just "{}", return an empty HashMap
+///         let content = fs::read_to_string(&self.path)?;
+///         if content.trim() == "{}" {
+///             return Ok(HashMap::new());
+///         }
+///
+///         // Parse the JSON file
+///         let map = serde_json::from_str(&content)
+///             .map_err(|e| PersistentError::Serde(e))?;
+///
+///         Ok(map)
+///     }
+///
+///     async fn save(&self, key: K, value: V) -> Result<(), PersistentError> {
+///         // Ensure the file exists
+///         self.ensure_file_exists()?;
+///
+///         // Load existing data
+///         let mut map: HashMap<K, V> = self.load_all().await?;
+///
+///         // Update the map
+///         map.insert(key, value);
+///
+///         // Write back to the file
+///         let content = serde_json::to_string_pretty(&map)
+///             .map_err(|e| PersistentError::Serde(e))?;
+///
+///         fs::write(&self.path, content)?;
+///
+///         Ok(())
+///     }
+///
+///     async fn delete(&self, key: &K) -> Result<(), PersistentError> {
+///         // Ensure the file exists
+///         self.ensure_file_exists()?;
+///
+///         // Load existing data
+///         let mut map: HashMap<K, V> = self.load_all().await?;
+///
+///         // Remove the key
+///         map.remove(key);
+///
+///         // Write back to the file
+///         let content = serde_json::to_string_pretty(&map)
+///             .map_err(|e| PersistentError::Serde(e))?;
+///
+///         fs::write(&self.path, content)?;
+///
+///         Ok(())
+///     }
+///
+///     async fn flush(&self) -> Result<(), PersistentError> {
+///         // No buffering in this implementation, so nothing to flush
+///         Ok(())
+///     }
+/// }
+/// ```
+///
+/// # Runtime Backend Selection
+///
+/// The trait is dyn-compatible, so backends can be boxed and picked at
+/// runtime. See [`DynBackend`](crate::DynBackend) for an example.
+///
+/// # Best Practices for Custom Backends
+///
+/// 1. **Error Handling**: Convert backend-specific errors to `PersistentError`
+/// 2. **Concurrency**: Ensure your backend is safe for concurrent access
+/// 3. **Performance**: Consider caching or batching operations for better performance
+/// 4. **Resilience**: Handle edge cases like missing files or corrupted data gracefully
+/// 5. **Testing**: Create tests that verify persistence across application restarts
+#[async_trait::async_trait]
+pub trait StorageBackend<K, V>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Load all key-value pairs from the storage backend.
+    ///
+    /// This method is called when initializing a `PersistentMap` to populate
+    /// the in-memory map with existing data.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if loading fails for any reason, such as:
+    /// - The storage location doesn't exist
+    /// - The data is corrupted or in an invalid format
+    /// - There are permission issues
+    ///
+    /// # Implementation Notes
+    ///
+    /// - This method should be idempotent and safe to call multiple times
+    /// - If the storage is empty or doesn't exist yet, return an empty `HashMap`
+    /// - Consider adding error recovery mechanisms for corrupted data
+    async fn load_all(&self) -> Result<HashMap<K, V>, PersistentError>;
+
+    /// Save a key-value pair to the storage backend.
+    ///
+    /// This method is called whenever a key-value pair is inserted into the map.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if saving fails for any reason, such as:
+    /// - The storage location is not writable
+    /// - There are permission issues
+    /// - The backend has reached capacity
+    ///
+    /// # Implementation Notes
+    ///
+    /// - This method should be atomic if possible
+    /// - Consider batching or caching writes for better performance
+    /// - If your backend requires serialization, handle serialization errors appropriately
+    async fn save(&self, key: K, value: V) -> Result<(), PersistentError>;
+
+    /// Save a key-value pair, reporting whether the key was new.
+    ///
+    /// Returns [`SaveOutcome::Inserted`] if no live value was stored for the
+    /// key and [`SaveOutcome::Updated`] if one was overwritten.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the check or the save fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation calls `contains_key` and then `save`,
+    ///   so a concurrent writer can make the report stale
+    /// - Override this method if your backend can save and report in one
+    ///   atomic step
+    async fn save_reporting(&self, key: K, value: V) -> Result<SaveOutcome, PersistentError> {
+        let existed = self.contains_key(&key).await?;
+        self.save(key, value).await?;
+        Ok(if existed {
+            SaveOutcome::Updated
+        } else {
+            SaveOutcome::Inserted
+        })
+    }
+
+    /// Save a key-value pair only if `version` is newer than the version
+    /// stored with the key, and return whether it was saved.
+    ///
+    /// The version is chosen by the caller, for example the version number
+    /// of an external source of truth, and stored with the value. A key
+    /// without a stored version, because it is absent or was last written by
+    /// another method, accepts any version.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the check or the save fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation returns `PersistentError::Unsupported`
+    /// - Backends that implement it must compare and save in one atomic step,
+    ///   so that concurrent writers sharing the backend cannot both win
+    async fn save_if_newer(&self, key: K, value: V, version: u64) -> Result<bool, PersistentError> {
+        let _ = (key, value, version);
+        Err(PersistentError::Unsupported("save_if_newer".to_string()))
+    }
+
+    /// Delete a key-value pair from the storage backend.
+    ///
+    /// This method is called whenever a key-value pair is removed from the map.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if deletion fails for any reason, such as:
+    /// - The storage location is not writable
+    /// - There are permission issues
+    ///
+    /// # Implementation Notes
+    ///
+    /// - This method should be idempotent - deleting a non-existent key should not be an error
+    /// - Consider optimizing for the case where the key doesn't exist
+    async fn delete(&self, key: &K) -> Result<(), PersistentError>;
+
+    /// Save a batch of key-value pairs to the storage backend.
+    ///
+    /// The entries are passed in the exact order the caller supplied them, so a
+    /// key may appear more than once and the last occurrence is the one that
+    /// must win once the batch is applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if saving any of the entries fails. If
+    /// some entries were saved before the failure, it is a
+    /// [`PersistentError::PartialBatch`] holding how many: the entries saved
+    /// are the first ones of the batch.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation calls `save` for each entry in order, and
+    ///   reports a failure after the first entry as a `PartialBatch`
+    /// - Backends that save the batch atomically, or in a single write that
+    ///   cannot tell how far it got, return the underlying error as it is
+    /// - Log-structured backends (where replaying the stored records rebuilds the
+    ///   state) must apply the entries in the given order
+    /// - Backends that store a single row per key may reorder the writes freely,
+    ///   as long as the last value for a duplicated key wins
+    async fn save_many(&self, entries: Vec<(K, V)>) -> Result<(), PersistentError> {
+        for (saved, (key, value)) in entries.into_iter().enumerate() {
+            if let Err(e) = self.save(key, value).await {
+                return Err(match saved {
+                    0 => e,
+                    saved => PersistentError::PartialBatch {
+                        saved,
+                        source: Box::new(e),
+                    },
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a batch of keys from the storage backend.
+    ///
+    /// Keys that don't exist are ignored, like in `delete`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if deleting any of the keys fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation calls `delete` for each key in order
+    /// - Override this method if your backend can delete many keys in one
+    ///   operation or transaction
+    async fn delete_many(&self, keys: Vec<K>) -> Result<(), PersistentError> {
+        for key in &keys {
+            self.delete(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete every key from the storage backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if listing or deleting the keys fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation reads the keys with `load_all_raw` and
+    ///   deletes them with `delete_many`
+    /// - Override this method if your backend can drop all entries at once
+    async fn delete_all(&self) -> Result<(), PersistentError> {
+        let keys = self.load_all_raw().await?.into_keys().collect();
+        self.delete_many(keys).await
+    }
+
+    /// Move the stored value of each `(from, to)` pair from key `from` to
+    /// key `to`.
+    ///
+    /// No `to` key is stored, unless it is also a `from` key of the same
+    /// call, and no key appears twice on either side. The values are moved
+    /// as stored, without decoding them into `V`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading, deleting or saving any entry
+    /// fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation reads the values with `load_many_raw`,
+    ///   deletes the old keys with `delete_many` and saves the values with
+    ///   `save_raw`, so it is not atomic and stored expiry is not moved
+    /// - Override this method if your backend can move rows in one transaction
+    async fn rename_keys(&self, renames: Vec<(K, K)>) -> Result<(), PersistentError> {
+        let (from, to): (Vec<K>, Vec<K>) = renames.into_iter().unzip();
+        let values = self.load_many_raw(&from).await?;
+        self.delete_many(from).await?;
+        for (key, value) in to.into_iter().zip(values) {
+            if let Some(value) = value {
+                self.save_raw(key, value).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a batch of saves and deletes as one unit of work.
+    ///
+    /// `saves` and `deletes` never share a key. This is used to persist the
+    /// write-behind buffer, so backends that support transactions should
+    /// apply the whole batch in a single one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if applying any part of the batch fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation calls `save_many` and then `delete_many`
+    /// - Override this method if your backend can apply both in one transaction
+    async fn write_batch(
+        &self,
+        saves: Vec<(K, V)>,
+        deletes: Vec<K>,
+    ) -> Result<(), PersistentError> {
+        if !saves.is_empty() {
+            self.save_many(saves).await?;
+        }
+        if !deletes.is_empty() {
+            self.delete_many(deletes).await?;
+        }
+        Ok(())
+    }
+
+    /// Load the stored serialized bytes for a single key.
+    ///
+    /// This is used to forward values without deserializing them into `V`.
+    /// The bytes are in the backend's native encoding, which is JSON for all
+    /// built-in backends.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation loads all data and re-serializes the value as JSON
+    /// - Override this method if your backend can return the stored bytes directly
+    async fn load_one_raw(&self, key: &K) -> Result<Option<Vec<u8>>, PersistentError> {
+        let mut all = self.load_all().await?;
+        match all.remove(key) {
+            Some(value) => Ok(Some(serde_json::to_vec(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Load the stored serialized bytes for every key.
+    ///
+    /// This is the bulk counterpart of `load_one_raw` and is used when values
+    /// have to be inspected or transformed before they are decoded into `V`,
+    /// for example by a value migration hook.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation loads all data and re-serializes each value as JSON,
+    ///   so stored values must still decode into `V`
+    /// - Override this method if your backend can return the stored bytes directly
+    async fn load_all_raw(&self) -> Result<HashMap<K, Vec<u8>>, PersistentError> {
+        self.load_all()
+            .await?
+            .into_iter()
+            .map(|(key, value)| Ok((key, serde_json::to_vec(&value)?)))
+            .collect()
+    }
+
+    /// Load the stored serialized bytes for each of `keys`.
+    ///
+    /// This is the batched counterpart of `load_one_raw`: the result holds
+    /// one entry per key, in the order of `keys`, which is `None` for keys
+    /// that are not stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation calls `load_one_raw` for each key
+    /// - Override this method if your backend can read several keys in one round trip
+    async fn load_many_raw(&self, keys: &[K]) -> Result<Vec<Option<Vec<u8>>>, PersistentError> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.load_one_raw(key).await?);
+        }
+        Ok(values)
+    }
+
+    /// Save an already serialized value for a key.
+    ///
+    /// `value` is the value's `V` encoded in the backend's native encoding
+    /// (JSON for all built-in backends), for example bytes obtained from
+    /// `load_one_raw`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the bytes cannot be decoded or saving fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation decodes the bytes as JSON and calls `save`
+    /// - Override this method if your backend can store the bytes directly
+    async fn save_raw(&self, key: K, value: Vec<u8>) -> Result<(), PersistentError> {
+        let value: V = serde_json::from_slice(&value)?;
+        self.save(key, value).await
+    }
+
+    /// Flush any buffered writes to the storage backend.
+    ///
+    /// This method is called when the user explicitly requests to ensure all data is persisted.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if flushing fails for any reason, such as:
+    /// - The storage location is not writable
+    /// - There are permission issues
+    ///
+    /// # Implementation Notes
+    ///
+    /// - This method is optional and has a default implementation that does nothing
+    /// - Backends that buffer writes should override this method to ensure data is persisted
+    /// - This method should be idempotent and safe to call multiple times
+    async fn flush(&self) -> Result<(), PersistentError> {
+        Ok(())
+    }
+
+    /// Make every completed write durable on stable storage.
+    ///
+    /// A map built with [`durable(true)`](PersistentMapBuilder::durable) calls
+    /// this after each write and only returns once it succeeded, so the write
+    /// survives a power failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the data cannot be synced to storage.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation calls `flush`
+    /// - File-based backends should override this to fsync their files
+    async fn sync(&self) -> Result<(), PersistentError> {
+        self.flush().await
+    }
+
+    /// Fold the backend's log of recent writes into its main storage and
+    /// shrink the log.
+    ///
+    /// This bounds the disk space used by backends that append writes to a
+    /// separate log, independently of making writes durable with `sync`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the checkpoint fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation does nothing
+    /// - Backends with a write-ahead log should override this to checkpoint
+    ///   and truncate it
+    async fn checkpoint(&self) -> Result<(), PersistentError> {
+        Ok(())
+    }
+
+    /// Check that the backend's storage is consistent and readable.
+    ///
+    /// A quick check looks for common corruption, and a `thorough` one
+    /// examines all of the storage, which can take as long as reading it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the check finds a problem or cannot
+    /// run.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation does nothing
+    /// - Backends with their own consistency checks, like database engines,
+    ///   should override this to run them
+    async fn check_integrity(&self, thorough: bool) -> Result<(), PersistentError> {
+        let _ = thorough;
+        Ok(())
+    }
+
+    /// Save a key-value pair that expires at `expires_at`.
+    ///
+    /// Saving the key again with plain [`save`](Self::save) clears the
+    /// expiry.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the pair cannot be saved.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation calls `save` and drops the expiry
+    /// - Backends that store expiry should also override `load_expiries`
+    ///   and `delete_expired`
+    async fn save_expiring(
+        &self,
+        key: K,
+        value: V,
+        expires_at: SystemTime,
+    ) -> Result<(), PersistentError> {
+        let _ = expires_at;
+        self.save(key, value).await
+    }
+
+    /// Load the stored expiry time of every key that has one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation returns no expiries, matching the
+    ///   default `save_expiring`
+    async fn load_expiries(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        Ok(HashMap::new())
+    }
+
+    /// Load the time each key was last written, for the keys the backend
+    /// keeps it for.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation returns no write times
+    /// - Backends that record when each row was written should override it
+    async fn load_write_times(&self) -> Result<HashMap<K, SystemTime>, PersistentError> {
+        Ok(HashMap::new())
+    }
+
+    /// Delete every entry whose expiry time is at or before `now`,
+    /// returning how many were deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from or deleting from the
+    /// backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation loads all expiries, filters them and
+    ///   deletes the expired keys with `delete_many`
+    /// - Override this method if your backend can delete by expiry directly
+    async fn delete_expired(&self, now: SystemTime) -> Result<usize, PersistentError> {
+        let expired: Vec<K> = self
+            .load_expiries()
+            .await?
+            .into_iter()
+            .filter(|(_, expires_at)| *expires_at <= now)
+            .map(|(key, _)| key)
+            .collect();
+        let count = expired.len();
+        if count > 0 {
+            self.delete_many(expired).await?;
+        }
+        Ok(count)
+    }
+
+    /// Load all key-value pairs whose key starts with `prefix`.
+    ///
+    /// Keys are matched on their string representation, the same one the
+    /// built-in backends store.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation loads all data and filters it
+    /// - Override this method if your backend can look up a key range directly
+    /// - The order of the returned entries is unspecified unless documented by the backend
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let all = self.load_all().await?;
+        Ok(all
+            .into_iter()
+            .filter(|(k, _)| k.to_string().starts_with(prefix))
+            .collect())
+    }
+
+    /// Load one page of entries, ordered by key.
+    ///
+    /// Keys are ordered by their string representation, the same one the
+    /// built-in backends store. The page skips the first `offset` entries and
+    /// holds at most `limit` entries; a page shorter than `limit` is the last
+    /// one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation loads and sorts all data, then slices it
+    /// - Override this method if your backend can read a page directly
+    async fn load_page(&self, offset: usize, limit: usize) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let mut all: Vec<(String, K, V)> = self
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), k, v))
+            .collect();
+        all.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(all
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, k, v)| (k, v))
+            .collect())
+    }
+
+    /// Load up to `limit` entries whose key comes after `last_key`, ordered by key.
+    ///
+    /// Keys are ordered by their string representation, as in `load_page`.
+    /// Passing the last key of one page as `last_key` returns the next page
+    /// (keyset pagination), and `None` starts at the first key. Unlike an
+    /// offset, the cursor stays correct when keys are inserted or removed
+    /// between calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation loads and sorts all data, then filters it
+    /// - Override this method if your backend can seek to a key directly
+    async fn load_after(
+        &self,
+        last_key: Option<K>,
+        limit: usize,
+    ) -> Result<Vec<(K, V)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let last_key = last_key.map(|k| k.to_string());
+        let mut all: Vec<(String, K, V)> = self
+            .load_all()
+            .await?
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), k, v))
+            .filter(|(k_str, _, _)| last_key.as_ref().map_or(true, |last| k_str > last))
+            .collect();
+        all.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(all
+            .into_iter()
+            .take(limit)
+            .map(|(_, k, v)| (k, v))
+            .collect())
+    }
+
+    /// Load the stored serialized bytes of every entry whose key starts with `prefix`.
+    ///
+    /// This is the raw counterpart of `scan_prefix`, used when stored values
+    /// have to pass through a value migration hook before they are decoded.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation filters `load_all_raw`
+    /// - Override this method together with `scan_prefix`
+    async fn scan_prefix_raw(&self, prefix: &str) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let all = self.load_all_raw().await?;
+        Ok(all
+            .into_iter()
+            .filter(|(k, _)| k.to_string().starts_with(prefix))
+            .collect())
+    }
+
+    /// Load one page of stored serialized bytes, ordered by key.
+    ///
+    /// This is the raw counterpart of `load_page`, with the same ordering and
+    /// paging rules.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation sorts `load_all_raw`, then slices it
+    /// - Override this method together with `load_page`
+    async fn load_page_raw(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let mut all: Vec<(String, K, Vec<u8>)> = self
+            .load_all_raw()
+            .await?
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), k, v))
+            .collect();
+        all.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(all
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, k, v)| (k, v))
+            .collect())
+    }
+
+    /// Load the stored serialized bytes of up to `limit` entries whose key
+    /// comes after `last_key`, ordered by key.
+    ///
+    /// This is the raw counterpart of `load_after`, with the same cursor
+    /// rules.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation sorts and filters `load_all_raw`
+    /// - Override this method together with `load_after`
+    async fn load_after_raw(
+        &self,
+        last_key: Option<K>,
+        limit: usize,
+    ) -> Result<Vec<(K, Vec<u8>)>, PersistentError>
+    where
+        K: ToString,
+    {
+        let last_key = last_key.map(|k| k.to_string());
+        let mut all: Vec<(String, K, Vec<u8>)> = self
+            .load_all_raw()
+            .await?
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), k, v))
+            .filter(|(k_str, _, _)| last_key.as_ref().map_or(true, |last| k_str > last))
+            .collect();
+        all.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(all
+            .into_iter()
+            .take(limit)
+            .map(|(_, k, v)| (k, v))
+            .collect())
+    }
+
+    /// Check if a key exists in the storage backend.
+    ///
+    /// This is an optional method with a default implementation that loads all data
+    /// and checks if the key exists. Backend implementations can override this
+    /// for better performance if they can check for existence without loading all data.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the check fails for any reason.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation is inefficient for large datasets
+    /// - Override this method if your backend can check for existence more efficiently
+    async fn contains_key(&self, key: &K) -> Result<bool, PersistentError> {
+        let all = self.load_all().await?;
+        Ok(all.contains_key(key))
+    }
+
+    /// Get the number of key-value pairs in the storage backend.
+    ///
+    /// This is an optional method with a default implementation that loads all data
+    /// and counts the entries. Backend implementations can override this
+    /// for better performance if they can count entries without loading all data.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the count operation fails for any reason.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation is inefficient for large datasets
+    /// - Override this method if your backend can count entries more efficiently
+    async fn len(&self) -> Result<usize, PersistentError> {
+        let all = self.load_all().await?;
+        Ok(all.len())
+    }
+
+    /// Check if the storage backend is empty.
+    ///
+    /// This is an optional method with a default implementation that uses `len()`.
+    /// Backend implementations can override this for better performance.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the check fails for any reason.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation uses `len()`, which may be inefficient
+    /// - Override this method if your backend can check emptiness more efficiently
+    async fn is_empty(&self) -> Result<bool, PersistentError> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// Get the time of the most recent write to the storage backend.
+    ///
+    /// This is a cheap way for pollers to check whether anything changed
+    /// since they last loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the modification time cannot be read.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation returns `None`, meaning the backend cannot tell
+    /// - File-based backends can return the file's modification time
+    async fn last_modified(&self) -> Result<Option<SystemTime>, PersistentError> {
+        Ok(None)
+    }
+
+    /// Load the changes made to the backend after `since`, by any process.
+    ///
+    /// Returns the changed keys in the order they were last changed, with
+    /// their current values as JSON bytes, together with the token to pass
+    /// to the next call. A key changed several times since the token is
+    /// reported once. With `since` set to `None`, no changes are returned
+    /// and the token marks the current state of the backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PersistentError::Unsupported` if the backend does not track
+    /// changes, or another `PersistentError` if reading from the backend fails.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation returns `PersistentError::Unsupported`
+    /// - Backends shared between processes can override this with a change
+    ///   sequence, such as a table maintained by triggers
+    async fn changes_since(
+        &self,
+        since: Option<ChangeToken>,
+    ) -> Result<(Vec<Change<K>>, ChangeToken), PersistentError> {
+        let _ = since;
+        Err(PersistentError::Unsupported("changes_since".to_string()))
+    }
+
+    /// Report storage-level metrics, such as file sizes or page counts.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PersistentError` if the metrics cannot be read.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation returns no metrics
+    /// - Metric names should be `snake_case` and include their unit where it is
+    ///   not obvious, like `file_size_bytes`
+    async fn stats(&self) -> Result<BackendStats, PersistentError> {
+        Ok(BackendStats::new())
+    }
+
+    /// Report whether the backend refuses every write.
+    ///
+    /// `PersistentMap` checks this before a write touches its in-memory map,
+    /// so a write refused by a read-only backend leaves the map unchanged.
+    ///
+    /// # Implementation Notes
+    ///
+    /// - The default implementation returns `false`
+    /// - Backends that return `true` must still fail their writes with
+    ///   `PersistentError::ReadOnly`, for callers using them directly
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/// Whether a write reported by [`StorageBackend::save_reporting`] created
+/// its key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SaveOutcome {
+    /// The key had no stored value.
+    Inserted,
+
+    /// The key's stored value was overwritten.
+    Updated,
+}
+
+/// Storage-level metrics reported by [`StorageBackend::stats`], by name.
+///
+/// Values are formatted as strings so every backend can report metrics of
+/// its own kind. The map is sorted by name.
+pub type BackendStats = BTreeMap<String, String>;
+
+/// Errors that can occur when using `PersistentMap`.
+///
+/// This enum represents all the possible errors that can occur when using
+/// the various storage backends.
+#[derive(Error, Debug)]
+pub enum PersistentError {
+    /// An error occurred in the `SQLite` backend.
+    #[cfg(feature = "sqlite")]
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] tokio_rusqlite::Error),
+
+    /// An error occurred in the CSV backend.
+    #[cfg(feature = "csv_backend")]
+    #[error("csv error: {0}")]
+    Csv(String),
+
+    /// An I/O error occurred.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A serialization or deserialization error occurred.
+    #[error("serde error: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    /// An error occurred in the Sled backend.
+    #[cfg(feature = "sled_backend")]
+    #[error("sled error: {0}")]
+    Sled(#[from] sled::Error),
+
+    /// A backend configuration is missing or invalid.
+    #[error("config error: {0}")]
+    Config(String),
+
+    /// An error occurred in the Postgres backend.
+    #[cfg(feature = "postgres")]
+    #[error("postgres error: {0}")]
+    Postgres(#[from] sqlx::Error),
+
+    /// An error occurred in the Redis backend.
+    #[cfg(feature = "redis_backend")]
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    /// An error occurred in the S3 backend.
+    #[cfg(feature = "s3")]
+    #[error("s3 error: {0}")]
+    S3(String),
+
+    /// The named write was rejected because the backend was opened
+    /// read-only.
+    #[error("read-only backend: {0} is not allowed")]
+    ReadOnly(String),
+
+    /// The backend does not support the named operation.
+    #[error("unsupported operation: {0}")]
+    Unsupported(String),
+
+    /// A key cannot be written, because it already exists or cannot be
+    /// parsed.
+    #[error("key error: {0}")]
+    Key(String),
+
+    /// An insert of a new key was rejected because the map already holds
+    /// the configured maximum number of entries.
+    #[error("capacity exceeded: the map is limited to {0} entries")]
+    CapacityExceeded(usize),
+
+    /// A batch save failed after saving its first `saved` entries.
+    #[error("batch save failed after {saved} entries: {source}")]
+    PartialBatch {
+        /// How many entries, from the start of the batch, were saved
+        saved: usize,
+
+        /// The error that stopped the batch
+        source: Box<Self>,
+    },
+}
+
+/// Shorthand Result with error defaulting to `PersistentError`.
+pub type Result<T, E = PersistentError> = std::result::Result<T, E>;
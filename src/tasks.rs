@@ -0,0 +1,110 @@
+//! Background tasks owned by a `PersistentMap`.
+//!
+//! Tasks are spawned on the ambient tokio runtime when the map is built and
+//! are aborted when the map is shut down or dropped.
+
+use crate::{write_behind::WriteBuffer, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash, Hasher},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinHandle;
+
+/// Handles of the background tasks spawned for a map.
+#[derive(Default)]
+pub struct BackgroundTasks {
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl BackgroundTasks {
+    pub fn push(&self, handle: JoinHandle<()>) {
+        self.handles
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(handle);
+    }
+
+    /// Aborts every task. Tasks stop at their next await point.
+    pub fn abort_all(&self) {
+        let handles =
+            std::mem::take(&mut *self.handles.lock().unwrap_or_else(PoisonError::into_inner));
+        for handle in handles {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for BackgroundTasks {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
+
+/// Spawns a task that flushes `backend` every `base` plus a random delay of up to `jitter`.
+///
+/// Flush errors are ignored; the flush is retried on the next tick.
+pub fn spawn_auto_flush<K, V, B>(
+    backend: Arc<B>,
+    base: Duration,
+    jitter: Duration,
+) -> JoinHandle<()>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(base + random_up_to(jitter)).await;
+            let _ = backend.flush().await;
+        }
+    })
+}
+
+/// Returns a pseudo-random duration in `0..=max`.
+///
+/// Uses the randomly keyed std hasher so no extra dependency is needed; the
+/// quality is plenty for spreading timers apart.
+fn random_up_to(max: Duration) -> Duration {
+    let max_nanos = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+    if max_nanos == 0 {
+        return Duration::ZERO;
+    }
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    Duration::from_nanos(hasher.finish() % max_nanos.saturating_add(1))
+}
+
+/// Spawns a task that drains `buffer` into `backend` every `interval`, or
+/// as soon as the buffer fills up.
+///
+/// Drain errors are ignored; the failed operations stay queued and are
+/// retried on the next drain.
+pub fn spawn_write_behind<K, V, B>(
+    buffer: Arc<WriteBuffer<K, V>>,
+    backend: Arc<B>,
+    interval: Duration,
+) -> JoinHandle<()>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                () = tokio::time::sleep(interval) => {}
+                () = buffer.full() => {}
+            }
+            let _ = buffer.drain(&*backend).await;
+        }
+    })
+}
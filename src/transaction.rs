@@ -0,0 +1,137 @@
+//! All-or-nothing batches of writes.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, hash::Hash};
+
+/// Records the writes of a [`PersistentMap::transaction`].
+///
+/// Writes are only staged here: nothing reaches the map or the backend
+/// until the closure returns and the transaction is committed. A key
+/// written more than once keeps its last write.
+#[derive(Debug)]
+pub struct TransactionBuilder<K, V> {
+    /// The staged writes in order, `None` for a removal
+    ops: Vec<(K, Option<V>)>,
+}
+
+impl<K, V> TransactionBuilder<K, V> {
+    /// Creates an empty transaction.
+    const fn new() -> Self {
+        Self { ops: Vec::new() }
+    }
+
+    /// Stages an insert of `value` under `key`.
+    pub fn insert(&mut self, key: K, value: V) -> &mut Self {
+        self.ops.push((key, Some(value)));
+        self
+    }
+
+    /// Stages the removal of `key`.
+    pub fn remove(&mut self, key: K) -> &mut Self {
+        self.ops.push((key, None));
+        self
+    }
+
+    /// Returns the number of staged writes, counting repeated keys.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if no writes are staged.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Applies the inserts and removals recorded by `f` as one unit.
+    ///
+    /// `f` stages writes on a [`TransactionBuilder`], and the whole set is
+    /// then handed to the backend in one
+    /// [`write_batch`](StorageBackend::write_batch) call. The in-memory map
+    /// is only updated once that call succeeds, so a failed transaction
+    /// leaves the map unchanged. Like [`insert`](Self::insert), the inserted
+    /// values have no time-to-live.
+    ///
+    /// The built-in `SQLite`, Postgres and Sled backends apply the batch
+    /// atomically, rolling it back on error. Other backends apply it with
+    /// the default `write_batch`, one write after the other, so a failure
+    /// may leave the backend with part of the transaction written even
+    /// though the map is unchanged. In write-behind mode the batch is only
+    /// queued, and is written with the other pending writes.
+    ///
+    /// The keys are not locked while the transaction commits, so it is not
+    /// isolated from concurrent writes to the same keys; the last writer
+    /// wins.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, u64, impl StorageBackend<String, u64> + Send + Sync>) -> Result<()> {
+    /// map.transaction(|tx| {
+    ///     tx.insert("alice".to_string(), 90);
+    ///     tx.insert("bob".to_string(), 110);
+    ///     tx.remove("pending_transfer".to_string());
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if a value cannot be serialized, if the new keys
+    /// would exceed [`max_entries`](crate::PersistentMapBuilder::max_entries),
+    /// or if the backend write fails. The map is unchanged in each case. Room
+    /// for the new keys is held from the check until they are cached, so
+    /// concurrent inserts cannot push the map past the limit meanwhile.
+    pub async fn transaction<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut TransactionBuilder<K, V>),
+    {
+        let mut builder = TransactionBuilder::new();
+        f(&mut builder);
+
+        // Only the last write of each key is applied
+        let mut last = HashMap::with_capacity(builder.len());
+        for (key, value) in builder.ops {
+            last.insert(self.keys.owned(key), value);
+        }
+        let mut saves = Vec::new();
+        let mut deletes = Vec::new();
+        for (key, value) in last {
+            match value {
+                Some(value) => {
+                    Self::check_serializable(&value)?;
+                    saves.push((key, value));
+                }
+                None => deletes.push(key),
+            }
+        }
+        if saves.is_empty() && deletes.is_empty() {
+            return Ok(());
+        }
+
+        // Hold the room of the new keys until they are cached, so concurrent
+        // inserts cannot take it while the batch is written
+        let mut reservation = self.reserve(saves.iter().map(|(key, _)| key))?;
+        self.persist_batch(saves.clone(), deletes.clone()).await?;
+        for key in &deletes {
+            self.remove_cached(key);
+        }
+        for (key, value) in saves {
+            self.insert_reserved(&mut reservation, key, value, None);
+        }
+        self.evict_over_capacity();
+        Ok(())
+    }
+}
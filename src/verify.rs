@@ -0,0 +1,94 @@
+//! Confirming that flushed entries were persisted.
+
+use crate::{PersistentError, PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::hash::Hash;
+
+/// How much [`PersistentMap::flush_and_verify`] checks after flushing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyDepth {
+    /// Read back up to this many cached entries, picked at random, and run
+    /// the backend's quick integrity check.
+    Sample(usize),
+
+    /// Read back every cached entry and run the backend's thorough
+    /// integrity check.
+    Full,
+}
+
+impl Default for VerifyDepth {
+    /// Samples 16 entries.
+    fn default() -> Self {
+        Self::Sample(16)
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Flushes and syncs the backend, then checks that the cached entries
+    /// can be read back from it.
+    ///
+    /// After the pending writes are drained and the backend is synced to
+    /// disk, the entries picked by `depth` are read back with
+    /// `load_many_raw`, bypassing the in-memory map, and compared with their
+    /// cached values. The backend's own integrity check runs last: on
+    /// `SQLite` this is `PRAGMA quick_check`, or `PRAGMA integrity_check`
+    /// for [`VerifyDepth::Full`]. Backends without such a check skip it.
+    ///
+    /// Use it at checkpoints that need more than [`flush`](Self::flush),
+    /// such as taking a backup before an upgrade. Writes made by other tasks
+    /// while it runs can make it report entries that differ, so call it
+    /// while the map is not being written to.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result, VerifyDepth};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// map.flush_and_verify(VerifyDepth::Full).await?;
+    /// // Safe to copy the database file now
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns a `PersistentError::Config` error naming the first entry
+    /// that is missing from the backend or differs from its cached value,
+    /// or the backend's error if flushing, reading or the integrity check
+    /// fails.
+    pub async fn flush_and_verify(&self, depth: VerifyDepth) -> Result<()> {
+        self.drain_pending().await?;
+        self.backend.sync().await?;
+
+        let entries = match depth {
+            VerifyDepth::Sample(n) => self.sample(n),
+            VerifyDepth::Full => self.first_n(usize::MAX),
+        };
+        let keys: Vec<K> = entries.iter().map(|(k, _)| k.clone()).collect();
+        let stored = self.backend.load_many_raw(&keys).await?;
+        for ((key, value), stored) in entries.iter().zip(stored) {
+            let Some(stored) = stored else {
+                return Err(PersistentError::Config(format!(
+                    "flushed entry {} is missing from the backend",
+                    serde_json::to_string(key)?
+                )));
+            };
+            let stored: serde_json::Value = serde_json::from_slice(&stored)?;
+            if stored != serde_json::to_value(value)? {
+                return Err(PersistentError::Config(format!(
+                    "flushed entry {} differs in the backend",
+                    serde_json::to_string(key)?
+                )));
+            }
+        }
+
+        self.backend
+            .check_integrity(matches!(depth, VerifyDepth::Full))
+            .await
+    }
+}
@@ -0,0 +1,160 @@
+//! Content versions for conditional reads.
+//!
+//! A value's version is a hash of its serialized form, so it needs no extra
+//! storage, survives restarts and is the same on every process sharing a
+//! backend. Object keys are sorted before hashing, so equal values have
+//! equal versions even when they contain maps with a random iteration order,
+//! such as `HashMap`.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::hash::Hash;
+
+/// The result of [`PersistentMap::get_if_changed`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IfChanged<V> {
+    /// The value differs from the caller's version.
+    Changed {
+        /// The current value
+        value: V,
+        /// The version of `value`, to pass to the next conditional read
+        version: u64,
+    },
+
+    /// The value still has the caller's version.
+    NotModified,
+
+    /// The key is absent or expired.
+    Missing,
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns the cached value of `key` together with its version.
+    ///
+    /// The version is a 64-bit FNV-1a hash of the value's JSON encoding, with
+    /// object keys sorted. It changes whenever the value changes, barring
+    /// hash collisions, which makes it usable as an HTTP `ETag`. It is
+    /// computed on every call, so reading large values this way costs a
+    /// serialization.
+    ///
+    /// Only the order of object keys is canonicalized. A value whose
+    /// `Serialize` output varies in other ways between calls, for example a
+    /// `HashSet` serialized as a JSON array, can get different versions
+    /// while it is equal.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// if let Some((value, version)) = map.get_versioned(&"page".to_string())? {
+    ///     println!("ETag: \"{version:x}\", body: {value}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized.
+    pub fn get_versioned(&self, key: &K) -> Result<Option<(V, u64)>> {
+        self.read(key, |value| Ok((value.clone(), version_of(value)?)))
+            .transpose()
+    }
+
+    /// Returns the cached value of `key` unless it still has `known_version`.
+    ///
+    /// This gives `If-None-Match` semantics: a client that sends the version
+    /// it got from an earlier read receives [`IfChanged::NotModified`] if the
+    /// value is unchanged, without the value being cloned. See
+    /// [`get_versioned`](Self::get_versioned) for how versions are computed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{IfChanged, PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>, etag: u64) -> Result<()> {
+    /// match map.get_if_changed(&"page".to_string(), etag)? {
+    ///     IfChanged::Changed { value, version } => println!("200, ETag {version:x}: {value}"),
+    ///     IfChanged::NotModified => println!("304"),
+    ///     IfChanged::Missing => println!("404"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized.
+    pub fn get_if_changed(&self, key: &K, known_version: u64) -> Result<IfChanged<V>> {
+        let result = self.read(key, |value| {
+            let version = version_of(value)?;
+            Ok(if version == known_version {
+                IfChanged::NotModified
+            } else {
+                IfChanged::Changed {
+                    value: value.clone(),
+                    version,
+                }
+            })
+        });
+        result.unwrap_or(Ok(IfChanged::Missing))
+    }
+}
+
+/// Hashes the canonical JSON encoding of `value` with 64-bit FNV-1a.
+///
+/// FNV-1a is used instead of `DefaultHasher` because its output is fixed,
+/// so versions stay valid across releases and processes.
+pub fn version_of<V: Serialize>(value: &V) -> Result<u64> {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut bytes = Vec::new();
+    write_canonical(&mut bytes, &serde_json::to_value(value)?)?;
+    Ok(bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    }))
+}
+
+/// Writes `value` as compact JSON with the keys of every object sorted.
+///
+/// The keys are sorted here rather than relying on `serde_json::Map`, whose
+/// order depends on whether `serde_json`'s `preserve_order` feature is enabled
+/// anywhere in the dependency graph.
+fn write_canonical(out: &mut Vec<u8>, value: &Value) -> Result<()> {
+    match value {
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(out, item)?;
+            }
+            out.push(b']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            out.push(b'{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key)?;
+                out.push(b':');
+                write_canonical(out, item)?;
+            }
+            out.push(b'}');
+        }
+        scalar => serde_json::to_writer(&mut *out, scalar)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! Views scoped to a key prefix.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A view of the entries of a map whose key starts with a prefix.
+///
+/// Created with [`PersistentMap::prefix_view`]. The view shares the parent
+/// map's in-memory entries and backend: keys passed to it are relative to
+/// the prefix, which it adds before calling the parent map and strips from
+/// the keys it returns. Keys outside the prefix cannot be reached through
+/// the view, which makes it a cheap way to split one map into sections, for
+/// example one per tenant.
+///
+/// Lookups go through the parent map, so its key normalizer applies to the
+/// full key. Iteration compares the prefix with the stored, normalized keys,
+/// so with a normalizer the prefix should already be in normalized form.
+pub struct PrefixView<'a, V, B>
+where
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<String, V> + Send + Sync + 'static,
+{
+    /// The parent map
+    map: &'a PersistentMap<String, V, B>,
+
+    /// Prefix added to every key
+    prefix: String,
+}
+
+impl<V, B> PrefixView<'_, V, B>
+where
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<String, V> + Send + Sync + 'static,
+{
+    /// Returns the prefix of the view.
+    #[must_use]
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Inserts `key` under the prefix, like [`PersistentMap::insert`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized or if saving to
+    /// the backend fails.
+    pub async fn insert(&self, key: &str, value: V) -> Result<Option<V>> {
+        self.map.insert(self.full_key(key), value).await
+    }
+
+    /// Returns the cached value of `key` under the prefix, like
+    /// [`PersistentMap::get`].
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<V> {
+        self.map.get(&self.full_key(key))
+    }
+
+    /// Returns `true` if `key` under the prefix is cached and unexpired.
+    #[must_use]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(&self.full_key(key))
+    }
+
+    /// Removes `key` under the prefix, like [`PersistentMap::remove`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if deleting from the backend fails.
+    pub async fn remove(&self, key: &str) -> Result<Option<V>> {
+        self.map.remove(&self.full_key(key)).await
+    }
+
+    /// Returns an iterator over clones of the cached, unexpired entries
+    /// under the prefix, with the prefix stripped from their keys, in
+    /// arbitrary order.
+    ///
+    /// Every cached entry of the parent map is visited, so this costs the
+    /// same as iterating the whole map.
+    pub fn iter(&self) -> impl Iterator<Item = (String, V)> + '_ {
+        self.map.map.iter().filter_map(move |entry| {
+            let key = entry.key().strip_prefix(self.prefix.as_str())?;
+            if self.map.eviction.is_expired(entry.key()) {
+                return None;
+            }
+            Some((key.to_string(), entry.value().clone()))
+        })
+    }
+
+    /// Returns the number of cached, unexpired entries under the prefix.
+    ///
+    /// Like [`iter`](Self::iter), this visits every cached entry.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns `true` if no cached, unexpired entry is under the prefix.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Prepends the prefix to `key`.
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+}
+
+impl<V, B> PersistentMap<String, V, B>
+where
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<String, V> + Send + Sync + 'static,
+{
+    /// Returns a view of the entries whose key starts with `prefix`.
+    ///
+    /// The view adds `prefix` to the keys passed to it and strips it from
+    /// the keys it returns, so code handed the view works with short keys
+    /// and cannot touch entries outside its section. See [`PrefixView`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// let acme = map.prefix_view("tenant/acme/");
+    /// acme.insert("plan", "enterprise".to_string()).await?;
+    /// assert_eq!(map.get(&"tenant/acme/plan".to_string()), Some("enterprise".to_string()));
+    /// assert_eq!(acme.get("plan"), Some("enterprise".to_string()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn prefix_view(&self, prefix: &str) -> PrefixView<'_, V, B> {
+        PrefixView {
+            map: self,
+            prefix: prefix.to_string(),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! A purely in-memory map without the serde bounds of `PersistentMap`.
+
+use dashmap::DashMap;
+use std::hash::Hash;
+
+/// A concurrent map that lives only in memory.
+///
+/// `VolatileMap` offers the core of the `PersistentMap` API for the
+/// no-persistence case that [`InMemoryBackend`](crate::in_memory::InMemoryBackend)
+/// also covers, without requiring `Serialize` or `DeserializeOwned` on the
+/// key and value types. This allows storing handles, channels and other
+/// values that cannot be serialized. Nothing is ever written anywhere, so
+/// every method is synchronous and infallible.
+///
+/// # Examples
+///
+/// ```rust
+/// use persistent_map::VolatileMap;
+/// use std::sync::mpsc;
+///
+/// // Senders are not serializable, so they can't go into a `PersistentMap`
+/// let subscribers: VolatileMap<String, mpsc::Sender<String>> = VolatileMap::new();
+/// let (tx, rx) = mpsc::channel();
+/// subscribers.insert("alice".to_string(), tx);
+///
+/// if let Some(tx) = subscribers.get(&"alice".to_string()) {
+///     tx.send("hello".to_string()).unwrap();
+/// }
+/// assert_eq!(rx.recv().unwrap(), "hello");
+/// ```
+#[derive(Debug)]
+pub struct VolatileMap<K, V>
+where
+    K: Eq + Hash,
+{
+    map: DashMap<K, V>,
+}
+
+impl<K, V> Default for VolatileMap<K, V>
+where
+    K: Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> VolatileMap<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Creates an empty map.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            map: DashMap::new(),
+        }
+    }
+
+    /// Inserts a key-value pair, returning the previous value of the key.
+    #[inline]
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, value)
+    }
+
+    /// Returns a clone of the value of `key`.
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.map.get(key).map(|r| r.value().clone())
+    }
+
+    /// Applies `f` to the value of `key` without cloning it.
+    pub fn read<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        self.map.get(key).map(|r| f(r.value()))
+    }
+
+    /// Removes a key, returning its value.
+    #[inline]
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.map.remove(key).map(|(_, v)| v)
+    }
+
+    /// Returns whether the map contains `key`.
+    #[inline]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Returns the number of entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns whether the map is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Removes every entry.
+    #[inline]
+    pub fn clear(&self) {
+        self.map.clear();
+    }
+
+    /// Returns a clone of every key, in unspecified order.
+    #[must_use]
+    pub fn keys(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.map.iter().map(|r| r.key().clone()).collect()
+    }
+}
@@ -0,0 +1,169 @@
+//! Waiting for keys to be inserted.
+//!
+//! A task waiting for a key registers a `Notify` for it, and every in-memory
+//! insert of the key wakes its waiters, which then check the map again.
+//! While nobody waits, inserts only pay for reading an atomic counter.
+
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+/// The tasks waiting for keys to be inserted, by key.
+pub struct KeyWaiters<K> {
+    waiting: Mutex<HashMap<K, Arc<Notify>>>,
+
+    /// Number of registered waits, to skip the table when it is empty
+    count: AtomicUsize,
+}
+
+impl<K> Default for KeyWaiters<K> {
+    fn default() -> Self {
+        Self {
+            waiting: Mutex::new(HashMap::new()),
+            count: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<K> KeyWaiters<K>
+where
+    K: Eq + Hash + Clone,
+{
+    fn waiting(&self) -> MutexGuard<'_, HashMap<K, Arc<Notify>>> {
+        self.waiting.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Registers a wait for `key`, returning the `Notify` to wait on.
+    fn register(&self, key: &K) -> Waiter<'_, K> {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        let notify = Arc::clone(self.waiting().entry(key.clone()).or_default());
+        Waiter {
+            waiters: self,
+            key: key.clone(),
+            notify,
+        }
+    }
+
+    /// Wakes the tasks waiting for `key`.
+    pub fn wake(&self, key: &K) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        if let Some(notify) = self.waiting().get(key) {
+            notify.notify_waiters();
+        }
+    }
+
+    /// Wakes every waiting task, for example after a bulk load.
+    pub fn wake_all(&self) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        for notify in self.waiting().values() {
+            notify.notify_waiters();
+        }
+    }
+}
+
+/// A registered wait, unregistered when dropped.
+struct Waiter<'a, K>
+where
+    K: Eq + Hash + Clone,
+{
+    waiters: &'a KeyWaiters<K>,
+    key: K,
+    notify: Arc<Notify>,
+}
+
+impl<K> Drop for Waiter<'_, K>
+where
+    K: Eq + Hash + Clone,
+{
+    fn drop(&mut self) {
+        let mut waiting = self.waiters.waiting();
+        // The table and this waiter are the only owners: nobody else waits
+        if Arc::strong_count(&self.notify) == 2 {
+            waiting.remove(&self.key);
+        }
+        drop(waiting);
+        self.waiters.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Returns the value of `key`, waiting up to `timeout` for it to be
+    /// inserted if it is absent.
+    ///
+    /// If the key is cached, or stored in the backend after being evicted or
+    /// never loaded, its value is returned right away. Otherwise the call
+    /// waits until an insert into this map adds the key, without polling,
+    /// and returns `None` if `timeout` elapses first. Writes made to the
+    /// backend by other processes are not noticed until they are loaded into
+    /// the map, for example by [`sync_changes`](Self::sync_changes).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// # use std::time::Duration;
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// // Another task inserts "job-7" once the job is done
+    /// match map.wait_for_key("job-7".to_string(), Duration::from_secs(30)).await? {
+    ///     Some(result) => println!("job finished: {result}"),
+    ///     None => println!("timed out"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if reading the key from the backend fails or the
+    /// stored value cannot be decoded.
+    pub async fn wait_for_key(&self, key: K, timeout: Duration) -> Result<Option<V>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let key = self.keys.owned(key);
+        let waiter = self.waiters.register(&key);
+
+        let notified = waiter.notify.notified();
+        tokio::pin!(notified);
+        // Enabled before checking so an insert right after the check wakes us
+        notified.as_mut().enable();
+        if let Some(value) = self.get(&key) {
+            return Ok(Some(value));
+        }
+        if !self.eviction.is_expired(&key) {
+            if let Some(bytes) = self.backend.load_one_raw(&key).await? {
+                return Ok(Some(self.decode_stored(&bytes)?));
+            }
+        }
+
+        loop {
+            if tokio::time::timeout_at(deadline, notified.as_mut())
+                .await
+                .is_err()
+            {
+                return Ok(self.get(&key));
+            }
+            notified.set(waiter.notify.notified());
+            notified.as_mut().enable();
+            if let Some(value) = self.get(&key) {
+                return Ok(Some(value));
+            }
+        }
+    }
+}
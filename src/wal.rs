@@ -0,0 +1,141 @@
+//! Write-ahead log backing the write-behind buffer.
+//!
+//! Each queued write is appended to a local file and synced to disk before
+//! the write returns, so buffered writes survive a crash. The log holds one
+//! JSON record per line, `{"save":[key,value]}` or `{"delete":key}`, in the
+//! order the writes were made. Once the buffer has been drained into the
+//! backend, the applied prefix of the log is discarded.
+
+use crate::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+/// One logged write.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Record<K, V> {
+    Save(K, V),
+    Delete(K),
+}
+
+/// An append-only log file.
+pub struct Wal {
+    path: PathBuf,
+
+    /// Handle opened in append mode
+    file: File,
+
+    /// Length of the log in bytes, always at a record boundary
+    len: u64,
+}
+
+impl Wal {
+    /// Opens or creates the log at `path` and returns the records it holds.
+    ///
+    /// A last line without a trailing newline is the remains of a write that
+    /// was interrupted before it was acknowledged; it is discarded and cut
+    /// off the file. Any complete line that cannot be decoded fails the open,
+    /// since skipping it could reorder or lose acknowledged writes.
+    pub fn open<K, V>(path: PathBuf) -> Result<(Self, Vec<Record<K, V>>)>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut records = Vec::new();
+        let mut complete = 0;
+        for line in bytes.split_inclusive(|&b| b == b'\n') {
+            let Some((b'\n', record)) = line.split_last() else {
+                break;
+            };
+            records.push(serde_json::from_slice(record)?);
+            complete += line.len();
+        }
+        if complete < bytes.len() {
+            file.set_len(complete as u64)?;
+            file.sync_all()?;
+        }
+
+        let wal = Self {
+            path,
+            file,
+            len: complete as u64,
+        };
+        Ok((wal, records))
+    }
+
+    /// Returns the length of the log in bytes.
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Appends `records` and syncs them to disk.
+    pub fn append<K, V>(&mut self, records: &[Record<&K, &V>]) -> Result<()>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        let mut bytes = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut bytes, record)?;
+            bytes.push(b'\n');
+        }
+        if let Err(e) = self
+            .file
+            .write_all(&bytes)
+            .and_then(|()| self.file.sync_data())
+        {
+            // Drop a partial record so the next append starts on a new line
+            let _ = self.file.set_len(self.len);
+            return Err(e.into());
+        }
+        self.len += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Discards the first `applied` bytes of the log, keeping later records.
+    ///
+    /// The remaining records are written to a temporary file that replaces
+    /// the log, so a crash leaves either the old or the new log in place.
+    pub fn discard_prefix(&mut self, applied: u64) -> Result<()> {
+        if applied >= self.len {
+            self.file.set_len(0)?;
+            self.file.sync_all()?;
+            self.len = 0;
+            return Ok(());
+        }
+
+        let mut rest = Vec::new();
+        let mut reader = File::open(&self.path)?;
+        reader.seek(SeekFrom::Start(applied))?;
+        reader.read_to_end(&mut rest)?;
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&rest)?;
+        tmp.sync_all()?;
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.len = rest.len() as u64;
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+//! Write-behind buffering of backend writes.
+//!
+//! In write-behind mode, writes update the in-memory map immediately and the
+//! matching backend operation is queued here. The queue keeps only the last
+//! operation per key and is drained as a single `write_batch` call, either
+//! by the background flusher or by an explicit flush. With a write-ahead log
+//! configured, every queued operation is also logged to disk first.
+//!
+//! Every queued operation gets a sequence number. A successful drain writes
+//! every operation queued before it started, so the buffer only has to
+//! remember the highest sequence number known to be written, and a caller
+//! waiting for one write compares its number against that.
+
+use crate::wal::{Record, Wal};
+use crate::{PersistentMap, Result, StorageBackend};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+use tokio::sync::Notify;
+
+/// A queued backend operation.
+enum Op<V> {
+    Save(V),
+    Delete,
+}
+
+/// The queue and its log, guarded together so that the log always covers
+/// exactly the operations queued since the last drain.
+struct Pending<K, V> {
+    /// The last pending operation of each key
+    ops: HashMap<K, Op<V>>,
+
+    /// Write-ahead log of the queued operations, if configured
+    wal: Option<Wal>,
+
+    /// Sequence number of the last queued operation
+    queued: u64,
+}
+
+/// Pending backend writes, coalesced per key.
+pub struct WriteBuffer<K, V> {
+    pending: Mutex<Pending<K, V>>,
+
+    /// Number of pending keys that triggers an early drain
+    max_pending: usize,
+
+    /// Wakes the background flusher when `max_pending` is reached
+    wake: Notify,
+
+    /// Serializes drains so batches reach the backend in order
+    drain_lock: tokio::sync::Mutex<()>,
+
+    /// Sequence number up to which every operation was written or discarded
+    written: AtomicU64,
+
+    /// Wakes the tasks waiting for a write when `written` advances
+    written_wake: Notify,
+}
+
+impl<K, V> WriteBuffer<K, V>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    pub fn new(max_pending: usize) -> Self {
+        Self::from_pending(
+            Pending {
+                ops: HashMap::new(),
+                wal: None,
+                queued: 0,
+            },
+            max_pending,
+        )
+    }
+
+    /// Creates a buffer that logs its operations to the file at `path`.
+    ///
+    /// Operations left in the log by a previous run are queued again, so the
+    /// next drain applies them to the backend.
+    pub fn with_wal(max_pending: usize, path: PathBuf) -> Result<Self> {
+        let (wal, records) = Wal::open(path)?;
+        let mut ops = HashMap::new();
+        for record in records {
+            match record {
+                Record::Save(key, value) => ops.insert(key, Op::Save(value)),
+                Record::Delete(key) => ops.insert(key, Op::Delete),
+            };
+        }
+        Ok(Self::from_pending(
+            Pending {
+                ops,
+                wal: Some(wal),
+                queued: 0,
+            },
+            max_pending,
+        ))
+    }
+
+    fn from_pending(pending: Pending<K, V>, max_pending: usize) -> Self {
+        Self {
+            pending: Mutex::new(pending),
+            max_pending: max_pending.max(1),
+            wake: Notify::new(),
+            drain_lock: tokio::sync::Mutex::new(()),
+            written: AtomicU64::new(0),
+            written_wake: Notify::new(),
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Pending<K, V>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Logs and queues `ops`, replacing any pending operations on their keys.
+    ///
+    /// Returns the sequence number of the last queued operation.
+    fn enqueue(&self, ops: Vec<(K, Op<V>)>) -> Result<u64> {
+        let mut pending = self.pending();
+        let Pending {
+            ops: queued,
+            wal,
+            queued: seq,
+        } = &mut *pending;
+        if let Some(wal) = wal {
+            let records: Vec<Record<&K, &V>> = ops
+                .iter()
+                .map(|(key, op)| match op {
+                    Op::Save(value) => Record::Save(key, value),
+                    Op::Delete => Record::Delete(key),
+                })
+                .collect();
+            wal.append(&records)?;
+        }
+        queued.extend(ops);
+        *seq += 1;
+        let (len, seq) = (queued.len(), *seq);
+        drop(pending);
+        if len >= self.max_pending {
+            self.wake.notify_one();
+        }
+        Ok(seq)
+    }
+
+    /// Queues a save of `key`, replacing any pending operation on it.
+    ///
+    /// Returns the sequence number to pass to [`written`](Self::written).
+    pub fn save(&self, key: K, value: V) -> Result<u64> {
+        self.enqueue(vec![(key, Op::Save(value))])
+    }
+
+    /// Queues saves of all entries, in order.
+    pub fn save_many(&self, entries: Vec<(K, V)>) -> Result<()> {
+        self.enqueue(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, Op::Save(value)))
+                .collect(),
+        )?;
+        Ok(())
+    }
+
+    /// Queues a delete of `key`, replacing any pending operation on it.
+    pub fn delete(&self, key: K) -> Result<()> {
+        self.enqueue(vec![(key, Op::Delete)])?;
+        Ok(())
+    }
+
+    /// Queues deletes of all keys.
+    pub fn delete_many(&self, keys: Vec<K>) -> Result<()> {
+        self.enqueue(keys.into_iter().map(|key| (key, Op::Delete)).collect())?;
+        Ok(())
+    }
+
+    /// Returns whether the operation numbered `seq` was written or
+    /// discarded.
+    pub fn is_written(&self, seq: u64) -> bool {
+        self.written.load(Ordering::SeqCst) >= seq
+    }
+
+    /// Waits until the operation numbered `seq` was written or discarded.
+    pub async fn written(&self, seq: u64) {
+        loop {
+            let notified = self.written_wake.notified();
+            tokio::pin!(notified);
+            // Enabled before checking so an advance right after the check wakes us
+            notified.as_mut().enable();
+            if self.is_written(seq) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Records that every operation up to `seq` was written or discarded.
+    fn advance_written(&self, seq: u64) {
+        self.written.fetch_max(seq, Ordering::SeqCst);
+        self.written_wake.notify_waiters();
+    }
+
+    /// Returns the number of keys with a pending operation.
+    pub fn len(&self) -> usize {
+        self.pending().ops.len()
+    }
+
+    /// Waits until the buffer holds `max_pending` keys.
+    pub async fn full(&self) {
+        self.wake.notified().await;
+    }
+
+    /// Discards all pending operations and their log records.
+    ///
+    /// Waits for a running drain to finish first. The returned guard keeps
+    /// new drains from starting until it is dropped, so the caller can clear
+    /// the backend without a discarded batch landing afterwards.
+    pub async fn discard(&self) -> Result<tokio::sync::MutexGuard<'_, ()>> {
+        let guard = self.drain_lock.lock().await;
+        let mut pending = self.pending();
+        pending.ops.clear();
+        if let Some(wal) = &mut pending.wal {
+            let logged = wal.len();
+            wal.discard_prefix(logged)?;
+        }
+        let queued = pending.queued;
+        drop(pending);
+        self.advance_written(queued);
+        Ok(guard)
+    }
+
+    /// Takes all pending operations, along with the length of the log that
+    /// covers them and the sequence number of the last one.
+    fn take_batch(&self) -> (HashMap<K, Op<V>>, Option<u64>, u64) {
+        let mut pending = self.pending();
+        let batch = std::mem::take(&mut pending.ops);
+        let logged = pending.wal.as_ref().map(Wal::len);
+        let queued = pending.queued;
+        drop(pending);
+        (batch, logged, queued)
+    }
+
+    /// Writes all pending operations to `backend` in one `write_batch` call.
+    ///
+    /// If the write fails, or the returned future is dropped before it
+    /// completes, the operations are queued again unless a newer operation
+    /// on the same key arrived in the meantime. This makes aborting a
+    /// background drain safe. If the write succeeds, the part of the log
+    /// covering the batch is discarded.
+    pub async fn drain<B>(&self, backend: &B) -> Result<()>
+    where
+        B: StorageBackend<K, V> + Send + Sync + ?Sized,
+    {
+        let _guard = self.drain_lock.lock().await;
+        let (batch, logged, queued) = self.take_batch();
+        if batch.is_empty() {
+            self.advance_written(queued);
+            return Ok(());
+        }
+
+        let mut saves = Vec::new();
+        let mut deletes = Vec::new();
+        for (key, op) in &batch {
+            match op {
+                Op::Save(value) => saves.push((key.clone(), value.clone())),
+                Op::Delete => deletes.push(key.clone()),
+            }
+        }
+        let mut requeue = Requeue {
+            pending: &self.pending,
+            batch: Some(batch),
+        };
+        backend.write_batch(saves, deletes).await?;
+        requeue.batch = None;
+        if let (Some(wal), Some(logged)) = (&mut self.pending().wal, logged) {
+            wal.discard_prefix(logged)?;
+        }
+        self.advance_written(queued);
+        Ok(())
+    }
+}
+
+/// A handle resolving once a write reached the backend, returned by
+/// [`PersistentMap::insert_with_ack`].
+///
+/// The handle only holds a sequence number, so dropping it unused costs
+/// nothing and leaves the write queued as usual.
+#[must_use = "drop the handle, or use `insert`, if the acknowledgment is not needed"]
+pub struct WriteAck<'a, K, V> {
+    /// The buffer holding the write and its sequence number, or `None` if
+    /// the write was already persisted
+    pending: Option<(&'a WriteBuffer<K, V>, u64)>,
+}
+
+impl<K, V> WriteAck<'_, K, V>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Returns whether the write has reached the backend.
+    #[must_use]
+    pub fn is_persisted(&self) -> bool {
+        self.pending
+            .map_or(true, |(buffer, seq)| buffer.is_written(seq))
+    }
+
+    /// Waits until the write has reached the backend.
+    ///
+    /// Resolves once a drain by the background flusher, or an explicit
+    /// flush, wrote the batch holding the write. A write that was replaced
+    /// by a later write to the same key before reaching the backend counts
+    /// as persisted once the later one is, and a write discarded by
+    /// [`clear_all`](PersistentMap::clear_all) counts as persisted when it is
+    /// discarded. Drains that fail leave the write queued, so this keeps
+    /// waiting for the retry.
+    pub async fn persisted(self) {
+        if let Some((buffer, seq)) = self.pending {
+            buffer.written(seq).await;
+        }
+    }
+}
+
+impl<K, V, B> PersistentMap<K, V, B>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    B: StorageBackend<K, V> + Send + Sync + 'static,
+{
+    /// Inserts a key-value pair like [`insert`](Self::insert), also returning
+    /// a handle that resolves once the write reached the backend.
+    ///
+    /// In write-behind mode the write is queued and the handle waits for the
+    /// background flusher to write it, without flushing anything else. In
+    /// write-through mode the write is persisted before this returns, so the
+    /// handle is already resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use persistent_map::{PersistentMap, StorageBackend, Result};
+    /// #
+    /// # async fn example(map: PersistentMap<String, String, impl StorageBackend<String, String> + Send + Sync>) -> Result<()> {
+    /// // Most writes are fire-and-forget
+    /// map.insert("page_views".to_string(), "1042".to_string()).await?;
+    ///
+    /// // This one is acknowledged once it is stored
+    /// let (_, ack) = map.insert_with_ack("order-17".to_string(), "paid".to_string()).await?;
+    /// ack.persisted().await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized, in which case the
+    /// map is left unchanged, or if saving to the backend or logging the
+    /// write fails.
+    pub async fn insert_with_ack(
+        &self,
+        key: K,
+        value: V,
+    ) -> Result<(Option<V>, WriteAck<'_, K, V>)> {
+        let key = self.keys.owned(key);
+        let _guard = self.key_locks.lock(&key).await;
+        Self::check_serializable(&value)?;
+        let old = self.insert_admitted(key.clone(), value.clone(), None)?;
+        let pending = if let Some(buffer) = &self.write_behind {
+            self.note_write(&key, Some(&value));
+            Some((&**buffer, buffer.save(key, value)?))
+        } else {
+            self.persist(key, value).await?;
+            None
+        };
+        self.evict_over_capacity();
+        Ok((old, WriteAck { pending }))
+    }
+}
+
+/// Puts a taken batch back into the queue when dropped, unless the batch
+/// was cleared after a successful write.
+struct Requeue<'a, K, V>
+where
+    K: Eq + Hash,
+{
+    pending: &'a Mutex<Pending<K, V>>,
+    batch: Option<HashMap<K, Op<V>>>,
+}
+
+impl<K, V> Drop for Requeue<'_, K, V>
+where
+    K: Eq + Hash,
+{
+    fn drop(&mut self) {
+        let Some(batch) = self.batch.take() else {
+            return;
+        };
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        for (key, op) in batch {
+            pending.ops.entry(key).or_insert(op);
+        }
+    }
+}
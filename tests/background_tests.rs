@@ -0,0 +1,159 @@
+#[cfg(feature = "runtime")]
+mod auto_flush {
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A backend that counts how often `flush` runs.
+    struct FlushCountingBackend {
+        flushes: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, String> for FlushCountingBackend {
+        async fn load_all(&self) -> Result<HashMap<String, String>, PersistentError> {
+            Ok(HashMap::new())
+        }
+
+        async fn save(&self, _key: String, _value: String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn flush(&self) -> Result<(), PersistentError> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_auto_flush_jittered_runs_until_shutdown() -> Result<()> {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let backend = FlushCountingBackend {
+            flushes: Arc::clone(&flushes),
+        };
+        let map: PersistentMap<String, String, _> = PersistentMap::builder(backend)
+            .auto_flush_jittered(Duration::from_secs(10), Duration::from_secs(5))
+            .build()
+            .await?;
+
+        // Every tick lands between 10s and 15s after the previous one
+        tokio::time::sleep(Duration::from_secs(9)).await;
+        assert_eq!(flushes.load(Ordering::SeqCst), 0);
+        tokio::time::sleep(Duration::from_secs(52)).await;
+        let ticks = flushes.load(Ordering::SeqCst);
+        assert!((4..=6).contains(&ticks), "unexpected flush count {ticks}");
+
+        // Shutdown performs a final flush and stops the task
+        map.shutdown().await?;
+        let after_shutdown = flushes.load(Ordering::SeqCst);
+        assert_eq!(after_shutdown, ticks + 1);
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(flushes.load(Ordering::SeqCst), after_shutdown);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "runtime")]
+mod shared_backend {
+    use persistent_map::{PersistentError, PersistentMap, Result, SharedBackend, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A backend whose `flush` takes 100ms and is counted.
+    struct SlowFlushBackend {
+        flushes: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, String> for SlowFlushBackend {
+        async fn load_all(&self) -> Result<HashMap<String, String>, PersistentError> {
+            Ok(HashMap::new())
+        }
+
+        async fn save(&self, _key: String, _value: String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn flush(&self) -> Result<(), PersistentError> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    async fn maps(
+        backend: &SharedBackend<SlowFlushBackend>,
+    ) -> Result<Vec<Arc<PersistentMap<String, String, SharedBackend<SlowFlushBackend>>>>> {
+        let mut maps = Vec::new();
+        for _ in 0..3 {
+            maps.push(Arc::new(PersistentMap::new(backend.clone()).await?));
+        }
+        Ok(maps)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overlapping_flushes_run_once_more() -> Result<()> {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let backend = SharedBackend::new(SlowFlushBackend {
+            flushes: Arc::clone(&flushes),
+        });
+        let maps = maps(&backend).await?;
+        assert_eq!(backend.handle_count(), 4);
+
+        let first = Arc::clone(&maps[0]);
+        let first = tokio::spawn(async move { first.flush().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // Both arrive during the first flush, so one more flush covers them
+        let rest: Vec<_> = maps[1..]
+            .iter()
+            .map(|map| {
+                let map = Arc::clone(map);
+                tokio::spawn(async move { map.flush().await })
+            })
+            .collect();
+        first.await.unwrap()?;
+        for task in rest {
+            task.await.unwrap()?;
+        }
+        assert_eq!(flushes.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_flush_window_coalesces_sequential_flushes() -> Result<()> {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let backend = SharedBackend::with_flush_window(
+            SlowFlushBackend {
+                flushes: Arc::clone(&flushes),
+            },
+            Duration::from_secs(1),
+        );
+        let maps = maps(&backend).await?;
+
+        for map in &maps {
+            map.flush().await?;
+        }
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        maps[0].flush().await?;
+        assert_eq!(flushes.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+}
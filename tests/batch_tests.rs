@@ -0,0 +1,318 @@
+#[cfg(feature = "csv_backend")]
+mod csv_batch {
+    use persistent_map::{PersistentMap, Result};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_csv_insert_batch_ordered_replays_in_order() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let csv_path = dir.path().join("batch.csv");
+        let csv_path_str = csv_path.to_str().unwrap();
+
+        {
+            let backend = persistent_map::csv::CsvBackend::new(csv_path_str);
+            let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+
+            map.insert_batch_ordered(vec![
+                ("key1".to_string(), "first".to_string()),
+                ("key2".to_string(), "value2".to_string()),
+                ("key1".to_string(), "last".to_string()),
+            ])
+            .await?;
+
+            assert_eq!(map.get(&"key1".to_string()), Some("last".to_string()));
+            assert_eq!(map.len(), 2);
+        }
+
+        // The rows are appended in the caller's order, so replaying the file
+        // must end with the last value written for each key
+        let contents = std::fs::read_to_string(&csv_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines, vec!["key1,first", "key2,value2", "key1,last"]);
+
+        {
+            let backend = persistent_map::csv::CsvBackend::new(csv_path_str);
+            let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+            assert_eq!(map.get(&"key1".to_string()), Some("last".to_string()));
+            assert_eq!(map.get(&"key2".to_string()), Some("value2".to_string()));
+        }
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite_batch {
+    use persistent_map::{PersistentMap, Result};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_sqlite_insert_batch_ordered() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("batch.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+            let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+
+            map.insert_batch_ordered(vec![
+                ("key1".to_string(), "first".to_string()),
+                ("key2".to_string(), "value2".to_string()),
+                ("key1".to_string(), "last".to_string()),
+            ])
+            .await?;
+        }
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+            let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+            assert_eq!(map.get(&"key1".to_string()), Some("last".to_string()));
+            assert_eq!(map.get(&"key2".to_string()), Some("value2".to_string()));
+            assert_eq!(map.len(), 2);
+        }
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_transaction() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("transaction.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+            let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+            map.insert("alice".to_string(), 100).await?;
+            map.insert("pending".to_string(), 10).await?;
+
+            map.transaction(|tx| {
+                tx.insert("alice".to_string(), 90)
+                    .insert("bob".to_string(), 5)
+                    .insert("bob".to_string(), 10)
+                    .remove("pending".to_string());
+                assert_eq!(tx.len(), 4);
+            })
+            .await?;
+            assert_eq!(map.get(&"alice".to_string()), Some(90));
+            assert_eq!(map.get(&"bob".to_string()), Some(10));
+            assert_eq!(map.get(&"pending".to_string()), None);
+
+            // An empty transaction writes nothing
+            map.transaction(|tx| assert!(tx.is_empty())).await?;
+        }
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+            let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+            assert_eq!(map.len(), 2);
+            assert_eq!(map.get(&"alice".to_string()), Some(90));
+            assert_eq!(map.get(&"bob".to_string()), Some(10));
+        }
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_remove_many() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("remove_many.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+            let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+            map.insert_many((0..5).map(|i| (format!("key{i}"), i)))
+                .await?;
+
+            let removed = map
+                .remove_many(&[
+                    "key3".to_string(),
+                    "missing".to_string(),
+                    "key0".to_string(),
+                    "key3".to_string(),
+                ])
+                .await?;
+            assert_eq!(removed, vec![Some(3), None, Some(0), None]);
+            assert_eq!(map.len(), 3);
+        }
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+            let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+            assert_eq!(map.len(), 3);
+            assert_eq!(map.get(&"key0".to_string()), None);
+            assert_eq!(map.get(&"key1".to_string()), Some(1));
+        }
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
+
+mod partial_batch {
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// A backend whose saves fail once it holds `limit` entries.
+    struct Limited {
+        limit: usize,
+        saved: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, u32> for Limited {
+        async fn load_all(&self) -> Result<HashMap<String, u32>, PersistentError> {
+            Ok(HashMap::new())
+        }
+
+        async fn save(&self, key: String, _value: u32) -> Result<(), PersistentError> {
+            let mut saved = self.saved.lock().unwrap();
+            let full = saved.len() == self.limit;
+            if !full {
+                saved.push(key);
+            }
+            drop(saved);
+            if full {
+                return Err(PersistentError::Config("backend full".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+    }
+
+    /// A backend whose deletes always fail.
+    struct Undeletable;
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, u32> for Undeletable {
+        async fn load_all(&self) -> Result<HashMap<String, u32>, PersistentError> {
+            Ok(HashMap::new())
+        }
+
+        async fn save(&self, _key: String, _value: u32) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &String) -> Result<(), PersistentError> {
+            Err(PersistentError::Config("deletes disabled".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_many_reports_saved_count() -> Result<()> {
+        let backend = Limited {
+            limit: 2,
+            saved: Mutex::new(Vec::new()),
+        };
+        let map = PersistentMap::new(backend).await?;
+
+        let result = map
+            .insert_many(["a", "b", "c", "d"].map(|k| (k.to_string(), 1)))
+            .await;
+        let Err(PersistentError::PartialBatch { saved, source }) = result else {
+            panic!("expected a partial batch error, got {result:?}");
+        };
+        assert_eq!(saved, 2);
+        assert!(matches!(*source, PersistentError::Config(_)));
+        assert_eq!(*map.backend().saved.lock().unwrap(), ["a", "b"]);
+
+        // A batch failing on its first entry reports the error as it is
+        assert!(matches!(
+            map.insert_many([("e".to_string(), 1)]).await,
+            Err(PersistentError::Config(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_transaction_leaves_the_map_unchanged() -> Result<()> {
+        let backend = Limited {
+            limit: 1,
+            saved: Mutex::new(Vec::new()),
+        };
+        let map = PersistentMap::new(backend).await?;
+        map.insert("a".to_string(), 1).await?;
+
+        let result = map
+            .transaction(|tx| {
+                tx.insert("b".to_string(), 2).remove("a".to_string());
+            })
+            .await;
+        assert!(matches!(result, Err(PersistentError::Config(_))));
+        assert_eq!(map.get(&"a".to_string()), Some(1));
+        assert_eq!(map.get(&"b".to_string()), None);
+        assert_eq!(map.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_remove_many_leaves_the_map_unchanged() -> Result<()> {
+        let map = PersistentMap::new(Undeletable).await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+
+        let result = map
+            .remove_many(&["b".to_string(), "a".to_string(), "missing".to_string()])
+            .await;
+        assert!(matches!(result, Err(PersistentError::Config(_))));
+        assert_eq!(map.get(&"a".to_string()), Some(1));
+        assert_eq!(map.get(&"b".to_string()), Some(2));
+        assert_eq!(map.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_transactions_respect_max_entries() -> Result<()> {
+        let backend = Limited {
+            limit: usize::MAX,
+            saved: Mutex::new(Vec::new()),
+        };
+        let map = std::sync::Arc::new(
+            PersistentMap::builder(backend)
+                .max_entries(3)
+                .build()
+                .await?,
+        );
+
+        let tasks: Vec<_> = (0..10)
+            .map(|i| {
+                let map = std::sync::Arc::clone(&map);
+                tokio::spawn(async move {
+                    map.transaction(|tx| {
+                        tx.insert(format!("k{i}"), i);
+                    })
+                    .await
+                })
+            })
+            .collect();
+        let mut committed = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(()) => committed += 1,
+                Err(PersistentError::CapacityExceeded(3)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        assert_eq!(committed, 3);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.backend().saved.lock().unwrap().len(), 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+#[cfg(all(feature = "compression", feature = "sqlite"))]
+mod tests {
+    use persistent_map::compressed::CompressedBackend;
+    use persistent_map::serialized::SerializedBackend;
+    use persistent_map::sqlite::SqliteBackend;
+    use persistent_map::{
+        CompressingSerializer, Compression, JsonSerializer, PersistentMap, Result, StorageBackend,
+    };
+    use tempfile::tempdir;
+
+    /// A string whose JSON encoding is exactly `len` bytes long.
+    fn json_of_len(len: usize) -> String {
+        "a".repeat(len - 2)
+    }
+
+    #[tokio::test]
+    async fn test_compression_threshold_boundary() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("compressed.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = CompressedBackend::new(SqliteBackend::new_binary(db_path_str).await?, 64);
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        map.insert("below".to_string(), json_of_len(63)).await?;
+        map.insert("at".to_string(), json_of_len(64)).await?;
+        map.insert("above".to_string(), json_of_len(1_000)).await?;
+        map.flush().await?;
+
+        // The header byte records which values were compressed
+        let stored: std::collections::HashMap<String, Vec<u8>> =
+            map.backend().inner().load_all().await?;
+        assert_eq!(stored["below"][0], 0);
+        assert_eq!(stored["below"].len(), 64);
+        assert_eq!(stored["at"][0], 1);
+        assert_eq!(stored["above"][0], 1);
+        assert!(stored["above"].len() < 100);
+
+        // Both forms decode when the map is loaded again
+        let backend = CompressedBackend::new(SqliteBackend::new_binary(db_path_str).await?, 64);
+        let reloaded: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        assert_eq!(reloaded.get(&"below".to_string()), Some(json_of_len(63)));
+        assert_eq!(reloaded.get(&"at".to_string()), Some(json_of_len(64)));
+        assert_eq!(reloaded.get(&"above".to_string()), Some(json_of_len(1_000)));
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compression_raw_values() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("compressed_raw.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = CompressedBackend::new(SqliteBackend::new_binary(db_path_str).await?, 16);
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        map.insert("small".to_string(), "x".to_string()).await?;
+        map.insert("large".to_string(), json_of_len(200)).await?;
+
+        // Raw reads see the JSON encoding, not the stored form
+        assert_eq!(
+            map.get_raw(&"small".to_string()).await?,
+            Some(b"\"x\"".to_vec())
+        );
+        assert_eq!(
+            map.get_raw(&"large".to_string()).await?,
+            Some(serde_json::to_vec(&json_of_len(200))?)
+        );
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    /// Inserts a repetitive document with `compression` and checks that it
+    /// is stored smaller than its JSON and reloads intact.
+    async fn check_compressing_serializer(compression: Compression) -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("compressing.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let doc = serde_json::json!({
+            "items": vec![serde_json::json!({"name": "widget", "tags": ["a", "b", "c"]}); 200],
+        });
+        let json = serde_json::to_vec(&doc)?;
+
+        let backend = SerializedBackend::new(
+            SqliteBackend::new_binary(db_path_str).await?,
+            CompressingSerializer::new(JsonSerializer, compression),
+        );
+        let map: PersistentMap<String, serde_json::Value, _> = PersistentMap::new(backend).await?;
+        map.insert("doc".to_string(), doc.clone()).await?;
+
+        let stored = StorageBackend::<String, Vec<u8>>::load_all(map.backend().inner()).await?;
+        assert!(stored["doc"].len() * 10 < json.len());
+        assert_eq!(map.get_raw(&"doc".to_string()).await?, Some(json));
+        drop(map);
+
+        // A value stored before compression was turned on still loads
+        let plain = CompressingSerializer::new(JsonSerializer, Compression::None);
+        let backend = SerializedBackend::new(SqliteBackend::new_binary(db_path_str).await?, plain);
+        StorageBackend::<String, u32>::save(&backend, "plain".to_string(), 7).await?;
+
+        let backend = SerializedBackend::new(
+            SqliteBackend::new_binary(db_path_str).await?,
+            CompressingSerializer::new(JsonSerializer, compression),
+        );
+        let reloaded: PersistentMap<String, serde_json::Value, _> =
+            PersistentMap::new(backend).await?;
+        assert_eq!(reloaded.get(&"doc".to_string()), Some(doc));
+        assert_eq!(
+            reloaded.get(&"plain".to_string()),
+            Some(serde_json::json!(7))
+        );
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compressing_serializer_gzip() -> Result<()> {
+        check_compressing_serializer(Compression::Gzip).await
+    }
+
+    #[cfg(feature = "zstd_compression")]
+    #[tokio::test]
+    async fn test_compressing_serializer_zstd() -> Result<()> {
+        check_compressing_serializer(Compression::Zstd).await
+    }
+}
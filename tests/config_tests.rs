@@ -0,0 +1,33 @@
+#[cfg(all(feature = "sqlite", feature = "in_memory"))]
+mod config {
+    use persistent_map::{build_backend, BackendConfig, PersistentMap, Result};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_build_backend_from_config() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("config.db");
+
+        let config: BackendConfig = serde_json::from_value(serde_json::json!({
+            "type": "sqlite",
+            "path": db_path.to_str().unwrap(),
+        }))?;
+        let map: PersistentMap<String, u32, _> =
+            PersistentMap::new(build_backend(&config).await?).await?;
+        map.insert("a".to_string(), 1).await?;
+        drop(map);
+
+        let map: PersistentMap<String, u32, _> =
+            PersistentMap::new(build_backend(&config).await?).await?;
+        assert_eq!(map.get(&"a".to_string()), Some(1));
+
+        let memory: PersistentMap<String, u32, _> =
+            PersistentMap::new(build_backend(&BackendConfig::InMemory).await?).await?;
+        assert!(memory.is_empty());
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
@@ -1,8 +1,31 @@
 #[cfg(feature = "csv_backend")]
 mod tests {
-    use persistent_map::{PersistentMap, Result};
+    use persistent_map::{csv::CsvBackend, PersistentMap, Result};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
     use tempfile::tempdir;
 
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: u32,
+        email: Option<String>,
+        home: Point,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Tagged {
+        name: String,
+        #[serde(flatten)]
+        extra: HashMap<String, u32>,
+    }
+
     #[tokio::test]
     async fn test_csv_backend() -> Result<()> {
         // Create a temporary directory for the test
@@ -51,10 +74,203 @@ mod tests {
         // Test flush
         map.flush().await?;
 
+        // Test backend stats
+        let stats = map.backend_stats().await?;
+        assert!(stats["file_size_bytes"].parse::<u64>().unwrap() > 0);
+
         // Clean up
         drop(map);
         dir.close().unwrap();
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_struct_values_are_written_as_columns() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("users.csv");
+        let alice = User {
+            name: "Alice, A.".to_string(),
+            age: 30,
+            email: None,
+            home: Point { x: 1, y: -2 },
+        };
+
+        let map = PersistentMap::new(CsvBackend::new(&path)).await?;
+        map.insert("alice".to_string(), alice.clone()).await?;
+        drop(map);
+
+        let contents = std::fs::read_to_string(&path)?;
+        assert_eq!(contents, "alice,\"Alice, A.\",30,,1,-2\n");
+
+        let map: PersistentMap<String, User, _> =
+            PersistentMap::new(CsvBackend::new(&path)).await?;
+        assert_eq!(map.get(&"alice".to_string()), Some(alice));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unflattenable_values_fall_back_to_a_json_column() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tagged.csv");
+        let tagged = Tagged {
+            name: "a".to_string(),
+            extra: HashMap::from([("score".to_string(), 7)]),
+        };
+
+        let map = PersistentMap::new(CsvBackend::new(&path)).await?;
+        map.insert("k1".to_string(), tagged.clone()).await?;
+        drop(map);
+
+        let contents = std::fs::read_to_string(&path)?;
+        assert_eq!(
+            contents,
+            "k1,\"{\"\"name\"\":\"\"a\"\",\"\"score\"\":7}\"\n"
+        );
+
+        let map: PersistentMap<String, Tagged, _> =
+            PersistentMap::new(CsvBackend::new(&path)).await?;
+        assert_eq!(map.get(&"k1".to_string()), Some(tagged));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_empty_sequences_fall_back_to_a_json_column() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lists.csv");
+
+        let map = PersistentMap::new(CsvBackend::new(&path)).await?;
+        map.insert("empty".to_string(), Vec::<String>::new())
+            .await?;
+        map.insert("one".to_string(), vec!["a".to_string()]).await?;
+        drop(map);
+
+        let contents = std::fs::read_to_string(&path)?;
+        assert_eq!(contents, "empty,[]\none,a\n");
+
+        let map: PersistentMap<String, Vec<String>, _> =
+            PersistentMap::new(CsvBackend::new(&path)).await?;
+        assert_eq!(map.get(&"empty".to_string()), Some(Vec::new()));
+        assert_eq!(map.get(&"one".to_string()), Some(vec!["a".to_string()]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compact_keeps_the_latest_row_of_each_key() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("counters.csv");
+
+        let map = PersistentMap::new(CsvBackend::new(&path)).await?;
+        for i in 0..3 {
+            map.insert("a".to_string(), i).await?;
+        }
+        map.insert("b, quoted".to_string(), 10).await?;
+        map.insert("a".to_string(), 3).await?;
+        assert_eq!(std::fs::read_to_string(&path)?.lines().count(), 5);
+
+        map.backend().compact().await?;
+        assert_eq!(std::fs::read_to_string(&path)?, "\"b, quoted\",10\na,3\n");
+        drop(map);
+
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(CsvBackend::new(&path)).await?;
+        assert_eq!(map.get(&"a".to_string()), Some(3));
+        assert_eq!(map.get(&"b, quoted".to_string()), Some(10));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compaction_threshold() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("threshold.csv");
+
+        let backend = CsvBackend::new(&path).with_compaction_threshold(64);
+        let map = PersistentMap::new(backend).await?;
+        for i in 0..100_u32 {
+            map.insert(format!("key{}", i % 4), i).await?;
+        }
+
+        // Each row is at most 10 bytes, so the file stays near the threshold
+        assert!(std::fs::metadata(&path)?.len() <= 64 + 10);
+        drop(map);
+
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(CsvBackend::new(&path)).await?;
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(&"key3".to_string()), Some(99));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_compaction_keeps_concurrent_writes() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("concurrent.csv");
+
+        let backend = CsvBackend::new(&path).with_compaction_threshold(256);
+        let map = std::sync::Arc::new(PersistentMap::new(backend).await?);
+        let tasks: Vec<_> = (0..8)
+            .map(|task| {
+                let map = std::sync::Arc::clone(&map);
+                tokio::spawn(async move {
+                    for i in 0..50_u32 {
+                        map.insert(format!("task{task}"), i).await?;
+                        map.insert(format!("task{task}-{i}"), i).await?;
+                    }
+                    Ok::<_, persistent_map::PersistentError>(())
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap()?;
+        }
+        drop(map);
+
+        // No row appended while another task compacted was lost
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(CsvBackend::new(&path)).await?;
+        assert_eq!(map.len(), 8 * 51);
+        for task in 0..8 {
+            assert_eq!(map.get(&format!("task{task}")), Some(49));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "csv_backend", feature = "in_memory"))]
+mod csv_interchange {
+    use persistent_map::{csv::CsvBackend, in_memory::InMemoryBackend, PersistentMap, Result};
+    use serde::{Deserialize, Serialize};
+    use tempfile::tempdir;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_csv() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("points.csv");
+
+        let source: PersistentMap<String, Point, _> =
+            PersistentMap::new(InMemoryBackend::new()).await?;
+        source.insert("b".to_string(), Point { x: 3, y: 4 }).await?;
+        source.insert("a".to_string(), Point { x: 1, y: 2 }).await?;
+        assert_eq!(source.export_csv(&path)?, 2);
+        assert_eq!(std::fs::read_to_string(&path)?, "a,1,2\nb,3,4\n");
+
+        // The export is a valid CSV backend file
+        let csv_map: PersistentMap<String, Point, _> =
+            PersistentMap::new(CsvBackend::new(&path)).await?;
+        assert_eq!(csv_map.get(&"b".to_string()), Some(Point { x: 3, y: 4 }));
+
+        std::fs::write(&path, "a,1,2\nc,5,6\na,7,8\n")?;
+        let target: PersistentMap<String, Point, _> =
+            PersistentMap::new(InMemoryBackend::new()).await?;
+        assert_eq!(target.import_csv(&path).await?, 3);
+        assert_eq!(target.len(), 2);
+        assert_eq!(target.get(&"a".to_string()), Some(Point { x: 7, y: 8 }));
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
 }
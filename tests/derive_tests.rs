@@ -0,0 +1,61 @@
+#[cfg(all(feature = "derive", feature = "sqlite"))]
+mod tests {
+    use persistent_map::{
+        sqlite::SqliteBackend, PersistentKey, PersistentMap, PersistentValue, Result,
+    };
+    use serde::{Deserialize, Serialize};
+    use tempfile::tempdir;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, PersistentKey)]
+    struct UserId {
+        tenant: u32,
+        id: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, PersistentValue)]
+    struct Profile {
+        name: String,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, PersistentValue)]
+    struct Versioned<T> {
+        version: u32,
+        value: T,
+    }
+
+    #[test]
+    fn test_derived_key_round_trips_through_json() {
+        let key = UserId { tenant: 1, id: 7 };
+        assert_eq!(key.to_string(), r#"{"tenant":1,"id":7}"#);
+        assert_eq!(key.to_string().parse::<UserId>().unwrap(), key);
+        assert!("not json".parse::<UserId>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_derived_types_in_sqlite_map() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("derive.db");
+        let db_path_str = db_path.to_str().unwrap();
+        let key = UserId { tenant: 1, id: 7 };
+        let profile = Versioned {
+            version: 2,
+            value: Profile {
+                name: "Ada".to_string(),
+            },
+        };
+
+        let map: PersistentMap<UserId, Versioned<Profile>, _> =
+            PersistentMap::new(SqliteBackend::new(db_path_str).await?).await?;
+        map.insert(key.clone(), profile.clone()).await?;
+        drop(map);
+
+        let map: PersistentMap<UserId, Versioned<Profile>, _> =
+            PersistentMap::new(SqliteBackend::new(db_path_str).await?).await?;
+        assert_eq!(map.get(&key), Some(profile));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
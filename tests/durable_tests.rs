@@ -0,0 +1,63 @@
+mod durable {
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A backend that stores nothing and counts its sync calls.
+    #[derive(Default)]
+    struct SyncCounting {
+        syncs: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, u32> for SyncCounting {
+        async fn load_all(&self) -> Result<HashMap<String, u32>, PersistentError> {
+            Ok(HashMap::new())
+        }
+
+        async fn save(&self, _key: String, _value: u32) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn sync(&self) -> Result<(), PersistentError> {
+            self.syncs.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_durable_syncs_every_write() -> Result<()> {
+        let backend = SyncCounting::default();
+        let syncs = Arc::clone(&backend.syncs);
+        let map = PersistentMap::builder(backend)
+            .durable(true)
+            .build()
+            .await?;
+
+        map.insert("a".to_string(), 1).await?;
+        map.insert_batch_ordered(vec![("b".to_string(), 2), ("c".to_string(), 3)])
+            .await?;
+        map.remove(&"a".to_string()).await?;
+        assert_eq!(syncs.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_not_durable_by_default() -> Result<()> {
+        let backend = SyncCounting::default();
+        let syncs = Arc::clone(&backend.syncs);
+        let map = PersistentMap::new(backend).await?;
+
+        map.insert("a".to_string(), 1).await?;
+        map.remove(&"a".to_string()).await?;
+        assert_eq!(syncs.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(feature = "in_memory")]
+mod in_memory_dyn {
+    use persistent_map::{
+        in_memory::InMemoryBackend, DynBackend, PersistentMap, Result, StorageBackend,
+    };
+
+    #[tokio::test]
+    async fn test_boxed_backend() -> Result<()> {
+        let boxed: Box<dyn StorageBackend<String, String>> = Box::new(InMemoryBackend::new());
+        drop(boxed);
+
+        let backend: DynBackend<String, String> = Box::new(InMemoryBackend::new());
+        let map = PersistentMap::new(backend).await?;
+
+        map.insert("key1".to_string(), "value1".to_string()).await?;
+        assert_eq!(map.get(&"key1".to_string()), Some("value1".to_string()));
+        assert!(map.backend().is_empty().await?);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite_dyn {
+    use persistent_map::{sqlite::SqliteBackend, DynBackend, PersistentMap, Result};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_boxed_sqlite_backend() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("dyn.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        {
+            let backend: DynBackend<String, String> =
+                Box::new(SqliteBackend::new(db_path_str).await?);
+            let map = PersistentMap::new(backend).await?;
+            map.insert("key1".to_string(), "value1".to_string()).await?;
+        }
+
+        {
+            let backend: DynBackend<String, String> =
+                Box::new(SqliteBackend::new(db_path_str).await?);
+            let map = PersistentMap::new(backend).await?;
+            assert_eq!(map.get(&"key1".to_string()), Some("value1".to_string()));
+            assert_eq!(
+                map.scan_prefix("key").await?,
+                vec![("key1".to_string(), "value1".to_string())]
+            );
+        }
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_arc_backend() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("arc.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = Arc::new(SqliteBackend::new(db_path_str).await?);
+        let writer = PersistentMap::new(Arc::clone(&backend)).await?;
+        let reader: PersistentMap<String, u32, _> =
+            PersistentMap::new(Arc::clone(&backend)).await?;
+        assert_eq!(Arc::strong_count(&backend), 3);
+
+        writer.insert("shared".to_string(), 7_u32).await?;
+        reader.load().await?;
+        assert_eq!(reader.get(&"shared".to_string()), Some(7));
+
+        drop(writer);
+        drop(reader);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
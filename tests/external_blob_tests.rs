@@ -0,0 +1,72 @@
+#[cfg(all(feature = "external_blobs", feature = "sqlite"))]
+mod tests {
+    use persistent_map::external::{ExternalBlobBackend, StoredValue};
+    use persistent_map::sqlite::SqliteBackend;
+    use persistent_map::{PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_external_blobs_round_trip() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("external.db");
+        let db_path_str = db_path.to_str().unwrap();
+        let blob_dir = dir.path().join("blobs");
+        let large = "a".repeat(1_000);
+
+        let backend =
+            ExternalBlobBackend::new(SqliteBackend::new(db_path_str).await?, &blob_dir, 64)?;
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        map.insert("small".to_string(), "x".to_string()).await?;
+        map.insert("large".to_string(), large.clone()).await?;
+        map.insert("copy".to_string(), large.clone()).await?;
+        map.flush().await?;
+
+        // Small values stay inline and equal large values share one file
+        let stored: HashMap<String, StoredValue> = map.backend().inner().load_all().await?;
+        assert_eq!(stored["small"], StoredValue::Inline("x".into()));
+        let StoredValue::Blob(hash) = &stored["large"] else {
+            panic!("large value stored inline");
+        };
+        assert_eq!(stored["copy"], stored["large"]);
+        assert_eq!(
+            std::fs::read(blob_dir.join(hash))?,
+            serde_json::to_vec(&large)?
+        );
+        assert_eq!(std::fs::read_dir(&blob_dir)?.count(), 1);
+        assert_eq!(
+            map.get_raw(&"large".to_string()).await?,
+            Some(serde_json::to_vec(&large)?)
+        );
+
+        // Blobs are read back when the map is loaded again
+        let backend =
+            ExternalBlobBackend::new(SqliteBackend::new(db_path_str).await?, &blob_dir, 64)?;
+        let reloaded: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        assert_eq!(reloaded.get(&"small".to_string()), Some("x".to_string()));
+        assert_eq!(reloaded.get(&"large".to_string()), Some(large.clone()));
+
+        // The file is only removed once no key refers to it
+        reloaded.remove(&"large".to_string()).await?;
+        assert_eq!(
+            reloaded
+                .backend()
+                .remove_unreferenced_blobs::<String>()
+                .await?,
+            0
+        );
+        reloaded.insert("copy".to_string(), "y".to_string()).await?;
+        assert_eq!(
+            reloaded
+                .backend()
+                .remove_unreferenced_blobs::<String>()
+                .await?,
+            1
+        );
+        assert!(!blob_dir.join(hash).exists());
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
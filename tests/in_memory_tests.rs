@@ -1,6 +1,6 @@
 #[cfg(feature = "in_memory")]
 mod tests {
-    use persistent_map::{PersistentMap, Result};
+    use persistent_map::{PersistentError, PersistentMap, Result};
 
     #[tokio::test]
     async fn test_in_memory_backend() -> Result<()> {
@@ -53,4 +53,1007 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_shrink_to_fit() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<u32, u32, _> = PersistentMap::new(backend).await?;
+        for i in 0..10_000 {
+            map.insert(i, i).await?;
+        }
+        let grown = map.capacity();
+        assert!(grown >= 10_000);
+
+        for i in 10..10_000 {
+            map.remove(&i).await?;
+        }
+        map.shrink_to_fit();
+        assert!(map.capacity() < grown / 10);
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.get(&9), Some(9));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_roundtrip_fails_without_persistence() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+
+        assert!(matches!(
+            map.verify_roundtrip().await,
+            Err(PersistentError::Config(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_changes_is_unsupported() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+
+        assert!(matches!(
+            map.sync_changes().await,
+            Err(PersistentError::Unsupported(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_entries_rejects_new_keys() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .max_entries(2)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+
+        assert!(matches!(
+            map.insert("c".to_string(), 3).await,
+            Err(PersistentError::CapacityExceeded(2))
+        ));
+        assert!(!map.contains_key(&"c".to_string()));
+        assert!(!map.contains_key_durable(&"c".to_string()).await?);
+
+        // Updates of existing keys are still allowed
+        assert_eq!(map.insert("a".to_string(), 10).await?, Some(1));
+
+        // A batch is inserted whole or not at all
+        assert!(matches!(
+            map.insert_batch_ordered(vec![("b".to_string(), 20), ("c".to_string(), 3)])
+                .await,
+            Err(PersistentError::CapacityExceeded(2))
+        ));
+        assert_eq!(map.get(&"b".to_string()), Some(2));
+
+        map.remove(&"b".to_string()).await?;
+        map.insert_batch_ordered(vec![("a".to_string(), 11), ("c".to_string(), 3)])
+            .await?;
+        assert_eq!(map.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reverse_lookup() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        map.insert("alice".to_string(), "admin".to_string()).await?;
+        map.insert("bob".to_string(), "user".to_string()).await?;
+        map.insert("carol".to_string(), "admin".to_string()).await?;
+        map.insert_with_ttl(
+            "dave".to_string(),
+            "guest".to_string(),
+            std::time::Duration::ZERO,
+        )
+        .await?;
+
+        assert!(map.contains_value(&"user".to_string()));
+        assert!(!map.contains_value(&"owner".to_string()));
+        assert!(!map.contains_value(&"guest".to_string()));
+
+        let mut admins = map.find_keys_for_value(&"admin".to_string());
+        admins.sort();
+        assert_eq!(admins, ["alice", "carol"]);
+        assert!(map.find_keys_for_value(&"guest".to_string()).is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_many() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+        map.insert_with_ttl("expired".to_string(), 3, std::time::Duration::ZERO)
+            .await?;
+
+        let keys = ["b", "missing", "a", "b", "expired"].map(String::from);
+        assert_eq!(
+            map.get_many(&keys),
+            vec![Some(2), None, Some(1), Some(2), None]
+        );
+        assert!(map.get_many(&[]).is_empty());
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod compare_and_swap {
+    use persistent_map::{PersistentError, PersistentMap, Result};
+    use serde::{Deserialize, Serialize};
+
+    /// A value without `PartialEq`.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct Lease {
+        owner: String,
+        term: u32,
+    }
+
+    fn lease(owner: &str, term: u32) -> Lease {
+        Lease {
+            owner: owner.to_string(),
+            term,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_swap_bytes() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<String, Lease, _> = PersistentMap::new(backend).await?;
+        let key = "leader".to_string();
+
+        // `None` only matches an absent key
+        assert!(
+            map.compare_and_swap_bytes(key.clone(), None, lease("a", 1))
+                .await?
+        );
+        assert!(
+            !map.compare_and_swap_bytes(key.clone(), None, lease("b", 1))
+                .await?
+        );
+
+        // A stale expectation leaves the value alone
+        assert!(
+            !map.compare_and_swap_bytes(key.clone(), Some(&lease("b", 1)), lease("b", 2))
+                .await?
+        );
+        assert_eq!(map.get(&key).unwrap().owner, "a");
+
+        assert!(
+            map.compare_and_swap_bytes(key.clone(), Some(&lease("a", 1)), lease("b", 2))
+                .await?
+        );
+        let current = map.get(&key).unwrap();
+        assert_eq!(current.owner, "b");
+        assert_eq!(current.term, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_swap() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map = std::sync::Arc::new(PersistentMap::new(backend).await?);
+        let key = "counter".to_string();
+
+        assert!(!map.compare_and_swap(&key, Some(&0_u32), 1).await?);
+        assert_eq!(map.get(&key), None);
+        assert!(map.compare_and_swap(&key, None, 0).await?);
+        assert!(!map.compare_and_swap(&key, None, 5).await?);
+
+        // Concurrent increments retry until their swap wins, so none is lost
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let map = map.clone();
+                let key = key.clone();
+                tokio::spawn(async move {
+                    for _ in 0..25 {
+                        loop {
+                            let current = map.get(&key);
+                            let next = current.unwrap() + 1;
+                            if map.compare_and_swap(&key, current.as_ref(), next).await? {
+                                break;
+                            }
+                        }
+                    }
+                    Ok::<_, persistent_map::PersistentError>(())
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap()?;
+        }
+        assert_eq!(map.get(&key), Some(100));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_compare_and_swap_respects_max_entries() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map = std::sync::Arc::new(
+            PersistentMap::builder(backend)
+                .max_entries(5)
+                .build()
+                .await?,
+        );
+        map.insert("a".to_string(), 1_u32).await?;
+
+        // Concurrent swaps of new keys never push the map past the limit
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let map = map.clone();
+                tokio::spawn(async move { map.compare_and_swap(&format!("k{i}"), None, i).await })
+            })
+            .collect();
+        let mut swapped = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(true) => swapped += 1,
+                Err(PersistentError::CapacityExceeded(5)) => {}
+                other => panic!("unexpected result {other:?}"),
+            }
+        }
+        assert_eq!(swapped, 4);
+        assert_eq!(map.len(), 5);
+
+        // Swapping the value of an existing key is still allowed
+        assert!(map.compare_and_swap(&"a".to_string(), Some(&1), 2).await?);
+        assert!(matches!(
+            map.compare_and_swap_bytes("new".to_string(), None, 1).await,
+            Err(PersistentError::CapacityExceeded(5))
+        ));
+        assert!(!map.contains_key(&"new".to_string()));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod debug_dump {
+    use persistent_map::{in_memory::InMemoryBackend, PersistentMap, Result};
+
+    #[tokio::test]
+    async fn test_debug_dump_truncates_values() -> Result<()> {
+        let map: PersistentMap<String, String, _> =
+            PersistentMap::new(InMemoryBackend::new()).await?;
+        map.insert("b".to_string(), "x".repeat(100)).await?;
+        map.insert("a".to_string(), "short".to_string()).await?;
+
+        let dump = map.debug_dump_truncated(10);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert!(lines[0].starts_with("backend: ") && lines[0].contains("InMemoryBackend"));
+        assert_eq!(lines[1], "entries: 2");
+        assert_eq!(lines[2], "capacity: unbounded");
+        assert_eq!(lines[3], r#""a" => "short""#);
+        assert_eq!(lines[4], r#""b" => "xxxxxxxxx..."#);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod eviction {
+    use persistent_map::{in_memory::InMemoryBackend, EvictionPolicy, PersistentMap, Result};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_capacity_eviction_invokes_callback() -> Result<()> {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&evicted);
+
+        let map: PersistentMap<String, String, _> = PersistentMap::builder(InMemoryBackend::new())
+            .max_capacity(2)
+            .on_evict(move |k, v| sink.lock().unwrap().push((k, v)))
+            .build()
+            .await?;
+
+        map.insert("key1".to_string(), "value1".to_string()).await?;
+        map.insert("key2".to_string(), "value2".to_string()).await?;
+
+        // Reading key1 makes key2 the least recently used entry
+        assert_eq!(map.get(&"key1".to_string()), Some("value1".to_string()));
+        map.insert("key3".to_string(), "value3".to_string()).await?;
+
+        assert_eq!(map.len(), 2);
+        assert!(!map.contains_key(&"key2".to_string()));
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            vec![("key2".to_string(), "value2".to_string())]
+        );
+
+        // Explicit removals are not evictions
+        map.remove(&"key1".to_string()).await?;
+        assert_eq!(evicted.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_are_purged() -> Result<()> {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&evicted);
+
+        let map: PersistentMap<String, String, _> = PersistentMap::builder(InMemoryBackend::new())
+            .on_evict(move |k, _| sink.lock().unwrap().push(k))
+            .build()
+            .await?;
+
+        map.insert_with_ttl(
+            "short".to_string(),
+            "value".to_string(),
+            Duration::from_millis(10),
+        )
+        .await?;
+        map.insert("long".to_string(), "value".to_string()).await?;
+        assert!(map.contains_key(&"short".to_string()));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(map.get(&"short".to_string()), None);
+        assert!(!map.contains_key(&"short".to_string()));
+
+        assert_eq!(map.purge_expired().await?, 1);
+        assert_eq!(map.len(), 1);
+        assert_eq!(*evicted.lock().unwrap(), vec!["short".to_string()]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired_uses_current_deadlines() -> Result<()> {
+        let map: PersistentMap<u32, u32, _> = PersistentMap::new(InMemoryBackend::new()).await?;
+        for i in 0..20 {
+            map.insert_with_ttl(i, i, Duration::from_millis(10)).await?;
+        }
+        for i in 20..30 {
+            map.insert_with_ttl(i, i, Duration::from_secs(60)).await?;
+        }
+        // Rewritten without a TTL, or with a longer one, so no longer due
+        for i in 0..5 {
+            map.insert(i, i).await?;
+        }
+        for i in 5..8 {
+            map.insert_with_ttl(i, i, Duration::from_secs(60)).await?;
+        }
+        map.remove(&8).await?;
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(map.purge_expired().await?, 11);
+        assert_eq!(map.len(), 18);
+        assert!((0..8).chain(20..30).all(|i| map.contains_key(&i)));
+        assert_eq!(map.purge_expired().await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_or_purge_removes_expired_entry() -> Result<()> {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&evicted);
+
+        let map: PersistentMap<String, String, _> = PersistentMap::builder(InMemoryBackend::new())
+            .on_evict(move |k, _| sink.lock().unwrap().push(k))
+            .build()
+            .await?;
+        // A live entry with a TTL is returned as it is
+        map.insert_with_ttl(
+            "session".to_string(),
+            "value".to_string(),
+            Duration::from_secs(1),
+        )
+        .await?;
+        assert_eq!(
+            map.get_or_purge(&"session".to_string()).await?,
+            Some("value".to_string())
+        );
+        map.remove(&"session".to_string()).await?;
+
+        map.insert_with_ttl(
+            "short".to_string(),
+            "value".to_string(),
+            Duration::from_millis(10),
+        )
+        .await?;
+        map.insert("long".to_string(), "value".to_string()).await?;
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(map.get_or_purge(&"short".to_string()).await?, None);
+        assert_eq!(*evicted.lock().unwrap(), vec!["short".to_string()]);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.purge_expired().await?, 0);
+
+        // Live and absent keys are plain lookups
+        assert_eq!(
+            map.get_or_purge(&"long".to_string()).await?,
+            Some("value".to_string())
+        );
+        assert_eq!(map.get_or_purge(&"missing".to_string()).await?, None);
+        assert_eq!(evicted.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lfu_evicts_least_frequently_used() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(InMemoryBackend::new())
+            .max_capacity(2)
+            .eviction(EvictionPolicy::Lfu)
+            .build()
+            .await?;
+
+        map.insert("hot".to_string(), 1).await?;
+        map.insert("cold".to_string(), 2).await?;
+        map.get(&"hot".to_string());
+        map.get(&"hot".to_string());
+        map.get(&"cold".to_string());
+
+        // "hot" was used least recently but most often
+        map.insert("new".to_string(), 3).await?;
+        assert!(map.contains_key(&"hot".to_string()));
+        assert!(!map.contains_key(&"cold".to_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fifo_ignores_accesses() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(InMemoryBackend::new())
+            .max_capacity(2)
+            .eviction(EvictionPolicy::Fifo)
+            .build()
+            .await?;
+
+        map.insert("first".to_string(), 1).await?;
+        map.insert("second".to_string(), 2).await?;
+        map.get(&"first".to_string());
+        map.insert("first".to_string(), 10).await?;
+
+        map.insert("third".to_string(), 3).await?;
+        assert!(!map.contains_key(&"first".to_string()));
+        assert!(map.contains_key(&"second".to_string()));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod json {
+    use persistent_map::{in_memory::InMemoryBackend, JsonMapExt, PersistentMap, Result};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_get_path() -> Result<()> {
+        let map: PersistentMap<String, Value, _> =
+            PersistentMap::new(InMemoryBackend::new()).await?;
+        map.insert(
+            "doc".to_string(),
+            json!({ "a": { "b": [10, 20] }, "name": "x" }),
+        )
+        .await?;
+
+        let key = "doc".to_string();
+        assert_eq!(map.get_path(&key, "/a/b/1"), Some(json!(20)));
+        assert_eq!(map.get_path(&key, "/a/missing"), None);
+        assert_eq!(map.get_path(&"nope".to_string(), "/a"), None);
+        assert_eq!(
+            map.get_path_as::<Vec<u32>>(&key, "/a/b")?,
+            Some(vec![10, 20])
+        );
+        assert!(map.get_path_as::<u32>(&key, "/name").is_err());
+
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "in_memory", feature = "runtime"))]
+mod key_lock {
+    use persistent_map::{in_memory::InMemoryBackend, KeyLock, PersistentMap, Result};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sync_key_lock_serializes_with_mut() -> Result<()> {
+        let map: Arc<PersistentMap<String, u32, _>> = Arc::new(
+            PersistentMap::builder(InMemoryBackend::new())
+                .key_lock(KeyLock::Sync)
+                .build()
+                .await?,
+        );
+        map.insert("counter".to_string(), 0).await?;
+
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                tokio::spawn(async move { map.with_mut(&"counter".to_string(), |n| *n += 1).await })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap()?, Some(()));
+        }
+        assert_eq!(map.get(&"counter".to_string()), Some(50));
+        assert_eq!(
+            map.with_mut(&"missing".to_string(), |n| *n += 1).await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sync_key_lock_serializes_insert_with_with_mut() -> Result<()> {
+        let map: Arc<PersistentMap<String, u32, _>> = Arc::new(
+            PersistentMap::builder(InMemoryBackend::new())
+                .key_lock(KeyLock::Sync)
+                .build()
+                .await?,
+        );
+        map.insert("counter".to_string(), 0).await?;
+
+        let increments: Vec<_> = (0..50)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                tokio::spawn(async move { map.with_mut(&"counter".to_string(), |n| *n += 1).await })
+            })
+            .collect();
+        let reset = {
+            let map = Arc::clone(&map);
+            tokio::spawn(async move { map.insert("counter".to_string(), 1000).await })
+        };
+        for task in increments {
+            assert_eq!(task.await.unwrap()?, Some(()));
+        }
+        let before_reset = reset.await.unwrap()?.unwrap();
+
+        // Every increment lands either before the reset or on top of it
+        let after_reset = map.get(&"counter".to_string()).unwrap() - 1000;
+        assert_eq!(before_reset + after_reset, 50);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_get_or_insert_with_computes_once() -> Result<()> {
+        let map: Arc<PersistentMap<String, u32, _>> =
+            Arc::new(PersistentMap::new(InMemoryBackend::new()).await?);
+        let computed = Arc::new(AtomicU32::new(0));
+
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let map = Arc::clone(&map);
+                let computed = Arc::clone(&computed);
+                tokio::spawn(async move {
+                    map.get_or_insert_with("page".to_string(), || async move {
+                        computed.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        i
+                    })
+                    .await
+                })
+            })
+            .collect();
+        let mut values = Vec::new();
+        for task in tasks {
+            values.push(task.await.unwrap()?);
+        }
+
+        // Every caller gets the one value that was computed
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        let resident = map.get(&"page".to_string()).unwrap();
+        assert!(values.iter().all(|&value| value == resident));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod prelude {
+    use persistent_map::prelude::*;
+
+    #[tokio::test]
+    async fn test_prelude_covers_typical_usage() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(InMemoryBackend::new())
+            .max_capacity(10)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        assert_eq!(map.get(&"a".to_string()), Some(1));
+        assert!(StorageBackend::<String, u32>::is_empty(map.backend()).await?);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod prefix_view {
+    use persistent_map::{in_memory::InMemoryBackend, PersistentMap, Result};
+
+    #[tokio::test]
+    async fn test_prefix_view_scopes_keys() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(InMemoryBackend::new()).await?;
+        map.insert("other".to_string(), 0).await?;
+        let acme = map.prefix_view("acme/");
+        let globex = map.prefix_view("globex/");
+
+        acme.insert("users", 3).await?;
+        acme.insert("plan", 1).await?;
+        globex.insert("users", 7).await?;
+
+        assert_eq!(map.get(&"acme/users".to_string()), Some(3));
+        assert_eq!(acme.get("users"), Some(3));
+        assert_eq!(globex.get("users"), Some(7));
+        assert!(!acme.contains_key("other"));
+
+        let mut entries: Vec<_> = acme.iter().collect();
+        entries.sort();
+        assert_eq!(entries, [("plan".to_string(), 1), ("users".to_string(), 3)]);
+        assert_eq!(globex.len(), 1);
+
+        assert_eq!(acme.remove("users").await?, Some(3));
+        assert_eq!(acme.len(), 1);
+        assert_eq!(globex.get("users"), Some(7));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod rate_limit {
+    use persistent_map::{in_memory::InMemoryBackend, PersistentMap, RateCounter, Result};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_check_and_increment_resets_after_window() -> Result<()> {
+        let limits: PersistentMap<String, RateCounter, _> =
+            PersistentMap::new(InMemoryBackend::new()).await?;
+        let window = Duration::from_millis(50);
+        let key = "client".to_string();
+
+        assert!(!limits.check_and_increment(key.clone(), window, 2).await?);
+        assert!(!limits.check_and_increment(key.clone(), window, 2).await?);
+        assert!(limits.check_and_increment(key.clone(), window, 2).await?);
+        assert_eq!(limits.get(&key).unwrap().count(), 2);
+
+        // Other keys have their own counters
+        assert!(
+            !limits
+                .check_and_increment("other".to_string(), window, 2)
+                .await?
+        );
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(!limits.check_and_increment(key.clone(), window, 2).await?);
+        assert_eq!(limits.get(&key).unwrap().count(), 1);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod serialization {
+    use persistent_map::{PersistentMap, Result};
+    use serde::{ser::Error, Deserialize, Serialize, Serializer};
+
+    /// A value whose serialization fails when `broken` is set.
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    #[serde(from = "bool")]
+    struct Fragile {
+        broken: bool,
+    }
+
+    impl From<bool> for Fragile {
+        fn from(broken: bool) -> Self {
+            Self { broken }
+        }
+    }
+
+    impl Serialize for Fragile {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if self.broken {
+                return Err(S::Error::custom("broken value"));
+            }
+            serializer.serialize_bool(self.broken)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unserializable_value_leaves_map_unchanged() -> Result<()> {
+        let backend = persistent_map::in_memory::InMemoryBackend::new();
+        let map: PersistentMap<String, Fragile, _> = PersistentMap::new(backend).await?;
+        let ok = Fragile { broken: false };
+        map.insert("a".to_string(), ok.clone()).await?;
+
+        assert!(map
+            .insert("a".to_string(), Fragile { broken: true })
+            .await
+            .is_err());
+        assert!(map
+            .insert("b".to_string(), Fragile { broken: true })
+            .await
+            .is_err());
+        assert!(map
+            .with_mut(&"a".to_string(), |value| value.broken = true)
+            .await
+            .is_err());
+
+        assert_eq!(map.get(&"a".to_string()), Some(ok));
+        assert!(!map.contains_key(&"b".to_string()));
+        assert_eq!(map.len(), 1);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod version {
+    use persistent_map::{in_memory::InMemoryBackend, IfChanged, PersistentMap, Result};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_get_if_changed() -> Result<()> {
+        let map: PersistentMap<String, String, _> =
+            PersistentMap::new(InMemoryBackend::new()).await?;
+        let key = "page".to_string();
+        assert_eq!(map.get_if_changed(&key, 0)?, IfChanged::Missing);
+
+        map.insert(key.clone(), "v1".to_string()).await?;
+        let (value, version) = map.get_versioned(&key)?.unwrap();
+        assert_eq!(value, "v1");
+        assert_eq!(map.get_if_changed(&key, version)?, IfChanged::NotModified);
+
+        map.insert(key.clone(), "v2".to_string()).await?;
+        let IfChanged::Changed {
+            value,
+            version: new_version,
+        } = map.get_if_changed(&key, version)?
+        else {
+            panic!("expected a changed value");
+        };
+        assert_eq!(value, "v2");
+        assert_ne!(new_version, version);
+
+        // Versions depend only on the content
+        map.insert(key.clone(), "v1".to_string()).await?;
+        assert_eq!(map.get_if_changed(&key, version)?, IfChanged::NotModified);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_version_ignores_map_order() -> Result<()> {
+        let map: PersistentMap<String, HashMap<String, u32>, _> =
+            PersistentMap::new(InMemoryBackend::new()).await?;
+        let key = "counts".to_string();
+
+        let ascending: HashMap<String, u32> = (0..50).map(|i| (format!("k{i}"), i)).collect();
+        map.insert(key.clone(), ascending).await?;
+        let (_, version) = map.get_versioned(&key)?.unwrap();
+
+        // An equal map with its own hasher state iterates in another order
+        let descending: HashMap<String, u32> =
+            (0..50).rev().map(|i| (format!("k{i}"), i)).collect();
+        map.insert(key.clone(), descending).await?;
+        assert_eq!(map.get_if_changed(&key, version)?, IfChanged::NotModified);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod snapshot {
+    use persistent_map::{in_memory::InMemoryBackend, PersistentMap, Result};
+    use std::{
+        collections::{HashMap, HashSet},
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_snapshot_index() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(InMemoryBackend::new())
+            .normalize_keys(|key: &String| key.to_lowercase())
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+
+        let snapshot = map.snapshot();
+        map.insert("b".to_string(), 2).await?;
+
+        assert_eq!(snapshot[&"a".to_string()], 1);
+        assert_eq!(snapshot[&"A".to_string()], 1);
+        assert_eq!(snapshot.get(&"b".to_string()), None);
+        assert_eq!(snapshot.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "key not present")]
+    async fn test_snapshot_index_missing_key_panics() {
+        let map: PersistentMap<String, u32, _> =
+            PersistentMap::new(InMemoryBackend::new()).await.unwrap();
+        let _ = map.snapshot()[&"missing".to_string()];
+    }
+
+    #[tokio::test]
+    async fn test_to_and_into_hashmap() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(InMemoryBackend::new()).await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert_with_ttl("gone".to_string(), 2, Duration::ZERO)
+            .await?;
+
+        let expected = HashMap::from([("a".to_string(), 1)]);
+        assert_eq!(map.to_hashmap(), expected);
+        assert_eq!(map.into_hashmap(), expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_iter_keys_and_values() -> Result<()> {
+        let map: PersistentMap<u32, u32, _> = PersistentMap::new(InMemoryBackend::new()).await?;
+        for i in 0..100 {
+            map.insert(i, i * 10).await?;
+        }
+        map.insert_with_ttl(1_000, 0, Duration::ZERO).await?;
+
+        let expected: HashSet<(u32, u32)> = (0..100).map(|i| (i, i * 10)).collect();
+        assert_eq!(map.iter().collect::<HashSet<_>>(), expected);
+        assert_eq!(
+            map.keys().collect::<HashSet<_>>(),
+            (0..100).collect::<HashSet<_>>()
+        );
+        let mut values: Vec<u32> = map.values().collect();
+        values.sort_unstable();
+        assert_eq!(values, (0..100).map(|i| i * 10).collect::<Vec<_>>());
+
+        // No lock is held while iterating
+        for (key, value) in map.iter() {
+            map.insert(key, value + 1).await?;
+        }
+        assert!(map.values().all(|value| value % 10 == 1));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod sample {
+    use persistent_map::{in_memory::InMemoryBackend, PersistentMap, Result};
+    use std::collections::HashSet;
+
+    #[tokio::test]
+    async fn test_first_n_and_sample() -> Result<()> {
+        let map: PersistentMap<u32, u32, _> = PersistentMap::new(InMemoryBackend::new()).await?;
+        for i in 0..100 {
+            map.insert(i, i * 10).await?;
+        }
+
+        let first = map.first_n(5);
+        assert_eq!(first.len(), 5);
+        assert!(first.iter().all(|(k, v)| *v == k * 10));
+        assert_eq!(map.first_n(500).len(), 100);
+
+        let sample = map.sample(10);
+        let keys: HashSet<_> = sample.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys.len(), 10);
+        assert!(sample.iter().all(|(k, v)| *v == k * 10));
+        assert_eq!(map.sample(500).len(), 100);
+        assert!(map.sample(0).is_empty());
+
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "in_memory", feature = "runtime"))]
+mod wait_for_key {
+    use persistent_map::{in_memory::InMemoryBackend, PersistentMap, Result};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_wait_for_key_sees_later_insert() -> Result<()> {
+        let map: Arc<PersistentMap<String, u32, _>> =
+            Arc::new(PersistentMap::new(InMemoryBackend::new()).await?);
+        map.insert("ready".to_string(), 1).await?;
+        assert_eq!(
+            map.wait_for_key("ready".to_string(), Duration::ZERO)
+                .await?,
+            Some(1)
+        );
+
+        let waiter = {
+            let map = Arc::clone(&map);
+            tokio::spawn(async move {
+                map.wait_for_key("result".to_string(), Duration::from_secs(10))
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        map.insert("result".to_string(), 42).await?;
+        assert_eq!(waiter.await.unwrap()?, Some(42));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_key_times_out() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(InMemoryBackend::new()).await?;
+        map.insert("other".to_string(), 1).await?;
+        assert_eq!(
+            map.wait_for_key("missing".to_string(), Duration::from_millis(20))
+                .await?,
+            None
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "in_memory", feature = "latency"))]
+mod latency {
+    use persistent_map::{in_memory::InMemoryBackend, PersistentMap, Result};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_latency_percentiles_count_backend_operations() -> Result<()> {
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(InMemoryBackend::new()).await?;
+        for i in 0..10 {
+            map.insert(format!("key{i}"), i).await?;
+        }
+        map.remove(&"key0".to_string()).await?;
+
+        let stats = map.latency_percentiles();
+        assert_eq!(stats.load.count, 1);
+        assert_eq!(stats.save.count, 10);
+        assert_eq!(stats.delete.count, 1);
+        assert!(stats.save.p50 <= stats.save.p95);
+        assert!(stats.save.p95 <= stats.save.p99);
+        assert!(stats.save.p99 < Duration::from_secs(1));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "in_memory")]
+mod volatile {
+    use persistent_map::VolatileMap;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    /// A key type without serde support.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Handle(u32);
+
+    #[test]
+    fn test_volatile_map_holds_unserializable_types() {
+        let map: VolatileMap<Handle, Arc<Mutex<Vec<u32>>>> = VolatileMap::new();
+        assert!(map.is_empty());
+
+        let log = Arc::default();
+        assert!(map.insert(Handle(1), Arc::clone(&log)).is_none());
+        map.get(&Handle(1)).unwrap().lock().unwrap().push(7);
+        assert_eq!(*log.lock().unwrap(), vec![7]);
+        assert_eq!(map.read(&Handle(1), |v| v.lock().unwrap().len()), Some(1));
+
+        assert!(map.contains_key(&Handle(1)));
+        assert_eq!(map.keys(), vec![Handle(1)]);
+        assert!(map.remove(&Handle(1)).is_some());
+        assert!(!map.contains_key(&Handle(1)));
+
+        map.insert(Handle(2), log);
+        map.clear();
+        assert_eq!(map.len(), 0);
+
+        // Values don't even have to be `Send`
+        let local: VolatileMap<u32, Rc<str>> = VolatileMap::default();
+        local.insert(1, Rc::from("one"));
+        assert_eq!(local.get(&1).as_deref(), Some("one"));
+    }
 }
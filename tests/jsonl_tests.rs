@@ -0,0 +1,105 @@
+#[cfg(feature = "jsonl_backend")]
+mod tests {
+    use persistent_map::{jsonl::JsonLinesBackend, PersistentMap, Result};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_jsonl_backend() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data.jsonl");
+
+        let map: PersistentMap<String, Option<u32>, _> =
+            PersistentMap::new(JsonLinesBackend::new(&path)).await?;
+        map.insert("a".to_string(), Some(1)).await?;
+        map.insert("b".to_string(), None).await?;
+        map.insert("a".to_string(), Some(2)).await?;
+        map.remove(&"b".to_string()).await?;
+        map.insert("c".to_string(), None).await?;
+
+        let contents = std::fs::read_to_string(&path)?;
+        assert_eq!(
+            contents,
+            "{\"k\":\"a\",\"v\":1}\n\
+             {\"k\":\"b\",\"v\":null}\n\
+             {\"k\":\"a\",\"v\":2}\n\
+             {\"k\":\"b\",\"d\":true}\n\
+             {\"k\":\"c\",\"v\":null}\n"
+        );
+
+        let reloaded: PersistentMap<String, Option<u32>, _> =
+            PersistentMap::new(JsonLinesBackend::new(&path)).await?;
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.get(&"a".to_string()), Some(Some(2)));
+        assert_eq!(reloaded.get(&"b".to_string()), None);
+        assert_eq!(reloaded.get(&"c".to_string()), Some(None));
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_tolerates_partial_last_line() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("partial.jsonl");
+        std::fs::write(&path, "{\"k\":1,\"v\":\"one\"}\n{\"k\":2,\"v\":\"tw")?;
+
+        let map: PersistentMap<u32, String, _> =
+            PersistentMap::new(JsonLinesBackend::new(&path)).await?;
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&1), Some("one".to_string()));
+
+        // The next append drops the partial line instead of extending it
+        map.insert(3, "three".to_string()).await?;
+        let reloaded: PersistentMap<u32, String, _> =
+            PersistentMap::new(JsonLinesBackend::new(&path)).await?;
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.get(&3), Some("three".to_string()));
+
+        // A corrupt line followed by others is an error
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+        file.write_all(b"not json\n{\"k\":4,\"v\":\"four\"}\n")?;
+        assert!(
+            PersistentMap::<u32, String, _>::new(JsonLinesBackend::new(&path))
+                .await
+                .is_err()
+        );
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_compact() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("compact.jsonl");
+
+        let map: PersistentMap<String, u32, _> =
+            PersistentMap::new(JsonLinesBackend::new(&path)).await?;
+        for i in 0..5 {
+            map.insert("counter".to_string(), i).await?;
+        }
+        map.insert("gone".to_string(), 1).await?;
+        map.remove(&"gone".to_string()).await?;
+        map.insert("kept".to_string(), 7).await?;
+
+        assert_eq!(map.backend().compact()?, 6);
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "{\"k\":\"counter\",\"v\":4}\n{\"k\":\"kept\",\"v\":7}\n"
+        );
+        assert_eq!(map.backend().compact()?, 0);
+
+        let reloaded: PersistentMap<String, u32, _> =
+            PersistentMap::new(JsonLinesBackend::new(&path)).await?;
+        assert_eq!(reloaded.get(&"counter".to_string()), Some(4));
+        assert_eq!(reloaded.get(&"kept".to_string()), Some(7));
+        assert_eq!(reloaded.len(), 2);
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,371 @@
+#[cfg(feature = "runtime")]
+mod concurrent_load {
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A backend that counts how often `load_all` runs.
+    #[derive(Default)]
+    struct CountingBackend {
+        loads: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, String> for CountingBackend {
+        async fn load_all(&self) -> Result<HashMap<String, String>, PersistentError> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(HashMap::from([("key1".to_string(), "value1".to_string())]))
+        }
+
+        async fn save(&self, _key: String, _value: String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &String) -> Result<(), PersistentError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_loads_share_one_fetch() -> Result<()> {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let backend = CountingBackend {
+            loads: Arc::clone(&loads),
+        };
+        let map = Arc::new(PersistentMap::new(backend).await?);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(map.loaded_count(), 1);
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                tokio::spawn(async move { map.load().await })
+            })
+            .collect();
+        for task in tasks {
+            // Callers that waited get the count of the load they shared
+            assert_eq!(task.await.unwrap()?, 1);
+        }
+
+        // All concurrent callers piggyback on a single backend fetch
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert_eq!(map.get(&"key1".to_string()), Some("value1".to_string()));
+
+        // A later call performs a fresh load
+        map.load().await?;
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+}
+
+mod read_your_writes {
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A backend whose reads lag behind its writes until `catch_up`.
+    #[derive(Default)]
+    struct Lagging {
+        /// What reads return
+        visible: Mutex<HashMap<String, String>>,
+
+        /// Writes not yet visible, `None` for deletes
+        lagging: Mutex<Vec<(String, Option<String>)>>,
+    }
+
+    impl Lagging {
+        fn catch_up(&self) {
+            let mut visible = self.visible.lock().unwrap();
+            for (key, value) in self.lagging.lock().unwrap().drain(..) {
+                match value {
+                    Some(value) => visible.insert(key, value),
+                    None => visible.remove(&key),
+                };
+            }
+        }
+
+        /// Applies a write by another process, visible right away.
+        fn external_write(&self, key: &str, value: &str) {
+            self.visible
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.to_string());
+        }
+    }
+
+    struct LaggingBackend(Arc<Lagging>);
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, String> for LaggingBackend {
+        async fn load_all(&self) -> Result<HashMap<String, String>, PersistentError> {
+            Ok(self.0.visible.lock().unwrap().clone())
+        }
+
+        async fn save(&self, key: String, value: String) -> Result<(), PersistentError> {
+            self.0.lagging.lock().unwrap().push((key, Some(value)));
+            Ok(())
+        }
+
+        async fn delete(&self, key: &String) -> Result<(), PersistentError> {
+            self.0.lagging.lock().unwrap().push((key.clone(), None));
+            Ok(())
+        }
+    }
+
+    fn key(k: &str) -> String {
+        k.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_insert_then_reload_keeps_local_write() -> Result<()> {
+        let state = Arc::new(Lagging::default());
+        state.external_write("a", "old");
+        state.external_write("b", "old");
+
+        // Without the mode, a reload brings back the stale value
+        let plain = PersistentMap::new(LaggingBackend(Arc::clone(&state))).await?;
+        plain.insert(key("a"), "new".to_string()).await?;
+        plain.load().await?;
+        assert_eq!(plain.get(&key("a")), Some("old".to_string()));
+        state.lagging.lock().unwrap().clear();
+
+        let map = PersistentMap::builder(LaggingBackend(Arc::clone(&state)))
+            .read_your_writes(Duration::from_secs(60))
+            .build()
+            .await?;
+        map.insert(key("a"), "new".to_string()).await?;
+        map.remove(&key("b")).await?;
+        map.load().await?;
+        assert_eq!(map.get(&key("a")), Some("new".to_string()));
+        assert_eq!(map.get(&key("b")), None);
+
+        // Once the backend returns the write, reloads apply it again
+        state.catch_up();
+        map.load().await?;
+        assert_eq!(map.get(&key("a")), Some("new".to_string()));
+        state.external_write("a", "external");
+        state.external_write("b", "external");
+        map.load().await?;
+        assert_eq!(map.get(&key("a")), Some("external".to_string()));
+        assert_eq!(map.get(&key("b")), Some("external".to_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_your_writes_window_expires() -> Result<()> {
+        let state = Arc::new(Lagging::default());
+        state.external_write("a", "old");
+
+        let map = PersistentMap::builder(LaggingBackend(Arc::clone(&state)))
+            .read_your_writes(Duration::from_millis(50))
+            .build()
+            .await?;
+        map.insert(key("a"), "new".to_string()).await?;
+        map.load().await?;
+        assert_eq!(map.get(&key("a")), Some("new".to_string()));
+
+        // A write the backend never shows is given up after the window
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        map.load().await?;
+        assert_eq!(map.get(&key("a")), Some("old".to_string()));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "runtime")]
+mod startup_load {
+    use persistent_map::{
+        PersistentError, PersistentMap, Result, StartupLoadPolicy, StorageBackend,
+    };
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A backend whose first loads fail or hang.
+    #[derive(Default)]
+    struct Degraded {
+        stored: Mutex<HashMap<String, String>>,
+        failing_loads: AtomicUsize,
+        hang: bool,
+    }
+
+    struct DegradedBackend(Arc<Degraded>);
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, String> for DegradedBackend {
+        async fn load_all(&self) -> Result<HashMap<String, String>, PersistentError> {
+            let failing = self.0.failing_loads.load(Ordering::SeqCst);
+            if failing > 0 {
+                self.0.failing_loads.store(failing - 1, Ordering::SeqCst);
+                if self.0.hang {
+                    std::future::pending::<()>().await;
+                }
+                return Err(PersistentError::Io(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "store unavailable",
+                )));
+            }
+            Ok(self.0.stored.lock().unwrap().clone())
+        }
+
+        async fn save(&self, key: String, value: String) -> Result<(), PersistentError> {
+            self.0.stored.lock().unwrap().insert(key, value);
+            Ok(())
+        }
+
+        async fn delete(&self, key: &String) -> Result<(), PersistentError> {
+            self.0.stored.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    fn degraded(failing_loads: usize, hang: bool) -> Arc<Degraded> {
+        let state = Degraded {
+            failing_loads: AtomicUsize::new(failing_loads),
+            hang,
+            ..Degraded::default()
+        };
+        let mut stored = state.stored.lock().unwrap();
+        stored.insert("a".to_string(), "stored".to_string());
+        stored.insert("b".to_string(), "stored".to_string());
+        drop(stored);
+        Arc::new(state)
+    }
+
+    #[tokio::test]
+    async fn test_startup_load_fails_fast() -> Result<()> {
+        let state = degraded(1, true);
+        let built = PersistentMap::builder(DegradedBackend(Arc::clone(&state)))
+            .startup_load_timeout(Duration::from_millis(50))
+            .build()
+            .await;
+        match built {
+            Err(PersistentError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+            other => panic!("expected a timeout, got {:?}", other.err()),
+        }
+
+        let state = degraded(1, false);
+        assert!(PersistentMap::new(DegradedBackend(state)).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_startup_load_retries_in_background() -> Result<()> {
+        let state = degraded(2, true);
+        let map = PersistentMap::builder(DegradedBackend(Arc::clone(&state)))
+            .startup_load_timeout(Duration::from_millis(50))
+            .startup_load_policy(StartupLoadPolicy::EmptyThenRetry)
+            .build()
+            .await?;
+        assert!(map.is_empty());
+
+        // Written before the retried load lands, so newer than the stored value
+        map.insert("b".to_string(), "local".to_string()).await?;
+
+        let mut waited = Duration::ZERO;
+        while map.get(&"a".to_string()).is_none() && waited < Duration::from_secs(10) {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            waited += Duration::from_millis(50);
+        }
+        assert_eq!(map.get(&"a".to_string()), Some("stored".to_string()));
+        assert_eq!(map.get(&"b".to_string()), Some("local".to_string()));
+        assert_eq!(state.failing_loads.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "runtime")]
+mod batch_loader {
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A backend whose entries are only found by point reads, like a cold
+    /// cache in front of a large store.
+    #[derive(Default)]
+    struct Cold {
+        rows: HashMap<u32, u32>,
+        batches: AtomicUsize,
+        singles: AtomicUsize,
+    }
+
+    struct ColdBackend(Arc<Cold>);
+
+    #[async_trait::async_trait]
+    impl StorageBackend<u32, u32> for ColdBackend {
+        async fn load_all(&self) -> Result<HashMap<u32, u32>, PersistentError> {
+            Ok(HashMap::new())
+        }
+
+        async fn save(&self, _key: u32, _value: u32) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &u32) -> Result<(), PersistentError> {
+            Ok(())
+        }
+
+        async fn load_one_raw(&self, key: &u32) -> Result<Option<Vec<u8>>, PersistentError> {
+            self.0.singles.fetch_add(1, Ordering::SeqCst);
+            Ok(self.0.rows.get(key).map(|v| v.to_string().into_bytes()))
+        }
+
+        async fn load_many_raw(
+            &self,
+            keys: &[u32],
+        ) -> Result<Vec<Option<Vec<u8>>>, PersistentError> {
+            self.0.batches.fetch_add(1, Ordering::SeqCst);
+            Ok(keys
+                .iter()
+                .map(|key| self.0.rows.get(key).map(|v| v.to_string().into_bytes()))
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_share_one_read() -> Result<()> {
+        let cold = Arc::new(Cold {
+            rows: (0..20).map(|i| (i, i * 10)).collect(),
+            ..Cold::default()
+        });
+        let map = Arc::new(
+            PersistentMap::builder(ColdBackend(Arc::clone(&cold)))
+                .load_batch_window(Duration::from_millis(20))
+                .build()
+                .await?,
+        );
+
+        let reads: Vec<_> = (0..25)
+            .map(|i| {
+                let map = Arc::clone(&map);
+                tokio::spawn(async move { map.get_durable(&i).await })
+            })
+            .collect();
+        for (i, read) in (0..).zip(reads) {
+            let expected = (i < 20).then_some(i * 10);
+            assert_eq!(read.await.unwrap()?, expected);
+        }
+        assert_eq!(cold.batches.load(Ordering::SeqCst), 1);
+        assert_eq!(cold.singles.load(Ordering::SeqCst), 0);
+        assert_eq!(map.len(), 20);
+
+        // A lone miss resolves once its window has passed
+        let lone = tokio::time::timeout(Duration::from_secs(1), map.get_durable(&24)).await;
+        assert_eq!(lone.unwrap()?, None);
+        assert_eq!(cold.batches.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+}
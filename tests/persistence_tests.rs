@@ -88,6 +88,52 @@ mod csv_persistence {
     }
 }
 
+#[cfg(feature = "sled_backend")]
+mod sled_persistence {
+    use persistent_map::{PersistentMap, Result};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_sled_persistence() -> Result<()> {
+        // Create a temporary directory for the test
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test_persistence.sled");
+
+        // First session: Create a map and insert data
+        {
+            let backend = persistent_map::sled::SledBackend::open(&db_path)?;
+            let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+
+            // Insert some data
+            map.insert("key1".to_string(), "value1".to_string()).await?;
+            map.insert("key2".to_string(), "value2".to_string()).await?;
+            map.insert("key3".to_string(), "value3".to_string()).await?;
+            map.remove(&"key3".to_string()).await?;
+
+            // Ensure data is persisted
+            map.flush().await?;
+
+            // Map is dropped here, closing the database
+        }
+
+        // Second session: Create a new map and verify data is still there
+        {
+            let backend = persistent_map::sled::SledBackend::open(&db_path)?;
+            let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+
+            // Verify data was persisted
+            assert_eq!(map.get(&"key1".to_string()), Some("value1".to_string()));
+            assert_eq!(map.get(&"key2".to_string()), Some("value2".to_string()));
+            assert_eq!(map.len(), 2);
+        }
+
+        // Clean up
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
+
 #[cfg(feature = "in_memory")]
 mod in_memory_persistence {
     use persistent_map::{PersistentMap, Result};
@@ -0,0 +1,102 @@
+#[cfg(feature = "postgres")]
+mod tests {
+    use persistent_map::postgres::PostgresBackend;
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use sqlx::PgPool;
+
+    /// Connects to the database named by `PERSISTENT_MAP_POSTGRES_URL`, or
+    /// returns `None` to skip the test when it is unset.
+    async fn pool() -> Result<Option<PgPool>> {
+        match std::env::var("PERSISTENT_MAP_POSTGRES_URL") {
+            Ok(url) => Ok(Some(PgPool::connect(&url).await?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Creates a backend over a fresh `table`.
+    async fn backend(pool: PgPool, table: &str) -> Result<PostgresBackend> {
+        sqlx::query(&format!("DROP TABLE IF EXISTS {table}"))
+            .execute(&pool)
+            .await?;
+        PostgresBackend::with_table(pool, table).await
+    }
+
+    #[tokio::test]
+    async fn test_postgres_backend() -> Result<()> {
+        let Some(pool) = pool().await? else {
+            return Ok(());
+        };
+
+        let map = PersistentMap::new(backend(pool.clone(), "pm_test_basic").await?).await?;
+        map.insert("key1".to_string(), vec![1_u32, 2]).await?;
+        map.insert("key2".to_string(), vec![3]).await?;
+        map.insert("key1".to_string(), vec![4]).await?;
+        map.remove(&"key2".to_string()).await?;
+        map.insert_raw("raw".to_string(), b"[5, 6]".to_vec())
+            .await?;
+        assert_eq!(
+            map.get_raw(&"raw".to_string()).await?,
+            Some(b"[5, 6]".to_vec())
+        );
+        drop(map);
+
+        let backend = PostgresBackend::with_table(pool, "pm_test_basic").await?;
+        assert_eq!(backend.table(), "pm_test_basic");
+        assert!(
+            StorageBackend::<String, Vec<u32>>::contains_key(&backend, &"raw".to_string()).await?
+        );
+        let reloaded: PersistentMap<String, Vec<u32>, _> = PersistentMap::new(backend).await?;
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.get(&"key1".to_string()), Some(vec![4]));
+        assert_eq!(reloaded.get(&"key2".to_string()), None);
+        assert_eq!(reloaded.get(&"raw".to_string()), Some(vec![5, 6]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_postgres_batches() -> Result<()> {
+        let Some(pool) = pool().await? else {
+            return Ok(());
+        };
+
+        let backend = backend(pool, "pm_test_batches").await?;
+        backend
+            .save_many(vec![
+                ("a".to_string(), 1_i64),
+                ("b".to_string(), 2),
+                ("a".to_string(), 3),
+            ])
+            .await?;
+        backend
+            .write_batch(vec![("c".to_string(), 4)], vec!["b".to_string()])
+            .await?;
+        let all: std::collections::HashMap<String, i64> = backend.load_all().await?;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all["a"], 3);
+        assert_eq!(all["c"], 4);
+
+        StorageBackend::<String, i64>::delete_many(&backend, vec!["a".to_string()]).await?;
+        assert_eq!(StorageBackend::<String, i64>::len(&backend).await?, 1);
+        StorageBackend::<String, i64>::delete_all(&backend).await?;
+        assert!(StorageBackend::<String, i64>::is_empty(&backend).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_postgres_rejects_invalid_table_names() -> Result<()> {
+        let Some(pool) = pool().await? else {
+            return Ok(());
+        };
+
+        for table in ["", "1st", "kv-2", "kv; DROP TABLE kv", &"t".repeat(64)] {
+            assert!(matches!(
+                PostgresBackend::with_table(pool.clone(), table).await,
+                Err(PersistentError::Config(_))
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(feature = "redis_backend")]
+mod tests {
+    use persistent_map::redis::RedisBackend;
+    use persistent_map::{PersistentMap, Result, StorageBackend};
+
+    /// Connects to the server named by `PERSISTENT_MAP_REDIS_URL` and
+    /// clears `hash`, or returns `None` to skip the test when it is unset.
+    async fn backend(hash: &str) -> Result<Option<RedisBackend>> {
+        let Ok(url) = std::env::var("PERSISTENT_MAP_REDIS_URL") else {
+            return Ok(None);
+        };
+        let backend = RedisBackend::connect(&url, hash).await?;
+        StorageBackend::<String, u32>::delete_all(&backend).await?;
+        Ok(Some(backend))
+    }
+
+    #[tokio::test]
+    async fn test_redis_backend() -> Result<()> {
+        let Some(backend) = backend("pm_test_basic").await? else {
+            return Ok(());
+        };
+
+        let map = PersistentMap::new(backend.clone()).await?;
+        map.insert("key1".to_string(), 1_u32).await?;
+        map.insert("key2".to_string(), 2).await?;
+        map.insert("key1".to_string(), 3).await?;
+        map.remove(&"key2".to_string()).await?;
+        drop(map);
+
+        assert_eq!(backend.hash(), "pm_test_basic");
+        let reloaded: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(reloaded.get(&"key1".to_string()), Some(3));
+        assert_eq!(
+            reloaded.get_raw(&"key1".to_string()).await?,
+            Some(b"3".to_vec())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redis_batches() -> Result<()> {
+        let Some(backend) = backend("pm_test_batches").await? else {
+            return Ok(());
+        };
+
+        backend
+            .save_many(vec![("a".to_string(), 1_u32), ("b".to_string(), 2)])
+            .await?;
+        backend
+            .write_batch(vec![("c".to_string(), 3)], vec!["b".to_string()])
+            .await?;
+        let all: std::collections::HashMap<String, u32> = backend.load_all().await?;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all["a"], 1);
+        assert_eq!(all["c"], 3);
+
+        let values = StorageBackend::<String, u32>::load_many_raw(
+            &backend,
+            &["c".to_string(), "b".to_string()],
+        )
+        .await?;
+        assert_eq!(values, vec![Some(b"3".to_vec()), None]);
+
+        StorageBackend::<String, u32>::delete_many(&backend, vec!["a".to_string()]).await?;
+        assert_eq!(StorageBackend::<String, u32>::len(&backend).await?, 1);
+        StorageBackend::<String, u32>::delete_all(&backend).await?;
+        assert!(StorageBackend::<String, u32>::is_empty(&backend).await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(feature = "sqlite")]
+mod tests {
+    use persistent_map::serialized::SerializedBackend;
+    use persistent_map::sqlite::SqliteBackend;
+    use persistent_map::{
+        BincodeSerializer, JsonSerializer, PersistentMap, Result, Serializer, StorageBackend,
+    };
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_bincode_serializer_roundtrip() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("bincode.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let samples = vec![0.1_f64, 1.0 / 3.0, f64::MAX];
+        let backend = SerializedBackend::new(
+            SqliteBackend::new_binary(db_path_str).await?,
+            BincodeSerializer,
+        );
+        let map: PersistentMap<String, Vec<f64>, _> = PersistentMap::new(backend).await?;
+        map.insert("samples".to_string(), samples.clone()).await?;
+
+        // The inner backend holds the bincode encoding
+        let stored = StorageBackend::<String, Vec<u8>>::load_all(map.backend().inner()).await?;
+        assert_eq!(stored["samples"], BincodeSerializer.serialize(&samples)?);
+
+        // Raw reads see JSON
+        assert_eq!(
+            map.get_raw(&"samples".to_string()).await?,
+            Some(serde_json::to_vec(&samples)?)
+        );
+        map.insert_raw("raw".to_string(), b"[2.5]".to_vec()).await?;
+        drop(map);
+
+        let backend = SerializedBackend::new(
+            SqliteBackend::new_binary(db_path_str).await?,
+            BincodeSerializer,
+        );
+        let reloaded: PersistentMap<String, Vec<f64>, _> = PersistentMap::new(backend).await?;
+        assert_eq!(reloaded.get(&"samples".to_string()), Some(samples));
+        assert_eq!(reloaded.get(&"raw".to_string()), Some(vec![2.5]));
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_json_serializer_is_the_default() -> Result<()> {
+        let backend: SerializedBackend<_> =
+            SerializedBackend::new(SqliteBackend::new_binary(":memory:").await?, JsonSerializer);
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("answer".to_string(), 42).await?;
+
+        let stored = StorageBackend::<String, Vec<u8>>::load_all(map.backend().inner()).await?;
+        assert_eq!(stored["answer"], b"42".to_vec());
+        assert!(JsonSerializer.deserialize::<u32>(b"not json").is_err());
+
+        Ok(())
+    }
+}
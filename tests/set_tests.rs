@@ -0,0 +1,63 @@
+#[cfg(feature = "sqlite")]
+mod tests {
+    use persistent_map::{sqlite::SqliteBackend, PersistentSet, Result};
+    use std::collections::HashSet;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_set_persists_members() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("set.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let set: PersistentSet<String, _> =
+            PersistentSet::new(SqliteBackend::new(db_path_str).await?).await?;
+        assert!(set.is_empty());
+        assert!(set.insert("a".to_string()).await?);
+        assert!(set.insert("b".to_string()).await?);
+        assert!(!set.insert("a".to_string()).await?);
+        assert!(set.remove(&"b".to_string()).await?);
+        assert!(!set.remove(&"b".to_string()).await?);
+        drop(set);
+
+        let set: PersistentSet<String, _> =
+            PersistentSet::new(SqliteBackend::new(db_path_str).await?).await?;
+        assert_eq!(set.len(), 1);
+        assert!(set.contains(&"a".to_string()));
+        assert!(!set.contains(&"b".to_string()));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec!["a".to_string()]);
+
+        drop(set);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_operations() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let left_path = dir.path().join("left.db");
+        let right_path = dir.path().join("right.db");
+
+        let left: PersistentSet<String, _> =
+            PersistentSet::new(SqliteBackend::new(left_path.to_str().unwrap()).await?).await?;
+        let right: PersistentSet<String, _> =
+            PersistentSet::new(SqliteBackend::new(right_path.to_str().unwrap()).await?).await?;
+        for key in ["a", "b"] {
+            left.insert(key.to_string()).await?;
+        }
+        for key in ["b", "c"] {
+            right.insert(key.to_string()).await?;
+        }
+
+        let set = |keys: &[&str]| keys.iter().map(ToString::to_string).collect::<HashSet<_>>();
+        assert_eq!(left.union(&right), set(&["a", "b", "c"]));
+        assert_eq!(left.intersection(&right), set(&["b"]));
+        assert_eq!(left.difference(&right), set(&["a"]));
+
+        drop((left, right));
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
 #[cfg(feature = "sqlite")]
 mod tests {
-    use persistent_map::{PersistentMap, Result};
+    use persistent_map::{
+        PersistentError, PersistentMap, RateCounter, Result, SaveOutcome, VerifyDepth,
+    };
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
     use tempfile::tempdir;
 
     #[tokio::test]
@@ -57,4 +61,1769 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_sqlite_raw_bytes() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("raw.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, Vec<u32>, _> = PersistentMap::new(backend).await?;
+
+        map.insert("key1".to_string(), vec![1, 2, 3]).await?;
+        assert_eq!(
+            map.get_raw(&"key1".to_string()).await?,
+            Some(b"[1,2,3]".to_vec())
+        );
+        assert_eq!(map.get_raw(&"missing".to_string()).await?, None);
+
+        // Raw writes are stored verbatim and decoded into the cache
+        map.insert_raw("key2".to_string(), b"[4, 5]".to_vec())
+            .await?;
+        assert_eq!(map.get(&"key2".to_string()), Some(vec![4, 5]));
+        assert_eq!(
+            map.get_raw(&"key2".to_string()).await?,
+            Some(b"[4, 5]".to_vec())
+        );
+
+        // Bytes that don't decode into `V` are rejected
+        assert!(map
+            .insert_raw("key3".to_string(), b"not json".to_vec())
+            .await
+            .is_err());
+        assert!(!map.contains_key(&"key3".to_string()));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_migrate_value() -> Result<()> {
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        enum Shape {
+            Circle { radius: u32 },
+            Square { side: u32 },
+        }
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("migrate.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        // Version 1 stored circles as a bare radius
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let old: PersistentMap<String, serde_json::Value, _> = PersistentMap::new(backend).await?;
+        old.insert("a".to_string(), serde_json::json!(3)).await?;
+        old.insert(
+            "b".to_string(),
+            serde_json::json!({ "Square": { "side": 2 } }),
+        )
+        .await?;
+        drop(old);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, Shape, _> = PersistentMap::builder(backend)
+            .migrate_value(|value| {
+                Ok(match value {
+                    serde_json::Value::Number(radius) => {
+                        serde_json::json!({ "Circle": { "radius": radius } })
+                    }
+                    current => current,
+                })
+            })
+            .build()
+            .await?;
+
+        assert_eq!(map.get(&"a".to_string()), Some(Shape::Circle { radius: 3 }));
+        assert_eq!(map.get(&"b".to_string()), Some(Shape::Square { side: 2 }));
+
+        // Without the hook the old shape fails to decode
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        assert!(PersistentMap::<String, Shape, _>::new(backend)
+            .await
+            .is_err());
+
+        // Reads that go to the backend apply the hook as well
+        map.clear();
+        assert_eq!(
+            map.values_with_prefix("a").await?,
+            vec![Shape::Circle { radius: 3 }]
+        );
+        let mut pages = map.iter_backend_paged(1);
+        assert_eq!(
+            pages.next_page().await?,
+            Some(vec![("a".to_string(), Shape::Circle { radius: 3 })])
+        );
+        assert_eq!(map.load_range(0, 10).await?, 2);
+        assert_eq!(
+            map.drain_prefix("a").await?,
+            vec![("a".to_string(), Shape::Circle { radius: 3 })]
+        );
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_repair_value() -> Result<()> {
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Settings {
+            theme: String,
+            font_size: u32,
+        }
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("repair.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        // Version 1 had no font size
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let old: PersistentMap<String, serde_json::Value, _> = PersistentMap::new(backend).await?;
+        old.insert("a".to_string(), serde_json::json!({ "theme": "dark" }))
+            .await?;
+        old.insert(
+            "b".to_string(),
+            serde_json::json!({ "theme": "light", "font_size": 20 }),
+        )
+        .await?;
+        drop(old);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, Settings, _> = PersistentMap::builder(backend)
+            .repair_value(|mut value| {
+                if let Some(fields) = value.as_object_mut() {
+                    fields.entry("font_size").or_insert(14.into());
+                }
+                value
+            })
+            .build()
+            .await?;
+
+        assert_eq!(map.get(&"a".to_string()).map(|s| s.font_size), Some(14));
+        assert_eq!(map.get(&"b".to_string()).map(|s| s.font_size), Some(20));
+        drop(map);
+
+        // A repair that does not fix the value still fails the load
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        assert!(PersistentMap::<String, Settings, _>::builder(backend)
+            .repair_value(|value| value)
+            .build()
+            .await
+            .is_err());
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_with_mut() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("with_mut.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: std::sync::Arc<PersistentMap<String, u32, _>> =
+            std::sync::Arc::new(PersistentMap::new(backend).await?);
+
+        assert_eq!(
+            map.with_mut(&"counter".to_string(), |n| *n += 1).await?,
+            None
+        );
+        assert!(!map.contains_key(&"counter".to_string()));
+
+        map.insert("counter".to_string(), 0).await?;
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let map = std::sync::Arc::clone(&map);
+                tokio::spawn(async move {
+                    map.with_mut(&"counter".to_string(), |n| {
+                        *n += 1;
+                        *n
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap()?.is_some());
+        }
+        assert_eq!(map.get(&"counter".to_string()), Some(20));
+        drop(map);
+
+        // The mutated value was persisted
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.get(&"counter".to_string()), Some(20));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_update() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("update.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, Vec<String>, _> = PersistentMap::new(backend).await?;
+        let key = "todo".to_string();
+        assert_eq!(map.update(&key, Vec::clear).await?, None);
+        assert!(!map.contains_key(&key));
+
+        map.insert(key.clone(), vec!["a".to_string()]).await?;
+        assert_eq!(
+            map.update(&key, |items| items.push("b".to_string()))
+                .await?,
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        drop(map);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, Vec<String>, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.get(&key), Some(vec!["a".to_string(), "b".to_string()]));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_normalize_keys() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("normalize.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .normalize_keys(|key: &String| key.to_lowercase())
+            .build()
+            .await?;
+
+        map.insert("Alice".to_string(), 1).await?;
+        assert_eq!(map.insert("ALICE".to_string(), 2).await?, Some(1));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&"alice".to_string()), Some(2));
+        assert!(map.contains_key(&"aLiCe".to_string()));
+        drop(map);
+
+        // Only the normalized key was persisted
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&"alice".to_string()), Some(2));
+        assert_eq!(map.get(&"Alice".to_string()), None);
+        drop(map);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .normalize_keys(|key: &String| key.to_lowercase())
+            .build()
+            .await?;
+        assert_eq!(map.remove(&"Alice".to_string()).await?, Some(2));
+        assert!(map.is_empty());
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_last_modified() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("mtime.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("a".to_string(), 1).await?;
+        let modified = map.last_modified().await?.expect("file has an mtime");
+        assert!(modified <= std::time::SystemTime::now());
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(":memory:").await?;
+        let memory: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(memory.last_modified().await?, None);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_drain_prefix() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("drain.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("user:1:a".to_string(), 1).await?;
+        map.insert("user:1:b".to_string(), 2).await?;
+        map.insert("user:2:a".to_string(), 3).await?;
+
+        let drained = map.drain_prefix("user:1:").await?;
+        assert_eq!(
+            drained,
+            vec![("user:1:a".to_string(), 1), ("user:1:b".to_string(), 2)]
+        );
+        assert_eq!(map.len(), 1);
+        assert!(map.drain_prefix("user:1:").await?.is_empty());
+        drop(map);
+
+        // The drained keys were deleted from the backend too
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&"user:2:a".to_string()), Some(3));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_contains_key_durable() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("contains.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .max_capacity(1)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+
+        // "a" was evicted from memory but still exists in the backend
+        assert!(!map.contains_key(&"a".to_string()));
+        assert!(map.contains_key_durable(&"a".to_string()).await?);
+        assert!(map.contains_key_durable(&"b".to_string()).await?);
+        assert!(!map.contains_key_durable(&"c".to_string()).await?);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_remove_evicted_key() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("remove_evicted.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .max_capacity(1)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+
+        // "a" is no longer cached, but removing it must still delete the row
+        assert!(!map.contains_key(&"a".to_string()));
+        assert_eq!(map.remove(&"a".to_string()).await?, None);
+        drop(map);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.get(&"a".to_string()), None);
+        assert_eq!(map.get(&"b".to_string()), Some(2));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_scan_prefix() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("prefix.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+
+        map.insert("user:b".to_string(), "2".to_string()).await?;
+        map.insert("user:a".to_string(), "1".to_string()).await?;
+        map.insert("user*x".to_string(), "glob".to_string()).await?;
+        map.insert("other".to_string(), "3".to_string()).await?;
+
+        assert_eq!(
+            map.scan_prefix("user:").await?,
+            vec![
+                ("user:a".to_string(), "1".to_string()),
+                ("user:b".to_string(), "2".to_string()),
+            ]
+        );
+        assert_eq!(
+            map.values_with_prefix("user*").await?,
+            vec!["glob".to_string()]
+        );
+
+        // Entries dropped from memory are still found in the backend
+        map.clear();
+        assert_eq!(
+            map.values_with_prefix("user:").await?,
+            vec!["1".to_string(), "2".to_string()]
+        );
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn test_sqlite_dump_restore() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("source.db");
+        let target_path = dir.path().join("target.db");
+
+        let backend =
+            persistent_map::sqlite::SqliteBackend::new(source_path.to_str().unwrap()).await?;
+        let source: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .max_capacity(1)
+            .build()
+            .await?;
+        source.insert("a".to_string(), 1).await?;
+        source.insert("b".to_string(), 2).await?;
+
+        // Evicted entries are read from the backend
+        let mut dump = Vec::new();
+        source.dump(&mut dump).await?;
+        assert!(dump.starts_with(b"PMAP"));
+
+        let backend =
+            persistent_map::sqlite::SqliteBackend::new(target_path.to_str().unwrap()).await?;
+        let target: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        target.insert("c".to_string(), 3).await?;
+        assert_eq!(target.restore(dump.as_slice()).await?, 2);
+        assert_eq!(target.get(&"a".to_string()), Some(1));
+        assert_eq!(target.get(&"b".to_string()), Some(2));
+        assert_eq!(target.get(&"c".to_string()), Some(3));
+
+        // A truncated dump is rejected without touching the map
+        target.remove(&"a".to_string()).await?;
+        assert!(target.restore(&dump[..dump.len() - 1]).await.is_err());
+        assert!(!target.contains_key(&"a".to_string()));
+
+        // A frame length beyond the end of the stream is not allocated up front
+        let mut bogus = b"PMAP\x01".to_vec();
+        bogus.extend_from_slice(&u32::MAX.to_be_bytes());
+        bogus.extend_from_slice(b"\"a\"");
+        assert!(target.restore(bogus.as_slice()).await.is_err());
+
+        drop(source);
+        drop(target);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_durable() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("durable.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .durable(true)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+        map.remove(&"b".to_string()).await?;
+        drop(map);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.get(&"a".to_string()), Some(1));
+        assert!(!map.contains_key(&"b".to_string()));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_backend_stats() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("stats.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+
+        let stats = map.backend_stats().await?;
+        assert_eq!(stats["entries"], "2");
+        assert!(stats["page_count"].parse::<u64>().unwrap() > 0);
+        assert!(stats.contains_key("freelist_count"));
+        assert!(stats.contains_key("journal_mode"));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_load_range() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("pages.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        for (i, key) in ["c", "a", "e", "b", "d"].into_iter().enumerate() {
+            map.insert(key.to_string(), u32::try_from(i).unwrap())
+                .await?;
+        }
+        map.clear();
+
+        assert_eq!(map.load_range(1, 2).await?, 2);
+        assert_eq!(map.len(), 2);
+        assert!(map.contains_key(&"b".to_string()));
+        assert!(map.contains_key(&"c".to_string()));
+
+        assert_eq!(map.load_range(3, 10).await?, 2);
+        assert_eq!(map.load_range(5, 10).await?, 0);
+        assert_eq!(map.len(), 4);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_iter_backend_paged() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("cursor.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        for (i, key) in ["c", "a", "e", "b", "d"].into_iter().enumerate() {
+            map.insert(key.to_string(), u32::try_from(i).unwrap())
+                .await?;
+        }
+
+        let mut pages = map.iter_backend_paged(2);
+        let mut keys = Vec::new();
+        while let Some(page) = pages.next_page().await? {
+            assert!(page.len() <= 2);
+            keys.extend(page.into_iter().map(|(k, _)| k));
+            // Inserting behind the cursor does not disturb it
+            map.insert("0".to_string(), 0).await?;
+        }
+        assert_eq!(keys, ["a", "b", "c", "d", "e"]);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_purge_expired_deletes_rows() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("expiry.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .max_capacity(1)
+            .build()
+            .await?;
+        map.insert_with_ttl("a".to_string(), 1, Duration::from_millis(20))
+            .await?;
+        // Evicts "a" from memory, so only the backend can delete its row
+        map.insert("b".to_string(), 2).await?;
+        assert!(!map.contains_key(&"a".to_string()));
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        map.purge_expired().await?;
+
+        let conn = tokio_rusqlite::Connection::open(db_path_str).await?;
+        let keys = conn
+            .call(|c| {
+                let mut stmt = c.prepare("SELECT key FROM kv ORDER BY key")?;
+                let keys = stmt
+                    .query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(keys)
+            })
+            .await?;
+        assert_eq!(keys, ["b"]);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_expiry_survives_reopen() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("reopen.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert_with_ttl("a".to_string(), 1, Duration::from_millis(50))
+            .await?;
+        map.insert("b".to_string(), 2).await?;
+        drop(map);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.get(&"a".to_string()), Some(1));
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(map.get(&"a".to_string()), None);
+        assert_eq!(map.purge_expired().await?, 1);
+        assert!(!map.contains_key_durable(&"a".to_string()).await?);
+        assert_eq!(map.get(&"b".to_string()), Some(2));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_sync_changes_from_another_connection() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("shared.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let reader: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let writer: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+
+        assert_eq!(reader.sync_changes().await?, 0);
+
+        writer.insert("a".to_string(), 1).await?;
+        writer.insert("b".to_string(), 2).await?;
+        writer.insert("a".to_string(), 3).await?;
+        assert_eq!(reader.sync_changes().await?, 2);
+        assert_eq!(reader.get(&"a".to_string()), Some(3));
+        assert_eq!(reader.get(&"b".to_string()), Some(2));
+
+        writer.remove(&"b".to_string()).await?;
+        writer
+            .insert_with_ttl("c".to_string(), 4, Duration::from_millis(50))
+            .await?;
+        assert_eq!(reader.sync_changes().await?, 2);
+        assert!(!reader.contains_key(&"b".to_string()));
+        assert_eq!(reader.get(&"c".to_string()), Some(4));
+
+        // Nothing changed since the last sync
+        assert_eq!(reader.sync_changes().await?, 0);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(reader.get(&"c".to_string()), None);
+
+        drop((reader, writer));
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_insert_durable_reports_evicted_value() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("durable_insert.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .max_capacity(1)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        // Evicts "a" from memory
+        map.insert("b".to_string(), 2).await?;
+
+        assert_eq!(map.insert_durable("a".to_string(), 3).await?, Some(1));
+        assert_eq!(map.insert_durable("a".to_string(), 4).await?, Some(3));
+        assert_eq!(map.insert_durable("c".to_string(), 5).await?, None);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_map_values_into_another_backend() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let src_path = dir.path().join("source.db");
+        let dst_path = dir.path().join("lengths.db");
+
+        let backend =
+            persistent_map::sqlite::SqliteBackend::new(src_path.to_str().unwrap()).await?;
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        map.insert("a".to_string(), "one".to_string()).await?;
+        map.insert("b".to_string(), "three".to_string()).await?;
+
+        let dst = persistent_map::sqlite::SqliteBackend::new(dst_path.to_str().unwrap()).await?;
+        let lengths = map.map_values(String::len, dst).await?;
+        assert_eq!(lengths.get(&"a".to_string()), Some(3));
+        assert_eq!(lengths.get(&"b".to_string()), Some(5));
+        drop(lengths);
+
+        let dst = persistent_map::sqlite::SqliteBackend::new(dst_path.to_str().unwrap()).await?;
+        let lengths: PersistentMap<String, usize, _> = PersistentMap::new(dst).await?;
+        assert_eq!(lengths.len(), 2);
+        assert_eq!(lengths.get(&"b".to_string()), Some(5));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_replace_backend() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let old_path = dir.path().join("old.db");
+        let new_path = dir.path().join("new.db");
+
+        let backend =
+            persistent_map::sqlite::SqliteBackend::new(old_path.to_str().unwrap()).await?;
+        let mut map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        map.insert("a".to_string(), "one".to_string()).await?;
+        map.insert_with_ttl("b".to_string(), "two".to_string(), Duration::from_secs(60))
+            .await?;
+
+        let new = persistent_map::sqlite::SqliteBackend::new(new_path.to_str().unwrap()).await?;
+        let moved = map.replace_backend(new).await?;
+        assert_eq!(moved.get(&"a".to_string()), Some("one".to_string()));
+        assert_eq!(moved.get(&"b".to_string()), Some("two".to_string()));
+        assert!(map.is_empty());
+
+        moved.insert("c".to_string(), "three".to_string()).await?;
+        drop(moved);
+
+        let new = persistent_map::sqlite::SqliteBackend::new(new_path.to_str().unwrap()).await?;
+        let expiries =
+            persistent_map::StorageBackend::<String, String>::load_expiries(&new).await?;
+        assert!(expiries.contains_key("b"));
+        let reopened: PersistentMap<String, String, _> = PersistentMap::new(new).await?;
+        assert_eq!(reopened.len(), 3);
+        assert_eq!(reopened.get(&"c".to_string()), Some("three".to_string()));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_entry_age() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("ages.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .track_entry_age()
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        assert!(map.entry_age(&"a".to_string()).unwrap() < Duration::from_secs(5));
+        assert_eq!(map.entry_age(&"missing".to_string()), None);
+        drop(map);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Reloaded entries take their age from the stored write time
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .track_entry_age()
+            .build()
+            .await?;
+        let age = map.entry_age(&"a".to_string()).unwrap();
+        assert!(age >= Duration::from_millis(50));
+        map.insert("a".to_string(), 2).await?;
+        assert!(map.entry_age(&"a".to_string()).unwrap() < age);
+        map.remove(&"a".to_string()).await?;
+        assert_eq!(map.entry_age(&"a".to_string()), None);
+        drop(map);
+
+        // Without tracking no age is known
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("b".to_string(), 1).await?;
+        assert_eq!(map.entry_age(&"b".to_string()), None);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_get_fresh() -> Result<()> {
+        use persistent_map::StorageBackend;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("fresh.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .track_entry_age()
+            .build()
+            .await?;
+        let key = "flag".to_string();
+        map.insert(key.clone(), 1).await?;
+        map.backend().save(key.clone(), 2).await?;
+
+        // A young enough value is served from memory
+        assert_eq!(map.get_fresh(&key, Duration::from_secs(60)).await?, Some(1));
+
+        // An older one is reloaded, which makes it young again
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            map.get_fresh(&key, Duration::from_millis(10)).await?,
+            Some(2)
+        );
+        assert_eq!(map.get(&key), Some(2));
+        assert!(map.entry_age(&key).unwrap() < Duration::from_millis(10));
+
+        // A key deleted from the backend is dropped from memory
+        StorageBackend::<String, u32>::delete(map.backend(), &key).await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(map.get_fresh(&key, Duration::from_millis(10)).await?, None);
+        assert_eq!(map.get(&key), None);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_batch_over_capacity_reads_through() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("bounded.db");
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path.to_str().unwrap()).await?;
+        let map: PersistentMap<u32, u32, _> = PersistentMap::builder(backend)
+            .max_capacity(3)
+            .build()
+            .await?;
+        map.insert_batch_ordered((0..10).map(|i| (i, i * 10)).collect())
+            .await?;
+
+        // The backend has every entry, the cache the last ones in the batch
+        assert_eq!(map.len(), 3);
+        assert!((7..10).all(|i| map.contains_key(&i)));
+        assert_eq!(map.get(&0), None);
+
+        for i in 0..10 {
+            assert_eq!(map.get_durable(&i).await?, Some(i * 10));
+            assert!(map.len() <= 3);
+        }
+        assert_eq!(map.get(&9), Some(90));
+        assert_eq!(map.get_durable(&10).await?, None);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_upsert_if_newer() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("upsert_versions.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        let key = "plan".to_string();
+        assert!(
+            map.upsert_if_newer(key.clone(), "v5".to_string(), 5)
+                .await?
+        );
+        assert!(
+            !map.upsert_if_newer(key.clone(), "v3".to_string(), 3)
+                .await?
+        );
+        assert!(
+            !map.upsert_if_newer(key.clone(), "v5 again".to_string(), 5)
+                .await?
+        );
+        assert_eq!(map.get(&key), Some("v5".to_string()));
+        assert!(
+            map.upsert_if_newer(key.clone(), "v7".to_string(), 7)
+                .await?
+        );
+
+        // Another map sharing the backend sees the stored version
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let other: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        assert!(
+            !other
+                .upsert_if_newer(key.clone(), "v6".to_string(), 6)
+                .await?
+        );
+        assert_eq!(other.get(&key), Some("v7".to_string()));
+
+        // A plain write clears the version
+        map.insert(key.clone(), "plain".to_string()).await?;
+        assert!(
+            map.upsert_if_newer(key.clone(), "v1".to_string(), 1)
+                .await?
+        );
+        assert_eq!(map.get(&key), Some("v1".to_string()));
+
+        drop((map, other));
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sqlite_upsert_if_newer_respects_max_entries() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("upsert_max_entries.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: Arc<PersistentMap<String, u32, _>> = Arc::new(
+            PersistentMap::builder(backend)
+                .max_entries(3)
+                .build()
+                .await?,
+        );
+
+        // Concurrent upserts of new keys never push the map past the limit,
+        // and rejected keys never reach the backend
+        let tasks: Vec<_> = (0..10)
+            .map(|i| {
+                let map = Arc::clone(&map);
+                tokio::spawn(async move { map.upsert_if_newer(format!("k{i}"), i, 1).await })
+            })
+            .collect();
+        let mut written = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(true) => written += 1,
+                Err(PersistentError::CapacityExceeded(3)) => {}
+                other => panic!("unexpected result {other:?}"),
+            }
+        }
+        assert_eq!(written, 3);
+        assert_eq!(map.len(), 3);
+        let mut stored = 0;
+        for i in 0..10 {
+            if map.contains_key_durable(&format!("k{i}")).await? {
+                stored += 1;
+            }
+        }
+        assert_eq!(stored, 3);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_swap() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("swap.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::builder(backend)
+            .max_capacity(2)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+        map.insert("c".to_string(), 3).await?;
+        // "a" was evicted, so its value comes from the backend
+        assert!(!map.contains_key(&"a".to_string()));
+
+        map.swap(&"a".to_string(), &"c".to_string()).await?;
+        assert_eq!(map.get(&"a".to_string()), Some(3));
+        assert_eq!(map.get(&"c".to_string()), Some(1));
+
+        // A missing value moves like any other
+        map.swap(&"b".to_string(), &"d".to_string()).await?;
+        map.swap(&"x".to_string(), &"y".to_string()).await?;
+        map.swap(&"a".to_string(), &"a".to_string()).await?;
+        drop(map);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&"a".to_string()), Some(3));
+        assert_eq!(map.get(&"b".to_string()), None);
+        assert_eq!(map.get(&"c".to_string()), Some(1));
+        assert_eq!(map.get(&"d".to_string()), Some(2));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_rate_limit_survives_reopen() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("limits.db");
+        let db_path_str = db_path.to_str().unwrap();
+        let window = Duration::from_secs(60);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let limits: PersistentMap<String, RateCounter, _> = PersistentMap::new(backend).await?;
+        assert!(
+            !limits
+                .check_and_increment("client".to_string(), window, 1)
+                .await?
+        );
+        drop(limits);
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let limits: PersistentMap<String, RateCounter, _> = PersistentMap::new(backend).await?;
+        assert!(
+            limits
+                .check_and_increment("client".to_string(), window, 1)
+                .await?
+        );
+
+        drop(limits);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_verify_roundtrip() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("verify.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        map.verify_roundtrip().await?;
+
+        // The sentinel is deleted again
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, String, _> = PersistentMap::new(backend).await?;
+        assert!(map.is_empty());
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_flush_and_verify() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("flush_verify.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        for i in 0..10 {
+            map.insert(format!("key{i}"), i).await?;
+        }
+        map.flush_and_verify(VerifyDepth::Sample(3)).await?;
+        map.flush_and_verify(VerifyDepth::Full).await?;
+
+        // A value changed behind the cache's back is reported
+        persistent_map::StorageBackend::save(map.backend(), "key4".to_string(), 40).await?;
+        assert!(matches!(
+            map.flush_and_verify(VerifyDepth::Full).await,
+            Err(PersistentError::Config(_))
+        ));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_read_pool() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("pool.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::with_read_pool(db_path_str, 3).await?;
+        let map: Arc<PersistentMap<String, u32, _>> = Arc::new(PersistentMap::new(backend).await?);
+        for i in 0..10 {
+            map.insert(format!("key{i}"), i).await?;
+        }
+
+        // Reads see the writer's commits, from every pooled connection
+        let tasks: Vec<_> = (0..10)
+            .map(|i| {
+                let map = Arc::clone(&map);
+                tokio::spawn(async move { map.get_raw(&format!("key{i}")).await })
+            })
+            .collect();
+        for (i, task) in tasks.into_iter().enumerate() {
+            assert_eq!(task.await.unwrap()?, Some(i.to_string().into_bytes()));
+        }
+        assert_eq!(map.backend_stats().await?["journal_mode"], "wal");
+
+        assert!(matches!(
+            persistent_map::sqlite::SqliteBackend::with_read_pool(":memory:", 2).await,
+            Err(PersistentError::Config(_))
+        ));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_binary_values() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("binary.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new_binary(db_path_str).await?;
+            let map: PersistentMap<String, Vec<f64>, _> = PersistentMap::new(backend).await?;
+            map.insert("samples".to_string(), vec![0.1, 1.0 / 3.0, f64::MAX])
+                .await?;
+            map.insert("empty".to_string(), Vec::new()).await?;
+            map.flush().await?;
+
+            // Raw reads still return JSON
+            assert_eq!(
+                map.get_raw(&"empty".to_string()).await?,
+                Some(b"[]".to_vec())
+            );
+        }
+
+        let backend = persistent_map::sqlite::SqliteBackend::new_binary(db_path_str).await?;
+        let map: PersistentMap<String, Vec<f64>, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            map.get(&"samples".to_string()),
+            Some(vec![0.1, 1.0 / 3.0, f64::MAX])
+        );
+        drop(map);
+
+        // The encoding is fixed when the database is created
+        assert!(matches!(
+            persistent_map::sqlite::SqliteBackend::new(db_path_str).await,
+            Err(PersistentError::Config(_))
+        ));
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_binary_rejects_json_database() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("json.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map = PersistentMap::new(backend).await?;
+        map.insert("key".to_string(), 1_u32).await?;
+        drop(map);
+
+        assert!(matches!(
+            persistent_map::sqlite::SqliteBackend::new_binary(db_path_str).await,
+            Err(PersistentError::Config(_))
+        ));
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_load_count() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("load_count.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let writer: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(writer.loaded_count(), 0);
+        for i in 0..3 {
+            writer.insert(format!("key{i}"), i).await?;
+        }
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let reader: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(reader.loaded_count(), 3);
+
+        // A reload counts what it read, not what the map holds
+        writer.remove(&"key0".to_string()).await?;
+        assert_eq!(reader.load().await?, 2);
+        assert_eq!(reader.loaded_count(), 2);
+        assert_eq!(reader.len(), 3);
+
+        drop((writer, reader));
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_with_table() -> Result<()> {
+        use persistent_map::sqlite::SqliteBackend;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("tables.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let sessions = SqliteBackend::with_table(db_path_str, "sessions").await?;
+        assert_eq!(sessions.table(), "sessions");
+        let sessions = PersistentMap::new(sessions).await?;
+        let users =
+            PersistentMap::new(SqliteBackend::with_table(db_path_str, "users").await?).await?;
+        sessions.insert("shared".to_string(), 1_u32).await?;
+        sessions.insert("session".to_string(), 2).await?;
+        users.insert("shared".to_string(), 10_u32).await?;
+        drop((sessions, users));
+
+        let sessions: PersistentMap<String, u32, _> =
+            PersistentMap::new(SqliteBackend::with_table(db_path_str, "sessions").await?).await?;
+        let users: PersistentMap<String, u32, _> =
+            PersistentMap::new(SqliteBackend::with_table(db_path_str, "users").await?).await?;
+        let default: PersistentMap<String, u32, _> =
+            PersistentMap::new(SqliteBackend::new(db_path_str).await?).await?;
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions.get(&"shared".to_string()), Some(1));
+        assert_eq!(users.len(), 1);
+        assert_eq!(users.get(&"shared".to_string()), Some(10));
+        assert!(default.is_empty());
+
+        users.remove(&"shared".to_string()).await?;
+        let reopened: PersistentMap<String, u32, _> =
+            PersistentMap::new(SqliteBackend::with_table(db_path_str, "sessions").await?).await?;
+        assert_eq!(reopened.get(&"shared".to_string()), Some(1));
+
+        for table in ["", "1st", "kv-2", "kv; DROP TABLE kv", "sqlite_master"] {
+            assert!(matches!(
+                SqliteBackend::with_table(db_path_str, table).await,
+                Err(PersistentError::Config(_))
+            ));
+        }
+
+        drop((sessions, users, default, reopened));
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_insert_reporting_sees_uncached_keys() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("reporting.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let first: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let second: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+
+        assert_eq!(
+            first.insert_reporting("a".to_string(), 1).await?,
+            SaveOutcome::Inserted
+        );
+        assert_eq!(
+            first.insert_reporting("a".to_string(), 2).await?,
+            SaveOutcome::Updated
+        );
+
+        // The second map never cached the key, but the backend knows it
+        assert_eq!(second.get(&"a".to_string()), None);
+        assert_eq!(
+            second.insert_reporting("a".to_string(), 3).await?,
+            SaveOutcome::Updated
+        );
+
+        // Expired rows count as absent
+        first
+            .insert_with_ttl("b".to_string(), 1, Duration::from_millis(1))
+            .await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            second.insert_reporting("b".to_string(), 2).await?,
+            SaveOutcome::Inserted
+        );
+
+        drop(first);
+        drop(second);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_clear_all() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("clear.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        {
+            let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+            let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+            map.insert("a".to_string(), 1).await?;
+            map.insert("b".to_string(), 2).await?;
+            map.clear_all().await?;
+            assert!(map.is_empty());
+        }
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert!(map.is_empty());
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_checkpoint_truncates_wal() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("checkpoint.db");
+        let db_path_str = db_path.to_str().unwrap();
+        let wal_path = dir.path().join("checkpoint.db-wal");
+
+        // A read pool switches the database to WAL mode
+        let backend = persistent_map::sqlite::SqliteBackend::with_read_pool(db_path_str, 1).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        for i in 0..100 {
+            map.insert(format!("key{i}"), i).await?;
+        }
+        assert!(std::fs::metadata(&wal_path)?.len() > 0);
+
+        map.checkpoint().await?;
+        assert_eq!(std::fs::metadata(&wal_path)?.len(), 0);
+        assert_eq!(map.get_raw(&"key7".to_string()).await?, Some(b"7".to_vec()));
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_get_stream() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("stream.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let writer: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        for i in 0..1_000 {
+            writer.insert(format!("key{i}"), i).await?;
+        }
+
+        // A second map caches only a few keys; the rest come from the backend
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let reader: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        reader.clear();
+        reader.insert("key5".to_string(), 500).await?;
+
+        let mut keys: Vec<String> = (0..1_000).rev().map(|i| format!("key{i}")).collect();
+        keys.push("missing".to_string());
+        let mut lookups = reader.get_stream(keys);
+        let mut results = Vec::new();
+        while let Some(result) = lookups.next().await? {
+            results.push(result);
+        }
+
+        assert_eq!(results.len(), 1_001);
+        assert_eq!(results[0], ("key999".to_string(), Some(999)));
+        assert_eq!(results[994], ("key5".to_string(), Some(500)));
+        assert_eq!(results[999], ("key0".to_string(), Some(0)));
+        assert_eq!(results[1_000], ("missing".to_string(), None));
+        // Backend reads are not cached
+        assert_eq!(reader.len(), 1);
+        assert!(lookups.next().await?.is_none());
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_rename_prefix() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("rename.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("users/alice".to_string(), 1).await?;
+        map.insert("users/bob".to_string(), 2).await?;
+        map.insert_with_ttl("users/carol".to_string(), 3, Duration::from_millis(300))
+            .await?;
+        map.insert("teams/core".to_string(), 4).await?;
+
+        assert_eq!(map.rename_prefix("users/", "accounts/").await?, 3);
+        assert_eq!(map.get(&"users/alice".to_string()), None);
+        assert_eq!(map.get(&"accounts/alice".to_string()), Some(1));
+        assert_eq!(map.get(&"accounts/carol".to_string()), Some(3));
+        assert_eq!(map.get(&"teams/core".to_string()), Some(4));
+
+        // A target that is itself moved is not a conflict
+        map.insert("a1".to_string(), 5).await?;
+        map.insert("ab1".to_string(), 6).await?;
+        assert_eq!(map.rename_prefix("a", "ab").await?, 5);
+        assert_eq!(map.get(&"ab1".to_string()), Some(5));
+        assert_eq!(map.get(&"abb1".to_string()), Some(6));
+        assert_eq!(map.get(&"abccounts/alice".to_string()), Some(1));
+
+        // An existing target leaves everything in place
+        map.insert("teams/bob".to_string(), 7).await?;
+        match map.rename_prefix("abccounts/", "teams/").await {
+            Err(PersistentError::Key(_)) => {}
+            other => panic!("expected a key error, got {other:?}"),
+        }
+        assert_eq!(map.get(&"abccounts/bob".to_string()), Some(2));
+        assert_eq!(map.get(&"teams/bob".to_string()), Some(7));
+
+        // The moved entry kept its expiry, in memory and in the backend
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(map.get(&"abccounts/carol".to_string()), None);
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let reloaded: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(reloaded.get(&"abccounts/alice".to_string()), Some(1));
+        assert_eq!(reloaded.get(&"abccounts/carol".to_string()), None);
+        assert_eq!(reloaded.get(&"abb1".to_string()), Some(6));
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_diff_backend() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("diff.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        map.insert("a".to_string(), 1).await?;
+        map.insert("b".to_string(), 2).await?;
+        assert!(map.diff_backend().await?.is_empty());
+
+        // Another map changes the backend behind the first one's cache
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let other: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        other.remove(&"a".to_string()).await?;
+        other.insert("b".to_string(), 20).await?;
+        other.insert("c".to_string(), 3).await?;
+
+        let diff = map.diff_backend().await?;
+        assert_eq!(diff.only_in_memory, vec!["a".to_string()]);
+        assert_eq!(diff.only_in_backend, vec!["c".to_string()]);
+        assert_eq!(diff.differs, vec![("b".to_string(), 2, 20)]);
+
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_metadata_columns() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("metadata.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        // A table from before the metadata columns existed
+        let conn = tokio_rusqlite::Connection::open(db_path_str).await?;
+        conn.call(|c| {
+            c.execute_batch(
+                "CREATE TABLE kv (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+                 INSERT INTO kv (key, value) VALUES ('legacy', '1');",
+            )?;
+            Ok(())
+        })
+        .await?;
+
+        let backend = persistent_map::sqlite::SqliteBackend::new(db_path_str).await?;
+        let map: PersistentMap<String, u32, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.get(&"legacy".to_string()), Some(1));
+
+        let before = SystemTime::now() - Duration::from_millis(5);
+        map.insert("a".to_string(), 1).await?;
+        map.insert("a".to_string(), 2).await?;
+        map.insert("b".to_string(), 1).await?;
+        let updated: Vec<String> = map.backend().query_updated_since(before).await?;
+        assert_eq!(updated, ["a", "b"]);
+        let later = SystemTime::now() + Duration::from_secs(60);
+        assert!(map
+            .backend()
+            .query_updated_since::<String>(later)
+            .await?
+            .is_empty());
+
+        let rows = conn
+            .call(|c| {
+                let mut stmt = c.prepare("SELECT key, version, updated_at FROM kv ORDER BY key")?;
+                let rows = stmt
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, Option<i64>>(1)?,
+                            row.get::<_, Option<i64>>(2)?.is_some(),
+                        ))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+        assert_eq!(
+            rows,
+            [
+                ("a".to_string(), Some(2), true),
+                ("b".to_string(), Some(1), true),
+                ("legacy".to_string(), None, false),
+            ]
+        );
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_serialized_keys() -> Result<()> {
+        use persistent_map::json_keys::JsonKey;
+        use persistent_map::sqlite::SqliteBackend;
+        use persistent_map::StorageBackend;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("grid.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let grid =
+            PersistentMap::new(SqliteBackend::new_serialized_keys(db_path_str).await?).await?;
+        grid.insert((3_i32, -1_i32), "tree".to_string()).await?;
+        grid.insert((0, 0), "origin".to_string()).await?;
+        grid.insert((0, 1), "rock".to_string()).await?;
+        grid.remove(&(0, 1)).await?;
+
+        // Keys are stored as their JSON text
+        let stored: std::collections::HashMap<JsonKey<(i32, i32)>, String> =
+            grid.backend().inner().load_all().await?;
+        assert_eq!(stored[&JsonKey((3, -1))], "tree");
+        assert_eq!(JsonKey((3, -1)).to_string(), "[3,-1]");
+        drop(grid);
+
+        let reloaded: PersistentMap<(i32, i32), String, _> =
+            PersistentMap::new(SqliteBackend::new_serialized_keys(db_path_str).await?).await?;
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.get(&(3, -1)), Some("tree".to_string()));
+        assert_eq!(reloaded.get(&(0, 0)), Some("origin".to_string()));
+        assert_eq!(reloaded.get(&(0, 1)), None);
+
+        drop(reloaded);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_open_read_only() -> Result<()> {
+        use persistent_map::sqlite::SqliteBackend;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("catalog.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let map = PersistentMap::new(SqliteBackend::new_binary(db_path_str).await?).await?;
+        map.insert("shipped".to_string(), 1.5_f64).await?;
+        drop(map);
+
+        let backend = SqliteBackend::open_read_only(db_path_str).await?;
+        assert!(backend.is_read_only());
+        let map: PersistentMap<String, f64, _> = PersistentMap::new(backend).await?;
+        assert_eq!(map.get(&"shipped".to_string()), Some(1.5));
+
+        let result = map.insert("new".to_string(), 2.0).await;
+        assert!(matches!(result, Err(PersistentError::ReadOnly(_))));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "read-only backend: insert is not allowed"
+        );
+        assert!(matches!(
+            map.insert("shipped".to_string(), 3.0).await,
+            Err(PersistentError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            map.remove(&"shipped".to_string()).await,
+            Err(PersistentError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            map.update(&"shipped".to_string(), |price| *price *= 2.0)
+                .await,
+            Err(PersistentError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            map.flush().await,
+            Err(PersistentError::ReadOnly(_))
+        ));
+
+        // Refused writes leave the in-memory map unchanged as well
+        assert_eq!(map.get(&"new".to_string()), None);
+        assert_eq!(map.get(&"shipped".to_string()), Some(1.5));
+        assert_eq!(map.len(), 1);
+        drop(map);
+
+        // The database is unchanged
+        let reopened: PersistentMap<String, f64, _> =
+            PersistentMap::new(SqliteBackend::open_read_only(db_path_str).await?).await?;
+        assert_eq!(reopened.len(), 1);
+        assert_eq!(reopened.get(&"shipped".to_string()), Some(1.5));
+
+        let missing = dir.path().join("missing.db");
+        assert!(SqliteBackend::open_read_only(missing.to_str().unwrap())
+            .await
+            .is_err());
+
+        drop(reopened);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_entry() -> Result<()> {
+        use persistent_map::sqlite::SqliteBackend;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("entry.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let map = PersistentMap::new(SqliteBackend::new(db_path_str).await?).await?;
+        let key = "visits".to_string();
+
+        // Vacant: the default is inserted and `and_modify` is skipped
+        let visits = map
+            .entry(key.clone())
+            .and_modify(|n| *n += 1)
+            .or_insert(1_u64)
+            .await?;
+        assert_eq!(visits, 1);
+
+        // Occupied: the value is modified and the default is not computed
+        let visits = map
+            .entry(key.clone())
+            .and_modify(|n| *n += 1)
+            .or_insert_with(|| unreachable!())
+            .await?;
+        assert_eq!(visits, 2);
+        assert_eq!(map.entry(key.clone()).or_insert(10).await?, 2);
+
+        // Awaiting an entry directly only modifies present keys
+        assert_eq!(
+            map.entry(key.clone()).and_modify(|n| *n *= 10).await?,
+            Some(20)
+        );
+
+        // Chained modifications are all applied, in order
+        assert_eq!(
+            map.entry(key.clone())
+                .and_modify(|n| *n += 1)
+                .and_modify(|n| *n *= 2)
+                .await?,
+            Some(42)
+        );
+        let absent = map.entry("absent".to_string());
+        assert_eq!(absent.key(), "absent");
+        assert_eq!(absent.and_modify(|n| *n += 1).await?, None);
+        assert!(!map.contains_key(&"absent".to_string()));
+        drop(map);
+
+        let reloaded: PersistentMap<String, u64, _> =
+            PersistentMap::new(SqliteBackend::new(db_path_str).await?).await?;
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(reloaded.get(&key), Some(42));
+
+        drop(reloaded);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sqlite_entry_respects_max_entries() -> Result<()> {
+        use persistent_map::sqlite::SqliteBackend;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("entry_max_entries.db");
+        let db_path_str = db_path.to_str().unwrap();
+
+        let map: Arc<PersistentMap<String, u64, _>> = Arc::new(
+            PersistentMap::builder(SqliteBackend::new(db_path_str).await?)
+                .max_entries(3)
+                .build()
+                .await?,
+        );
+
+        // Concurrent inserts through vacant entries never overshoot the limit
+        let tasks: Vec<_> = (0..10)
+            .map(|i| {
+                let map = Arc::clone(&map);
+                tokio::spawn(async move { map.entry(format!("k{i}")).or_insert(i).await })
+            })
+            .collect();
+        let mut inserted = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok(_) => inserted += 1,
+                Err(PersistentError::CapacityExceeded(3)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        assert_eq!(inserted, 3);
+        assert_eq!(map.len(), 3);
+
+        drop(map);
+        let reloaded: PersistentMap<String, u64, _> =
+            PersistentMap::new(SqliteBackend::new(db_path_str).await?).await?;
+        assert_eq!(reloaded.len(), 3);
+
+        drop(reloaded);
+        dir.close().unwrap();
+
+        Ok(())
+    }
 }
@@ -0,0 +1,312 @@
+#[cfg(feature = "runtime")]
+mod write_behind {
+    use persistent_map::{PersistentError, PersistentMap, Result, StorageBackend};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A backend that stores entries in memory and counts its write calls.
+    ///
+    /// While `stalled` is set, batch writes never complete.
+    #[derive(Clone, Default)]
+    struct RecordingBackend {
+        data: Arc<Mutex<HashMap<String, u32>>>,
+        single_writes: Arc<AtomicUsize>,
+        batches: Arc<AtomicUsize>,
+        stalled: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend<String, u32> for RecordingBackend {
+        async fn load_all(&self) -> Result<HashMap<String, u32>, PersistentError> {
+            Ok(self.data.lock().unwrap().clone())
+        }
+
+        async fn save(&self, key: String, value: u32) -> Result<(), PersistentError> {
+            self.single_writes.fetch_add(1, Ordering::SeqCst);
+            self.data.lock().unwrap().insert(key, value);
+            Ok(())
+        }
+
+        async fn delete(&self, key: &String) -> Result<(), PersistentError> {
+            self.single_writes.fetch_add(1, Ordering::SeqCst);
+            self.data.lock().unwrap().remove(key);
+            Ok(())
+        }
+
+        async fn write_batch(
+            &self,
+            saves: Vec<(String, u32)>,
+            deletes: Vec<String>,
+        ) -> Result<(), PersistentError> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            if self.stalled.load(Ordering::SeqCst) {
+                std::future::pending::<()>().await;
+            }
+            let mut data = self.data.lock().unwrap();
+            data.extend(saves);
+            for key in &deletes {
+                data.remove(key);
+            }
+            drop(data);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_writes_one_batch() -> Result<()> {
+        let backend = RecordingBackend::default();
+        backend.data.lock().unwrap().insert("old".to_string(), 0);
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_secs(3600), 1_000)
+            .build()
+            .await?;
+
+        for i in 0..10 {
+            map.insert(format!("key{i}"), i).await?;
+        }
+        map.insert("key0".to_string(), 100).await?;
+        map.remove(&"old".to_string()).await?;
+
+        // Nothing reached the backend yet
+        assert_eq!(backend.batches.load(Ordering::SeqCst), 0);
+        assert!(backend.data.lock().unwrap().contains_key("old"));
+
+        map.flush().await?;
+        assert_eq!(backend.batches.load(Ordering::SeqCst), 1);
+        assert_eq!(backend.single_writes.load(Ordering::SeqCst), 0);
+        let data = backend.data.lock().unwrap().clone();
+        assert_eq!(data.len(), 10);
+        assert_eq!(data.get("key0"), Some(&100));
+        assert!(!data.contains_key("old"));
+
+        // An empty buffer doesn't touch the backend
+        map.flush().await?;
+        assert_eq!(backend.batches.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clear_all_discards_queued_writes() -> Result<()> {
+        let backend = RecordingBackend::default();
+        backend.data.lock().unwrap().insert("old".to_string(), 0);
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_secs(3600), 1_000)
+            .build()
+            .await?;
+
+        map.insert("queued".to_string(), 1).await?;
+        map.clear_all().await?;
+        map.flush().await?;
+
+        assert!(map.is_empty());
+        assert!(backend.data.lock().unwrap().is_empty());
+        assert_eq!(backend.batches.load(Ordering::SeqCst), 0);
+
+        // Writes after the clear are queued as usual
+        map.insert("new".to_string(), 2).await?;
+        map.flush().await?;
+        assert_eq!(backend.data.lock().unwrap().get("new"), Some(&2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_with_ack_resolves_after_drain() -> Result<()> {
+        let backend = RecordingBackend::default();
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_millis(50), 1_000)
+            .build()
+            .await?;
+
+        let (old, ack) = map.insert_with_ack("a".to_string(), 1).await?;
+        assert_eq!(old, None);
+        assert!(!ack.is_persisted());
+        assert!(backend.data.lock().unwrap().is_empty());
+
+        // The background flusher writes the batch and resolves the handle
+        tokio::time::timeout(Duration::from_secs(5), ack.persisted())
+            .await
+            .expect("write was not persisted");
+        assert_eq!(backend.data.lock().unwrap().get("a"), Some(&1));
+
+        // Ignored handles leave the write queued as usual
+        let _ = map.insert_with_ack("b".to_string(), 2).await?;
+        map.flush().await?;
+        assert_eq!(backend.data.lock().unwrap().get("b"), Some(&2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_with_ack_waits_for_retry() -> Result<()> {
+        let backend = RecordingBackend::default();
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_secs(3600), 1_000)
+            .build()
+            .await?;
+        let (_, ack) = map.insert_with_ack("a".to_string(), 1).await?;
+
+        // A drain that never completes does not resolve the handle
+        backend.stalled.store(true, Ordering::SeqCst);
+        assert!(tokio::time::timeout(Duration::from_millis(50), map.flush())
+            .await
+            .is_err());
+        assert!(!ack.is_persisted());
+
+        backend.stalled.store(false, Ordering::SeqCst);
+        map.flush().await?;
+        assert!(ack.is_persisted());
+        ack.persisted().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_with_ack_without_write_behind() -> Result<()> {
+        let backend = RecordingBackend::default();
+        let map = PersistentMap::new(backend.clone()).await?;
+
+        let (_, ack) = map.insert_with_ack("a".to_string(), 1).await?;
+        assert!(ack.is_persisted());
+        assert_eq!(backend.data.lock().unwrap().get("a"), Some(&1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_flush_requeues_the_batch() -> Result<()> {
+        let backend = RecordingBackend::default();
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_secs(3600), 1_000)
+            .build()
+            .await?;
+        map.insert("a".to_string(), 1).await?;
+
+        // The flush is dropped while the batch is being written
+        backend.stalled.store(true, Ordering::SeqCst);
+        assert!(tokio::time::timeout(Duration::from_millis(50), map.flush())
+            .await
+            .is_err());
+        assert!(backend.data.lock().unwrap().is_empty());
+
+        backend.stalled.store(false, Ordering::SeqCst);
+        map.flush().await?;
+        assert_eq!(backend.data.lock().unwrap().get("a"), Some(&1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_drains_in_background() -> Result<()> {
+        let backend = RecordingBackend::default();
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_secs(3600), 3)
+            .build()
+            .await?;
+
+        for i in 0..3 {
+            map.insert(format!("key{i}"), i).await?;
+        }
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while backend.batches.load(Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("the background task drains a full buffer");
+        assert_eq!(backend.data.lock().unwrap().len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interval_drains_in_background() -> Result<()> {
+        let backend = RecordingBackend::default();
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_millis(20), 1_000)
+            .build()
+            .await?;
+
+        map.insert("key".to_string(), 1).await?;
+        map.remove(&"key".to_string()).await?;
+        map.insert("other".to_string(), 2).await?;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while backend.batches.load(Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("the background task drains the buffer every interval");
+        let data = backend.data.lock().unwrap().clone();
+        assert_eq!(data, HashMap::from([("other".to_string(), 2)]));
+        assert_eq!(backend.single_writes.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wal_logs_writes_until_drained() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("map.wal");
+        let backend = RecordingBackend::default();
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_secs(3600), 1_000)
+            .wal(&wal_path)
+            .build()
+            .await?;
+
+        map.insert("a".to_string(), 1).await?;
+        map.remove(&"a".to_string()).await?;
+        map.insert("b".to_string(), 2).await?;
+        assert_eq!(
+            std::fs::read_to_string(&wal_path)?,
+            "{\"save\":[\"a\",1]}\n{\"delete\":\"a\"}\n{\"save\":[\"b\",2]}\n"
+        );
+        assert!(backend.data.lock().unwrap().is_empty());
+
+        map.flush().await?;
+        assert_eq!(backend.data.lock().unwrap().get("b"), Some(&2));
+        assert_eq!(std::fs::metadata(&wal_path)?.len(), 0);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wal_is_replayed_on_build() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("map.wal");
+        let backend = RecordingBackend::default();
+        backend.data.lock().unwrap().insert("old".to_string(), 0);
+
+        // A crash left two acknowledged writes and a torn record behind
+        std::fs::write(
+            &wal_path,
+            "{\"save\":[\"a\",1]}\n{\"delete\":\"old\"}\n{\"save\":[\"b\",",
+        )?;
+
+        let map = PersistentMap::builder(backend.clone())
+            .write_behind(Duration::from_secs(3600), 1_000)
+            .wal(&wal_path)
+            .build()
+            .await?;
+        assert_eq!(map.get(&"a".to_string()), Some(1));
+        assert!(!map.contains_key(&"old".to_string()));
+        assert!(!map.contains_key(&"b".to_string()));
+
+        let data = backend.data.lock().unwrap().clone();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data.get("a"), Some(&1));
+        assert_eq!(std::fs::metadata(&wal_path)?.len(), 0);
+
+        drop(map);
+        dir.close().unwrap();
+
+        Ok(())
+    }
+}